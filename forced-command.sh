@@ -0,0 +1,64 @@
+#!/bin/bash
+# Forced command for the dockup backup account on the backup target.
+#
+# Only allows the fixed set of operations dockup issues in `remote_allowlist` mode
+# (see src/transport.rs), each confined to the backup root given as first argument.
+#
+# ~/.ssh/authorized_keys of the backup user:
+#   command="/usr/local/bin/dockup-forced-command.sh /srv/backups",restrict ssh-ed25519 AAAA...
+
+set -euo pipefail
+
+ROOT=$(realpath -m "${1:?usage: $0 <backup root>}")
+
+deny() {
+    echo "dockup: denied: $SSH_ORIGINAL_COMMAND" >&2
+    exit 1
+}
+
+# Split like a shell would (quotes only, no expansion)
+mapfile -t ARGS < <(xargs -n1 printf '%s\n' <<< "${SSH_ORIGINAL_COMMAND:-}")
+[ "${#ARGS[@]}" -ge 2 ] || deny
+
+inside_root() {
+    local target
+    target=$(realpath -m "$1")
+    [[ "$target" == "$ROOT"/* ]] || return 1
+    echo "$target"
+}
+
+# Uploads land under a temporary name and are renamed once verified, two paths
+if [ "${#ARGS[@]}" -eq 5 ] && [ "${ARGS[*]:0:3}" = "mv -f --" ]; then
+    FROM=$(inside_root "${ARGS[3]}") || deny
+    TO=$(inside_root "${ARGS[4]}") || deny
+    exec mv -f -- "$FROM" "$TO"
+fi
+
+PATH_ARG="${ARGS[-1]}"
+# Resumable downloads read chunks with `dd ... if=<path>`
+if [ "${ARGS[0]}" = "dd" ]; then
+    [[ "$PATH_ARG" == if=* ]] || deny
+    PATH_ARG="${PATH_ARG#if=}"
+fi
+TARGET=$(realpath -m "$PATH_ARG")
+[[ "$TARGET" == "$ROOT" || "$TARGET" == "$ROOT"/* ]] || deny
+
+case "${ARGS[*]:0:${#ARGS[@]}-1}" in
+    "mkdir -p --") exec mkdir -p -- "$TARGET" ;;
+    "ls -1 --") exec ls -1 -- "$TARGET" ;;
+    "cat --") exec cat -- "$TARGET" ;;
+    "wc -c --") exec wc -c -- "$TARGET" ;;
+    "sha256sum --") exec sha256sum -- "$TARGET" ;;
+    "rm -rf --")
+        [ "$TARGET" != "$ROOT" ] || deny
+        exec rm -rf -- "$TARGET"
+        ;;
+    "scp -t" | "scp -d -t" | "scp -p -t" | "scp -p -d -t") exec "${ARGS[@]:0:${#ARGS[@]}-1}" "$TARGET" ;;
+    "scp -f" | "scp -p -f") exec "${ARGS[@]:0:${#ARGS[@]}-1}" "$TARGET" ;;
+    "dd bs="*)
+        DD_RE='^dd bs=[0-9]+ skip=[0-9]+ count=1 status=none$'
+        [[ "${ARGS[*]:0:${#ARGS[@]}-1}" =~ $DD_RE ]] || deny
+        exec "${ARGS[@]:0:${#ARGS[@]}-1}" "if=$TARGET"
+        ;;
+    *) deny ;;
+esac
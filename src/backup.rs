@@ -1,14 +1,33 @@
 use crate::{
+    catalog::Catalog,
+    compression,
     config::Config,
-    scanner::{scan_projects, BackupApplication, BackupType, VolumeType},
+    crypto, dbdump,
+    dedup::{self, ChunkStore},
+    fssnap, heartbeat,
+    hooks::{self, Hook, HookEnv},
+    i18n::{t, tf},
+    images, incremental, index, plain_eprintln, plain_println, progress,
+    quiesce::Quiesce,
+    scanner::{
+        self, scan_projects, ArchiveStats, BackupApplication, BackupType, Checksum, Volume,
+        VolumeType,
+    },
+    schedule, shutdown, state, telemetry, thresholds,
+    transport::{self, StorageBackend},
+    verify,
 };
-use anyhow::Result;
-use chrono::Local;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
 use std::{
     fs::{self, File},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::Command,
+    sync::Arc,
+    time::Instant,
 };
+use tokio::sync::Semaphore;
 
 #[derive(Debug)]
 pub struct BackupThingSummary {
@@ -17,58 +36,526 @@ pub struct BackupThingSummary {
     pub size: String,
     pub duration: String,
     pub volume_type: String,
+    /// Size and throughput figures, only for archives that were uploaded.
+    pub stats: Option<ArchiveStats>,
+    /// Seconds the project's containers were stopped or paused while archiving.
+    pub downtime: Option<f64>,
 }
 
 pub struct AppSummary {
     pub name: String,
     pub volume_statuses: Vec<BackupThingSummary>,
+    /// Reason from the project's `.dockup-skip` marker, if it was skipped.
+    pub skipped: Option<String>,
+    /// Archives above the configured size thresholds.
+    pub warnings: Vec<String>,
+    /// Archives far smaller than usual, see `thresholds::size_anomalies`.
+    pub anomalies: Vec<String>,
 }
 
-pub fn run_backup(config: &Config, mode: bool) -> Result<Vec<AppSummary>> {
-    let apps = scan_projects(config)?;
+/// Run summary uploaded as `last_run.json` to the remote root, so the backup server can
+/// monitor freshness on its own.
+#[derive(Serialize, Deserialize)]
+pub struct LastRun {
+    pub status: String,
+    pub backup_type: BackupType,
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    pub total_archives: usize,
+    pub failed_archives: usize,
+    pub error: Option<String>,
+    pub projects: Vec<LastRunProject>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LastRunProject {
+    pub name: String,
+    pub status: String,
+    pub archives: usize,
+    pub failed_archives: usize,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    #[serde(default)]
+    pub anomalies: Vec<String>,
+}
+
+impl AppSummary {
+    pub fn failed_count(&self) -> usize {
+        self.volume_statuses
+            .iter()
+            .filter(|v| v.status != "✅")
+            .count()
+    }
+}
+
+/// Backs up all scanned projects, or only those named in `projects`.
+pub async fn run_backup(
+    config: &Config,
+    mode: bool,
+    tags: &[String],
+    projects: &[String],
+) -> Result<Vec<AppSummary>> {
+    telemetry::init(config);
+    progress::emit("scan", None, None, None);
+    let scan = scan_projects(config)?;
+    let mut apps = scan.apps;
+    if !projects.is_empty() {
+        if let Some(missing) = projects
+            .iter()
+            .find(|name| !apps.iter().any(|app| &app.name == *name))
+        {
+            anyhow::bail!(
+                "No project {} to back up (skipped projects can't be selected)",
+                missing
+            );
+        }
+        apps.retain(|app| projects.contains(&app.name));
+    }
     println!("{:?}", apps);
     let mut summaries: Vec<AppSummary> = Vec::new();
+    let mut catalog = if config.canary {
+        None
+    } else {
+        Catalog::open()
+            .map_err(|e| log::warn!("⚠️  Backup catalog unavailable: {e}"))
+            .ok()
+    };
 
-    backup_config(config)?;
+    if config.dedup && config.encryption.enabled() {
+        anyhow::bail!("The chunk store can't be encrypted, disable `dedup` or encryption");
+    }
+    if config.incremental && config.encryption.enabled() {
+        anyhow::bail!(
+            "Incremental backups can't be encrypted, disable `incremental` or encryption"
+        );
+    }
+    backup_config(config, &apps)?;
 
-    for mut app in apps {
-        let backup_type = if mode {
-            BackupType::Scheduled
-        } else {
-            BackupType::Manual
+    let backup_type = if mode {
+        BackupType::Scheduled
+    } else {
+        BackupType::Manual
+    };
+    log::info!("Backup mode: {}", backup_type);
+
+    // Every project runs in its own blocking task, at most `max_concurrent_projects` at a
+    // time. Results are awaited in scan order so the report keeps its order. A project that
+    // fails outright is reported as failed, the others carry on.
+    let shared = Arc::new(config.clone());
+    let limit = Arc::new(Semaphore::new(config.max_concurrent_projects));
+    let mut tasks = Vec::new();
+    for app in apps {
+        let config = Arc::clone(&shared);
+        let limit = Arc::clone(&limit);
+        let tags = tags.to_vec();
+        let name = app.name.clone();
+        let task = tokio::spawn(async move {
+            let _permit = limit.acquire_owned().await?;
+            let project = tokio::task::spawn_blocking(move || {
+                backup_project_with_hooks(config, app, backup_type, &tags)
+            });
+            anyhow::Ok(project.await??)
+        });
+        tasks.push((name, task));
+    }
+
+    for (name, task) in tasks {
+        let (app, volume_statuses) = match task.await.map_err(anyhow::Error::from) {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) | Err(e) => {
+                log::error!("❌ Backup of {} failed: {e:#}", name);
+                progress::emit("failed", Some(&name), None, None);
+                summaries.push(AppSummary {
+                    name,
+                    volume_statuses: vec![BackupThingSummary {
+                        name: "Project".into(),
+                        status: "❌ Backup failed".into(),
+                        size: "-".into(),
+                        duration: "-".into(),
+                        volume_type: "-".into(),
+                        stats: None,
+                        downtime: None,
+                    }],
+                    skipped: None,
+                    warnings: Vec::new(),
+                    anomalies: Vec::new(),
+                });
+                continue;
+            }
         };
-        app.backup_type = Some(backup_type.clone());
-        log::info!("Backup mode: {}", backup_type);
-        log::info!("🗂  Backing up: {}", app.name);
-        let mut volume_statuses = Vec::new();
-        let timestamp_str = app.timestamp.format("%Y_%m_%d_%H%M%S").to_string();
-        let remote_base = format!(
-            "{}/{}/{}",
-            config.remote_backup_path, app.name, timestamp_str
+        let mut warnings =
+            thresholds::size_warnings(config, catalog.as_ref(), &app, &volume_statuses);
+        warnings.extend(
+            app.inconsistent_databases
+                .iter()
+                .map(|service| tf("report.warn_inconsistent", &[service])),
         );
-        run_remote_cmd(
-            config,
-            &format!("mkdir -p {}/REPO {}/VOLUMES", remote_base, remote_base),
-        )?;
-
-        let mut created_files: Vec<PathBuf> = Vec::new();
-        let start_repo_time = Local::now();
-        let repo_tar = create_tar(&app.application_path, "repo.tar.gz")?;
-        created_files.push(repo_tar.clone());
-
-        if let Err(e) = scp_upload(
-            config,
-            &repo_tar,
-            &format!("{}/REPO/repo.tar.gz", remote_base),
-        ) {
+        let anomalies =
+            thresholds::size_anomalies(config, catalog.as_ref(), &app, &volume_statuses);
+        let aborted = volume_statuses
+            .iter()
+            .any(|status| status.status == shutdown::ABORTED);
+        if let Some(catalog) = catalog.as_mut().filter(|_| !aborted) {
+            if let Err(e) = catalog.record_snapshot(&app, &volume_statuses) {
+                log::warn!("⚠️  Failed to record {} in the catalog: {e}", app.name);
+            }
+        }
+        summaries.push(AppSummary {
+            name: app.name.clone(),
+            volume_statuses,
+            skipped: None,
+            warnings,
+            anomalies,
+        });
+    }
+    for skipped in scan.skipped {
+        progress::emit("skipped", Some(&skipped.name), None, None);
+        summaries.push(AppSummary {
+            name: skipped.name,
+            volume_statuses: Vec::new(),
+            skipped: Some(skipped.reason),
+            warnings: Vec::new(),
+            anomalies: Vec::new(),
+        });
+    }
+    if shutdown::requested() {
+        shutdown::remove_helpers();
+    }
+    progress::emit("done", None, None, None);
+    telemetry::export();
+    Ok(summaries)
+}
+
+/// Runs `backup_project` between the project's `pre_backup` and `post_backup` hooks, and
+/// `on_failure` if anything failed. A failing `pre_backup` hook skips the backup. A project
+/// interrupted by a shutdown has its incomplete snapshot folder deleted.
+fn backup_project_with_hooks(
+    config: Arc<Config>,
+    app: BackupApplication,
+    backup_type: BackupType,
+    tags: &[String],
+) -> Result<(BackupApplication, Vec<BackupThingSummary>)> {
+    let project_path = app.application_path.to_string_lossy().to_string();
+    let backup_path = format!(
+        "{}/{}/{}",
+        config.remote_backup_path,
+        app.name,
+        app.snapshot_folder()
+    );
+    let name = app.name.clone();
+    let env = |status| HookEnv {
+        project: &name,
+        project_path: &project_path,
+        backup_path: &backup_path,
+        status,
+    };
+    let hook_failed = |hook: &str, e: anyhow::Error| {
+        log::error!("❌ {e}");
+        BackupThingSummary {
+            name: format!("{} hook", hook),
+            status: "❌ Hook failed".into(),
+            size: "-".into(),
+            duration: "-".into(),
+            volume_type: "Hook".into(),
+            stats: None,
+            downtime: None,
+        }
+    };
+
+    if shutdown::requested() {
+        return Ok((app, vec![aborted("Project", "-")]));
+    }
+    if let Err(e) = hooks::run(&config, Hook::PreBackup, &env("pending")) {
+        let failed = hook_failed("pre_backup", e);
+        if let Err(e) = hooks::run(&config, Hook::OnFailure, &env("failure")) {
+            log::error!("❌ {e}");
+        }
+        return Ok((app, vec![failed]));
+    }
+
+    let result = backup_project_quiesced(Arc::clone(&config), app, backup_type, tags);
+    if let Ok((_, statuses)) = &result {
+        if statuses.iter().any(|s| s.status == shutdown::ABORTED) {
+            match transport::open(&config).delete(&backup_path) {
+                Ok(()) => log::info!("🧹 Removed incomplete snapshot {}", backup_path),
+                Err(e) => log::error!(
+                    "❌ Failed to remove incomplete snapshot {}: {e}",
+                    backup_path
+                ),
+            }
+        }
+    }
+    let succeeded =
+        matches!(&result, Ok((_, statuses)) if statuses.iter().all(|s| s.status == "✅"));
+    let status = if succeeded { "success" } else { "failure" };
+    let mut hook_statuses = Vec::new();
+    if let Err(e) = hooks::run(&config, Hook::PostBackup, &env(status)) {
+        hook_statuses.push(hook_failed("post_backup", e));
+    }
+    if !succeeded || !hook_statuses.is_empty() {
+        if let Err(e) = hooks::run(&config, Hook::OnFailure, &env("failure")) {
+            hook_statuses.push(hook_failed("on_failure", e));
+        }
+    }
+    result.map(|(app, mut statuses)| {
+        statuses.extend(hook_statuses);
+        (app, statuses)
+    })
+}
+
+/// Dumps the project's databases if `db_dumps` is enabled, runs the dump commands from its
+/// compose labels and records or saves its images if `images` is enabled, then runs `backup_project` with its containers stopped or paused if
+/// `quiesce` is configured for it and records the downtime on every archive. With
+/// `fs_snapshots` the containers are only halted while the snapshots are taken. Dumps come
+/// first, they need the database running.
+fn backup_project_quiesced(
+    config: Arc<Config>,
+    mut app: BackupApplication,
+    backup_type: BackupType,
+    tags: &[String],
+) -> Result<(BackupApplication, Vec<BackupThingSummary>)> {
+    let remote_base = format!(
+        "{}/{}/{}",
+        config.remote_backup_path,
+        app.name,
+        app.snapshot_folder()
+    );
+    for vol in app.volumes.iter_mut() {
+        if matches!(vol.volume_type, VolumeType::Mount) {
+            vol.driver = scanner::volume_driver(&vol.docker_volume(&app.name));
+            if let Some(driver) = &vol.driver {
+                log::info!("🌐 Volume `{}` uses the {} driver", vol.name, driver.name);
+            }
+        }
+    }
+    let mut dumps = if config.db_dumps != "off" {
+        dbdump::dump_all(&config, &*transport::open(&config), &mut app, &remote_base)
+    } else {
+        Vec::new()
+    };
+    if !app.dump_commands.is_empty() {
+        dumps.extend(dbdump::dump_labelled(
+            &config,
+            &*transport::open(&config),
+            &app,
+            &remote_base,
+        ));
+    }
+    if config.images != "off" {
+        dumps.extend(images::backup_images(
+            &config,
+            &*transport::open(&config),
+            &mut app,
+            &remote_base,
+        ));
+    }
+    // Recorded in meta.json along with the archives' stats
+    app.stats = dumps
+        .iter()
+        .filter_map(|summary| summary.stats.clone())
+        .collect();
+    let with_dumps = |result: Result<(BackupApplication, Vec<BackupThingSummary>)>| {
+        result.map(|(app, mut statuses)| {
+            statuses.splice(0..0, dumps);
+            (app, statuses)
+        })
+    };
+
+    if config.fs_snapshots {
+        let snapshots = fssnap::Plan::for_project(&app)
+            .and_then(|plan| take_snapshots(&config, &plan, &mut app));
+        match snapshots {
+            Ok((snapshots, downtime)) => {
+                let result = backup_project(config, app, backup_type, tags);
+                snapshots.release();
+                return with_dumps(result.map(|(app, mut statuses)| {
+                    for status in statuses.iter_mut() {
+                        status.downtime = downtime;
+                    }
+                    (app, statuses)
+                }));
+            }
+            Err(e) => log::warn!("⚠️  {e:#}, backing up {} without snapshots", app.name),
+        }
+    }
+
+    let Some(quiesce) = Quiesce::for_project(&config, &app.name) else {
+        return with_dumps(backup_project(config, app, backup_type, tags));
+    };
+    let dir = app.application_path.clone();
+    log::info!("⏸  Containers of {}: {}", app.name, quiesce.name());
+    let halted = match quiesce.halt(&dir) {
+        Ok(()) => Some(Instant::now()),
+        Err(e) => {
+            log::warn!("⚠️  {e}, backing up {} while it runs", app.name);
+            // A partial stop leaves some containers down, bring them back before continuing
+            quiesce.resume(&dir).ok();
+            None
+        }
+    };
+
+    let result = backup_project(config, app, backup_type, tags);
+    let Some(halted) = halted else {
+        return with_dumps(result);
+    };
+    let resumed = quiesce.resume(&dir);
+    let downtime = halted.elapsed().as_secs_f64();
+    if let Err(e) = &resumed {
+        log::error!("❌ Failed to restart containers in {}: {e}", dir.display());
+    }
+    with_dumps(result.map(|(app, mut statuses)| {
+        for status in statuses.iter_mut() {
+            status.downtime = Some(downtime);
+        }
+        match resumed {
+            Ok(()) => log::info!(
+                "▶️  {} running again after {:.1} seconds",
+                app.name,
+                downtime
+            ),
+            Err(_) => {
+                statuses.push(BackupThingSummary {
+                    name: "Containers".into(),
+                    status: "❌ Restart failed".into(),
+                    size: "-".into(),
+                    duration: "-".into(),
+                    volume_type: "Quiesce".into(),
+                    stats: None,
+                    downtime: Some(downtime),
+                });
+            }
+        }
+        (app, statuses)
+    }))
+}
+
+/// Takes the filesystem snapshots of `plan` for `app`, with its containers halted only
+/// meanwhile if `quiesce` is configured. Returns the snapshots and that downtime.
+fn take_snapshots(
+    config: &Config,
+    plan: &fssnap::Plan,
+    app: &mut BackupApplication,
+) -> Result<(fssnap::Snapshots, Option<f64>)> {
+    let quiesce = Quiesce::for_project(config, &app.name);
+    let dir = app.application_path.clone();
+    let halted = quiesce.and_then(|quiesce| match quiesce.halt(&dir) {
+        Ok(()) => Some((quiesce, Instant::now())),
+        Err(e) => {
+            log::warn!("⚠️  {e}, snapshotting {} while it runs", app.name);
+            quiesce.resume(&dir).ok();
+            None
+        }
+    });
+    let snapshots = plan.take(app);
+    let downtime = halted.map(|(quiesce, halted)| {
+        if let Err(e) = quiesce.resume(&dir) {
+            log::error!("❌ Failed to restart containers in {}: {e}", dir.display());
+        }
+        halted.elapsed().as_secs_f64()
+    });
+    let snapshots = snapshots?;
+    log::info!("📸 Archiving {} from filesystem snapshots", app.name);
+    Ok((snapshots, downtime))
+}
+
+/// Backs up a single project and uploads its metadata. Temporary archives live in a
+/// directory of their own, so concurrent projects don't overwrite each other's files.
+fn backup_project(
+    config: Arc<Config>,
+    mut app: BackupApplication,
+    backup_type: BackupType,
+    tags: &[String],
+) -> Result<(BackupApplication, Vec<BackupThingSummary>)> {
+    let transport = transport::open(&config);
+    app.backup_type = Some(backup_type);
+    app.tags = tags.to_vec();
+    app.compression = Some(config.compression.clone());
+    app.encrypted = config.encryption.enabled();
+    app.encryption_method = app
+        .encrypted
+        .then(|| config.encryption.method().to_string());
+    app.dedup = config.dedup;
+    for vol in app.volumes.iter_mut() {
+        if let Some(globs) = config.excludes.get(&format!("{}.{}", app.name, vol.name)) {
+            vol.excludes.extend(globs.iter().cloned());
+        }
+    }
+    log::info!("🗂  Backing up: {}", app.name);
+    progress::emit("project_start", Some(&app.name), None, None);
+    let mut volume_statuses = Vec::new();
+    let timestamp_str = app.snapshot_folder();
+    let remote_base = format!(
+        "{}/{}/{}",
+        config.remote_backup_path, app.name, timestamp_str
+    );
+    transport.mkdir(&format!("{}/REPO", remote_base))?;
+    transport.mkdir(&format!("{}/VOLUMES", remote_base))?;
+
+    if config.incremental {
+        let volume_statuses = incremental::backup(&config, &*transport, &mut app, &remote_base);
+        progress::emit("project_done", Some(&app.name), None, None);
+        save_metadata(&config, &app, format!("{}/meta.json", remote_base))?;
+        return Ok((app, volume_statuses));
+    }
+
+    let work_dir = PathBuf::from("/tmp").join(format!("dockup-{}-{}", app.name, timestamp_str));
+    fs::create_dir_all(&work_dir)?;
+
+    if config.adaptive_compression && !config.dedup && config.compression != "none" {
+        for vol in app.volumes.iter_mut() {
+            if is_incompressible(&app.name, vol) {
+                vol.compression = Some("none".to_string());
+            }
+        }
+    }
+
+    let mut created_files: Vec<PathBuf> = Vec::new();
+    let start_repo_time = Local::now();
+    progress::emit("archive", Some(&app.name), Some("REPO"), None);
+    let (repo_tar, repo_original) = archiving(
+        &config,
+        &app.name,
+        "REPO",
+        &work_dir.join(app.repo_archive()),
+        || {
+            create_tar(
+                &config,
+                &app.application_path,
+                &[],
+                &config.compression,
+                &work_dir,
+                &app.repo_archive(),
+            )
+        },
+    )?;
+    let repo_tar = crypto::seal(&config, repo_tar)?;
+    created_files.push(repo_tar.clone());
+
+    let repo_bytes = file_bytes(&repo_tar);
+    progress::emit("upload", Some(&app.name), Some("REPO"), repo_bytes);
+    match upload(
+        &config,
+        &*transport,
+        &app.name,
+        "REPO",
+        &repo_tar,
+        &format!(
+            "{}/REPO/{}",
+            remote_base,
+            repo_tar.file_name().unwrap().to_string_lossy()
+        ),
+    ) {
+        Err(e) => {
             log::error!("❌ Failed to upload repo tarball: {e}");
-        } else {
+            progress::emit("failed", Some(&app.name), Some("REPO"), None);
+        }
+        Ok(sha256) => {
+            progress::emit("uploaded", Some(&app.name), Some("REPO"), repo_bytes);
             let repo_size = get_file_size(&repo_tar)?;
-            let duration = format!(
-                "{:.2} seconds",
-                (Local::now().timestamp_millis() - start_repo_time.timestamp_millis()) as f64
-                    / 1000.0
-            );
+            let seconds = (Local::now().timestamp_millis() - start_repo_time.timestamp_millis())
+                as f64
+                / 1000.0;
+            let duration = format!("{:.2} seconds", seconds);
             let repo_size_str = format!("{}", repo_size);
             let repo_summary = BackupThingSummary {
                 name: "REPO".to_string(),
@@ -76,261 +563,668 @@ pub fn run_backup(config: &Config, mode: bool) -> Result<Vec<AppSummary>> {
                 size: repo_size_str,
                 duration,
                 volume_type: "Repo".to_string(),
+                stats: Some(ArchiveStats {
+                    name: "REPO".to_string(),
+                    original_bytes: repo_original,
+                    compressed_bytes: repo_bytes.unwrap_or_default(),
+                    seconds,
+                    checksum: Some(Checksum {
+                        file: format!("REPO/{}", repo_tar.file_name().unwrap().to_string_lossy()),
+                        sha256,
+                    }),
+                }),
+                downtime: None,
             };
             volume_statuses.push(repo_summary);
         }
+    }
+
+    // Volumes run in blocking tasks, at most `max_concurrent_volumes` at a time, and are
+    // collected in compose order.
+    let runtime = tokio::runtime::Handle::current();
+    let limit = Arc::new(Semaphore::new(config.max_concurrent_volumes));
+    let project = Arc::new(app.clone());
+    let remote_base = Arc::new(remote_base);
+    let work_dir = Arc::new(work_dir);
+    let tasks: Vec<_> = app
+        .volumes
+        .iter()
+        .cloned()
+        .map(|vol| {
+            let config = Arc::clone(&config);
+            let limit = Arc::clone(&limit);
+            let project = Arc::clone(&project);
+            let remote_base = Arc::clone(&remote_base);
+            let work_dir = Arc::clone(&work_dir);
+            runtime.spawn(async move {
+                let _permit = limit.acquire_owned().await?;
+                if shutdown::requested() {
+                    return Ok((aborted(&vol.name, "-"), None));
+                }
+                let volume = tokio::task::spawn_blocking(move || {
+                    backup_volume(&config, &project, &vol, &remote_base, &work_dir)
+                });
+                anyhow::Ok(volume.await??)
+            })
+        })
+        .collect();
+    for task in tasks {
+        let (summary, created) = runtime.block_on(task)??;
+        created_files.extend(created);
+        volume_statuses.push(summary);
+    }
+    progress::emit("project_done", Some(&app.name), None, None);
+    app.stats.extend(
+        volume_statuses
+            .iter()
+            .filter_map(|summary| summary.stats.clone()),
+    );
 
+    // An aborted snapshot is deleted again, it gets no metadata
+    if !volume_statuses
+        .iter()
+        .any(|s| s.status == shutdown::ABORTED)
+    {
+        let remote_meta_path = format!("{}/meta.json", remote_base);
+        save_metadata(&config, &app, remote_meta_path)?;
+    }
+
+    for f in created_files {
+        if let Err(e) = fs::remove_file(&f) {
+            log::warn!("⚠️  Failed to delete temp file {:?}: {e}", f);
+        } else {
+            log::info!("🧹 Deleted temp file {:?}", f);
+        }
+    }
+    let _ = fs::remove_dir(work_dir.as_path());
+    Ok((app, volume_statuses))
+}
+
+/// Summary of an archive not made because of a shutdown.
+fn aborted(name: &str, volume_type: &str) -> BackupThingSummary {
+    BackupThingSummary {
+        name: name.to_string(),
+        status: shutdown::ABORTED.into(),
+        size: "-".into(),
+        duration: "-".into(),
+        volume_type: volume_type.to_string(),
+        stats: None,
+        downtime: None,
+    }
+}
+
+pub fn dry_run(config: &Config) -> Result<()> {
+    let scan = scan_projects(config)?;
+    let apps = scan.apps;
+    let timestamp = Local::now().format("%Y%m%d_%H%M").to_string();
+
+    log::info!("Starting dry run...");
+    plain_println!("\n{}", t("dry_run.config"));
+    println!(
+        "{}",
+        tf(
+            "dry_run.save_config",
+            &[&crypto::sealed_name(
+                config,
+                &format!("{}/config.json", config.remote_backup_path)
+            )]
+        )
+    );
+
+    for app in apps {
+        plain_println!("\n{}", tf("dry_run.project", &[&app.name]));
+        println!(
+            "{}",
+            tf(
+                "dry_run.create_folder",
+                &[&format!(
+                    "{}/{}/{}",
+                    config.remote_backup_path, app.name, timestamp
+                )]
+            )
+        );
+        println!(
+            "{}",
+            tf("dry_run.archive", &[&app.application_path.display()])
+        );
         for vol in &app.volumes {
-            let start_volume_time = Local::now();
-            let (_success, summary) = match vol.volume_type {
-                VolumeType::Bind => {
-                    // 🧱 Handle bind mount
-                    let sanitized = vol
-                        .path
-                        .to_string_lossy()
-                        .trim_start_matches("./")
-                        .replace('/', "_");
-                    let tar_name = format!("{sanitized}.tar.gz");
-                    match create_tar(&vol.path, &tar_name) {
+            println!("{}", tf("dry_run.archive_volume", &[&vol.name]));
+        }
+    }
+    for skipped in scan.skipped {
+        plain_println!(
+            "\n{}",
+            tf("dry_run.skip", &[&skipped.name, &skipped.reason])
+        );
+    }
+
+    Ok(())
+}
+
+/// Command writing an uncompressed tar stream of `src` to stdout, without paths matching
+/// `excludes` and without btrfs snapshots taken in it (see [`fssnap`]).
+fn tar_command(src: &Path, excludes: &[String]) -> Command {
+    let mut tar = Command::new("tar");
+    tar.args(["-cf", "-"])
+        .arg(format!("--exclude={}*", fssnap::SNAPSHOT_DIR_PREFIX))
+        .args(exclude_args(excludes))
+        .args(["-C", src.to_str().unwrap(), "."]);
+    tar
+}
+
+/// Command streaming a Docker volume as tar through a helper container, optionally cut
+/// off after `limit` bytes inside the container.
+pub fn volume_tar_command(volume: &str, excludes: &[String], limit: Option<u64>) -> Command {
+    // The excludes are passed as arguments to the script, so they need no quoting
+    let mut script = "tar -cf - \"$@\" -C /data .".to_string();
+    if let Some(limit) = limit {
+        script.push_str(&format!(" | head -c {}", limit));
+    }
+    let mut docker = Command::new("docker");
+    docker
+        .args([
+            "run",
+            "--rm",
+            "--label",
+            shutdown::HELPER_LABEL,
+            "-v",
+            &format!("{}:/data", volume),
+            "alpine",
+            "sh",
+            "-c",
+            &script,
+            "sh",
+        ])
+        .args(exclude_args(excludes));
+    docker
+}
+
+/// `--exclude` options for tar. A trailing slash would keep tar from matching the
+/// directory, so `cache/` excludes `cache` like it does in `.gitignore`.
+fn exclude_args(excludes: &[String]) -> Vec<String> {
+    excludes
+        .iter()
+        .map(|glob| format!("--exclude={}", glob.trim_end_matches('/')))
+        .collect()
+}
+
+/// Runs `archive` with heartbeats reporting the size of `output` so far.
+fn archiving<T>(
+    config: &Config,
+    project: &str,
+    item: &str,
+    output: &Path,
+    archive: impl FnOnce() -> T,
+) -> T {
+    let step = heartbeat::Step {
+        action: "archiving",
+        project,
+        item,
+        total: None,
+    };
+    heartbeat::run(
+        config.heartbeat_secs,
+        &step,
+        || fs::metadata(output).ok().map(|meta| meta.len()),
+        archive,
+    )
+}
+
+/// Uploads `local` to `remote` and checks the copy (see [`verify::put_verified`]), with
+/// heartbeats reporting how much arrived on the target. Returns the SHA-256 of `local`.
+fn upload(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    project: &str,
+    item: &str,
+    local: &Path,
+    remote: &str,
+) -> Result<String> {
+    let step = heartbeat::Step {
+        action: "uploading",
+        project,
+        item,
+        total: fs::metadata(local).ok().map(|meta| meta.len()),
+    };
+    heartbeat::run(
+        config.heartbeat_secs,
+        &step,
+        || {
+            transport::open(config)
+                .size(&format!("{}{}", remote, verify::PARTIAL_SUFFIX))
+                .ok()
+        },
+        || verify::put_verified(transport, local, remote),
+    )
+}
+
+/// Creates a compressed tarball of `src`, returns its path and the uncompressed size.
+fn create_tar(
+    config: &Config,
+    src: &PathBuf,
+    excludes: &[String],
+    algorithm: &str,
+    work_dir: &Path,
+    output: &str,
+) -> Result<(PathBuf, u64)> {
+    let output_path = work_dir.join(output);
+    let original = compression::write_archive(
+        tar_command(src, excludes),
+        algorithm,
+        config.compression_level,
+        &output_path,
+    )?;
+    Ok((output_path, original))
+}
+
+fn create_volume_tar(
+    config: &Config,
+    volume: &str,
+    excludes: &[String],
+    algorithm: &str,
+    work_dir: &Path,
+    tar_name: &str,
+) -> Result<(PathBuf, u64)> {
+    let output_path = work_dir.join(tar_name);
+
+    // The container only streams the tar, compression happens on the host
+    let original = compression::write_archive(
+        volume_tar_command(volume, excludes, None),
+        algorithm,
+        config.compression_level,
+        &output_path,
+    )
+    .with_context(|| format!("Failed to create tarball for volume: {}", volume))?;
+
+    Ok((output_path, original))
+}
+
+/// Archives, encrypts and uploads one volume. Returns its summary and the temporary
+/// archive to clean up.
+fn backup_volume(
+    config: &Config,
+    app: &BackupApplication,
+    vol: &Volume,
+    remote_base: &str,
+    work_dir: &Path,
+) -> Result<(BackupThingSummary, Option<PathBuf>)> {
+    let transport = transport::open(config);
+    if app.dedup {
+        let summary = backup_volume_dedup(config, &*transport, app, vol, remote_base, work_dir);
+        return Ok((summary, None));
+    }
+    let mut created = None;
+    let start_volume_time = Local::now();
+    progress::emit("archive", Some(&app.name), Some(&vol.name), None);
+    let (success, summary) = match vol.volume_type {
+        VolumeType::Bind => {
+            // 🧱 Handle bind mount
+            let tar_name = app.volume_archive(vol);
+            match archiving(
+                config,
+                &app.name,
+                &vol.name,
+                &work_dir.join(&tar_name),
+                || {
+                    create_tar(
+                        config,
+                        vol.snapshot.as_ref().unwrap_or(&vol.path),
+                        &vol.excludes,
+                        volume_algorithm(config, vol),
+                        work_dir,
+                        &tar_name,
+                    )
+                },
+            )
+            .and_then(|(tar, original)| Ok((crypto::seal(config, tar)?, original)))
+            {
+                Err(e) => {
+                    log::error!(
+                        "❌ Failed to create tarball for bind mount `{}`: {}",
+                        vol.name,
+                        e
+                    );
+                    (
+                        false,
+                        BackupThingSummary {
+                            name: vol.name.clone(),
+                            status: "❌ Failed to tar bind mount".into(),
+                            size: "-".into(),
+                            duration: "-".into(),
+                            volume_type: "Bind".to_string(),
+                            stats: None,
+                            downtime: None,
+                        },
+                    )
+                }
+                Ok((tar, original)) => {
+                    created = Some(tar.clone());
+                    let bytes = file_bytes(&tar);
+                    progress::emit("upload", Some(&app.name), Some(&vol.name), bytes);
+                    let upload_res = upload(
+                        config,
+                        &*transport,
+                        &app.name,
+                        &vol.name,
+                        &tar,
+                        &format!(
+                            "{}/VOLUMES/{}",
+                            remote_base,
+                            tar.file_name().unwrap().to_string_lossy()
+                        ),
+                    );
+                    let seconds = (Local::now().timestamp_millis()
+                        - start_volume_time.timestamp_millis())
+                        as f64
+                        / 1000.0;
+                    let duration = format!("{:.2} seconds", seconds);
+                    match upload_res {
                         Err(e) => {
-                            log::error!(
-                                "❌ Failed to create tarball for bind mount `{}`: {}",
-                                vol.name,
-                                e
-                            );
+                            log::error!("❌ Upload failed for bind mount `{}`: {}", vol.name, e);
                             (
                                 false,
                                 BackupThingSummary {
                                     name: vol.name.clone(),
-                                    status: "❌ Failed to tar bind mount".into(),
+                                    status: "❌ Upload failed".into(),
                                     size: "-".into(),
-                                    duration: "-".into(),
+                                    duration,
                                     volume_type: "Bind".to_string(),
+                                    stats: None,
+                                    downtime: None,
                                 },
                             )
                         }
-                        Ok(tar) => {
-                            created_files.push(tar.clone());
-                            let upload_res = scp_upload(
-                                config,
-                                &tar,
-                                &format!(
-                                    "{}/VOLUMES/{}",
-                                    remote_base,
-                                    tar.file_name().unwrap().to_string_lossy()
-                                ),
-                            );
-                            let duration = format!(
-                                "{:.2} seconds",
-                                (Local::now().timestamp_millis()
-                                    - start_volume_time.timestamp_millis())
-                                    as f64
-                                    / 1000.0
-                            );
-                            if let Err(e) = upload_res {
-                                log::error!(
-                                    "❌ Upload failed for bind mount `{}`: {}",
-                                    vol.name,
-                                    e
-                                );
-                                (
-                                    false,
-                                    BackupThingSummary {
-                                        name: vol.name.clone(),
-                                        status: "❌ Upload failed".into(),
-                                        size: "-".into(),
-                                        duration,
-                                        volume_type: "Bind".to_string(),
-                                    },
-                                )
-                            } else {
-                                let size = get_file_size(&tar)?;
-                                log::info!("✅ Bind mount `{}` backed up", vol.name);
-                                (
-                                    true,
-                                    BackupThingSummary {
+                        Ok(sha256) => {
+                            let size = get_file_size(&tar)?;
+                            log::info!("✅ Bind mount `{}` backed up", vol.name);
+                            progress::emit("uploaded", Some(&app.name), Some(&vol.name), bytes);
+                            (
+                                true,
+                                BackupThingSummary {
+                                    name: vol.name.clone(),
+                                    status: "✅".into(),
+                                    size,
+                                    duration,
+                                    volume_type: "Bind".to_string(),
+                                    stats: Some(ArchiveStats {
                                         name: vol.name.clone(),
-                                        status: "✅".into(),
-                                        size,
-                                        duration,
-                                        volume_type: "Bind".to_string(),
-                                    },
-                                )
-                            }
+                                        original_bytes: original,
+                                        compressed_bytes: bytes.unwrap_or_default(),
+                                        seconds,
+                                        checksum: Some(Checksum {
+                                            file: format!(
+                                                "VOLUMES/{}",
+                                                tar.file_name().unwrap().to_string_lossy()
+                                            ),
+                                            sha256,
+                                        }),
+                                    }),
+                                    downtime: None,
+                                },
+                            )
                         }
                     }
                 }
+            }
+        }
 
-                VolumeType::Mount => {
-                    // 📦 Handle Docker volume
-                    let docker_vol = format!("{}_{}", app.name, vol.name);
-                    let sanitized = vol
-                        .path
-                        .to_string_lossy()
-                        .trim_start_matches("./")
-                        .replace('/', "_");
-                    let tar_name = format!("{sanitized}.tar.gz");
-                    match create_volume_tar(&docker_vol, &tar_name) {
+        VolumeType::Mount => {
+            // 📦 Handle Docker volume
+            let docker_vol = vol.docker_volume(&app.name);
+            let tar_name = app.volume_archive(vol);
+            let archived = archiving(
+                config,
+                &app.name,
+                &vol.name,
+                &work_dir.join(&tar_name),
+                || match &vol.snapshot {
+                    Some(snapshot) => create_tar(
+                        config,
+                        snapshot,
+                        &vol.excludes,
+                        volume_algorithm(config, vol),
+                        work_dir,
+                        &tar_name,
+                    ),
+                    None => create_volume_tar(
+                        config,
+                        &docker_vol,
+                        &vol.excludes,
+                        volume_algorithm(config, vol),
+                        work_dir,
+                        &tar_name,
+                    ),
+                },
+            );
+            match archived.and_then(|(tar, original)| Ok((crypto::seal(config, tar)?, original))) {
+                Err(e) => {
+                    log::error!(
+                        "❌ Failed to create Docker volume tarball `{}`: {}",
+                        vol.name,
+                        e
+                    );
+                    (
+                        false,
+                        BackupThingSummary {
+                            name: vol.name.clone(),
+                            status: "❌ Failed to tar Docker volume".into(),
+                            size: "-".into(),
+                            duration: "-".into(),
+                            volume_type: "Docker".to_string(),
+                            stats: None,
+                            downtime: None,
+                        },
+                    )
+                }
+                Ok((tar, original)) => {
+                    created = Some(tar.clone());
+                    let bytes = file_bytes(&tar);
+                    progress::emit("upload", Some(&app.name), Some(&vol.name), bytes);
+                    let upload_res = upload(
+                        config,
+                        &*transport,
+                        &app.name,
+                        &vol.name,
+                        &tar,
+                        &format!(
+                            "{}/VOLUMES/{}",
+                            remote_base,
+                            tar.file_name().unwrap().to_string_lossy()
+                        ),
+                    );
+                    let seconds = (Local::now().timestamp_millis()
+                        - start_volume_time.timestamp_millis())
+                        as f64
+                        / 1000.0;
+                    let duration = format!("{:.2} seconds", seconds);
+                    match upload_res {
                         Err(e) => {
-                            log::error!(
-                                "❌ Failed to create Docker volume tarball `{}`: {}",
-                                vol.name,
-                                e
-                            );
+                            log::error!("❌ Upload failed for Docker volume `{}`: {}", vol.name, e);
                             (
                                 false,
                                 BackupThingSummary {
                                     name: vol.name.clone(),
-                                    status: "❌ Failed to tar Docker volume".into(),
+                                    status: "❌ Upload failed".into(),
                                     size: "-".into(),
-                                    duration: "-".into(),
+                                    duration,
                                     volume_type: "Docker".to_string(),
+                                    stats: None,
+                                    downtime: None,
                                 },
                             )
                         }
-                        Ok(tar) => {
-                            created_files.push(tar.clone());
-                            let upload_res = scp_upload(
-                                config,
-                                &tar,
-                                &format!(
-                                    "{}/VOLUMES/{}",
-                                    remote_base,
-                                    tar.file_name().unwrap().to_string_lossy()
-                                ),
-                            );
-                            let duration = format!(
-                                "{:.2} seconds",
-                                (Local::now().timestamp_millis()
-                                    - start_volume_time.timestamp_millis())
-                                    as f64
-                                    / 1000.0
-                            );
-                            if let Err(e) = upload_res {
-                                log::error!(
-                                    "❌ Upload failed for Docker volume `{}`: {}",
-                                    vol.name,
-                                    e
-                                );
-                                (
-                                    false,
-                                    BackupThingSummary {
-                                        name: vol.name.clone(),
-                                        status: "❌ Upload failed".into(),
-                                        size: "-".into(),
-                                        duration,
-                                        volume_type: "Docker".to_string(),
-                                    },
-                                )
-                            } else {
-                                let size = get_file_size(&tar)?;
-                                log::info!("✅ Docker volume `{}` backed up", vol.name);
-                                (
-                                    true,
-                                    BackupThingSummary {
+                        Ok(sha256) => {
+                            let size = get_file_size(&tar)?;
+                            log::info!("✅ Docker volume `{}` backed up", vol.name);
+                            progress::emit("uploaded", Some(&app.name), Some(&vol.name), bytes);
+                            (
+                                true,
+                                BackupThingSummary {
+                                    name: vol.name.clone(),
+                                    status: "✅".into(),
+                                    size,
+                                    duration,
+                                    volume_type: "Docker".to_string(),
+                                    stats: Some(ArchiveStats {
                                         name: vol.name.clone(),
-                                        status: "✅".into(),
-                                        size,
-                                        duration,
-                                        volume_type: "Docker".to_string(),
-                                    },
-                                )
-                            }
+                                        original_bytes: original,
+                                        compressed_bytes: bytes.unwrap_or_default(),
+                                        seconds,
+                                        checksum: Some(Checksum {
+                                            file: format!(
+                                                "VOLUMES/{}",
+                                                tar.file_name().unwrap().to_string_lossy()
+                                            ),
+                                            sha256,
+                                        }),
+                                    }),
+                                    downtime: None,
+                                },
+                            )
                         }
                     }
                 }
-            };
-
-            volume_statuses.push(summary);
-        }
-        summaries.push(AppSummary {
-            name: app.name.clone(),
-            volume_statuses,
-        });
-
-        let remote_meta_path = format!("{}/meta.json", remote_base);
-        save_metadata(config, &app, remote_meta_path)?;
-
-        for f in created_files {
-            if let Err(e) = fs::remove_file(&f) {
-                log::warn!("⚠️  Failed to delete temp file {:?}: {e}", f);
-            } else {
-                log::info!("🧹 Deleted temp file {:?}", f);
             }
         }
+    };
+
+    if !success {
+        progress::emit("failed", Some(&app.name), Some(&vol.name), None);
     }
-    Ok(summaries)
+    Ok((summary, created))
 }
 
-pub fn dry_run(config: &Config) -> Result<()> {
-    let apps = scan_projects(config)?;
-    let timestamp = Local::now().format("%Y%m%d_%H%M").to_string();
-
-    log::info!("Starting dry run...");
-    println!("\n🚧 Dry run: dockup config");
-    println!(
-        "   Would save dockup config to {}/config.json",
-        config.remote_backup_path
+/// Stores a volume's tar stream in the chunk store, uploading only chunks not on the
+/// target yet.
+fn backup_volume_dedup(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    app: &BackupApplication,
+    vol: &Volume,
+    remote_base: &str,
+    work_dir: &Path,
+) -> BackupThingSummary {
+    let started = Local::now();
+    let (producer, volume_type) = match vol.volume_type {
+        VolumeType::Bind => (
+            tar_command(vol.snapshot.as_ref().unwrap_or(&vol.path), &vol.excludes),
+            "Bind",
+        ),
+        VolumeType::Mount if vol.snapshot.is_some() => (
+            tar_command(vol.snapshot.as_ref().unwrap(), &vol.excludes),
+            "Docker",
+        ),
+        VolumeType::Mount => (
+            volume_tar_command(&vol.docker_volume(&app.name), &vol.excludes, None),
+            "Docker",
+        ),
+    };
+    let manifest = format!(
+        "{}/VOLUMES/{}{}",
+        remote_base,
+        app.volume_dir(vol),
+        dedup::MANIFEST_SUFFIX
     );
-
-    for app in apps {
-        println!("\n🚧 Dry run: {}", app.name);
-        println!(
-            "   Would create remote folder: {}/{}/{}",
-            config.remote_backup_path, app.name, timestamp
-        );
-        println!("   Would archive: {:?}", app.application_path);
-        for vol in &app.volumes {
-            println!("   Would archive volume: {}", vol.name);
+    progress::emit("upload", Some(&app.name), Some(&vol.name), None);
+    let result = ChunkStore::new(config, transport, work_dir).store(producer, &manifest);
+    let seconds = (Local::now().timestamp_millis() - started.timestamp_millis()) as f64 / 1000.0;
+    let duration = format!("{:.2} seconds", seconds);
+    match result {
+        Ok(stored) => {
+            log::info!(
+                "✅ `{}` stored, {} of {} chunks new",
+                vol.name,
+                stored.new_chunks,
+                stored.chunks
+            );
+            progress::emit(
+                "uploaded",
+                Some(&app.name),
+                Some(&vol.name),
+                Some(stored.uploaded_bytes),
+            );
+            BackupThingSummary {
+                name: vol.name.clone(),
+                status: "✅".into(),
+                size: stored.uploaded_bytes.to_string(),
+                duration,
+                volume_type: volume_type.to_string(),
+                stats: Some(ArchiveStats {
+                    name: vol.name.clone(),
+                    original_bytes: stored.original_bytes,
+                    compressed_bytes: stored.uploaded_bytes,
+                    seconds,
+                    checksum: None,
+                }),
+                downtime: None,
+            }
+        }
+        Err(e) => {
+            log::error!(
+                "❌ Failed to store `{}` in the chunk store: {}",
+                vol.name,
+                e
+            );
+            progress::emit("failed", Some(&app.name), Some(&vol.name), None);
+            BackupThingSummary {
+                name: vol.name.clone(),
+                status: "❌ Chunk upload failed".into(),
+                size: "-".into(),
+                duration,
+                volume_type: volume_type.to_string(),
+                stats: None,
+                downtime: None,
+            }
         }
     }
-
-    Ok(())
 }
 
-fn create_tar(src: &PathBuf, output: &str) -> Result<PathBuf> {
-    let output_path = PathBuf::from("/tmp").join(output);
-    let status = Command::new("tar")
-        .args([
-            "-czf",
-            output_path.to_str().unwrap(),
-            "-C",
-            src.to_str().unwrap(),
-            ".",
-        ])
-        .status()?;
-    if !status.success() {
-        anyhow::bail!("Failed to create tarball: {:?}", output_path);
-    }
-    Ok(output_path)
+fn volume_algorithm<'a>(config: &'a Config, volume: &'a Volume) -> &'a str {
+    volume
+        .compression
+        .as_deref()
+        .unwrap_or(config.compression.as_str())
 }
 
-fn create_volume_tar(volume: &str, tar_name: &str) -> Result<PathBuf> {
-    let output_path = PathBuf::from("/tmp").join(tar_name);
-
-    let status = Command::new("docker")
-        .args([
-            "run",
-            "--rm",
-            "-v",
-            &format!("{}:/data", volume),
-            "-v",
-            "/tmp:/backup",
-            "alpine",
-            "sh",
-            "-c",
-            &format!("tar -czf /backup/{} -C /data .", tar_name),
-        ])
-        .status()?;
-
-    if !status.success() {
-        anyhow::bail!("Failed to create tarball for volume: {}", volume);
+/// Samples the start of a volume's tar stream and tells whether compressing it is a waste
+/// of CPU time (media, already compressed database files, ...).
+fn is_incompressible(project: &str, volume: &Volume) -> bool {
+    let producer = match volume.volume_type {
+        VolumeType::Bind => tar_command(
+            volume.snapshot.as_ref().unwrap_or(&volume.path),
+            &volume.excludes,
+        ),
+        VolumeType::Mount if volume.snapshot.is_some() => {
+            tar_command(volume.snapshot.as_ref().unwrap(), &volume.excludes)
+        }
+        VolumeType::Mount => volume_tar_command(
+            &volume.docker_volume(project),
+            &volume.excludes,
+            Some(compression::SAMPLE_BYTES),
+        ),
+    };
+    match compression::sample_ratio(producer) {
+        Ok(Some(ratio)) if ratio >= compression::INCOMPRESSIBLE_RATIO => {
+            log::info!(
+                "🗜  Volume `{}` compresses to {:.0}% in a sample, storing it uncompressed",
+                volume.name,
+                ratio * 100.0
+            );
+            true
+        }
+        Ok(_) => false,
+        Err(e) => {
+            log::warn!("⚠️  Failed to sample volume `{}`: {e}", volume.name);
+            false
+        }
     }
+}
 
-    Ok(output_path)
+fn file_bytes(path: &PathBuf) -> Option<u64> {
+    fs::metadata(path).ok().map(|meta| meta.len())
 }
 
-fn get_file_size(path: &PathBuf) -> Result<String> {
+pub fn get_file_size(path: &PathBuf) -> Result<String> {
     let output = Command::new("du")
         .args(["-sh", path.to_str().unwrap()])
         .output()?;
@@ -342,86 +1236,165 @@ fn get_file_size(path: &PathBuf) -> Result<String> {
     Ok(size.to_string())
 }
 
-fn run_remote_cmd(cfg: &Config, cmd: &str) -> Result<()> {
-    let full_cmd = format!(
-        "ssh -i {} -p {} {}@{} '{}'",
-        cfg.ssh_key, cfg.ssh_port, cfg.ssh_user, cfg.ssh_host, cmd
-    );
-    let status = Command::new("sh").arg("-c").arg(full_cmd).status()?;
-    if !status.success() {
-        anyhow::bail!("SSH command failed: {}", cmd);
-    }
-    Ok(())
-}
-
-fn scp_upload(cfg: &Config, local: &PathBuf, remote_path: &str) -> Result<()> {
-    let remote = format!("{}@{}:{}", cfg.ssh_user, cfg.ssh_host, remote_path);
-    let status = Command::new("scp")
-        .args([
-            "-i",
-            &cfg.ssh_key,
-            "-P",
-            &cfg.ssh_port.to_string(),
-            local.to_str().unwrap(),
-            &remote,
-        ])
-        .status()?;
-    if !status.success() {
-        anyhow::bail!("SCP upload failed: {:?}", local);
-    }
-    Ok(())
-}
-
-// This saves the latest dockup config to the target location
-fn backup_config(config: &Config) -> Result<()> {
+// This saves the latest dockup config, schedule and state to the target location
+fn backup_config(config: &Config, apps: &[BackupApplication]) -> Result<()> {
     let config_path = dirs::home_dir()
         .ok_or_else(|| anyhow::anyhow!("Failed to get home directory"))?
         .join(".dockup")
         .join("config.json");
 
+    // The config holds the SSH, SMTP and S3 credentials, it is encrypted like the archives
+    let remote = format!("{}/config.json", config.remote_backup_path);
     log::info!(
-        "⚙️  Backing up config to: {}/config.json",
-        config.remote_backup_path
+        "⚙️  Backing up config to: {}",
+        crypto::sealed_name(config, &remote)
     );
-    if let Err(e) = scp_upload(
-        config,
-        &config_path,
-        &format!("{}", config.remote_backup_path),
-    ) {
-        log::error!("❌ Failed to upload config file: {e}");
+    match crypto::put_sealed(config, &*transport::open(config), &config_path, &remote) {
+        Ok(_) => log::info!("✅ Config file uploaded successfully"),
+        Err(e) => log::error!("❌ Failed to upload config file: {e:#}"),
+    }
+    if let Err(e) = schedule::backup_schedule(config) {
+        log::error!("❌ Failed to upload schedule.json: {e}");
+    }
+    if config.canary {
+        return Ok(());
+    }
+    if let Err(e) = state::backup_state(config, apps) {
+        log::error!("❌ Failed to upload the state: {e}");
     }
-    log::info!("✅ Config file uploaded successfully");
 
     Ok(())
 }
 
-pub fn save_metadata(
-    config: &Config,
-    app: &BackupApplication,
-    remote_path: String,
-) -> std::io::Result<()> {
-    let local_meta_path = PathBuf::from("/tmp").join(format!("{}_meta.json", app.name));
+/// Uploads the snapshot's `meta.json` (volumes, backup type, archive stats and checksums)
+/// to `remote_path`. Restore only lists snapshot folders with a `meta.json`, so it is
+/// written last, uploaded like an archive and a failed upload fails the project instead
+/// of leaving a snapshot that can't be restored.
+pub fn save_metadata(config: &Config, app: &BackupApplication, remote_path: String) -> Result<()> {
+    let local_meta_path =
+        PathBuf::from("/tmp").join(format!("{}_{}_meta.json", app.name, app.snapshot_folder()));
     let file = File::create(&local_meta_path)?;
     serde_json::to_writer_pretty(&file, app)?;
-    println!(
-        "✅ Metadata written locally to {}",
-        local_meta_path.display()
+    drop(file);
+
+    let uploaded = verify::put_verified(&*transport::open(config), &local_meta_path, &remote_path);
+    if let Err(e) = fs::remove_file(&local_meta_path) {
+        plain_eprintln!("⚠️  Failed to delete local meta.json: {}", e);
+    }
+    uploaded.with_context(|| format!("Failed to upload meta.json of {}", app.name))?;
+    plain_println!(
+        "✅ Uploaded meta.json to remote backup path: {}",
+        remote_path
     );
+    if let Err(e) = index::add(config, app) {
+        log::warn!("⚠️  Failed to add {} to the index: {e:#}", app.name);
+    }
+    Ok(())
+}
 
-    // Upload it
-    if let Err(e) = scp_upload(config, &local_meta_path, &remote_path) {
-        eprintln!("❌ Failed to upload meta.json: {}", e);
+pub fn save_last_run(
+    config: &Config,
+    backup_type: BackupType,
+    started: DateTime<Local>,
+    result: &Result<Vec<AppSummary>>,
+) {
+    let (projects, error) = match result {
+        Ok(summaries) => (
+            summaries
+                .iter()
+                .map(|summary| LastRunProject {
+                    name: summary.name.clone(),
+                    status: if summary.skipped.is_some() {
+                        "skipped".into()
+                    } else if summary.failed_count() == 0 && summary.anomalies.is_empty() {
+                        "success".into()
+                    } else {
+                        "failed".into()
+                    },
+                    archives: summary.volume_statuses.len(),
+                    failed_archives: summary.failed_count(),
+                    warnings: summary.warnings.clone(),
+                    anomalies: summary.anomalies.clone(),
+                })
+                .collect::<Vec<_>>(),
+            None,
+        ),
+        Err(e) => (Vec::new(), Some(e.to_string())),
+    };
+    let total_archives = projects.iter().map(|p| p.archives).sum();
+    let failed_archives = projects.iter().map(|p| p.failed_archives).sum();
+    let status = if error.is_some() {
+        "failed"
+    } else if shutdown::requested() {
+        "aborted"
+    } else if failed_archives > 0 || projects.iter().any(|p| !p.anomalies.is_empty()) {
+        "partial"
     } else {
-        println!(
-            "✅ Uploaded meta.json to remote backup path: {}",
-            remote_path
-        );
+        "success"
+    };
+    let last_run = LastRun {
+        status: status.to_string(),
+        backup_type,
+        started,
+        finished: Local::now(),
+        total_archives,
+        failed_archives,
+        error,
+        projects,
+    };
+
+    if let Err(e) = Catalog::open().and_then(|catalog| catalog.record_run(&last_run)) {
+        log::warn!("⚠️  Failed to record the run in the catalog: {e}");
     }
 
-    // Clean up local temp file
-    if let Err(e) = fs::remove_file(&local_meta_path) {
-        eprintln!("⚠️  Failed to delete local meta.json: {}", e);
+    let local_path = PathBuf::from("/tmp").join("dockup_last_run.json");
+    let remote_path = format!("{}/last_run.json", config.remote_backup_path);
+    let upload = File::create(&local_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| Ok(serde_json::to_writer_pretty(file, &last_run)?))
+        .and_then(|_| transport::open(config).put(&local_path, &remote_path));
+    match upload {
+        Ok(()) => log::info!("✅ Uploaded run summary to {}", remote_path),
+        Err(e) => log::error!("❌ Failed to upload last_run.json: {e}"),
     }
 
-    Ok(())
+    if let Err(e) = fs::remove_file(&local_path) {
+        log::warn!("⚠️  Failed to delete local last_run.json: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn archives_leave_out_excluded_paths() {
+        let root = TempDir::new("excludes");
+        let src = root.join("src");
+        fs::create_dir_all(src.join("cache")).unwrap();
+        fs::create_dir_all(src.join("logs")).unwrap();
+        fs::write(src.join("cache/blob"), "x").unwrap();
+        fs::write(src.join("logs/app.log"), "x").unwrap();
+        fs::write(src.join("logs/keep.txt"), "x").unwrap();
+        fs::write(src.join("data.db"), "x").unwrap();
+
+        let excludes = ["cache/".to_string(), "*.log".to_string()];
+        let archive = tar_command(&src, &excludes).output().unwrap();
+        assert!(archive.status.success());
+        let tarball = root.join("archive.tar");
+        fs::write(&tarball, archive.stdout).unwrap();
+        let listing = Command::new("tar")
+            .arg("-tf")
+            .arg(&tarball)
+            .output()
+            .unwrap();
+        let mut members: Vec<_> = String::from_utf8(listing.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        members.sort();
+        assert_eq!(members, ["./", "./data.db", "./logs/", "./logs/keep.txt"]);
+    }
 }
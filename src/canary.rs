@@ -0,0 +1,181 @@
+//! `dockup backup --canary <project>`: a trial run of the whole backup pipeline.
+//!
+//! After upgrading dockup or changing the config, one (ideally small) project is backed up
+//! the normal way, hooks, dumps and upload included, but into
+//! `<remote_backup_path>/.canary`. The canary snapshot is not part of the history: it isn't
+//! recorded in the catalog, listed for restore, pruned or written to `last_run.json`, and
+//! no report is sent. Only the newest canary snapshot of a project is kept on the target.
+//! The outcome is compared with the previous canary run and saved to
+//! `~/.dockup/canary.json`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{backup, config::Config, plain_println, transport};
+
+/// Folder below `remote_backup_path` holding the canary snapshots. Remote scans skip
+/// folders with a dot, so they never show up as a project.
+pub const CANARY_DIR: &str = ".canary";
+
+#[derive(Serialize, Deserialize)]
+struct CanaryRun {
+    project: String,
+    version: String,
+    started: DateTime<Local>,
+    finished: DateTime<Local>,
+    status: String,
+    archives: Vec<CanaryArchive>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CanaryArchive {
+    name: String,
+    status: String,
+    size: String,
+    duration: String,
+}
+
+fn canary_path() -> Result<PathBuf> {
+    Ok(dirs::home_dir()
+        .context("Failed to get home directory")?
+        .join(".dockup")
+        .join("canary.json"))
+}
+
+/// Backs up `project` into the canary folder and reports the outcome. Fails if any
+/// archive failed.
+pub async fn run(config: &Config, project: &str) -> Result<()> {
+    let mut canary = config.clone();
+    canary.remote_backup_path = format!("{}/{}", config.remote_backup_path, CANARY_DIR);
+    canary.canary = true;
+    log::info!("🐤 Canary run of {}", project);
+    let started = Local::now();
+    let summaries = backup::run_backup(
+        &canary,
+        false,
+        &["canary".to_string()],
+        &[project.to_string()],
+    )
+    .await?;
+    let summary = summaries
+        .into_iter()
+        .find(|summary| summary.name == project)
+        .context("The project was not backed up")?;
+
+    let run = CanaryRun {
+        project: project.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        started,
+        finished: Local::now(),
+        status: if summary.failed_count() == 0 {
+            "success".to_string()
+        } else {
+            "failed".to_string()
+        },
+        archives: summary
+            .volume_statuses
+            .iter()
+            .map(|archive| CanaryArchive {
+                name: archive.name.clone(),
+                status: archive.status.clone(),
+                size: archive.size.clone(),
+                duration: archive.duration.clone(),
+            })
+            .collect(),
+    };
+    let previous = canary_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str::<CanaryRun>(&content).ok())
+        .filter(|previous| previous.project == run.project);
+    print_run(&run, previous.as_ref());
+    for anomaly in &summary.anomalies {
+        plain_println!("🚨 {}", anomaly);
+    }
+    for warning in &summary.warnings {
+        plain_println!("⚠️  {}", warning);
+    }
+
+    let path = canary_path()?;
+    fs::write(&path, serde_json::to_string_pretty(&run)?)
+        .with_context(|| format!("Failed to write {:?}", path))?;
+    remove_older(&canary, project);
+
+    if run.status != "success" {
+        anyhow::bail!(
+            "Canary run of {} failed: {} of {} archives failed",
+            project,
+            summary.failed_count(),
+            run.archives.len()
+        );
+    }
+    Ok(())
+}
+
+/// Prints every archive of `run`, with the result of the previous canary run where it
+/// differs.
+fn print_run(run: &CanaryRun, previous: Option<&CanaryRun>) {
+    match previous {
+        Some(previous) => plain_println!(
+            "🐤 Canary {} with dockup {} (previous run {} with {}: {})",
+            run.status,
+            run.version,
+            previous.finished.format("%Y-%m-%d %H:%M"),
+            previous.version,
+            previous.status
+        ),
+        None => plain_println!("🐤 Canary {} with dockup {}", run.status, run.version),
+    }
+    for archive in &run.archives {
+        let before =
+            previous.and_then(|previous| previous.archives.iter().find(|a| a.name == archive.name));
+        let change = match before {
+            None if previous.is_some() => " (new)".to_string(),
+            Some(before) if before.status != archive.status => {
+                format!(" (was {})", before.status)
+            }
+            Some(before) if before.size != archive.size => format!(" (was {})", before.size),
+            _ => String::new(),
+        };
+        plain_println!(
+            "   {} {} {} {}{}",
+            archive.status,
+            archive.name,
+            archive.size,
+            archive.duration,
+            change
+        );
+    }
+    if let Some(previous) = previous {
+        for archive in &previous.archives {
+            if !run.archives.iter().any(|a| a.name == archive.name) {
+                plain_println!("   ➖ {} (missing since the previous run)", archive.name);
+            }
+        }
+    }
+}
+
+/// Deletes all but the newest canary snapshot of `project`.
+fn remove_older(canary: &Config, project: &str) {
+    let transport = transport::open(canary);
+    let dir = format!("{}/{}", canary.remote_backup_path, project);
+    let mut folders = match transport.list(&dir) {
+        Ok(folders) => folders,
+        Err(e) => {
+            log::warn!("⚠️  Could not list the canary snapshots: {e}");
+            return;
+        }
+    };
+    // Snapshot folders are timestamps, so the newest sorts last
+    folders.retain(|folder| !folder.contains('.'));
+    folders.sort();
+    folders.pop();
+    for folder in folders {
+        if let Err(e) = transport.delete(&format!("{}/{}", dir, folder)) {
+            log::warn!("⚠️  Failed to remove canary snapshot {}: {e}", folder);
+        }
+    }
+}
@@ -0,0 +1,1009 @@
+//! Local SQLite catalog of backup snapshots and runs in `~/.dockup/catalog.db`.
+//!
+//! Every backup run records its snapshots (with the full `meta.json`), the per-archive
+//! results and a run summary. The catalog can be moved between machines as JSON with
+//! `dockup catalog export` / `import`, or reconstructed from the snapshot metadata on the
+//! backup target with `dockup catalog rebuild`. Bytes moved to and from each backup target
+//! are summed up per month for `dockup usage --transfer`. `dockup verify` records the
+//! outcome of each verification.
+//!
+//! The catalog doubles as a cache of the snapshots on the target: the restore TUI lists
+//! them from here and works offline. `dockup catalog refresh` (or the refresh key in the
+//! TUI) brings it in line with the target's index.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::{
+    backup::{BackupThingSummary, LastRun},
+    config::{Config, PRIMARY_TARGET},
+    crypto, dedup, index,
+    restore::{scan_backup_target, scan_snapshot_folders},
+    scanner::{BackupApplication, VolumeType},
+    transport,
+    utils::format_bytes,
+};
+
+/// Version of the JSON export format.
+const EXPORT_VERSION: u32 = 1;
+
+pub struct Catalog {
+    conn: Connection,
+}
+
+/// Portable form of the whole catalog, as written by `dockup catalog export`.
+#[derive(Serialize, Deserialize)]
+pub struct CatalogExport {
+    pub version: u32,
+    pub snapshots: Vec<SnapshotRecord>,
+    pub runs: Vec<RunRecord>,
+    #[serde(default)]
+    pub verifications: Vec<VerificationRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotRecord {
+    pub meta: BackupApplication,
+    pub archives: Vec<ArchiveRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchiveRecord {
+    pub name: String,
+    pub volume_type: String,
+    pub status: String,
+    pub size: String,
+    pub duration: String,
+    /// Uncompressed size, missing for failed archives and rebuilt entries.
+    #[serde(default)]
+    pub original_bytes: Option<u64>,
+    #[serde(default)]
+    pub compressed_bytes: Option<u64>,
+    #[serde(default)]
+    pub seconds: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RunRecord {
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    pub backup_type: String,
+    pub status: String,
+    pub total_archives: usize,
+    pub failed_archives: usize,
+    pub error: Option<String>,
+}
+
+/// Outcome of one `dockup verify` run.
+#[derive(Serialize, Deserialize)]
+pub struct VerificationRecord {
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    /// Whether it was started by `verify_schedule`.
+    pub scheduled: bool,
+    pub verified: usize,
+    pub failed: usize,
+    /// Archives that failed, `project/snapshot/file: reason` per line.
+    pub error: Option<String>,
+}
+
+/// Bytes moved to and from one target in one month (`YYYY-MM`).
+pub struct TransferRecord {
+    pub target: String,
+    pub month: String,
+    pub uploaded: u64,
+    pub downloaded: u64,
+}
+
+/// Stored snapshots of one project, as recorded in the catalog.
+pub struct StoredRecord {
+    pub project: String,
+    pub snapshots: u64,
+    pub bytes: u64,
+}
+
+/// Catalog summary of one project, exposed by `dockup serve` on `/metrics`.
+pub struct ProjectMetrics {
+    pub project: String,
+    pub last_success: Option<DateTime<Local>>,
+    pub last_bytes: u64,
+    pub failed_archives: u64,
+}
+
+pub fn catalog_path() -> Result<PathBuf> {
+    Ok(dirs::home_dir()
+        .context("Failed to get home directory")?
+        .join(".dockup")
+        .join("catalog.db"))
+}
+
+impl Catalog {
+    pub fn open() -> Result<Self> {
+        let path = catalog_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(&path)
+            .with_context(|| format!("Failed to open catalog {}", path.display()))?;
+        // Concurrent backups record their transfers from several connections
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        let catalog = Self { conn };
+        catalog.migrate()?;
+        Ok(catalog)
+    }
+
+    fn migrate(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS snapshots (
+                project TEXT NOT NULL,
+                folder TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                meta TEXT NOT NULL,
+                PRIMARY KEY (project, folder)
+            );
+            CREATE TABLE IF NOT EXISTS archives (
+                project TEXT NOT NULL,
+                folder TEXT NOT NULL,
+                name TEXT NOT NULL,
+                volume_type TEXT NOT NULL,
+                status TEXT NOT NULL,
+                size TEXT NOT NULL,
+                duration TEXT NOT NULL,
+                PRIMARY KEY (project, folder, name)
+            );
+            CREATE TABLE IF NOT EXISTS runs (
+                started TEXT PRIMARY KEY,
+                finished TEXT NOT NULL,
+                backup_type TEXT NOT NULL,
+                status TEXT NOT NULL,
+                total_archives INTEGER NOT NULL,
+                failed_archives INTEGER NOT NULL,
+                error TEXT
+            );",
+        )?;
+        let version: u32 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < 1 {
+            self.conn.execute_batch(
+                "ALTER TABLE archives ADD COLUMN original_bytes INTEGER;
+                ALTER TABLE archives ADD COLUMN compressed_bytes INTEGER;
+                ALTER TABLE archives ADD COLUMN seconds REAL;
+                PRAGMA user_version = 1;",
+            )?;
+        }
+        if version < 2 {
+            self.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS transfer (
+                    target TEXT NOT NULL,
+                    month TEXT NOT NULL,
+                    uploaded INTEGER NOT NULL,
+                    downloaded INTEGER NOT NULL,
+                    PRIMARY KEY (target, month)
+                );
+                PRAGMA user_version = 2;",
+            )?;
+        }
+        if version < 3 {
+            self.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS verifications (
+                    started TEXT PRIMARY KEY,
+                    finished TEXT NOT NULL,
+                    scheduled INTEGER NOT NULL,
+                    verified INTEGER NOT NULL,
+                    failed INTEGER NOT NULL,
+                    error TEXT
+                );
+                PRAGMA user_version = 3;",
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Adds transferred bytes to the current month of `target`.
+    pub fn record_transfer(&self, target: &str, uploaded: u64, downloaded: u64) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO transfer (target, month, uploaded, downloaded) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (target, month) DO UPDATE SET
+                uploaded = uploaded + excluded.uploaded,
+                downloaded = downloaded + excluded.downloaded",
+            params![
+                target,
+                Local::now().format("%Y-%m").to_string(),
+                uploaded,
+                downloaded
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Transferred bytes per target and month, newest month first.
+    pub fn transfer(&self) -> Result<Vec<TransferRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT target, month, uploaded, downloaded FROM transfer
+             ORDER BY month DESC, target",
+        )?;
+        let records = stmt
+            .query_map([], |row| {
+                Ok(TransferRecord {
+                    target: row.get(0)?,
+                    month: row.get(1)?,
+                    uploaded: row.get(2)?,
+                    downloaded: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+
+    /// Per project: number of snapshots and the sum of their uploaded archive sizes.
+    pub fn stored(&self) -> Result<Vec<StoredRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT s.project, COUNT(DISTINCT s.folder), SUM(COALESCE(a.compressed_bytes, 0))
+             FROM snapshots s
+             JOIN archives a ON a.project = s.project AND a.folder = s.folder
+             GROUP BY s.project
+             ORDER BY s.project",
+        )?;
+        let records = stmt
+            .query_map([], |row| {
+                Ok(StoredRecord {
+                    project: row.get(0)?,
+                    snapshots: row.get(1)?,
+                    bytes: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+
+    /// Records a snapshot and its archive results, replacing an earlier entry.
+    pub fn record_snapshot(
+        &mut self,
+        meta: &BackupApplication,
+        archives: &[BackupThingSummary],
+    ) -> Result<()> {
+        let record = SnapshotRecord {
+            meta: meta.clone(),
+            archives: archives
+                .iter()
+                .map(|archive| ArchiveRecord {
+                    name: archive.name.clone(),
+                    volume_type: archive.volume_type.clone(),
+                    status: archive.status.clone(),
+                    size: archive.size.clone(),
+                    duration: archive.duration.clone(),
+                    original_bytes: archive.stats.as_ref().map(|s| s.original_bytes),
+                    compressed_bytes: archive.stats.as_ref().map(|s| s.compressed_bytes),
+                    seconds: archive.stats.as_ref().map(|s| s.seconds),
+                })
+                .collect(),
+        };
+        let tx = self.conn.transaction()?;
+        insert_snapshot(&tx, &record)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn record_run(&self, run: &LastRun) -> Result<()> {
+        insert_run(&self.conn, &run_record(run))
+    }
+
+    pub fn record_verification(&self, verification: &VerificationRecord) -> Result<()> {
+        insert_verification(&self.conn, verification)
+    }
+
+    /// The most recent verification run.
+    pub fn last_verification(&self) -> Result<Option<VerificationRecord>> {
+        Ok(self.verifications(true)?.pop())
+    }
+
+    /// Verification runs, oldest first, or only the newest one with `last`.
+    fn verifications(&self, last: bool) -> Result<Vec<VerificationRecord>> {
+        let mut stmt = self.conn.prepare(if last {
+            "SELECT started, finished, scheduled, verified, failed, error
+             FROM verifications ORDER BY started DESC LIMIT 1"
+        } else {
+            "SELECT started, finished, scheduled, verified, failed, error
+             FROM verifications ORDER BY started"
+        })?;
+        let verifications = stmt
+            .query_map([], |row| {
+                Ok(VerificationRecord {
+                    started: parse_time(row.get(0)?),
+                    finished: parse_time(row.get(1)?),
+                    scheduled: row.get(2)?,
+                    verified: row.get(3)?,
+                    failed: row.get(4)?,
+                    error: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(verifications)
+    }
+
+    pub fn export(&self) -> Result<CatalogExport> {
+        let mut snapshots = Vec::new();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT project, folder, meta FROM snapshots ORDER BY project, timestamp")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (project, folder, meta) = row?;
+            let meta: BackupApplication = serde_json::from_str(&meta)
+                .with_context(|| format!("Corrupt catalog entry {}/{}", project, folder))?;
+            let archives = self.archives(&project, &folder)?;
+            snapshots.push(SnapshotRecord { meta, archives });
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT started, finished, backup_type, status, total_archives, failed_archives, error
+             FROM runs ORDER BY started",
+        )?;
+        let runs = stmt
+            .query_map([], |row| {
+                Ok(RunRecord {
+                    started: parse_time(row.get::<_, String>(0)?),
+                    finished: parse_time(row.get::<_, String>(1)?),
+                    backup_type: row.get(2)?,
+                    status: row.get(3)?,
+                    total_archives: row.get(4)?,
+                    failed_archives: row.get(5)?,
+                    error: row.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(CatalogExport {
+            version: EXPORT_VERSION,
+            snapshots,
+            runs,
+            verifications: self.verifications(false)?,
+        })
+    }
+
+    /// Merges an export into the catalog. Entries already present are overwritten.
+    pub fn import(&mut self, export: &CatalogExport) -> Result<()> {
+        if export.version > EXPORT_VERSION {
+            anyhow::bail!(
+                "Catalog export version {} is newer than supported ({})",
+                export.version,
+                EXPORT_VERSION
+            );
+        }
+        let tx = self.conn.transaction()?;
+        for snapshot in &export.snapshots {
+            insert_snapshot(&tx, snapshot)?;
+        }
+        for run in &export.runs {
+            insert_run(&tx, run)?;
+        }
+        for verification in &export.verifications {
+            insert_verification(&tx, verification)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replaces all snapshots with the ones found on the backup target. Archive results
+    /// are reconstructed from the files present (status and size only), run history is
+    /// kept and the remote `last_run.json` is added to it. The target is scanned folder by
+    /// folder and its `index.json` rewritten from the result.
+    pub fn rebuild(&mut self, config: &Config) -> Result<usize> {
+        let backups = scan_snapshot_folders(config)?;
+        if !config.read_only {
+            index::write(config, &backups)?;
+        }
+        let transport = transport::open(config);
+
+        let mut records = Vec::new();
+        for meta in backups {
+            let base = format!(
+                "{}/{}/{}",
+                config.remote_backup_path,
+                meta.name,
+                meta.snapshot_folder()
+            );
+            let suffix = match meta.encrypted {
+                true => crypto::suffix(meta.encryption_method.as_deref().unwrap_or("age")),
+                false => "",
+            };
+            if meta.incremental {
+                let mut archives = vec![remote_dir(
+                    &*transport,
+                    "REPO",
+                    "Repo",
+                    &format!("{}/REPO", base),
+                )];
+                for vol in &meta.volumes {
+                    let volume_type = match vol.volume_type {
+                        VolumeType::Bind => "Bind",
+                        VolumeType::Mount => "Docker",
+                    };
+                    archives.push(remote_dir(
+                        &*transport,
+                        &vol.name,
+                        volume_type,
+                        &format!("{}/VOLUMES/{}", base, meta.volume_dir(vol)),
+                    ));
+                }
+                log::info!("📇 {} {}", meta.name, meta.snapshot_folder());
+                records.push(SnapshotRecord { meta, archives });
+                continue;
+            }
+            let mut archives = vec![remote_archive(
+                &*transport,
+                "REPO",
+                "Repo",
+                &format!("{}/REPO/{}{}", base, meta.repo_archive(), suffix),
+            )];
+            for vol in &meta.volumes {
+                let path = match meta.dedup {
+                    true => format!(
+                        "{}/VOLUMES/{}{}",
+                        base,
+                        meta.volume_dir(vol),
+                        dedup::MANIFEST_SUFFIX
+                    ),
+                    false => format!("{}/VOLUMES/{}{}", base, meta.volume_archive(vol), suffix),
+                };
+                let volume_type = match vol.volume_type {
+                    VolumeType::Bind => "Bind",
+                    VolumeType::Mount => "Docker",
+                };
+                archives.push(remote_archive(&*transport, &vol.name, volume_type, &path));
+            }
+            log::info!("📇 {} {}", meta.name, meta.snapshot_folder());
+            records.push(SnapshotRecord { meta, archives });
+        }
+
+        let last_run = transport
+            .read(&format!("{}/last_run.json", config.remote_backup_path))
+            .ok()
+            .and_then(|json| serde_json::from_str::<LastRun>(&json).ok());
+
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM archives", [])?;
+        tx.execute("DELETE FROM snapshots", [])?;
+        for record in &records {
+            insert_snapshot(&tx, record)?;
+        }
+        if let Some(run) = &last_run {
+            insert_run(&tx, &run_record(run))?;
+        }
+        tx.commit()?;
+        Ok(records.len())
+    }
+
+    /// Metadata of every snapshot in the catalog, by project and oldest first.
+    pub fn snapshots(&self) -> Result<Vec<BackupApplication>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT project, folder, meta FROM snapshots ORDER BY project, timestamp")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        let mut snapshots = Vec::new();
+        for row in rows {
+            let (project, folder, meta) = row?;
+            snapshots.push(
+                serde_json::from_str(&meta)
+                    .with_context(|| format!("Corrupt catalog entry {}/{}", project, folder))?,
+            );
+        }
+        Ok(snapshots)
+    }
+
+    /// Brings the snapshots in line with the target's index: snapshots no longer on the
+    /// target are removed, new ones (e.g. backed up from another host) are added with the
+    /// archive stats of their `meta.json`. Unlike `rebuild` no archive is looked at.
+    /// Returns the number of snapshots added and removed. The catalog only records the
+    /// primary target, refreshing it from another one would drop snapshots it doesn't have.
+    pub fn refresh(&mut self, config: &Config) -> Result<(usize, usize)> {
+        if config.target_name != PRIMARY_TARGET {
+            anyhow::bail!(
+                "The catalog records the {} target, not {}",
+                PRIMARY_TARGET,
+                config.target_name
+            );
+        }
+        let on_target = scan_backup_target(config)?;
+        let mut known: HashSet<(String, String)> = self
+            .snapshots()?
+            .iter()
+            .map(|meta| (meta.name.clone(), meta.snapshot_folder()))
+            .collect();
+        let tx = self.conn.transaction()?;
+        let mut added = 0;
+        for meta in on_target {
+            if known.remove(&(meta.name.clone(), meta.snapshot_folder())) {
+                continue;
+            }
+            let archives = archives_from_stats(&meta);
+            insert_snapshot(&tx, &SnapshotRecord { meta, archives })?;
+            added += 1;
+        }
+        // What is left wasn't found on the target
+        for (project, folder) in &known {
+            delete_snapshot(&tx, project, folder)?;
+        }
+        tx.commit()?;
+        Ok((added, known.len()))
+    }
+
+    /// Removes pruned snapshots.
+    pub fn remove_snapshots(&mut self, snapshots: &[&BackupApplication]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for meta in snapshots {
+            delete_snapshot(&tx, &meta.name, &meta.snapshot_folder())?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Average uncompressed size of an archive over the last `last` snapshots it was backed
+    /// up successfully in, with the number of snapshots averaged.
+    pub fn archive_average(
+        &self,
+        project: &str,
+        name: &str,
+        last: usize,
+    ) -> Result<Option<(f64, usize)>> {
+        let (average, count): (Option<f64>, i64) = self.conn.query_row(
+            "SELECT AVG(bytes), COUNT(*) FROM (
+                SELECT a.original_bytes AS bytes FROM archives a
+                JOIN snapshots s ON s.project = a.project AND s.folder = a.folder
+                WHERE a.project = ?1 AND a.name = ?2 AND a.status = '✅'
+                  AND a.original_bytes IS NOT NULL
+                ORDER BY s.timestamp DESC LIMIT ?3
+             )",
+            params![project, name, last as i64],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok(average.map(|average| (average, count as usize)))
+    }
+
+    /// Uploaded size of an archive in the newest snapshot taken at or before `before`.
+    pub fn archive_bytes_before(
+        &self,
+        project: &str,
+        name: &str,
+        before: DateTime<Local>,
+    ) -> Result<Option<u64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT a.compressed_bytes FROM archives a
+             JOIN snapshots s ON s.project = a.project AND s.folder = a.folder
+             WHERE a.project = ?1 AND a.name = ?2 AND a.compressed_bytes IS NOT NULL
+               AND s.timestamp <= ?3
+             ORDER BY s.timestamp DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query(params![project, name, before.to_rfc3339()])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Per project: newest fully successful snapshot, uploaded size of the newest snapshot
+    /// and the number of failed archives over all snapshots.
+    pub fn project_metrics(&self) -> Result<Vec<ProjectMetrics>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT s.project, s.timestamp,
+                    SUM(CASE WHEN a.status = '✅' THEN 0 ELSE 1 END),
+                    SUM(COALESCE(a.compressed_bytes, 0))
+             FROM snapshots s
+             JOIN archives a ON a.project = s.project AND a.folder = s.folder
+             GROUP BY s.project, s.folder
+             ORDER BY s.project, s.timestamp",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    parse_time(row.get(1)?),
+                    row.get::<_, u64>(2)?,
+                    row.get::<_, u64>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut metrics: Vec<ProjectMetrics> = Vec::new();
+        for (project, timestamp, failed, bytes) in rows {
+            let entry = match metrics.last_mut() {
+                Some(last) if last.project == project => last,
+                _ => {
+                    metrics.push(ProjectMetrics {
+                        project,
+                        last_success: None,
+                        last_bytes: 0,
+                        failed_archives: 0,
+                    });
+                    metrics.last_mut().expect("just pushed")
+                }
+            };
+            if failed == 0 {
+                entry.last_success = Some(timestamp);
+            }
+            entry.last_bytes = bytes;
+            entry.failed_archives += failed;
+        }
+        Ok(metrics)
+    }
+
+    /// The newest `limit` snapshots of `project` with their archives, newest first.
+    pub fn project_snapshots(&self, project: &str, limit: usize) -> Result<Vec<SnapshotRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT folder, meta FROM snapshots WHERE project = ?1
+             ORDER BY timestamp DESC LIMIT ?2",
+        )?;
+        let rows = stmt
+            .query_map(params![project, limit as i64], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut snapshots = Vec::new();
+        for (folder, meta) in rows {
+            let meta: BackupApplication = serde_json::from_str(&meta)
+                .with_context(|| format!("Corrupt catalog entry {}/{}", project, folder))?;
+            let archives = self.archives(project, &folder)?;
+            snapshots.push(SnapshotRecord { meta, archives });
+        }
+        Ok(snapshots)
+    }
+
+    /// Snapshots taken since `since` with their archives, of every project or only of
+    /// `project`, oldest first.
+    pub fn history(
+        &self,
+        since: DateTime<Local>,
+        project: Option<&str>,
+    ) -> Result<Vec<SnapshotRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT project, folder, meta FROM snapshots
+             WHERE timestamp >= ?1 AND (?2 IS NULL OR project = ?2)
+             ORDER BY timestamp",
+        )?;
+        let rows = stmt
+            .query_map(params![since.to_rfc3339(), project], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut snapshots = Vec::new();
+        for (project, folder, meta) in rows {
+            let meta: BackupApplication = serde_json::from_str(&meta)
+                .with_context(|| format!("Corrupt catalog entry {}/{}", project, folder))?;
+            let archives = self.archives(&project, &folder)?;
+            snapshots.push(SnapshotRecord { meta, archives });
+        }
+        Ok(snapshots)
+    }
+
+    /// The most recent backup run.
+    pub fn last_run(&self) -> Result<Option<RunRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT started, finished, backup_type, status, total_archives, failed_archives,
+                    error FROM runs ORDER BY started DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query([])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(RunRecord {
+                started: parse_time(row.get(0)?),
+                finished: parse_time(row.get(1)?),
+                backup_type: row.get(2)?,
+                status: row.get(3)?,
+                total_archives: row.get(4)?,
+                failed_archives: row.get(5)?,
+                error: row.get(6)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    fn archives(&self, project: &str, folder: &str) -> Result<Vec<ArchiveRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, volume_type, status, size, duration, original_bytes,
+                    compressed_bytes, seconds FROM archives
+             WHERE project = ?1 AND folder = ?2 ORDER BY name",
+        )?;
+        let archives = stmt
+            .query_map(params![project, folder], |row| {
+                Ok(ArchiveRecord {
+                    name: row.get(0)?,
+                    volume_type: row.get(1)?,
+                    status: row.get(2)?,
+                    size: row.get(3)?,
+                    duration: row.get(4)?,
+                    original_bytes: row.get(5)?,
+                    compressed_bytes: row.get(6)?,
+                    seconds: row.get(7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(archives)
+    }
+}
+
+fn run_record(run: &LastRun) -> RunRecord {
+    RunRecord {
+        started: run.started,
+        finished: run.finished,
+        backup_type: run.backup_type.to_string(),
+        status: run.status.clone(),
+        total_archives: run.total_archives,
+        failed_archives: run.failed_archives,
+        error: run.error.clone(),
+    }
+}
+
+/// Archive result as far as it can be told from the backup target: present (with its
+/// size) or missing.
+fn remote_archive(
+    transport: &dyn transport::StorageBackend,
+    name: &str,
+    volume_type: &str,
+    path: &str,
+) -> ArchiveRecord {
+    let (status, size) = match transport.size(path) {
+        Ok(size) => ("✅".to_string(), size.to_string()),
+        Err(_) => ("❌ Missing on target".to_string(), "-".to_string()),
+    };
+    ArchiveRecord {
+        name: name.to_string(),
+        volume_type: volume_type.to_string(),
+        status,
+        size,
+        duration: "-".to_string(),
+        original_bytes: None,
+        compressed_bytes: None,
+        seconds: None,
+    }
+}
+
+/// Result of an incremental snapshot's directory: present or missing. Its size is not
+/// known without walking it.
+fn remote_dir(
+    transport: &dyn transport::StorageBackend,
+    name: &str,
+    volume_type: &str,
+    path: &str,
+) -> ArchiveRecord {
+    let status = match transport.list(path) {
+        Ok(_) => "✅".to_string(),
+        Err(_) => "❌ Missing on target".to_string(),
+    };
+    ArchiveRecord {
+        name: name.to_string(),
+        volume_type: volume_type.to_string(),
+        status,
+        size: "-".to_string(),
+        duration: "-".to_string(),
+        original_bytes: None,
+        compressed_bytes: None,
+        seconds: None,
+    }
+}
+
+fn insert_snapshot(conn: &Connection, record: &SnapshotRecord) -> Result<()> {
+    let meta = &record.meta;
+    let folder = meta.snapshot_folder();
+    conn.execute(
+        "INSERT OR REPLACE INTO snapshots (project, folder, timestamp, meta)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            meta.name,
+            folder,
+            meta.timestamp.to_rfc3339(),
+            serde_json::to_string(meta)?
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM archives WHERE project = ?1 AND folder = ?2",
+        params![meta.name, folder],
+    )?;
+    for archive in &record.archives {
+        conn.execute(
+            "INSERT INTO archives (project, folder, name, volume_type, status, size, duration,
+                                   original_bytes, compressed_bytes, seconds)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                meta.name,
+                folder,
+                archive.name,
+                archive.volume_type,
+                archive.status,
+                archive.size,
+                archive.duration,
+                archive.original_bytes,
+                archive.compressed_bytes,
+                archive.seconds
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn delete_snapshot(conn: &Connection, project: &str, folder: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM archives WHERE project = ?1 AND folder = ?2",
+        params![project, folder],
+    )?;
+    conn.execute(
+        "DELETE FROM snapshots WHERE project = ?1 AND folder = ?2",
+        params![project, folder],
+    )?;
+    Ok(())
+}
+
+/// Archive results of a snapshot as far as its `meta.json` tells: only archives that were
+/// uploaded have stats, so all of them succeeded.
+fn archives_from_stats(meta: &BackupApplication) -> Vec<ArchiveRecord> {
+    meta.stats
+        .iter()
+        .map(|stats| {
+            let folder = stats
+                .checksum
+                .as_ref()
+                .and_then(|checksum| checksum.file.split('/').next());
+            let volume_type = match meta.volumes.iter().find(|vol| vol.name == stats.name) {
+                Some(vol) if matches!(vol.volume_type, VolumeType::Bind) => "Bind",
+                Some(_) => "Docker",
+                None if stats.name == "REPO" => "Repo",
+                None if folder == Some("DUMPS") => "Dump",
+                None if folder == Some("IMAGES") => "Image",
+                None => "-",
+            };
+            ArchiveRecord {
+                name: stats.name.clone(),
+                volume_type: volume_type.to_string(),
+                status: "✅".to_string(),
+                size: format_bytes(stats.compressed_bytes),
+                duration: format!("{:.2} seconds", stats.seconds),
+                original_bytes: Some(stats.original_bytes),
+                compressed_bytes: Some(stats.compressed_bytes),
+                seconds: Some(stats.seconds),
+            }
+        })
+        .collect()
+}
+
+fn insert_run(conn: &Connection, run: &RunRecord) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO runs
+         (started, finished, backup_type, status, total_archives, failed_archives, error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            run.started.to_rfc3339(),
+            run.finished.to_rfc3339(),
+            run.backup_type,
+            run.status,
+            run.total_archives,
+            run.failed_archives,
+            run.error
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_verification(conn: &Connection, verification: &VerificationRecord) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO verifications
+         (started, finished, scheduled, verified, failed, error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            verification.started.to_rfc3339(),
+            verification.finished.to_rfc3339(),
+            verification.scheduled,
+            verification.verified,
+            verification.failed,
+            verification.error
+        ],
+    )?;
+    Ok(())
+}
+
+fn parse_time(value: String) -> DateTime<Local> {
+    DateTime::parse_from_rfc3339(&value)
+        .map(|time| time.with_timezone(&Local))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(catalog: &Catalog, table: &str) -> Vec<String> {
+        let mut stmt = catalog
+            .conn
+            .prepare(&format!("PRAGMA table_info({})", table))
+            .unwrap();
+        stmt.query_map([], |row| row.get(1))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .unwrap()
+    }
+
+    fn user_version(catalog: &Catalog) -> u32 {
+        catalog
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn migrates_a_new_catalog_to_the_latest_version() {
+        let catalog = Catalog {
+            conn: Connection::open_in_memory().unwrap(),
+        };
+        catalog.migrate().unwrap();
+        assert_eq!(user_version(&catalog), 3);
+        assert!(columns(&catalog, "archives").contains(&"seconds".to_string()));
+        assert!(!columns(&catalog, "transfer").is_empty());
+        assert!(!columns(&catalog, "verifications").is_empty());
+    }
+
+    #[test]
+    fn upgrades_an_old_catalog_and_keeps_its_archives() {
+        let catalog = Catalog {
+            conn: Connection::open_in_memory().unwrap(),
+        };
+        catalog
+            .conn
+            .execute_batch(
+                "CREATE TABLE archives (
+                    project TEXT NOT NULL,
+                    folder TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    volume_type TEXT NOT NULL,
+                    status TEXT NOT NULL,
+                    size TEXT NOT NULL,
+                    duration TEXT NOT NULL,
+                    PRIMARY KEY (project, folder, name)
+                );
+                INSERT INTO archives VALUES ('app', 'snap', 'db.tar.gz', 'Volume', 'ok', '1 KB', '1s');",
+            )
+            .unwrap();
+        catalog.migrate().unwrap();
+
+        assert_eq!(user_version(&catalog), 3);
+        for column in ["original_bytes", "compressed_bytes", "seconds"] {
+            assert!(columns(&catalog, "archives").contains(&column.to_string()));
+        }
+        let archives = catalog.archives("app", "snap").unwrap();
+        assert_eq!(archives.len(), 1);
+        assert_eq!(archives[0].name, "db.tar.gz");
+        assert_eq!(archives[0].original_bytes, None);
+    }
+
+    #[test]
+    fn migrating_twice_keeps_the_data() {
+        let catalog = Catalog {
+            conn: Connection::open_in_memory().unwrap(),
+        };
+        catalog.migrate().unwrap();
+        catalog.record_transfer("primary", 10, 5).unwrap();
+        catalog.migrate().unwrap();
+
+        assert_eq!(user_version(&catalog), 3);
+        let transfer = catalog.transfer().unwrap();
+        assert_eq!(transfer.len(), 1);
+        assert_eq!((transfer[0].uploaded, transfer[0].downloaded), (10, 5));
+    }
+}
@@ -0,0 +1,162 @@
+//! Compression of backup archives: `gzip` (default), `zstd`, `xz` or `none`.
+//!
+//! Archives are written as an uncompressed tar stream (by `tar` or inside the helper
+//! container) piped through the compressor CLI, so the same algorithm and level apply to
+//! project files and Docker volumes alike.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub const ALGORITHMS: [&str; 4] = ["gzip", "zstd", "xz", "none"];
+
+/// Bytes of a volume's tar stream sampled by adaptive compression.
+pub const SAMPLE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Compressed size relative to the original from which data is not worth compressing.
+pub const INCOMPRESSIBLE_RATIO: f64 = 0.95;
+
+/// Archive file extension for `algorithm`.
+pub fn extension(algorithm: &str) -> &'static str {
+    match algorithm {
+        "zstd" => ".tar.zst",
+        "xz" => ".tar.xz",
+        "none" => ".tar",
+        _ => ".tar.gz",
+    }
+}
+
+/// Checks `level` is valid for `algorithm`.
+pub fn validate_level(algorithm: &str, level: u32) -> Result<()> {
+    let range = match algorithm {
+        "gzip" => 1..=9,
+        "zstd" => 1..=19,
+        "xz" => 0..=9,
+        _ => anyhow::bail!("{} has no compression level", algorithm),
+    };
+    if !range.contains(&level) {
+        anyhow::bail!(
+            "Invalid {} compression level {} (expected {}-{})",
+            algorithm,
+            level,
+            range.start(),
+            range.end()
+        );
+    }
+    Ok(())
+}
+
+fn compressor(algorithm: &str, level: Option<u32>) -> Option<Command> {
+    let mut cmd = match algorithm {
+        "none" => return None,
+        "zstd" => {
+            let mut cmd = Command::new("zstd");
+            cmd.args(["-q", "-c", "-T0"]);
+            cmd
+        }
+        "xz" => {
+            let mut cmd = Command::new("xz");
+            cmd.args(["-c", "-T0"]);
+            cmd
+        }
+        _ => {
+            let mut cmd = Command::new("gzip");
+            cmd.arg("-c");
+            cmd
+        }
+    };
+    if let Some(level) = level {
+        cmd.arg(format!("-{}", level));
+    }
+    Some(cmd)
+}
+
+/// Runs `producer`, which writes a tar stream to stdout, and stores it compressed at
+/// `output`. Returns the size of the uncompressed tar stream.
+pub fn write_archive(
+    mut producer: Command,
+    algorithm: &str,
+    level: Option<u32>,
+    output: &Path,
+) -> Result<u64> {
+    let file = File::create(output).with_context(|| format!("Failed to create {:?}", output))?;
+    let Some(mut compressor) = compressor(algorithm, level) else {
+        let status = producer.stdout(file).status()?;
+        if !status.success() {
+            anyhow::bail!("Failed to create tarball: {:?}", output);
+        }
+        return Ok(fs::metadata(output)?.len());
+    };
+
+    let mut tar = producer.stdout(Stdio::piped()).spawn()?;
+    let mut compress = compressor
+        .stdin(Stdio::piped())
+        .stdout(file)
+        .spawn()
+        .with_context(|| format!("Failed to run {}, is it installed?", algorithm))?;
+    // Copy through here instead of connecting the pipes directly to count the raw bytes
+    let mut stream = tar.stdout.take().expect("stdout is piped");
+    let mut stdin = compress.stdin.take().expect("stdin is piped");
+    let copied = io::copy(&mut stream, &mut stdin);
+    drop(stdin);
+    let tarred = tar.wait()?;
+    let compressed = compress.wait()?;
+    let original = copied.with_context(|| format!("Failed to compress {:?}", output))?;
+    if !tarred.success() || !compressed.success() {
+        anyhow::bail!("Failed to create tarball: {:?}", output);
+    }
+    Ok(original)
+}
+
+/// Compresses the first [`SAMPLE_BYTES`] of `producer`'s tar stream with a fast gzip and
+/// returns the compressed size relative to the sample, `None` for an empty stream.
+pub fn sample_ratio(mut producer: Command) -> Result<Option<f64>> {
+    let mut child = producer
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut sample = Vec::new();
+    let read = child
+        .stdout
+        .take()
+        .expect("stdout is piped")
+        .take(SAMPLE_BYTES)
+        .read_to_end(&mut sample);
+    // The rest of the stream is not needed
+    let _ = child.kill();
+    let _ = child.wait();
+    read.context("Failed to read sample")?;
+    if sample.is_empty() {
+        return Ok(None);
+    }
+
+    let mut gzip = Command::new("gzip")
+        .args(["-1", "-c"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run gzip, is it installed?")?;
+    let mut stdin = gzip.stdin.take().expect("stdin is piped");
+    let sampled = sample.len();
+    // Feed from a thread, gzip blocks on a full stdout pipe otherwise
+    let writer = std::thread::spawn(move || stdin.write_all(&sample));
+    let output = gzip.wait_with_output()?;
+    writer.join().expect("sample writer panicked")?;
+    Ok(Some(output.stdout.len() as f64 / sampled as f64))
+}
+
+/// `tar` flag to extract an archive, chosen by its file extension.
+pub fn extract_flag(archive: &Path) -> Option<&'static str> {
+    let name = archive.to_string_lossy();
+    if name.ends_with(".tar.zst") {
+        Some("--zstd")
+    } else if name.ends_with(".tar.xz") {
+        Some("-J")
+    } else if name.ends_with(".tar.gz") {
+        Some("-z")
+    } else {
+        None
+    }
+}
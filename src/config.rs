@@ -1,30 +1,100 @@
-use crate::email;
+use crate::{compression, email, i18n, keys, plain_println, theme, transport};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     fs,
     io::{self, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 #[serde(default)]
 pub struct RawConfig {
-    pub docker_parent: Option<String>,
+    pub docker_parent: Option<DockerParents>,
     pub remote_backup_path: Option<String>,
     pub ssh_user: Option<String>,
     pub ssh_host: Option<String>,
     pub ssh_key: Option<String>,
     pub ssh_port: Option<u16>,
+    pub ssh_auth: Option<String>,
+    pub ssh_password: Option<String>,
+    pub remote_allowlist: Option<bool>,
+    pub storage_backend: Option<String>,
+    pub s3: Option<S3Config>,
+    pub encryption: Option<EncryptionConfig>,
+    pub size_warning: Option<SizeWarningConfig>,
+    pub compression: Option<String>,
+    pub compression_level: Option<u32>,
+    pub adaptive_compression: Option<bool>,
+    pub incremental: Option<bool>,
+    pub fs_snapshots: Option<bool>,
+    pub dedup: Option<bool>,
+    pub db_dumps: Option<String>,
+    pub images: Option<String>,
+    pub restore_rate_limit: Option<u64>,
+    pub restore_safety_snapshot: Option<bool>,
+    pub confirm_destructive: Option<bool>,
+    pub read_only: Option<bool>,
+    pub max_concurrent_projects: Option<usize>,
+    pub scan_depth: Option<usize>,
+    pub max_concurrent_volumes: Option<usize>,
+    pub heartbeat_secs: Option<u64>,
     pub email_host: Option<String>,
     pub email_port: Option<u16>,
     pub email_user: Option<String>,
     pub email_password: Option<String>,
     pub receiver_mail: Option<String>,
+    pub fallback_email_host: Option<String>,
+    pub fallback_email_port: Option<u16>,
+    pub fallback_email_user: Option<String>,
+    pub fallback_email_password: Option<String>,
+    pub fallback_webhook_url: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub dashboard_url: Option<String>,
+    pub report_mode: Option<String>,
+    pub language: Option<String>,
     pub interval: Option<RawIntervalConfig>,
+    pub auto_prune: Option<bool>,
+    pub prune_max_snapshots: Option<usize>,
+    pub prune_max_percent: Option<u32>,
+    pub verify_schedule: Option<String>,
+    pub retention_tags: Option<HashMap<String, u32>>,
+    pub retention: Option<HashMap<String, HashMap<String, u32>>>,
+    pub adopted_projects: Option<Vec<String>>,
+    pub discovery: Option<String>,
+    pub include_projects: Option<Vec<String>>,
+    pub exclude_projects: Option<Vec<String>>,
+    pub hooks: Option<HooksConfig>,
+    pub project_hooks: Option<HashMap<String, HooksConfig>>,
+    pub quiesce: Option<HashMap<String, String>>,
+    pub excludes: Option<HashMap<String, Vec<String>>>,
+    pub only_volumes: Option<HashMap<String, Vec<String>>>,
+    pub restore_order: Option<HashMap<String, Vec<String>>>,
+    pub targets: Option<HashMap<String, HashMap<String, String>>>,
+    pub prices: Option<HashMap<String, PriceConfig>>,
+    pub cost_budget: Option<f64>,
+    pub tui: Option<TuiConfig>,
 }
 
+/// `docker_parent` as a single directory (older configs) or a list of them.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum DockerParents {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl DockerParents {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            DockerParents::One(parent) => vec![parent],
+            DockerParents::Many(parents) => parents,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct RawIntervalConfig {
     pub hour: Option<u32>,
     pub day: Option<u32>,
@@ -35,18 +105,267 @@ pub struct RawIntervalConfig {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
-    pub docker_parent: String,
+    /// Parent directories of the project folders.
+    pub docker_parent: Vec<String>,
     pub remote_backup_path: String,
     pub ssh_user: String,
     pub ssh_host: String,
     pub ssh_key: String,
     pub ssh_port: u16,
+    /// `key` (default) or `password`. Password auth is less secure and only meant for
+    /// targets where key auth isn't possible.
+    pub ssh_auth: String,
+    pub ssh_password: Option<String>,
+    /// Hardened mode: only issue the fixed remote operations in `transport`, confined to
+    /// `remote_backup_path`.
+    pub remote_allowlist: bool,
+    /// `ssh` (default), `s3` or `local` (`remote_backup_path` is a mounted directory).
+    pub storage_backend: String,
+    pub s3: S3Config,
+    pub encryption: EncryptionConfig,
+    pub size_warning: SizeWarningConfig,
+    /// Archive compression: `gzip` (default), `zstd`, `xz` or `none`.
+    pub compression: String,
+    /// Compressor level, the compressor's default when unset.
+    pub compression_level: Option<u32>,
+    /// Store volumes uncompressed when a sample shows they barely compress (default on).
+    pub adaptive_compression: bool,
+    /// Mirror directories with `rsync --link-dest` instead of uploading tarballs.
+    pub incremental: bool,
+    /// Archive volumes from btrfs, ZFS or LVM snapshots instead of the live data.
+    pub fs_snapshots: bool,
+    /// Store volumes in the deduplicated chunk store instead of as archives.
+    pub dedup: bool,
+    /// Dump Postgres and MySQL/MariaDB services: `off` (default), `alongside` or `instead` of their volumes.
+    pub db_dumps: String,
+    /// Images of the compose services: `off` (default), `digests` (record the exact
+    /// versions) or `save` (also store them with `docker save`).
+    pub images: String,
+    /// Download rate limit for restores in KiB/s, unlimited when unset.
+    pub restore_rate_limit: Option<u64>,
+    /// Keep the replaced data as `.pre-restore-<ts>` so `restore --undo` can revert (default on).
+    pub restore_safety_snapshot: bool,
+    /// Require a token sent via the notification channel before restore and prune.
+    pub confirm_destructive: bool,
+    /// Disable restore and prune entirely, for hosts that should only create backups.
+    pub read_only: bool,
+    /// Projects backed up at the same time, 1 (default) backs up one after another.
+    pub max_concurrent_projects: usize,
+    /// Directory levels below `docker_parent` searched for projects, 1 (default) means
+    /// only its direct subdirectories.
+    pub scan_depth: usize,
+    /// Volumes of one project archived and uploaded at the same time, 1 by default.
+    pub max_concurrent_volumes: usize,
+    /// Seconds between the heartbeat lines logged during long archive and upload steps
+    /// (default 60), 0 turns them off.
+    pub heartbeat_secs: u64,
     pub email_host: String,
     pub email_port: u16,
     pub email_user: String,
     pub email_password: String,
     pub receiver_mail: String,
+    /// Secondary SMTP relay used when sending through the primary one fails. Port, user
+    /// and password default to the primary settings.
+    pub fallback_email_host: Option<String>,
+    pub fallback_email_port: Option<u16>,
+    pub fallback_email_user: Option<String>,
+    pub fallback_email_password: Option<String>,
+    /// Last resort: the report is POSTed as JSON to this URL if no email could be sent.
+    pub fallback_webhook_url: Option<String>,
+    /// OTLP/HTTP collector (e.g. `http://localhost:4318`) backup runs are traced to.
+    pub otlp_endpoint: Option<String>,
+    /// Address `dockup serve` is reachable at (e.g. `http://backup.lan:9797`), project
+    /// headings in the email report link to its pages.
+    pub dashboard_url: Option<String>,
+    /// `full` (default), `summary` (collapse successful projects, attach the full report)
+    /// or `auto` (summary once there are more than 10 projects).
+    pub report_mode: String,
+    /// Language of reports and CLI messages, `en` (default) or `de`.
+    pub language: String,
     pub interval: IntervalConfig,
+    /// Apply the retention policy after every backup run.
+    pub auto_prune: bool,
+    /// A prune deleting more snapshots than this needs `--force` or a confirmation, 0
+    /// disables the limit (default).
+    pub prune_max_snapshots: usize,
+    /// A prune deleting more than this percentage of the stored data needs `--force` or a
+    /// confirmation (default 50), 0 disables the limit.
+    pub prune_max_percent: u32,
+    /// Cron expression (e.g. `30 3 * * 0`) for scheduled `dockup verify` runs, installed
+    /// next to the backup schedule by `dockup interval install` and `dockup setup-systemd`.
+    pub verify_schedule: Option<String>,
+    /// Tag name -> days to keep snapshots carrying that tag, regardless of `interval`.
+    pub retention_tags: HashMap<String, u32>,
+    /// Project name -> interval (`hour` ... `year`) -> count, replacing that value of
+    /// `interval` when the project's snapshots are pruned.
+    pub retention: HashMap<String, HashMap<String, u32>>,
+    /// Project directories outside `docker_parent`, registered via `dockup adopt`.
+    pub adopted_projects: Vec<String>,
+    /// Where projects are found: `directory` (default, `docker_parent` and adopted ones),
+    /// `docker` (compose projects running on the daemon) or `both`.
+    pub discovery: String,
+    /// Only back up projects matching one of these names (`*` wildcards), all if empty.
+    pub include_projects: Vec<String>,
+    /// Never back up projects matching one of these names (`*` wildcards).
+    pub exclude_projects: Vec<String>,
+    /// Hook commands run for every project.
+    pub hooks: HooksConfig,
+    /// Project name -> hook commands run after the global ones.
+    pub project_hooks: HashMap<String, HooksConfig>,
+    /// Project name -> `stop` or `pause` its containers while it is archived.
+    pub quiesce: HashMap<String, String>,
+    /// `<project>.<volume>` -> globs left out of that volume's archives.
+    pub excludes: HashMap<String, Vec<String>>,
+    /// Project name -> the only volumes (names as in the compose file, `*` wildcards)
+    /// backed up of that project, all if the project is missing.
+    pub only_volumes: HashMap<String, Vec<String>>,
+    /// Project name -> items (volumes, `REPO`, `DB:<service>`) restored first, in this order.
+    pub restore_order: HashMap<String, Vec<String>>,
+    /// Target name -> storage settings overriding the primary target's, for restoring from
+    /// copies of the backups kept elsewhere. Backups are only written to the primary target.
+    pub targets: HashMap<String, HashMap<String, String>>,
+    /// Target name -> prices for the cost estimate of `dockup usage`.
+    pub prices: HashMap<String, PriceConfig>,
+    /// Warn when the projected monthly cost of all targets exceeds this amount.
+    pub cost_budget: Option<f64>,
+    pub tui: TuiConfig,
+    /// Target the storage settings belong to, `primary` unless picked with `target`.
+    #[serde(skip)]
+    pub target_name: String,
+    /// Set for `backup --canary`, whose snapshot is kept out of the catalog.
+    #[serde(skip)]
+    pub canary: bool,
+}
+
+/// Settings holding secrets or paths to them, as keys of `config set`. Masked by `config
+/// export --redact-secrets`, left out of `--export-template`. Webhook URLs usually carry a
+/// token.
+const SECRET_KEYS: [&str; 8] = [
+    "ssh_password",
+    "ssh_key",
+    "email_password",
+    "fallback_email_password",
+    "fallback_webhook_url",
+    "s3.access_key",
+    "s3.secret_key",
+    "encryption.identity",
+];
+
+/// Settings only meaningful on the host they were made on, left out of templates.
+const HOST_KEYS: [&str; 1] = ["adopted_projects"];
+
+/// Name of the target configured by the top-level storage settings.
+pub const PRIMARY_TARGET: &str = "primary";
+
+/// Keys a secondary target can override, besides `s3.*`.
+const TARGET_KEYS: [&str; 8] = [
+    "remote_backup_path",
+    "ssh_user",
+    "ssh_host",
+    "ssh_key",
+    "ssh_port",
+    "ssh_auth",
+    "ssh_password",
+    "storage_backend",
+];
+
+/// S3-compatible object storage, used with `storage_backend = "s3"`. Credentials fall
+/// back to the usual `aws` CLI configuration when unset.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct S3Config {
+    pub bucket: String,
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+}
+
+/// Client-side encryption of archives before upload, off while `recipient` is unset.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// `age` (default) or `gpg`.
+    pub method: Option<String>,
+    /// age public key (`age1...`) or gpg key ID / fingerprint archives are encrypted to.
+    pub recipient: Option<String>,
+    /// age identity file used to decrypt during restore. gpg uses its keyring and agent.
+    pub identity: Option<String>,
+}
+
+impl EncryptionConfig {
+    pub fn enabled(&self) -> bool {
+        self.recipient.is_some()
+    }
+
+    pub fn method(&self) -> &str {
+        self.method.as_deref().unwrap_or("age")
+    }
+}
+
+/// Warnings for archives that are unexpectedly large or growing fast, off while unset.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct SizeWarningConfig {
+    /// Warn when a single archive exceeds this size in GB.
+    pub max_archive_gb: Option<f64>,
+    /// Warn when an archive grew by more than this percentage compared to a week ago.
+    pub max_growth_percent: Option<f64>,
+    /// Fail the run when an archive is this many percent smaller than its recent average.
+    pub max_shrink_percent: Option<f64>,
+}
+
+/// Prices of a backup target, in any currency as long as it's the same everywhere.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct PriceConfig {
+    /// Per GB stored for a month.
+    pub storage_per_gb: f64,
+    /// Per GB downloaded from the target.
+    pub egress_per_gb: f64,
+}
+
+/// The restore TUI.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct TuiConfig {
+    /// Action -> comma separated keys replacing its default ones, see `keys.rs`.
+    pub keys: HashMap<String, String>,
+    /// `dark` (default) or `light`, see `theme.rs`.
+    pub theme: Option<String>,
+    /// Color slot -> color overriding the theme's.
+    pub colors: HashMap<String, String>,
+}
+
+/// Shell commands run around a project's backup or restore, see `hooks.rs`.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Before archiving. If it fails the project is not backed up.
+    pub pre_backup: Option<String>,
+    /// After the backup, also when it failed (e.g. to start a stopped app again).
+    pub post_backup: Option<String>,
+    /// After a backup with failed archives or a failed hook.
+    pub on_failure: Option<String>,
+    /// After a restore in which every item succeeded (e.g. to run migrations).
+    pub post_restore: Option<String>,
+}
+
+impl HooksConfig {
+    fn set(&mut self, hook: &str, value: &str) -> Result<()> {
+        let command = match hook {
+            "pre_backup" => &mut self.pre_backup,
+            "post_backup" => &mut self.post_backup,
+            "on_failure" => &mut self.on_failure,
+            "post_restore" => &mut self.post_restore,
+            _ => anyhow::bail!(
+                "Unknown hook: {} (expected pre_backup, post_backup, on_failure or post_restore)",
+                hook
+            ),
+        };
+        *command = optional(value);
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -58,7 +377,28 @@ pub struct IntervalConfig {
     pub year: u32,
 }
 
+/// Intervals a project can override in `retention.<project>.<interval>`.
+const RETENTION_TIERS: [&str; 5] = ["hour", "day", "week", "month", "year"];
+
 impl Config {
+    /// The retention policy of `project`: `interval` with the project's overrides applied.
+    pub fn retention_for(&self, project: &str) -> IntervalConfig {
+        let mut interval = self.interval;
+        if let Some(tiers) = self.retention.get(project) {
+            for (tier, count) in tiers {
+                match tier.as_str() {
+                    "hour" => interval.hour = *count,
+                    "day" => interval.day = *count,
+                    "week" => interval.week = *count,
+                    "month" => interval.month = *count,
+                    "year" => interval.year = *count,
+                    _ => {}
+                }
+            }
+        }
+        interval
+    }
+
     pub fn config_path() -> PathBuf {
         dirs::home_dir()
             .expect("Could not determine home directory")
@@ -74,6 +414,64 @@ impl Config {
         Ok(())
     }
 
+    /// `dockup init`: writes the config without asking, for provisioning with tools like
+    /// Ansible. `from` is a JSON file in the layout of `config.json`, any subset of it;
+    /// `settings` are `key=value` pairs as taken by `config set`, applied on top. What the
+    /// wizard would ask for and neither sets gets a default, except the settings a backup
+    /// can't run without. An existing config is only replaced with `force`.
+    pub fn init(from: Option<&Path>, settings: &[String], force: bool) -> Result<Self> {
+        let path = Self::config_path();
+        if path.exists() && !force {
+            anyhow::bail!(
+                "{} already exists, pass --force to replace it",
+                path.display()
+            );
+        }
+        let raw: RawConfig = match from {
+            Some(from) => {
+                let data = fs::read_to_string(from)
+                    .with_context(|| format!("Failed to read {}", from.display()))?;
+                serde_json::from_str(&data)
+                    .with_context(|| format!("Invalid answers file {}", from.display()))?
+            }
+            None => RawConfig::default(),
+        };
+        let mut config = raw.with_defaults().finalize()?;
+        for setting in settings {
+            let (key, value) = setting
+                .split_once('=')
+                .with_context(|| format!("Expected key=value, got `{}`", setting))?;
+            config.set_key_value(key.trim(), value.trim())?;
+        }
+
+        let mut missing = Vec::new();
+        if config.docker_parent.is_empty() {
+            missing.push("docker_parent");
+        }
+        if config.remote_backup_path.is_empty() {
+            missing.push("remote_backup_path");
+        }
+        if config.storage_backend == "ssh" {
+            if config.ssh_user.is_empty() {
+                missing.push("ssh_user");
+            }
+            if config.ssh_host.is_empty() {
+                missing.push("ssh_host");
+            }
+            if config.ssh_key.is_empty() && !config.uses_password_auth() {
+                missing.push("ssh_key");
+            }
+        }
+        if !missing.is_empty() {
+            anyhow::bail!("Missing settings: {}", missing.join(", "));
+        }
+        if config.email_host.is_empty() {
+            log::warn!("⚠️  No email_host set, reports can't be emailed");
+        }
+        config.save()?;
+        Ok(config)
+    }
+
     pub async fn load_or_create() -> Result<Self> {
         let path = Self::config_path();
 
@@ -90,24 +488,108 @@ impl Config {
         Ok(finalized)
     }
 
-    pub async fn test_ssh(&self) -> Result<()> {
-        let output = std::process::Command::new("ssh")
-            .arg("-i")
-            .arg(&self.ssh_key)
-            .arg("-p")
-            .arg(self.ssh_port.to_string())
-            .arg(format!("{}@{}", self.ssh_user, self.ssh_host))
-            .arg("echo 'SSH connection successful'")
-            .output()?;
-
-        if output.status.success() {
-            log::info!("✅ SSH connection successful");
-        } else {
-            log::error!(
-                "❌ SSH connection failed: {}",
-                String::from_utf8_lossy(&output.stderr)
+    /// All target names, `primary` first.
+    pub fn target_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.targets.keys().cloned().collect();
+        names.sort();
+        names.insert(0, PRIMARY_TARGET.to_string());
+        names
+    }
+
+    /// The config with the storage settings of target `name` applied. The result has no
+    /// further targets, so it can be handed to anything expecting a single target.
+    pub fn target(&self, name: &str) -> Result<Config> {
+        let mut config = self.clone();
+        config.targets.clear();
+        config.target_name = name.to_string();
+        if name == PRIMARY_TARGET {
+            return Ok(config);
+        }
+        let overrides = self.targets.get(name).with_context(|| {
+            format!(
+                "Unknown target {} (configured: {})",
+                name,
+                self.target_names().join(", ")
+            )
+        })?;
+        for (key, value) in overrides {
+            config
+                .set_key_value(key, value)
+                .with_context(|| format!("Invalid setting targets.{}.{}", name, key))?;
+        }
+        Ok(config)
+    }
+
+    /// The config as JSON for `config export`. With `redact` set secrets are masked. A
+    /// `template` leaves them out together with host specific settings, it is meant as
+    /// answers file for `dockup init --from`. Returns the JSON and the secrets that were set.
+    pub fn export(&self, redact: bool, template: bool) -> Result<(String, Vec<String>)> {
+        let mut json = serde_json::to_value(self)?;
+        let mut secrets = Vec::new();
+        for key in SECRET_KEYS {
+            let (parent, name) = match key.split_once('.') {
+                Some((section, name)) => (json.get_mut(section), name),
+                None => (Some(&mut json), key),
+            };
+            let Some(parent) = parent.and_then(|parent| parent.as_object_mut()) else {
+                continue;
+            };
+            if !is_set(parent.get(name)) {
+                continue;
+            }
+            secrets.push(key.to_string());
+            if template {
+                parent.remove(name);
+            } else if redact {
+                parent.insert(name.into(), REDACTED.into());
+            }
+        }
+        // Secondary targets override the same settings
+        if let Some(targets) = json.get_mut("targets").and_then(|t| t.as_object_mut()) {
+            for (target, settings) in targets.iter_mut() {
+                let Some(settings) = settings.as_object_mut() else {
+                    continue;
+                };
+                for key in SECRET_KEYS {
+                    if !is_set(settings.get(key)) {
+                        continue;
+                    }
+                    secrets.push(format!("targets.{}.{}", target, key));
+                    if template {
+                        settings.remove(key);
+                    } else if redact {
+                        settings.insert(key.into(), REDACTED.into());
+                    }
+                }
+            }
+        }
+        if template {
+            if let Some(config) = json.as_object_mut() {
+                for key in HOST_KEYS {
+                    config.remove(key);
+                }
+            }
+        }
+        Ok((serde_json::to_string_pretty(&json)?, secrets))
+    }
+
+    pub fn uses_password_auth(&self) -> bool {
+        self.ssh_auth == "password"
+    }
+
+    pub async fn test_target(&self) -> Result<()> {
+        if self.storage_backend == "ssh" && self.uses_password_auth() {
+            log::warn!(
+                "⚠️  SSH password authentication is enabled. Prefer key auth where possible."
             );
         }
+        match transport::open(self).check() {
+            Ok(()) => log::info!("✅ Backup target reachable ({})", self.storage_backend),
+            Err(e) => log::error!(
+                "❌ Backup target not reachable ({}): {e}",
+                self.storage_backend
+            ),
+        }
         Ok(())
     }
 
@@ -117,12 +599,17 @@ impl Config {
 
     pub fn set_key_value(&mut self, key: &str, value: &str) -> Result<()> {
         match key {
-            "docker_parent" => self.docker_parent = value.to_string(),
+            "docker_parent" => self.docker_parent = list(value),
             "remote_backup_path" => self.remote_backup_path = value.to_string(),
             "ssh_user" => self.ssh_user = value.to_string(),
             "ssh_host" => self.ssh_host = value.to_string(),
             "ssh_key" => self.ssh_key = value.to_string(),
             "ssh_port" => self.ssh_port = value.parse().context("Invalid value for ssh_port")?,
+            "ssh_auth" => match value {
+                "key" | "password" => self.ssh_auth = value.to_string(),
+                _ => anyhow::bail!("Invalid value for ssh_auth: expected `key` or `password`"),
+            },
+            "ssh_password" => self.ssh_password = (!value.is_empty()).then(|| value.to_string()),
             "email_host" => self.email_host = value.to_string(),
             "email_port" => {
                 self.email_port = value.parse().context("Invalid value for email_port")?
@@ -130,6 +617,210 @@ impl Config {
             "email_user" => self.email_user = value.to_string(),
             "email_password" => self.email_password = value.to_string(),
             "receiver_mail" => self.receiver_mail = value.to_string(),
+            "fallback_email_host" => self.fallback_email_host = optional(value),
+            "fallback_email_port" => {
+                self.fallback_email_port = match value {
+                    "" => None,
+                    _ => Some(
+                        value
+                            .parse()
+                            .context("Invalid value for fallback_email_port")?,
+                    ),
+                }
+            }
+            "fallback_email_user" => self.fallback_email_user = optional(value),
+            "fallback_email_password" => self.fallback_email_password = optional(value),
+            "fallback_webhook_url" => self.fallback_webhook_url = optional(value),
+            "otlp_endpoint" => self.otlp_endpoint = optional(value),
+            "dashboard_url" => {
+                self.dashboard_url = optional(value.trim_end_matches('/'));
+            }
+            "remote_allowlist" => {
+                self.remote_allowlist = value
+                    .parse()
+                    .context("Invalid value for remote_allowlist")?
+            }
+            "s3.bucket" => self.s3.bucket = value.to_string(),
+            "s3.endpoint" => self.s3.endpoint = optional(value),
+            "s3.region" => self.s3.region = optional(value),
+            "s3.access_key" => self.s3.access_key = optional(value),
+            "s3.secret_key" => self.s3.secret_key = optional(value),
+            "storage_backend" => match value {
+                "ssh" | "s3" | "local" => self.storage_backend = value.to_string(),
+                _ => anyhow::bail!(
+                    "Invalid value for storage_backend: expected `ssh`, `s3` or `local`"
+                ),
+            },
+            "compression" => {
+                if !compression::ALGORITHMS.contains(&value) {
+                    anyhow::bail!(
+                        "Invalid value for compression: expected one of {}",
+                        compression::ALGORITHMS.join(", ")
+                    );
+                }
+                if let Some(level) = self.compression_level {
+                    compression::validate_level(value, level)?;
+                }
+                self.compression = value.to_string()
+            }
+            "compression_level" => {
+                self.compression_level = match value {
+                    "" => None,
+                    _ => {
+                        let level = value
+                            .parse()
+                            .context("Invalid value for compression_level")?;
+                        compression::validate_level(&self.compression, level)?;
+                        Some(level)
+                    }
+                }
+            }
+            "adaptive_compression" => {
+                self.adaptive_compression = value
+                    .parse()
+                    .context("Invalid value for adaptive_compression")?
+            }
+            "incremental" => {
+                self.incremental = value.parse().context("Invalid value for incremental")?
+            }
+            "fs_snapshots" => {
+                self.fs_snapshots = value.parse().context("Invalid value for fs_snapshots")?
+            }
+            "dedup" => self.dedup = value.parse().context("Invalid value for dedup")?,
+            "discovery" => match value {
+                "directory" | "docker" | "both" => self.discovery = value.to_string(),
+                _ => anyhow::bail!(
+                    "Invalid value for discovery: expected `directory`, `docker` or `both`"
+                ),
+            },
+            "images" => match value {
+                "off" | "digests" | "save" => self.images = value.to_string(),
+                _ => anyhow::bail!("Invalid value for images: expected `off`, `digests` or `save`"),
+            },
+            "db_dumps" => match value {
+                "off" | "alongside" | "instead" => self.db_dumps = value.to_string(),
+                _ => anyhow::bail!(
+                    "Invalid value for db_dumps: expected `off`, `alongside` or `instead`"
+                ),
+            },
+            "read_only" => {
+                let read_only = value
+                    .parse::<bool>()
+                    .context("Invalid value for read_only")?;
+                if self.read_only && !read_only {
+                    anyhow::bail!(
+                        "read_only can't be turned off from the CLI, edit the config file instead"
+                    );
+                }
+                self.read_only = read_only
+            }
+            "confirm_destructive" => {
+                self.confirm_destructive = value
+                    .parse()
+                    .context("Invalid value for confirm_destructive")?
+            }
+            "restore_safety_snapshot" => {
+                self.restore_safety_snapshot = value
+                    .parse()
+                    .context("Invalid value for restore_safety_snapshot")?
+            }
+            "encryption.method" => match value {
+                "" => self.encryption.method = None,
+                "age" | "gpg" => self.encryption.method = Some(value.to_string()),
+                _ => anyhow::bail!("Invalid value for encryption.method: expected `age` or `gpg`"),
+            },
+            "encryption.recipient" => self.encryption.recipient = optional(value),
+            "encryption.identity" => self.encryption.identity = optional(value),
+            "size_warning.max_archive_gb" => {
+                self.size_warning.max_archive_gb = match value {
+                    "" => None,
+                    _ => Some(
+                        value
+                            .parse()
+                            .context("Invalid value for size_warning.max_archive_gb")?,
+                    ),
+                }
+            }
+            "size_warning.max_growth_percent" => {
+                self.size_warning.max_growth_percent = match value {
+                    "" => None,
+                    _ => Some(
+                        value
+                            .parse()
+                            .context("Invalid value for size_warning.max_growth_percent")?,
+                    ),
+                }
+            }
+            "size_warning.max_shrink_percent" => {
+                self.size_warning.max_shrink_percent = match value {
+                    "" => None,
+                    _ => {
+                        let percent: f64 = value
+                            .parse()
+                            .context("Invalid value for size_warning.max_shrink_percent")?;
+                        if !(0.0..=100.0).contains(&percent) {
+                            anyhow::bail!(
+                                "Invalid value for size_warning.max_shrink_percent: expected 0 to 100"
+                            );
+                        }
+                        Some(percent)
+                    }
+                }
+            }
+            "restore_rate_limit" => {
+                self.restore_rate_limit = match value {
+                    "" => None,
+                    _ => Some(
+                        value
+                            .parse()
+                            .context("Invalid value for restore_rate_limit")?,
+                    ),
+                }
+            }
+            "scan_depth" => {
+                let depth = value.parse().context("Invalid value for scan_depth")?;
+                if depth == 0 {
+                    anyhow::bail!("scan_depth must be at least 1");
+                }
+                self.scan_depth = depth
+            }
+            "max_concurrent_projects" => {
+                let max: usize = value
+                    .parse()
+                    .context("Invalid value for max_concurrent_projects")?;
+                if max == 0 {
+                    anyhow::bail!("max_concurrent_projects must be at least 1");
+                }
+                self.max_concurrent_projects = max
+            }
+            "max_concurrent_volumes" => {
+                let max: usize = value
+                    .parse()
+                    .context("Invalid value for max_concurrent_volumes")?;
+                if max == 0 {
+                    anyhow::bail!("max_concurrent_volumes must be at least 1");
+                }
+                self.max_concurrent_volumes = max
+            }
+            "heartbeat_secs" => {
+                self.heartbeat_secs = value.parse().context("Invalid value for heartbeat_secs")?
+            }
+            "language" => {
+                if !i18n::SUPPORTED_LANGUAGES.contains(&value) {
+                    anyhow::bail!(
+                        "Unsupported language: {} (supported: {})",
+                        value,
+                        i18n::SUPPORTED_LANGUAGES.join(", ")
+                    );
+                }
+                self.language = value.to_string()
+            }
+            "report_mode" => match value {
+                "full" | "summary" | "auto" => self.report_mode = value.to_string(),
+                _ => anyhow::bail!(
+                    "Invalid value for report_mode: expected `full`, `summary` or `auto`"
+                ),
+            },
             "interval.hour" => {
                 self.interval.hour = value.parse().context("Invalid value for interval.hour")?
             }
@@ -145,6 +836,221 @@ impl Config {
             "interval.year" => {
                 self.interval.year = value.parse().context("Invalid value for interval.year")?
             }
+            "auto_prune" => {
+                self.auto_prune = value.parse().context("Invalid value for auto_prune")?
+            }
+            "prune_max_snapshots" => {
+                self.prune_max_snapshots = value
+                    .parse()
+                    .context("Invalid value for prune_max_snapshots")?
+            }
+            "prune_max_percent" => {
+                self.prune_max_percent = value
+                    .parse()
+                    .context("Invalid value for prune_max_percent")?
+            }
+            "verify_schedule" => {
+                if !value.is_empty() && value.split_whitespace().count() != 5 {
+                    anyhow::bail!("verify_schedule must be a five-field cron expression");
+                }
+                self.verify_schedule = optional(value);
+            }
+            _ if key.starts_with("hooks.") => {
+                self.hooks.set(key.trim_start_matches("hooks."), value)?
+            }
+            _ if key.starts_with("project_hooks.") => {
+                let (project, hook) = key
+                    .trim_start_matches("project_hooks.")
+                    .rsplit_once('.')
+                    .context("Expected project_hooks.<project>.<hook>")?;
+                let hooks = self.project_hooks.entry(project.to_string()).or_default();
+                hooks.set(hook, value)?;
+                if hooks.pre_backup.is_none()
+                    && hooks.post_backup.is_none()
+                    && hooks.on_failure.is_none()
+                    && hooks.post_restore.is_none()
+                {
+                    self.project_hooks.remove(project);
+                }
+            }
+            _ if key.starts_with("quiesce.") => {
+                let project = key.trim_start_matches("quiesce.");
+                match value {
+                    "" => {
+                        self.quiesce.remove(project);
+                    }
+                    "stop" | "pause" => {
+                        self.quiesce.insert(project.to_string(), value.to_string());
+                    }
+                    _ => anyhow::bail!("Invalid value for {key}: expected `stop` or `pause`"),
+                }
+            }
+            "include_projects" => self.include_projects = list(value),
+            "exclude_projects" => self.exclude_projects = list(value),
+            _ if key.starts_with("excludes.") => {
+                let volume = key.trim_start_matches("excludes.");
+                if !volume.contains('.') {
+                    anyhow::bail!("Invalid key {key}: expected excludes.<project>.<volume>");
+                }
+                let globs = list(value);
+                if globs.is_empty() {
+                    self.excludes.remove(volume);
+                } else {
+                    self.excludes.insert(volume.to_string(), globs);
+                }
+            }
+            _ if key.starts_with("only_volumes.") => {
+                let project = key.trim_start_matches("only_volumes.");
+                let volumes = list(value);
+                if volumes.is_empty() {
+                    self.only_volumes.remove(project);
+                } else {
+                    self.only_volumes.insert(project.to_string(), volumes);
+                }
+            }
+            "tui.theme" => match value {
+                "" => self.tui.theme = None,
+                _ if theme::THEMES.contains(&value) => self.tui.theme = Some(value.to_string()),
+                _ => anyhow::bail!("Invalid value for tui.theme: expected `dark` or `light`"),
+            },
+            _ if key.starts_with("tui.colors.") => {
+                let slot = key.trim_start_matches("tui.colors.");
+                if !theme::SLOTS.contains(&slot) {
+                    anyhow::bail!(
+                        "Unknown color slot: {} (expected one of {})",
+                        slot,
+                        theme::SLOTS.join(", ")
+                    );
+                }
+                if value.is_empty() {
+                    self.tui.colors.remove(slot);
+                } else {
+                    theme::parse_color(value)?;
+                    self.tui.colors.insert(slot.to_string(), value.to_string());
+                }
+            }
+            _ if key.starts_with("tui.keys.") => {
+                let action = key.trim_start_matches("tui.keys.");
+                if keys::Action::from_name(action).is_none() {
+                    anyhow::bail!(
+                        "Unknown TUI action: {} (expected one of {})",
+                        action,
+                        keys::Action::ALL.map(keys::Action::name).join(", ")
+                    );
+                }
+                if keys::parse_keys(value)?.is_empty() {
+                    self.tui.keys.remove(action);
+                } else {
+                    self.tui.keys.insert(action.to_string(), value.to_string());
+                }
+            }
+            _ if key.starts_with("targets.") => {
+                let (name, setting) = key
+                    .trim_start_matches("targets.")
+                    .split_once('.')
+                    .context("Expected targets.<name>.<key>")?;
+                if name == PRIMARY_TARGET {
+                    anyhow::bail!("The primary target is configured by the top-level keys");
+                }
+                if !TARGET_KEYS.contains(&setting) && !setting.starts_with("s3.") {
+                    anyhow::bail!(
+                        "Unknown target key: {} (expected one of {}, s3.*)",
+                        setting,
+                        TARGET_KEYS.join(", ")
+                    );
+                }
+                if !value.is_empty() {
+                    self.clone().set_key_value(setting, value)?;
+                }
+                let target = self.targets.entry(name.to_string()).or_default();
+                if value.is_empty() {
+                    target.remove(setting);
+                    if target.is_empty() {
+                        self.targets.remove(name);
+                    }
+                } else {
+                    target.insert(setting.to_string(), value.to_string());
+                }
+            }
+            _ if key.starts_with("prices.") => {
+                let (target, price) = key
+                    .trim_start_matches("prices.")
+                    .rsplit_once('.')
+                    .context("Expected prices.<target>.<price>")?;
+                let amount = match value {
+                    "" => 0.0,
+                    _ => value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|amount| *amount >= 0.0)
+                        .with_context(|| format!("Invalid value for {key}"))?,
+                };
+                let prices = self.prices.entry(target.to_string()).or_default();
+                match price {
+                    "storage_per_gb" => prices.storage_per_gb = amount,
+                    "egress_per_gb" => prices.egress_per_gb = amount,
+                    _ => anyhow::bail!(
+                        "Unknown price: {} (expected storage_per_gb or egress_per_gb)",
+                        price
+                    ),
+                }
+                if prices.storage_per_gb == 0.0 && prices.egress_per_gb == 0.0 {
+                    self.prices.remove(target);
+                }
+            }
+            "cost_budget" => {
+                self.cost_budget = match value {
+                    "" => None,
+                    _ => Some(value.parse().context("Invalid value for cost_budget")?),
+                }
+            }
+            _ if key.starts_with("restore_order.") => {
+                let project = key.trim_start_matches("restore_order.");
+                let order = list(value);
+                if order.is_empty() {
+                    self.restore_order.remove(project);
+                } else {
+                    self.restore_order.insert(project.to_string(), order);
+                }
+            }
+            _ if key.starts_with("retention_tags.") => {
+                let tag = key.trim_start_matches("retention_tags.");
+                if value.is_empty() {
+                    self.retention_tags.remove(tag);
+                } else {
+                    let days = value
+                        .parse::<u32>()
+                        .context(format!("Invalid value for {key} (days to keep)"))?;
+                    self.retention_tags.insert(tag.to_string(), days);
+                }
+            }
+            _ if key.starts_with("retention.") => {
+                let Some((project, tier)) = key.trim_start_matches("retention.").rsplit_once('.')
+                else {
+                    anyhow::bail!("Expected retention.<project>.<hour/day/week/month/year>");
+                };
+                if !RETENTION_TIERS.contains(&tier) {
+                    anyhow::bail!(
+                        "Invalid interval in {key}: expected one of hour, day, week, month or year"
+                    );
+                }
+                if value.is_empty() {
+                    if let Some(tiers) = self.retention.get_mut(project) {
+                        tiers.remove(tier);
+                        if tiers.is_empty() {
+                            self.retention.remove(project);
+                        }
+                    }
+                } else {
+                    let count = value
+                        .parse::<u32>()
+                        .context(format!("Invalid value for {key} (snapshots to keep)"))?;
+                    self.retention
+                        .entry(project.to_string())
+                        .or_default()
+                        .insert(tier.to_string(), count);
+                }
+            }
             _ => anyhow::bail!("Unknown config key: {}", key),
         }
         Ok(())
@@ -194,6 +1100,24 @@ impl Config {
         }
     }
 
+    /// Time between two scheduled backups, as `suggested_cron` schedules them.
+    pub fn backup_period(&self) -> Option<chrono::Duration> {
+        let every = |count: u32, total: u32| (total / count).max(1) as i64;
+        if self.interval.hour > 0 {
+            Some(chrono::Duration::minutes(every(self.interval.hour, 60)))
+        } else if self.interval.day > 0 {
+            Some(chrono::Duration::hours(every(self.interval.day, 24)))
+        } else if self.interval.week > 0 {
+            Some(chrono::Duration::days(every(self.interval.week, 7)))
+        } else if self.interval.month > 0 {
+            Some(chrono::Duration::days(30 * every(self.interval.month, 30)))
+        } else if self.interval.year > 0 {
+            Some(chrono::Duration::days(30 * every(self.interval.year, 12)))
+        } else {
+            None
+        }
+    }
+
     pub fn cron_human_summary(&self) -> String {
         let mut explanation = String::new();
         explanation.push_str("📦 Current Backup Retention Policy:\n");
@@ -216,6 +1140,20 @@ impl Config {
             self.interval.year
         ));
 
+        for (tag, days) in &self.retention_tags {
+            explanation.push_str(&format!("  - Snapshots tagged `{tag}` kept: {days} days\n"));
+        }
+
+        let mut projects: Vec<_> = self.retention.keys().collect();
+        projects.sort();
+        for project in projects {
+            let interval = self.retention_for(project);
+            explanation.push_str(&format!(
+                "  - {project}: {} hourly, {} daily, {} weekly, {} monthly, {} yearly\n",
+                interval.hour, interval.day, interval.week, interval.month, interval.year
+            ));
+        }
+
         explanation.push('\n');
 
         if let Some(cron) = self.suggested_cron() {
@@ -241,6 +1179,32 @@ impl Config {
     }
 }
 
+/// Empty values unset optional settings.
+/// Replaces secrets in `config export --redact-secrets`.
+const REDACTED: &str = "<redacted>";
+
+fn is_set(value: Option<&serde_json::Value>) -> bool {
+    match value {
+        None | Some(serde_json::Value::Null) => false,
+        Some(serde_json::Value::String(s)) => !s.is_empty(),
+        Some(_) => true,
+    }
+}
+
+fn optional(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Comma separated list, empty entries dropped.
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
 impl RawConfig {
     pub async fn interactive_create() -> Result<Self> {
         fn ask(prompt: &str) -> Result<String> {
@@ -278,12 +1242,56 @@ impl RawConfig {
             }
         };
 
+        let docker_parent = ask("Docker parent directories (comma separated)")?;
+        let remote_backup_path = ask("Remote backup path")?;
+        let ssh_user = ask("SSH user")?;
+        let ssh_host = ask("SSH host")?;
+        let (ssh_auth, ssh_key, ssh_password) =
+            match ask("SSH authentication method (key/password)")?.as_str() {
+                "password" => {
+                    plain_println!(
+                        "⚠️  Password authentication is less secure than key authentication."
+                    );
+                    println!("   Leave the password empty to be prompted by ssh on every run.");
+                    let password = ask("SSH password")?;
+                    (
+                        "password".to_string(),
+                        String::new(),
+                        (!password.is_empty()).then_some(password),
+                    )
+                }
+                _ => ("key".to_string(), ask("SSH private key path")?, None),
+            };
+
         let config = RawConfig {
-            docker_parent: Some(ask("Docker parent directory")?),
-            remote_backup_path: Some(ask("Remote backup path")?),
-            ssh_user: Some(ask("SSH user")?),
-            ssh_host: Some(ask("SSH host")?),
-            ssh_key: Some(ask("SSH private key path")?),
+            docker_parent: Some(DockerParents::Many(list(&docker_parent))),
+            remote_backup_path: Some(remote_backup_path),
+            ssh_user: Some(ssh_user),
+            ssh_host: Some(ssh_host),
+            ssh_key: Some(ssh_key),
+            ssh_auth: Some(ssh_auth),
+            ssh_password,
+            remote_allowlist: Some(false),
+            storage_backend: Some("ssh".to_string()),
+            s3: None,
+            encryption: None,
+            size_warning: None,
+            compression: None,
+            compression_level: None,
+            adaptive_compression: None,
+            incremental: None,
+            fs_snapshots: None,
+            dedup: None,
+            db_dumps: None,
+            images: None,
+            restore_rate_limit: None,
+            restore_safety_snapshot: None,
+            confirm_destructive: None,
+            read_only: None,
+            max_concurrent_projects: None,
+            scan_depth: None,
+            max_concurrent_volumes: None,
+            heartbeat_secs: None,
             ssh_port: Some(
                 ask("SSH port (normally 22)")?
                     .parse()
@@ -294,7 +1302,36 @@ impl RawConfig {
             email_user: Some(ask("Email user")?),
             email_password: Some(ask("Email password")?),
             receiver_mail: Some(ask("Receiver email")?),
+            fallback_email_host: None,
+            fallback_email_port: None,
+            fallback_email_user: None,
+            fallback_email_password: None,
+            fallback_webhook_url: None,
+            otlp_endpoint: None,
+            dashboard_url: None,
+            report_mode: None,
+            language: None,
             interval: Some(interval),
+            auto_prune: Some(false),
+            prune_max_snapshots: None,
+            prune_max_percent: None,
+            verify_schedule: None,
+            retention_tags: None,
+            retention: None,
+            adopted_projects: None,
+            discovery: None,
+            include_projects: None,
+            exclude_projects: None,
+            hooks: None,
+            project_hooks: None,
+            quiesce: None,
+            excludes: None,
+            only_volumes: None,
+            restore_order: None,
+            targets: None,
+            prices: None,
+            cost_budget: None,
+            tui: None,
         };
 
         let test_prompt =
@@ -302,13 +1339,41 @@ impl RawConfig {
         let finalized = config.clone().finalize()?; // clone here to reuse for testing
 
         if test_prompt.eq_ignore_ascii_case("y") {
-            finalized.test_ssh().await?;
+            finalized.test_target().await?;
             finalized.test_email().await?;
         }
 
         Ok(config)
     }
 
+    /// Fills in what `finalize` would otherwise ask for: the default interval, SSH port 22
+    /// and empty values for the rest, checked by `Config::init`.
+    fn with_defaults(mut self) -> Self {
+        let interval = self.interval.get_or_insert_with(Default::default);
+        interval.hour.get_or_insert(0);
+        interval.day.get_or_insert(2);
+        interval.week.get_or_insert(7);
+        interval.month.get_or_insert(4);
+        interval.year.get_or_insert(12);
+        self.docker_parent
+            .get_or_insert_with(|| DockerParents::Many(Vec::new()));
+        for field in [
+            &mut self.remote_backup_path,
+            &mut self.ssh_user,
+            &mut self.ssh_host,
+            &mut self.ssh_key,
+            &mut self.email_host,
+            &mut self.email_user,
+            &mut self.email_password,
+            &mut self.receiver_mail,
+        ] {
+            field.get_or_insert_with(String::new);
+        }
+        self.ssh_port.get_or_insert(22);
+        self.email_port.get_or_insert(465);
+        self
+    }
+
     pub fn finalize(mut self) -> Result<Config> {
         fn ask<T: std::str::FromStr>(field: &str) -> T
         where
@@ -370,18 +1435,88 @@ impl RawConfig {
         };
 
         Ok(Config {
-            docker_parent: get!(docker_parent, String),
+            docker_parent: self
+                .docker_parent
+                .take()
+                .map(DockerParents::into_vec)
+                .unwrap_or_else(|| list(&ask::<String>("docker_parent"))),
             remote_backup_path: get!(remote_backup_path, String),
             ssh_user: get!(ssh_user, String),
             ssh_host: get!(ssh_host, String),
             ssh_key: get!(ssh_key, String),
             ssh_port: get!(ssh_port, u16),
+            ssh_auth: self.ssh_auth.take().unwrap_or_else(|| "key".to_string()),
+            ssh_password: self.ssh_password.take(),
+            remote_allowlist: self.remote_allowlist.take().unwrap_or(false),
+            storage_backend: self
+                .storage_backend
+                .take()
+                .unwrap_or_else(|| "ssh".to_string()),
+            s3: self.s3.take().unwrap_or_default(),
+            encryption: self.encryption.take().unwrap_or_default(),
+            size_warning: self.size_warning.take().unwrap_or_default(),
+            compression: self
+                .compression
+                .take()
+                .unwrap_or_else(|| "gzip".to_string()),
+            compression_level: self.compression_level.take(),
+            adaptive_compression: self.adaptive_compression.take().unwrap_or(true),
+            incremental: self.incremental.take().unwrap_or(false),
+            fs_snapshots: self.fs_snapshots.take().unwrap_or(false),
+            dedup: self.dedup.take().unwrap_or(false),
+            db_dumps: self.db_dumps.take().unwrap_or_else(|| "off".to_string()),
+            images: self.images.take().unwrap_or_else(|| "off".to_string()),
+            restore_rate_limit: self.restore_rate_limit.take(),
+            restore_safety_snapshot: self.restore_safety_snapshot.take().unwrap_or(true),
+            confirm_destructive: self.confirm_destructive.take().unwrap_or(false),
+            read_only: self.read_only.take().unwrap_or(false),
+            max_concurrent_projects: self.max_concurrent_projects.take().unwrap_or(1).max(1),
+            scan_depth: self.scan_depth.take().unwrap_or(1).max(1),
+            max_concurrent_volumes: self.max_concurrent_volumes.take().unwrap_or(1).max(1),
+            heartbeat_secs: self.heartbeat_secs.take().unwrap_or(60),
             email_host: get!(email_host, String),
             email_port: get!(email_port, u16),
             email_user: get!(email_user, String),
             email_password: get!(email_password, String),
             receiver_mail: get!(receiver_mail, String),
+            fallback_email_host: self.fallback_email_host.take(),
+            fallback_email_port: self.fallback_email_port.take(),
+            fallback_email_user: self.fallback_email_user.take(),
+            fallback_email_password: self.fallback_email_password.take(),
+            fallback_webhook_url: self.fallback_webhook_url.take(),
+            otlp_endpoint: self.otlp_endpoint.take(),
+            dashboard_url: self.dashboard_url.take(),
+            report_mode: self
+                .report_mode
+                .take()
+                .unwrap_or_else(|| "full".to_string()),
+            language: self.language.take().unwrap_or_else(|| "en".to_string()),
             interval,
+            auto_prune: self.auto_prune.take().unwrap_or(false),
+            prune_max_snapshots: self.prune_max_snapshots.take().unwrap_or(0),
+            prune_max_percent: self.prune_max_percent.take().unwrap_or(50),
+            verify_schedule: self.verify_schedule.take(),
+            retention_tags: self.retention_tags.take().unwrap_or_default(),
+            retention: self.retention.take().unwrap_or_default(),
+            adopted_projects: self.adopted_projects.take().unwrap_or_default(),
+            discovery: self
+                .discovery
+                .take()
+                .unwrap_or_else(|| "directory".to_string()),
+            include_projects: self.include_projects.take().unwrap_or_default(),
+            exclude_projects: self.exclude_projects.take().unwrap_or_default(),
+            hooks: self.hooks.take().unwrap_or_default(),
+            project_hooks: self.project_hooks.take().unwrap_or_default(),
+            quiesce: self.quiesce.take().unwrap_or_default(),
+            excludes: self.excludes.take().unwrap_or_default(),
+            only_volumes: self.only_volumes.take().unwrap_or_default(),
+            restore_order: self.restore_order.take().unwrap_or_default(),
+            targets: self.targets.take().unwrap_or_default(),
+            prices: self.prices.take().unwrap_or_default(),
+            cost_budget: self.cost_budget.take(),
+            tui: self.tui.take().unwrap_or_default(),
+            target_name: PRIMARY_TARGET.to_string(),
+            canary: false,
         })
     }
 }
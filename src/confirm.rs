@@ -0,0 +1,136 @@
+//! Guards for destructive commands: `read_only` disables them, `confirm_destructive`
+//! requires a second person.
+//!
+//! For confirmation a random token is sent through the notification channel (email with
+//! its fallbacks) and has to be typed in to continue. Whoever runs the command therefore
+//! needs the person reading the notifications to agree, and a mistyped command can't
+//! overwrite production data on its own.
+//!
+//! Commands that would destroy more than a configured limit (`prune_max_snapshots`,
+//! `prune_max_percent`) alert through the same channel and need `--force` or a
+//! confirmation at the terminal.
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::{
+    config::Config,
+    email,
+    i18n::{t, tf},
+    plain_println,
+};
+
+/// Tokens are only accepted for this long after they were sent.
+const TOKEN_VALIDITY: Duration = Duration::from_secs(10 * 60);
+
+/// Fails if the host is `read_only`, so `action` can't be run at all.
+pub fn forbid_read_only(config: &Config, action: &str) -> Result<()> {
+    if config.read_only {
+        anyhow::bail!(
+            "{} is disabled, this host is configured as read_only",
+            action
+        );
+    }
+    Ok(())
+}
+
+/// Asks for a confirmation token before `action` runs. Does nothing unless
+/// `confirm_destructive` is enabled.
+pub async fn require(config: &Config, action: &str) -> Result<()> {
+    if !config.confirm_destructive {
+        return Ok(());
+    }
+    let token = token()?;
+    let host = hostname();
+    email::send_summary_email(
+        config,
+        t("confirm.subject"),
+        &tf("confirm.body", &[&action, &host, &token]),
+    )
+    .await
+    .context("Failed to send the confirmation token, aborting")?;
+    let sent = Instant::now();
+
+    plain_println!(
+        "🔐 {} needs confirmation. A token was sent to {}.",
+        action,
+        config.receiver_mail
+    );
+    print!("Confirmation token: ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    if sent.elapsed() > TOKEN_VALIDITY {
+        anyhow::bail!("Confirmation token expired, aborting");
+    }
+    if input.trim() != token {
+        log::warn!("⚠️  Wrong confirmation token for: {}", action);
+        anyhow::bail!("Wrong confirmation token, aborting");
+    }
+    log::info!("✅ Confirmed: {}", action);
+    Ok(())
+}
+
+/// Handles `action` exceeding a safety limit, as described by `excess`: alerts through
+/// the notification channel and fails unless `force` is set or it is confirmed at the
+/// terminal. With `interactive` false nobody is asked.
+pub async fn require_over_limit(
+    config: &Config,
+    action: &str,
+    excess: &str,
+    force: bool,
+    interactive: bool,
+) -> Result<()> {
+    log::warn!("⚠️  {} exceeds its safety limit: {}", action, excess);
+    let outcome = if force {
+        t("limit.forced")
+    } else {
+        t("limit.stopped")
+    };
+    if let Err(e) = email::send_summary_email(
+        config,
+        t("limit.subject"),
+        &tf("limit.body", &[&action, &hostname(), &excess, &outcome]),
+    )
+    .await
+    {
+        log::warn!("⚠️  Failed to send the alert: {e}");
+    }
+    if force {
+        return Ok(());
+    }
+    if !interactive || !io::stdin().is_terminal() {
+        anyhow::bail!(
+            "{} exceeds its safety limit ({}), rerun it with --force if this is intended",
+            action,
+            excess
+        );
+    }
+    print!("Type `yes` to continue anyway: ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim() != "yes" {
+        anyhow::bail!("Not confirmed, aborting");
+    }
+    log::info!("✅ Confirmed: {}", action);
+    Ok(())
+}
+
+/// Eight random hex digits.
+fn token() -> Result<String> {
+    let mut bytes = [0u8; 4];
+    File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut bytes))
+        .context("Failed to generate a confirmation token")?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/etc/hostname")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_else(|_| "unknown host".into())
+}
@@ -0,0 +1,211 @@
+//! Backup coverage: what runs on the host vs. what dockup backs up.
+//!
+//! Running containers are grouped by their compose project (`com.docker.compose.*`
+//! labels). A compose project counts as covered when its working directory or name matches
+//! a scanned project, skipped projects are excluded on purpose and count as covered too.
+//! Containers started without compose are never covered.
+//!
+//! `dockup status` also shows the last successful snapshot of every project from the
+//! catalog and fails if one is older than the backup interval, for monitoring.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{
+    catalog::Catalog,
+    config::Config,
+    plain_println,
+    scanner::{scan_projects, ScanResult},
+};
+
+/// A compose project or standalone container that is running but not backed up.
+pub struct Unprotected {
+    pub name: String,
+    /// Compose working directory, `None` for containers started without compose.
+    pub path: Option<PathBuf>,
+    pub containers: Vec<String>,
+}
+
+/// Running compose projects and containers not covered by `scan`.
+pub fn find_unprotected(scan: &ScanResult) -> Result<Vec<Unprotected>> {
+    let output = Command::new("docker")
+        .args([
+            "ps",
+            "--format",
+            "{{.Names}}\t{{.Label \"com.docker.compose.project\"}}\t{{.Label \"com.docker.compose.project.working_dir\"}}",
+        ])
+        .output()
+        .context("Failed to run docker ps")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "docker ps failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let covered = |project: &str, dir: &Path| {
+        scan.apps
+            .iter()
+            .any(|app| app.name == project || same_dir(&app.application_path, dir))
+            || scan
+                .skipped
+                .iter()
+                .any(|skipped| skipped.name == project || same_dir(&skipped.path, dir))
+    };
+
+    let mut projects: BTreeMap<String, Unprotected> = BTreeMap::new();
+    let mut standalone = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut fields = line.split('\t');
+        let container = fields.next().unwrap_or_default().to_string();
+        let project = fields.next().unwrap_or_default();
+        let dir = Path::new(fields.next().unwrap_or_default());
+        if project.is_empty() {
+            standalone.push(Unprotected {
+                name: container.clone(),
+                path: None,
+                containers: vec![container],
+            });
+            continue;
+        }
+        if covered(project, dir) {
+            continue;
+        }
+        projects
+            .entry(project.to_string())
+            .or_insert_with(|| Unprotected {
+                name: project.to_string(),
+                path: (!dir.as_os_str().is_empty()).then(|| dir.to_path_buf()),
+                containers: Vec::new(),
+            })
+            .containers
+            .push(container);
+    }
+    Ok(projects.into_values().chain(standalone).collect())
+}
+
+/// Unprotected projects for the backup report. Failures only skip the report section.
+pub fn for_report(config: &Config) -> Vec<Unprotected> {
+    match scan_projects(config).and_then(|scan| find_unprotected(&scan)) {
+        Ok(unprotected) => unprotected,
+        Err(e) => {
+            log::warn!("⚠️  Could not check for unprotected projects: {e}");
+            Vec::new()
+        }
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Short description for the report and `dockup status`.
+pub fn describe(item: &Unprotected) -> String {
+    match &item.path {
+        Some(path) => format!(
+            "{} ({}, containers: {})",
+            item.name,
+            path.display(),
+            item.containers.join(", ")
+        ),
+        None => format!("{} (container without compose project)", item.name),
+    }
+}
+
+/// `dockup status`: lists covered, skipped and unprotected projects. With
+/// `only_unprotected` just the unprotected ones are listed.
+pub fn run_status(config: &Config, only_unprotected: bool) -> Result<()> {
+    let scan = scan_projects(config)?;
+    let unprotected = find_unprotected(&scan)?;
+    let mut stale = Vec::new();
+    if !only_unprotected {
+        let last_success = last_success();
+        let deadline = config
+            .backup_period()
+            .map(|period| Local::now() - period - chrono::Duration::minutes(STALE_GRACE_MINUTES));
+        for app in &scan.apps {
+            let last = last_success.get(&app.name);
+            let line = match last {
+                Some(last) => format!(
+                    "{} ({}), last backup {}",
+                    app.name,
+                    app.application_path.display(),
+                    last.format("%Y-%m-%d %H:%M")
+                ),
+                None => format!(
+                    "{} ({}), never backed up",
+                    app.name,
+                    app.application_path.display()
+                ),
+            };
+            match deadline {
+                Some(deadline) if last.is_none_or(|last| *last < deadline) => {
+                    plain_println!("❌ {} (stale)", line);
+                    stale.push(app.name.clone());
+                }
+                _ => plain_println!("✅ {}", line),
+            }
+        }
+        for skipped in &scan.skipped {
+            plain_println!("⏸  {} (skipped: {})", skipped.name, skipped.reason);
+        }
+        match Catalog::open().and_then(|catalog| catalog.last_verification()) {
+            Ok(Some(verification)) => plain_println!(
+                "{} Last verification {}: {} archives verified, {} failed",
+                if verification.failed == 0 {
+                    "🔍"
+                } else {
+                    "❌"
+                },
+                verification.finished.format("%Y-%m-%d %H:%M"),
+                verification.verified,
+                verification.failed
+            ),
+            Ok(None) if config.verify_schedule.is_some() => {
+                plain_println!("⚠️  verify_schedule is set, but no verification ran yet")
+            }
+            _ => {}
+        }
+    }
+    for item in &unprotected {
+        plain_println!("⚠️  {}", describe(item));
+    }
+    if unprotected.is_empty() {
+        plain_println!("✅ Every running container is covered by a backup");
+    } else {
+        plain_println!(
+            "\n{} running projects or containers are not backed up",
+            unprotected.len()
+        );
+    }
+    if !stale.is_empty() {
+        anyhow::bail!(
+            "No successful backup within the backup interval for: {}",
+            stale.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Minutes a scheduled backup may run late before its project counts as stale.
+const STALE_GRACE_MINUTES: i64 = 60;
+
+/// Project -> time of its newest fully successful snapshot in the catalog.
+fn last_success() -> HashMap<String, DateTime<Local>> {
+    match Catalog::open().and_then(|catalog| catalog.project_metrics()) {
+        Ok(metrics) => metrics
+            .into_iter()
+            .filter_map(|metrics| Some((metrics.project, metrics.last_success?)))
+            .collect(),
+        Err(e) => {
+            log::warn!("⚠️  Backup catalog unavailable: {e}");
+            HashMap::new()
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! `dockup interval install`/`uninstall`: manages the dockup entry in the user's crontab.
+//!
+//! The entry is tagged with a marker comment, so installing again replaces it instead of
+//! adding a second one and other entries are left alone. With `verify_schedule` set a
+//! second entry runs `dockup verify -s` on its own schedule.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+
+const MARKER: &str = "# dockup scheduled backup";
+const VERIFY_MARKER: &str = "# dockup scheduled verify";
+
+/// Adds or updates the dockup entry using `suggested_cron()`, and the verify entry using
+/// `verify_schedule` (removing it when that is unset).
+pub fn install(config: &Config) -> Result<()> {
+    let cron = config
+        .suggested_cron()
+        .context("No backup interval is configured, set one with `dockup interval set`")?;
+    let exe = std::env::current_exe().context("Failed to locate the dockup binary")?;
+    install_entry(&format!("{} {} backup -s {}", cron, exe.display(), MARKER))?;
+    match &config.verify_schedule {
+        Some(schedule) => install_entry(&format!(
+            "{} {} verify -s {}",
+            schedule,
+            exe.display(),
+            VERIFY_MARKER
+        )),
+        None => remove(&[VERIFY_MARKER]).map(drop),
+    }
+}
+
+/// The installed dockup entry, if any.
+pub fn entry() -> Result<Option<String>> {
+    find(MARKER)
+}
+
+/// The installed verify entry, if any.
+pub fn verify_entry() -> Result<Option<String>> {
+    find(VERIFY_MARKER)
+}
+
+fn find(marker: &str) -> Result<Option<String>> {
+    Ok(read()?
+        .lines()
+        .find(|line| line.ends_with(marker))
+        .map(str::to_string))
+}
+
+/// Replaces the dockup entry with `entry` (as returned by [`entry`] or [`verify_entry`]).
+pub fn install_entry(entry: &str) -> Result<()> {
+    let marker = [MARKER, VERIFY_MARKER]
+        .into_iter()
+        .find(|marker| entry.ends_with(marker))
+        .context("Not a dockup crontab entry")?;
+    let mut lines = without_entries(&read()?, &[marker]);
+    lines.push(entry.to_string());
+    write(&lines)?;
+    log::info!("✅ Crontab entry installed: {}", entry);
+    Ok(())
+}
+
+/// Removes the dockup entries, if any.
+pub fn uninstall() -> Result<()> {
+    if remove(&[MARKER, VERIFY_MARKER])? {
+        log::info!("✅ Crontab entry removed");
+    } else {
+        log::info!("No dockup entry in the crontab");
+    }
+    Ok(())
+}
+
+/// Removes the entries tagged with `markers`, returns whether there were any.
+fn remove(markers: &[&str]) -> Result<bool> {
+    let current = read()?;
+    let lines = without_entries(&current, markers);
+    if lines.len() == current.lines().count() {
+        return Ok(false);
+    }
+    write(&lines)?;
+    Ok(true)
+}
+
+fn without_entries(crontab: &str, markers: &[&str]) -> Vec<String> {
+    crontab
+        .lines()
+        .filter(|line| !markers.iter().any(|marker| line.ends_with(marker)))
+        .map(str::to_string)
+        .collect()
+}
+
+fn read() -> Result<String> {
+    let output = Command::new("crontab")
+        .arg("-l")
+        .output()
+        .context("Failed to run crontab, is cron installed?")?;
+    if !output.status.success() {
+        // `crontab -l` fails when the user has no crontab yet
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no crontab") {
+            return Ok(String::new());
+        }
+        anyhow::bail!("crontab -l failed: {}", stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn write(lines: &[String]) -> Result<()> {
+    let mut child = Command::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to run crontab")?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    for line in lines {
+        writeln!(stdin, "{}", line)?;
+    }
+    drop(stdin);
+    if !child.wait()?.success() {
+        anyhow::bail!("Failed to write the crontab");
+    }
+    Ok(())
+}
@@ -0,0 +1,252 @@
+//! Client-side encryption of backup tarballs with the `age` or `gpg` CLI.
+//!
+//! Encrypted archives are uploaded with an additional `.age` / `.gpg` suffix and the
+//! snapshot's `meta.json` records `encrypted` and the method, so restore knows how to
+//! decrypt them.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use crate::{config::Config, transport::StorageBackend};
+
+/// File suffix of archives encrypted with `method`.
+pub fn suffix(method: &str) -> &'static str {
+    match method {
+        "gpg" => ".gpg",
+        _ => ".age",
+    }
+}
+
+/// Encrypts `tarball` for the configured recipient and removes the plaintext. Returns the
+/// tarball unchanged when encryption is disabled.
+pub fn seal(config: &Config, tarball: PathBuf) -> Result<PathBuf> {
+    let Some(recipient) = &config.encryption.recipient else {
+        return Ok(tarball);
+    };
+    let method = config.encryption.method();
+    let mut encrypted = tarball.clone().into_os_string();
+    encrypted.push(suffix(method));
+    let encrypted = PathBuf::from(encrypted);
+
+    let mut cmd = match method {
+        "gpg" => {
+            let mut cmd = Command::new("gpg");
+            cmd.args(["--batch", "--yes", "--trust-model", "always"])
+                .arg("--recipient")
+                .arg(recipient)
+                .arg("--output")
+                .arg(&encrypted)
+                .arg("--encrypt")
+                .arg(&tarball);
+            cmd
+        }
+        _ => {
+            let mut cmd = Command::new("age");
+            cmd.arg("--encrypt")
+                .arg("--recipient")
+                .arg(recipient)
+                .arg("--output")
+                .arg(&encrypted)
+                .arg(&tarball);
+            cmd
+        }
+    };
+    let output = run(&mut cmd, method);
+    fs::remove_file(&tarball).ok();
+    if let Err(e) = output {
+        fs::remove_file(&encrypted).ok();
+        return Err(e.context(format!("Failed to encrypt {:?}", tarball)));
+    }
+    Ok(encrypted)
+}
+
+/// `remote` as stored by [`put_sealed`]: with the method's suffix when encryption is enabled.
+pub fn sealed_name(config: &Config, remote: &str) -> String {
+    if config.encryption.enabled() {
+        format!("{}{}", remote, suffix(config.encryption.method()))
+    } else {
+        remote.to_string()
+    }
+}
+
+/// Uploads `local` to `remote`, encrypted if enabled: a copy is sealed and stored under
+/// [`sealed_name`], and a plaintext copy left by a run from before encryption was enabled
+/// is deleted. `local` itself is kept. Returns the remote path.
+pub fn put_sealed(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    local: &Path,
+    remote: &str,
+) -> Result<String> {
+    if !config.encryption.enabled() {
+        transport.put(local, remote)?;
+        return Ok(remote.to_string());
+    }
+    let name = local.file_name().context("File without name")?;
+    let mut copy = std::env::temp_dir()
+        .join(format!("dockup_seal_{}_", std::process::id()))
+        .into_os_string();
+    copy.push(name);
+    let copy = PathBuf::from(copy);
+    fs::copy(local, &copy).with_context(|| format!("Failed to copy {:?}", local))?;
+    let sealed = seal(config, copy)?;
+    let sealed_remote = sealed_name(config, remote);
+    let uploaded = transport.put(&sealed, &sealed_remote);
+    fs::remove_file(&sealed).ok();
+    uploaded?;
+    // Usually there is none, so a failure here means nothing
+    transport.delete(remote).ok();
+    Ok(sealed_remote)
+}
+
+/// Downloads a file uploaded with [`put_sealed`] to `local`, decrypting it if it was
+/// stored encrypted with either method.
+pub fn get_sealed(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    remote: &str,
+    local: &Path,
+) -> Result<()> {
+    for method in ["age", "gpg"] {
+        let mut encrypted = local.as_os_str().to_owned();
+        encrypted.push(suffix(method));
+        let encrypted = PathBuf::from(encrypted);
+        if transport
+            .get(&format!("{}{}", remote, suffix(method)), &encrypted)
+            .is_ok()
+        {
+            let opened = open(config, method, &encrypted, local);
+            fs::remove_file(&encrypted).ok();
+            return opened;
+        }
+    }
+    transport.get(remote, local)
+}
+
+/// Reads a text file uploaded with [`put_sealed`].
+pub fn read_sealed(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    remote: &str,
+) -> Result<String> {
+    let name = remote.rsplit('/').next().unwrap_or_default();
+    let local = std::env::temp_dir().join(format!("dockup_open_{}_{}", std::process::id(), name));
+    let content = get_sealed(config, transport, remote, &local)
+        .and_then(|()| Ok(fs::read_to_string(&local)?));
+    fs::remove_file(&local).ok();
+    content
+}
+
+/// Decrypts `encrypted` into `output`, with the configured identity file for age or the
+/// gpg keyring/agent for gpg.
+pub fn open(config: &Config, method: &str, encrypted: &Path, output: &Path) -> Result<()> {
+    let mut cmd = match method {
+        "gpg" => {
+            let mut cmd = Command::new("gpg");
+            cmd.args(["--batch", "--yes"])
+                .arg("--output")
+                .arg(output)
+                .arg("--decrypt")
+                .arg(encrypted);
+            cmd
+        }
+        _ => {
+            let identity =
+                config.encryption.identity.as_ref().context(
+                    "Backup is encrypted with age but no encryption.identity is configured",
+                )?;
+            let mut cmd = Command::new("age");
+            cmd.arg("--decrypt")
+                .arg("--identity")
+                .arg(identity)
+                .arg("--output")
+                .arg(output)
+                .arg(encrypted);
+            cmd
+        }
+    };
+    run(&mut cmd, method)
+        .map(|_| ())
+        .with_context(|| format!("Failed to decrypt {:?}", encrypted))
+}
+
+fn run(cmd: &mut Command, method: &str) -> Result<Output> {
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to run {}, is it installed?", method))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} exited with {}: {}",
+            method,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::RawConfig, local::LocalBackend, testing::TempDir};
+
+    fn config(root: &Path, recipient: Option<&str>) -> Config {
+        let raw: RawConfig = serde_json::from_value(serde_json::json!({
+            "docker_parent": [],
+            "remote_backup_path": root,
+            "ssh_user": "",
+            "ssh_host": "",
+            "ssh_key": "",
+            "ssh_port": 22,
+            "email_host": "",
+            "email_port": 465,
+            "email_user": "",
+            "email_password": "",
+            "receiver_mail": "",
+            "interval": { "hour": 0, "day": 1, "week": 0, "month": 0, "year": 0 },
+            "storage_backend": "local",
+            "encryption": { "recipient": recipient },
+        }))
+        .unwrap();
+        raw.finalize().unwrap()
+    }
+
+    #[test]
+    fn sealed_names_carry_the_method_suffix() {
+        let root = Path::new("/backups");
+        assert_eq!(
+            sealed_name(&config(root, None), "/backups/config.json"),
+            "/backups/config.json"
+        );
+        let mut encrypted = config(root, Some("age1example"));
+        assert_eq!(
+            sealed_name(&encrypted, "/backups/config.json"),
+            "/backups/config.json.age"
+        );
+        encrypted.encryption.method = Some("gpg".to_string());
+        assert_eq!(
+            sealed_name(&encrypted, "/backups/config.json"),
+            "/backups/config.json.gpg"
+        );
+    }
+
+    #[test]
+    fn plain_files_round_trip_without_encryption() {
+        let root = TempDir::new("sealed");
+        let config = config(&root, None);
+        let transport = LocalBackend::new(&config);
+        let local = root.join("local.json");
+        fs::write(&local, "{}").unwrap();
+        let remote = format!("{}/remote.json", root.display());
+        assert_eq!(
+            put_sealed(&config, &transport, &local, &remote).unwrap(),
+            remote
+        );
+        // The source is left alone
+        assert!(local.is_file());
+        assert_eq!(read_sealed(&config, &transport, &remote).unwrap(), "{}");
+        assert!(read_sealed(&config, &transport, &format!("{}/missing", root.display())).is_err());
+    }
+}
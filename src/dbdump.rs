@@ -0,0 +1,463 @@
+//! Database-aware dumps of PostgreSQL and MySQL/MariaDB services (`db_dumps`).
+//!
+//! Services whose image is Postgres (or PostGIS) are dumped with `pg_dumpall` inside the
+//! running container, as the `POSTGRES_USER` from the compose environment. MySQL and
+//! MariaDB services are dumped with `mysqldump`/`mariadb-dump` as root, with the root
+//! password from the container's environment. The dump is compressed like the archives and
+//! uploaded to `DUMPS/<service>.sql<ext>`. With `db_dumps = "instead"` the raw data volumes
+//! of successfully dumped services are left out, a copy of a live data directory is often
+//! not consistent anyway. `dockup restore --databases` pipes a dump back in.
+//!
+//! Any other service can be dumped with its own command by labelling it with
+//! `dockup.dump.cmd` and `dockup.dump.file`, see [`dump_labelled`].
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use yaml_rust::YamlLoader;
+
+use crate::{
+    backup::{get_file_size, BackupThingSummary},
+    compression,
+    config::Config,
+    crypto, progress,
+    scanner::{compose_value, ArchiveStats, BackupApplication, Checksum, DumpCommand},
+    transport::StorageBackend,
+    verify,
+};
+
+/// Restore items of the form `DB:<service>` load that service's dump.
+pub const RESTORE_PREFIX: &str = "DB:";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Postgres,
+    Mysql,
+    Mariadb,
+}
+
+impl Engine {
+    pub fn from_image(image: &str) -> Option<Self> {
+        if image.contains("postgres") || image.contains("postgis") {
+            Some(Engine::Postgres)
+        } else if image.contains("mariadb") {
+            Some(Engine::Mariadb)
+        } else if image.contains("mysql") {
+            Some(Engine::Mysql)
+        } else {
+            None
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Engine::Postgres => "Postgres",
+            Engine::Mysql => "MySQL",
+            Engine::Mariadb => "MariaDB",
+        }
+    }
+}
+
+/// A database service found in a compose file.
+pub struct DatabaseService {
+    pub engine: Engine,
+    pub service: String,
+    pub user: String,
+    pub password: Option<String>,
+    /// Host side of the service's volume mounts, as in `Volume::name`.
+    pub volumes: Vec<String>,
+}
+
+impl DatabaseService {
+    /// Command run in the container that writes the dump to stdout.
+    fn dump_command(&self) -> Vec<String> {
+        match self.engine {
+            Engine::Postgres => vec!["pg_dumpall".into(), "-U".into(), self.user.clone()],
+            Engine::Mysql | Engine::Mariadb => self.mysql_shell(
+                self.mysql_client("dump")
+                    + " --all-databases --single-transaction --routines --events",
+            ),
+        }
+    }
+
+    /// Command run in the container that loads a dump from stdin.
+    fn load_command(&self) -> Vec<String> {
+        match self.engine {
+            Engine::Postgres => vec![
+                "psql".into(),
+                "-q".into(),
+                "-U".into(),
+                self.user.clone(),
+                "-d".into(),
+                "postgres".into(),
+            ],
+            Engine::Mysql | Engine::Mariadb => self.mysql_shell(self.mysql_client("")),
+        }
+    }
+
+    /// `mysqldump`/`mysql` or their MariaDB names, which newer MariaDB images only ship.
+    fn mysql_client(&self, suffix: &str) -> String {
+        match (self.engine, suffix) {
+            (Engine::Mariadb, "") => "mariadb".to_string(),
+            (Engine::Mariadb, suffix) => format!("mariadb-{}", suffix),
+            (_, suffix) => format!("mysql{}", suffix),
+        }
+    }
+
+    /// Runs `client` as root through a shell, so the password can be taken from the
+    /// container's environment (it is often interpolated in the compose file).
+    fn mysql_shell(&self, client: String) -> Vec<String> {
+        vec![
+            "sh".into(),
+            "-c".into(),
+            format!(
+                "MYSQL_PWD=\"${{MYSQL_ROOT_PASSWORD:-$MARIADB_ROOT_PASSWORD}}\" exec {} -u root",
+                client
+            ),
+        ]
+    }
+}
+
+/// Finds Postgres and MySQL/MariaDB services in `compose_file`.
+pub fn detect(compose_file: &Path) -> Result<Vec<DatabaseService>> {
+    let content = fs::read_to_string(compose_file)
+        .with_context(|| format!("Failed to read {:?}", compose_file))?;
+    let yamls = YamlLoader::load_from_str(&content)?;
+    let Some(services) = yamls.first().and_then(|root| root["services"].as_hash()) else {
+        return Ok(Vec::new());
+    };
+
+    let mut found = Vec::new();
+    for (name, service) in services {
+        let (Some(name), Some(image)) = (name.as_str(), service["image"].as_str()) else {
+            continue;
+        };
+        let Some(engine) = Engine::from_image(image) else {
+            continue;
+        };
+        let env = |key: &str| compose_value(&service["environment"], key);
+        let postgres = engine == Engine::Postgres;
+        found.push(DatabaseService {
+            engine,
+            service: name.to_string(),
+            user: match postgres {
+                true => env("POSTGRES_USER").unwrap_or_else(|| "postgres".to_string()),
+                false => "root".to_string(),
+            },
+            // Interpolated values are only known to compose, the container has them anyway
+            password: env("POSTGRES_PASSWORD")
+                .filter(|password| postgres && !password.contains("${")),
+            volumes: service["volumes"]
+                .as_vec()
+                .into_iter()
+                .flatten()
+                .filter_map(|vol| vol.as_str()?.split_once(':').map(|(host, _)| host))
+                .map(str::to_string)
+                .collect(),
+        });
+    }
+    Ok(found)
+}
+
+/// File name of a service's dump in `DUMPS/` (before encryption).
+pub fn dump_name(service: &str, algorithm: &str) -> String {
+    format!(
+        "{}.sql{}",
+        service,
+        compression::extension(algorithm).trim_start_matches(".tar")
+    )
+}
+
+/// Dumps every database service of `app` into `<remote_base>/DUMPS`. Records the dumped
+/// services in `app.dumps` and, with `db_dumps = "instead"`, drops their volumes.
+pub fn dump_all(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    app: &mut BackupApplication,
+    remote_base: &str,
+) -> Vec<BackupThingSummary> {
+    let services = match detect(&app.compose_file()) {
+        Ok(services) => services,
+        Err(e) => {
+            log::warn!("⚠️  Could not look for databases in {}: {e}", app.name);
+            return Vec::new();
+        }
+    };
+    let mut summaries = Vec::new();
+    for service in services {
+        let started = Local::now();
+        progress::emit("archive", Some(&app.name), Some(&service.service), None);
+        let result = dump(config, transport, app, &service, remote_base);
+        if result.is_ok() {
+            app.dumps.push(service.service.clone());
+            if config.db_dumps == "instead" {
+                app.volumes
+                    .retain(|vol| !service.volumes.contains(&vol.name));
+            }
+        }
+        summaries.push(summary(
+            &app.name,
+            &service.service,
+            service.engine.label(),
+            started,
+            result,
+        ));
+    }
+    summaries
+}
+
+/// Runs the `dockup.dump.cmd` of every labelled service of `app` and uploads the file it
+/// wrote to `DUMPS/<service>-<file name>`, as is (only encrypted).
+pub fn dump_labelled(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    app: &BackupApplication,
+    remote_base: &str,
+) -> Vec<BackupThingSummary> {
+    let mut summaries = Vec::new();
+    for command in &app.dump_commands {
+        let started = Local::now();
+        progress::emit("archive", Some(&app.name), Some(&command.service), None);
+        let result = run_labelled(config, transport, app, command, remote_base);
+        summaries.push(summary(
+            &app.name,
+            &command.service,
+            "Dump",
+            started,
+            result,
+        ));
+    }
+    summaries
+}
+
+fn run_labelled(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    app: &BackupApplication,
+    command: &DumpCommand,
+    remote_base: &str,
+) -> Result<(String, ArchiveStats)> {
+    let compose = |args: &[&str]| -> Result<()> {
+        let status = Command::new("docker")
+            .arg("compose")
+            .args(args)
+            .current_dir(&app.application_path)
+            .stdout(Stdio::null())
+            .status()
+            .context("Failed to run docker compose")?;
+        if !status.success() {
+            anyhow::bail!("docker compose {} failed", args[0]);
+        }
+        Ok(())
+    };
+    compose(&["exec", "-T", &command.service, "sh", "-c", &command.cmd])?;
+
+    let file_name = Path::new(&command.file)
+        .file_name()
+        .with_context(|| format!("dockup.dump.file {} is not a file", command.file))?;
+    let local = staging(
+        app,
+        &format!("{}-{}", command.service, file_name.to_string_lossy()),
+    )?;
+    let result = compose(&[
+        "cp",
+        &format!("{}:{}", command.service, command.file),
+        &local.to_string_lossy(),
+    ])
+    .and_then(|()| {
+        let original = fs::metadata(&local)?.len();
+        seal_and_upload(
+            config,
+            transport,
+            &local,
+            &format!("{}/DUMPS", remote_base),
+            &command.service,
+            original,
+        )
+    });
+    fs::remove_file(&local).ok();
+    result
+}
+
+/// Logs the outcome of a dump and turns it into a report row.
+fn summary(
+    app: &str,
+    name: &str,
+    volume_type: &str,
+    started: chrono::DateTime<Local>,
+    result: Result<(String, ArchiveStats)>,
+) -> BackupThingSummary {
+    let seconds = (Local::now().timestamp_millis() - started.timestamp_millis()) as f64 / 1000.0;
+    let (status, size, stats) = match result {
+        Ok((size, stats)) => {
+            log::info!("✅ Dumped `{}`", name);
+            progress::emit("uploaded", Some(app), Some(name), None);
+            (
+                "✅".to_string(),
+                size,
+                Some(ArchiveStats { seconds, ..stats }),
+            )
+        }
+        Err(e) => {
+            log::error!("❌ Failed to dump `{}`: {:#}", name, e);
+            progress::emit("failed", Some(app), Some(name), None);
+            ("❌ Dump failed".to_string(), "-".to_string(), None)
+        }
+    };
+    BackupThingSummary {
+        name: name.to_string(),
+        status,
+        size,
+        duration: format!("{:.2} seconds", seconds),
+        volume_type: volume_type.to_string(),
+        stats,
+        downtime: None,
+    }
+}
+
+fn dump(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    app: &BackupApplication,
+    service: &DatabaseService,
+    remote_base: &str,
+) -> Result<(String, ArchiveStats)> {
+    let mut producer = Command::new("docker");
+    producer
+        .args(["compose", "exec", "-T"])
+        .current_dir(&app.application_path);
+    if let Some(password) = &service.password {
+        producer.args(["-e", &format!("PGPASSWORD={}", password)]);
+    }
+    producer.arg(&service.service).args(service.dump_command());
+
+    let local = staging(app, &dump_name(&service.service, &config.compression))?;
+    let result = compression::write_archive(
+        producer,
+        &config.compression,
+        config.compression_level,
+        &local,
+    )
+    .and_then(|original| {
+        seal_and_upload(
+            config,
+            transport,
+            &local,
+            &format!("{}/DUMPS", remote_base),
+            &service.service,
+            original,
+        )
+    });
+    fs::remove_file(&local).ok();
+    result
+}
+
+/// Local path for the dump `name` of `app`, the file name is kept for the upload.
+pub fn staging(app: &BackupApplication, name: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("dockup-dumps-{}", app.name));
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(name))
+}
+
+/// Encrypts `local` if enabled and uploads it into the remote directory `dir`.
+pub fn seal_and_upload(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    local: &Path,
+    dir: &str,
+    name: &str,
+    original: u64,
+) -> Result<(String, ArchiveStats)> {
+    let sealed = crypto::seal(config, local.to_path_buf())?;
+    let uploaded = upload(transport, &sealed, dir);
+    if sealed != local {
+        fs::remove_file(&sealed).ok();
+    }
+    let (size, compressed_bytes, checksum) = uploaded?;
+    Ok((
+        size,
+        ArchiveStats {
+            name: name.to_string(),
+            original_bytes: original,
+            compressed_bytes,
+            seconds: 0.0,
+            checksum: Some(checksum),
+        },
+    ))
+}
+
+fn upload(
+    transport: &dyn StorageBackend,
+    sealed: &Path,
+    dir: &str,
+) -> Result<(String, u64, Checksum)> {
+    transport.mkdir(dir)?;
+    let name = sealed
+        .file_name()
+        .context("Dump without file name")?
+        .to_string_lossy();
+    let sha256 = verify::put_verified(transport, sealed, &format!("{}/{}", dir, name))?;
+    // Relative to the snapshot folder, `DUMPS` or `IMAGES`
+    let folder = dir.rsplit('/').next().unwrap_or_default();
+    Ok((
+        get_file_size(&sealed.to_path_buf())?,
+        fs::metadata(sealed)?.len(),
+        Checksum {
+            file: format!("{}/{}", folder, name),
+            sha256,
+        },
+    ))
+}
+
+/// Starts decompressing `file` (by its extension) to a piped stdout.
+pub fn decompress(file: &Path) -> Result<Child> {
+    let program = match file.extension().and_then(|ext| ext.to_str()) {
+        Some("zst") => "zstd",
+        Some("xz") => "xz",
+        Some("gz") => "gzip",
+        _ => "cat",
+    };
+    let mut decompress = Command::new(program);
+    if program != "cat" {
+        decompress.arg("-dc");
+    }
+    decompress
+        .arg(file)
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to decompress {:?}", file))
+}
+
+/// Pipes the downloaded dump `file` of `service` into the running database of `app`.
+pub fn load(app: &BackupApplication, service: &str, file: &Path) -> Result<()> {
+    let compose_file = app.compose_file();
+    let service = detect(&compose_file)?
+        .into_iter()
+        .find(|found| found.service == service)
+        .with_context(|| format!("No database service {} in {:?}", service, compose_file))?;
+
+    let mut decompress = decompress(file)?;
+    let stdout = decompress.stdout.take().expect("stdout is piped");
+
+    let mut consumer = Command::new("docker");
+    consumer
+        .args(["compose", "exec", "-T"])
+        .current_dir(&app.application_path);
+    if let Some(password) = &service.password {
+        consumer.args(["-e", &format!("PGPASSWORD={}", password)]);
+    }
+    let loaded = consumer
+        .arg(&service.service)
+        .args(service.load_command())
+        .stdin(stdout)
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to run docker compose exec")?;
+    let decompressed = decompress.wait()?;
+    if !decompressed.success() || !loaded.success() {
+        anyhow::bail!("Failed to load the dump into {}", service.service);
+    }
+    Ok(())
+}
@@ -0,0 +1,393 @@
+//! Deduplicated chunk store for volumes (`dedup = true`).
+//!
+//! A volume's tar stream is split into content-defined chunks (gear rolling hash, 1-16 MiB,
+//! about 4 MiB on average), so a change in the middle of a file only affects the chunks
+//! around it instead of shifting everything after it. Each chunk is stored once per backup
+//! target as `.chunks/<xx>/<sha256>` (gzip compressed) and a snapshot only writes a
+//! manifest `VOLUMES/<volume>.manifest.json` listing its chunks in order. Chunks already on
+//! the target are not uploaded again, so databases that change slightly between runs only
+//! cost the changed chunks.
+
+use anyhow::{Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{config::Config, transport::StorageBackend};
+
+pub const MANIFEST_SUFFIX: &str = ".manifest.json";
+
+const MIN_CHUNK: usize = 1 << 20;
+const MAX_CHUNK: usize = 16 << 20;
+/// A boundary is cut where the low 22 hash bits are zero, ~4 MiB apart on average.
+const BOUNDARY_MASK: u64 = (1 << 22) - 1;
+
+/// Random values per byte for the gear hash (splitmix64 sequence).
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Chunk list of one stored stream.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    /// Size of the original stream.
+    pub size: u64,
+    /// SHA-256 of every chunk, in stream order.
+    pub chunks: Vec<String>,
+}
+
+/// Result of storing a stream.
+pub struct Stored {
+    /// Size of the original stream.
+    pub original_bytes: u64,
+    /// Compressed bytes of chunks that were not on the target yet.
+    pub uploaded_bytes: u64,
+    pub chunks: usize,
+    pub new_chunks: usize,
+}
+
+pub fn chunk_root(config: &Config) -> String {
+    format!("{}/.chunks", config.remote_backup_path)
+}
+
+/// Chunks on the target, listed lazily per prefix directory and cached.
+pub struct ChunkStore<'a> {
+    transport: &'a dyn StorageBackend,
+    root: String,
+    work_dir: PathBuf,
+    known: HashMap<String, HashSet<String>>,
+}
+
+impl<'a> ChunkStore<'a> {
+    pub fn new(config: &Config, transport: &'a dyn StorageBackend, work_dir: &Path) -> Self {
+        Self {
+            transport,
+            root: chunk_root(config),
+            work_dir: work_dir.to_path_buf(),
+            known: HashMap::new(),
+        }
+    }
+
+    /// Splits the tar stream written by `producer` into chunks, uploads the new ones and
+    /// writes the manifest to `manifest_path`.
+    pub fn store(&mut self, mut producer: Command, manifest_path: &str) -> Result<Stored> {
+        let name = Path::new(manifest_path)
+            .file_name()
+            .context("Manifest path without file name")?;
+        let local = self.work_dir.join(name);
+        // Compressed chunks are staged here, one file per stream for concurrent volumes
+        let staged = local.with_extension("chunk");
+        let mut child = producer.stdout(Stdio::piped()).spawn()?;
+        let stream = child.stdout.take().expect("stdout is piped");
+        let mut manifest = Manifest {
+            size: 0,
+            chunks: Vec::new(),
+        };
+        let mut uploaded_bytes = 0;
+        let mut new_chunks = 0;
+        let size = split(stream, |chunk| {
+            let (hash, uploaded) = self.put(chunk, &staged)?;
+            if let Some(bytes) = uploaded {
+                uploaded_bytes += bytes;
+                new_chunks += 1;
+            }
+            manifest.chunks.push(hash);
+            Ok(())
+        });
+        let status = child.wait()?;
+        manifest.size = size?;
+        if !status.success() {
+            anyhow::bail!("Failed to create tar stream for {}", manifest_path);
+        }
+
+        fs::write(&local, serde_json::to_string(&manifest)?)?;
+        self.transport.put(&local, manifest_path)?;
+        fs::remove_file(&local).ok();
+        Ok(Stored {
+            original_bytes: manifest.size,
+            uploaded_bytes,
+            chunks: manifest.chunks.len(),
+            new_chunks,
+        })
+    }
+
+    /// Uploads a chunk unless it is already stored. Returns its hash and the uploaded
+    /// (compressed) size for new chunks.
+    fn put(&mut self, chunk: &[u8], local: &Path) -> Result<(String, Option<u64>)> {
+        let hash = format!("{:x}", Sha256::digest(chunk));
+        let prefix = hash[..2].to_string();
+        let dir = format!("{}/{}", self.root, prefix);
+        let transport = self.transport;
+        let known = self.known.entry(prefix).or_insert_with(|| {
+            transport
+                .list(&dir)
+                .map(|names| names.into_iter().collect())
+                .unwrap_or_default()
+        });
+        if known.contains(&hash) {
+            return Ok((hash, None));
+        }
+        if known.is_empty() {
+            transport.mkdir(&dir)?;
+        }
+
+        let mut encoder = GzEncoder::new(File::create(local)?, Compression::fast());
+        encoder.write_all(chunk)?;
+        encoder.finish()?;
+        let bytes = fs::metadata(local)?.len();
+        let result = transport.put(local, &format!("{}/{}", dir, hash));
+        fs::remove_file(local).ok();
+        result?;
+        known.insert(hash.clone());
+        Ok((hash, Some(bytes)))
+    }
+}
+
+/// Reassembles the stream of `manifest_path` into the file `output`. Nothing is left
+/// behind if it fails.
+pub fn restore(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    manifest_path: &str,
+    output: &Path,
+) -> Result<()> {
+    let manifest: Manifest = serde_json::from_str(&transport.read(manifest_path)?)
+        .with_context(|| format!("Invalid manifest {}", manifest_path))?;
+    let chunk_file = output.with_extension("chunk");
+    let result = reassemble(config, transport, &manifest, output, &chunk_file);
+    fs::remove_file(&chunk_file).ok();
+    let result = result.and_then(|size| {
+        if size != manifest.size {
+            anyhow::bail!("Restored size does not match manifest {}", manifest_path);
+        }
+        Ok(())
+    });
+    if result.is_err() {
+        fs::remove_file(output).ok();
+    }
+    result
+}
+
+/// Writes the chunks of `manifest` to `output` one after another, each downloaded to
+/// `chunk_file` first. Returns the size written.
+fn reassemble(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    manifest: &Manifest,
+    output: &Path,
+    chunk_file: &Path,
+) -> Result<u64> {
+    let root = chunk_root(config);
+    let mut out = File::create(output).with_context(|| format!("Failed to create {:?}", output))?;
+    for hash in &manifest.chunks {
+        transport.get(&format!("{}/{}/{}", root, &hash[..2], hash), chunk_file)?;
+        let mut data = Vec::new();
+        GzDecoder::new(File::open(chunk_file)?).read_to_end(&mut data)?;
+        if format!("{:x}", Sha256::digest(&data)) != *hash {
+            anyhow::bail!("Chunk {} is corrupt", hash);
+        }
+        out.write_all(&data)?;
+    }
+    Ok(out.metadata()?.len())
+}
+
+/// Deletes chunks no longer referenced by any of `manifests`. Returns the number removed.
+pub fn collect_garbage(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    manifests: &[String],
+) -> Result<usize> {
+    let mut referenced = HashSet::new();
+    for path in manifests {
+        let manifest: Manifest = serde_json::from_str(&transport.read(path)?)
+            .with_context(|| format!("Invalid manifest {}", path))?;
+        referenced.extend(manifest.chunks);
+    }
+    let root = chunk_root(config);
+    let mut removed = 0;
+    for prefix in transport.list(&root).unwrap_or_default() {
+        let dir = format!("{}/{}", root, prefix);
+        for hash in transport.list(&dir)? {
+            if !referenced.contains(&hash) {
+                transport.delete(&format!("{}/{}", dir, hash))?;
+                removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Feeds content-defined chunks of `reader` to `emit`, returns the total size.
+fn split(mut reader: impl Read, mut emit: impl FnMut(&[u8]) -> Result<()>) -> Result<u64> {
+    let mut block = vec![0u8; 1 << 20];
+    let mut chunk = Vec::with_capacity(MAX_CHUNK);
+    let mut hash = 0u64;
+    let mut total = 0;
+    loop {
+        let read = match reader.read(&mut block) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        total += read as u64;
+        for &byte in &block[..read] {
+            chunk.push(byte);
+            hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+            if chunk.len() >= MAX_CHUNK || (chunk.len() >= MIN_CHUNK && hash & BOUNDARY_MASK == 0) {
+                emit(&chunk)?;
+                chunk.clear();
+                hash = 0;
+            }
+        }
+    }
+    if !chunk.is_empty() {
+        emit(&chunk)?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::RawConfig, local::LocalBackend, testing::TempDir};
+
+    /// Deterministic incompressible test data.
+    fn random_bytes(len: usize, mut seed: u64) -> Vec<u8> {
+        let mut data = Vec::with_capacity(len + 8);
+        while data.len() < len {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            data.extend_from_slice(&seed.to_le_bytes());
+        }
+        data.truncate(len);
+        data
+    }
+
+    fn chunks(data: &[u8]) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        let total = split(data, |chunk| {
+            chunks.push(chunk.to_vec());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(total, data.len() as u64);
+        chunks
+    }
+
+    #[test]
+    fn chunks_reassemble_to_the_input() {
+        let data = random_bytes(40 << 20, 1);
+        let chunks = chunks(&data);
+        assert!(chunks.len() > 2);
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(rest
+            .iter()
+            .all(|chunk| (MIN_CHUNK..=MAX_CHUNK).contains(&chunk.len())));
+        assert!(last.len() <= MAX_CHUNK);
+        assert_eq!(chunks.concat(), data);
+    }
+
+    #[test]
+    fn boundaries_survive_an_insertion() {
+        let data = random_bytes(40 << 20, 2);
+        let mut changed = data.clone();
+        changed.splice(1000..1000, random_bytes(12_345, 3));
+        let before = chunks(&data);
+        let after = chunks(&changed);
+        // Only the chunk holding the insertion differs, the later boundaries shift with it
+        assert_ne!(before[0], after[0]);
+        assert_eq!(before[1..], after[1..]);
+    }
+
+    #[test]
+    fn empty_input_has_no_chunks() {
+        assert!(chunks(&[]).is_empty());
+    }
+
+    fn config(root: &Path) -> Config {
+        let raw: RawConfig = serde_json::from_value(serde_json::json!({
+            "docker_parent": [],
+            "remote_backup_path": root,
+            "ssh_user": "",
+            "ssh_host": "",
+            "ssh_key": "",
+            "ssh_port": 22,
+            "email_host": "",
+            "email_port": 465,
+            "email_user": "",
+            "email_password": "",
+            "receiver_mail": "",
+            "interval": { "hour": 0, "day": 1, "week": 0, "month": 0, "year": 0 },
+            "storage_backend": "local",
+        }))
+        .unwrap();
+        raw.finalize().unwrap()
+    }
+
+    /// Stores `data` as the stream of `<root>/backups/volume.manifest.json`.
+    fn stored(root: &Path, data: &[u8]) -> (Config, String) {
+        let backups = root.join("backups");
+        fs::create_dir_all(&backups).unwrap();
+        let source = root.join("source");
+        fs::write(&source, data).unwrap();
+        let config = config(&backups);
+        let manifest = format!("{}/volume{}", backups.display(), MANIFEST_SUFFIX);
+        let mut producer = Command::new("cat");
+        producer.arg(&source);
+        ChunkStore::new(&config, &LocalBackend::new(&config), root)
+            .store(producer, &manifest)
+            .unwrap();
+        (config, manifest)
+    }
+
+    #[test]
+    fn restores_the_stored_stream() {
+        let root = TempDir::new("dedup-restore");
+        let data = random_bytes(3 << 20, 4);
+        let (config, manifest) = stored(&root, &data);
+        let output = root.join("volume.tar");
+        restore(&config, &LocalBackend::new(&config), &manifest, &output).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), data);
+        assert!(!output.with_extension("chunk").exists());
+    }
+
+    #[test]
+    fn failed_restores_leave_nothing_behind() {
+        let root = TempDir::new("dedup-corrupt");
+        // More than one chunk, the corrupt one comes after some were written
+        let (config, manifest) = stored(&root, &random_bytes(20 << 20, 5));
+        let stored: Manifest =
+            serde_json::from_str(&fs::read_to_string(&manifest).unwrap()).unwrap();
+        assert!(stored.chunks.len() > 1);
+        let hash = stored.chunks.last().unwrap();
+        let chunk = format!("{}/{}/{}", chunk_root(&config), &hash[..2], hash);
+        let mut encoder = GzEncoder::new(File::create(chunk).unwrap(), Compression::fast());
+        encoder.write_all(b"garbage").unwrap();
+        encoder.finish().unwrap();
+
+        let output = root.join("volume.tar");
+        assert!(restore(&config, &LocalBackend::new(&config), &manifest, &output).is_err());
+        assert!(!output.exists());
+        assert!(!output.with_extension("chunk").exists());
+    }
+}
@@ -1,42 +1,234 @@
-use crate::config::Config;
+use crate::{config::Config, i18n::t};
 use anyhow::Result;
 use lettre::{
     message::{Mailbox, Message},
     transport::smtp::authentication::Credentials,
     AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
 };
+use std::io::Write;
+use std::process::{Command, Stdio};
 
 /// Send summary email after backup job
-use lettre::message::{header::ContentType, SinglePart};
+use lettre::message::{header::ContentType, Attachment, MultiPart, SinglePart};
+
+/// SMTP relay settings, either the primary one or the configured fallback.
+struct SmtpRelay<'a> {
+    host: &'a str,
+    port: u16,
+    user: &'a str,
+    password: &'a str,
+}
 
 pub async fn send_summary_email(cfg: &Config, subject: &str, html_body: &str) -> Result<()> {
-    let email = Message::builder()
-        .from(cfg.email_user.parse::<Mailbox>()?)
-        .to(cfg.receiver_mail.parse::<Mailbox>()?)
-        .subject(subject)
-        .singlepart(
-            SinglePart::builder()
-                .header(ContentType::TEXT_HTML)
-                .body(html_body.to_string()),
-        )?;
+    send_report_email(cfg, subject, html_body, None).await
+}
 
-    let creds = Credentials::new(cfg.email_user.clone(), cfg.email_password.clone());
+/// Sends the report through the primary SMTP relay and falls back to the secondary relay
+/// and then the webhook when a send fails, so a broken relay doesn't swallow reports.
+/// `attachment` is attached as `dockup-report.html` (not forwarded to the webhook).
+pub async fn send_report_email(
+    cfg: &Config,
+    subject: &str,
+    html_body: &str,
+    attachment: Option<&str>,
+) -> Result<()> {
+    let primary = SmtpRelay {
+        host: &cfg.email_host,
+        port: cfg.email_port,
+        user: &cfg.email_user,
+        password: &cfg.email_password,
+    };
+    match send_smtp(cfg, &primary, subject, html_body, attachment).await {
+        Ok(()) => {
+            log::info!("✅ Email sent to {}", cfg.receiver_mail);
+            return Ok(());
+        }
+        Err(e) => log::error!("❌ Failed to send email: {e}"),
+    }
 
-    let mailer = AsyncSmtpTransport::<Tokio1Executor>::relay(&cfg.email_host)?
-        .port(cfg.email_port)
-        .credentials(creds)
-        .build();
+    if let Some(fallback) = fallback_relay(cfg) {
+        match send_smtp(cfg, &fallback, subject, html_body, attachment).await {
+            Ok(()) => {
+                log::info!(
+                    "✅ Email sent to {} via fallback relay {}",
+                    cfg.receiver_mail,
+                    fallback.host
+                );
+                return Ok(());
+            }
+            Err(e) => log::error!("❌ Fallback relay {} failed: {e}", fallback.host),
+        }
+    }
 
-    match mailer.send(email).await {
-        Ok(_) => log::info!("✅ Email sent to {}", cfg.receiver_mail),
-        Err(e) => log::error!("❌ Failed to send email: {e}"),
+    if let Some(url) = &cfg.fallback_webhook_url {
+        match send_webhook(url, subject, html_body) {
+            Ok(()) => {
+                log::info!("✅ Report delivered to fallback webhook");
+                return Ok(());
+            }
+            Err(e) => log::error!("❌ Fallback webhook failed: {e}"),
+        }
     }
 
+    log::error!("❌ Report could not be delivered through any notification channel");
     Ok(())
 }
 
 pub async fn send_test_email(cfg: &Config) -> Result<()> {
-    let subject = "Dockup Test Email";
-    let body = "If you are reading this, the email configuration is working.";
+    let subject = t("test_email.subject");
+    let body = t("test_email.body");
     send_summary_email(cfg, subject, body).await
 }
+
+/// Sends a test message through every configured notification channel on its own (no
+/// fallback between them) and reports the result per channel. Fails if any channel failed.
+pub async fn test_notification_channels(cfg: &Config) -> Result<()> {
+    let subject = t("test_email.subject");
+    let body = t("test_email.body");
+    let mut results = Vec::new();
+
+    let primary = SmtpRelay {
+        host: &cfg.email_host,
+        port: cfg.email_port,
+        user: &cfg.email_user,
+        password: &cfg.email_password,
+    };
+    results.push((
+        format!("SMTP {}:{}", primary.host, primary.port),
+        send_smtp(cfg, &primary, subject, body, None).await,
+    ));
+    if let Some(fallback) = fallback_relay(cfg) {
+        results.push((
+            format!("Fallback SMTP {}:{}", fallback.host, fallback.port),
+            send_smtp(cfg, &fallback, subject, body, None).await,
+        ));
+    }
+    if let Some(url) = &cfg.fallback_webhook_url {
+        results.push((format!("Webhook {}", url), send_webhook(url, subject, body)));
+    }
+
+    let mut failed = 0;
+    for (channel, result) in &results {
+        match result {
+            Ok(()) => log::info!("✅ {}: test message sent", channel),
+            Err(e) => {
+                failed += 1;
+                log::error!("❌ {}: {e}", channel);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!(
+            "{} of {} notification channels failed",
+            failed,
+            results.len()
+        );
+    }
+    Ok(())
+}
+
+fn fallback_relay(cfg: &Config) -> Option<SmtpRelay<'_>> {
+    let host = cfg.fallback_email_host.as_deref()?;
+    Some(SmtpRelay {
+        host,
+        port: cfg.fallback_email_port.unwrap_or(cfg.email_port),
+        user: cfg
+            .fallback_email_user
+            .as_deref()
+            .unwrap_or(&cfg.email_user),
+        password: cfg
+            .fallback_email_password
+            .as_deref()
+            .unwrap_or(&cfg.email_password),
+    })
+}
+
+async fn send_smtp(
+    cfg: &Config,
+    relay: &SmtpRelay<'_>,
+    subject: &str,
+    html_body: &str,
+    attachment: Option<&str>,
+) -> Result<()> {
+    let builder = Message::builder()
+        .from(relay.user.parse::<Mailbox>()?)
+        .to(cfg.receiver_mail.parse::<Mailbox>()?)
+        .subject(subject);
+    let body = SinglePart::builder()
+        .header(ContentType::TEXT_HTML)
+        .body(html_body.to_string());
+    let email = match attachment {
+        Some(full_report) => builder.multipart(
+            MultiPart::mixed().singlepart(body).singlepart(
+                Attachment::new("dockup-report.html".to_string())
+                    .body(full_report.to_string(), ContentType::TEXT_HTML),
+            ),
+        )?,
+        None => builder.singlepart(body)?,
+    };
+
+    let creds = Credentials::new(relay.user.to_string(), relay.password.to_string());
+
+    let mailer = AsyncSmtpTransport::<Tokio1Executor>::relay(relay.host)?
+        .port(relay.port)
+        .credentials(creds)
+        .build();
+
+    mailer.send(email).await?;
+    Ok(())
+}
+
+/// POSTs the report as JSON (`subject`, plain `text` and `html`) to a webhook.
+fn send_webhook(url: &str, subject: &str, html_body: &str) -> Result<()> {
+    let payload = serde_json::json!({
+        "subject": subject,
+        "text": format!("{}\n\n{}", subject, strip_html(html_body)),
+        "html": html_body,
+    });
+    let mut child = Command::new("curl")
+        .args([
+            "-fsS",
+            "-X",
+            "POST",
+            "-H",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(payload.to_string().as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "curl exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
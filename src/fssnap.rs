@@ -0,0 +1,263 @@
+//! Archiving volumes from filesystem snapshots (`fs_snapshots`).
+//!
+//! With `fs_snapshots = true` the volumes of a project are read from a read-only snapshot
+//! of the filesystem they live on instead of the live data: a btrfs snapshot of the
+//! subvolume, a ZFS snapshot of the dataset or an LVM snapshot volume mounted read-only.
+//! The snapshots of a project are taken together, so the archives are crash-consistent
+//! with each other, and `quiesce` only halts the containers while they are taken. If a
+//! volume's filesystem can't be snapshotted, the project is backed up the normal way.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{
+    incremental,
+    scanner::{BackupApplication, VolumeType},
+};
+
+/// Inode number of the root directory of every btrfs subvolume.
+const BTRFS_SUBVOLUME_INODE: u64 = 256;
+
+/// btrfs snapshots live in the root of their subvolume under this prefix. That may be the
+/// project directory itself, so archives of directories leave them out.
+pub const SNAPSHOT_DIR_PREFIX: &str = ".dockup-snapshot-";
+
+#[derive(Clone, PartialEq)]
+enum Filesystem {
+    /// Root directory of the subvolume.
+    Btrfs(PathBuf),
+    /// Dataset and its mountpoint.
+    Zfs(String, PathBuf),
+    /// Volume group, logical volume, filesystem type and mountpoint.
+    Lvm(String, String, String, PathBuf),
+}
+
+impl Filesystem {
+    /// Directory `path` is relative to in a snapshot.
+    fn root(&self) -> &Path {
+        match self {
+            Filesystem::Btrfs(root) | Filesystem::Zfs(_, root) | Filesystem::Lvm(_, _, _, root) => {
+                root
+            }
+        }
+    }
+}
+
+/// The filesystems to snapshot for a project and where each volume lives on them.
+pub struct Plan {
+    filesystems: Vec<Filesystem>,
+    /// Volume name -> index into `filesystems` and path relative to its root.
+    volumes: HashMap<String, (usize, PathBuf)>,
+    /// Prefix of the snapshot names, unique per project and process.
+    name: String,
+}
+
+/// Snapshots taken for a project, dropped with [`Snapshots::release`].
+pub struct Snapshots {
+    taken: Vec<(Filesystem, PathBuf)>,
+    name: String,
+}
+
+impl Plan {
+    /// Looks up the filesystem of every volume of `app`. Fails if one of them doesn't
+    /// support snapshots or has no host directory (volumes of other drivers).
+    pub fn for_project(app: &BackupApplication) -> Result<Self> {
+        let mut plan = Plan {
+            filesystems: Vec::new(),
+            volumes: HashMap::new(),
+            name: format!("dockup-{}-{}", app.name, std::process::id()),
+        };
+        for vol in &app.volumes {
+            let path = match vol.volume_type {
+                VolumeType::Bind => vol.path.clone(),
+                VolumeType::Mount if vol.driver.is_some() => {
+                    anyhow::bail!("volume `{}` is not on this host", vol.name)
+                }
+                VolumeType::Mount => incremental::mountpoint(&vol.docker_volume(&app.name))?,
+            };
+            let path = path
+                .canonicalize()
+                .with_context(|| format!("Failed to resolve {:?}", path))?;
+            let filesystem = filesystem(&path)
+                .with_context(|| format!("Volume `{}` can't be snapshotted", vol.name))?;
+            let relative = path.strip_prefix(filesystem.root())?.to_path_buf();
+            let index = match plan.filesystems.iter().position(|f| *f == filesystem) {
+                Some(index) => index,
+                None => {
+                    plan.filesystems.push(filesystem);
+                    plan.filesystems.len() - 1
+                }
+            };
+            plan.volumes.insert(vol.name.clone(), (index, relative));
+        }
+        Ok(plan)
+    }
+
+    /// Takes the snapshots and points every volume of `app` at its copy. Snapshots taken
+    /// before a failure are dropped again.
+    pub fn take(&self, app: &mut BackupApplication) -> Result<Snapshots> {
+        let mut snapshots = Snapshots {
+            taken: Vec::new(),
+            name: self.name.clone(),
+        };
+        for (index, filesystem) in self.filesystems.iter().enumerate() {
+            let name = format!("{}-{}", self.name, index);
+            match snapshot(filesystem, &name) {
+                Ok(path) => snapshots.taken.push((filesystem.clone(), path)),
+                Err(e) => {
+                    snapshots.release();
+                    return Err(e);
+                }
+            }
+        }
+        for vol in app.volumes.iter_mut() {
+            let (index, relative) = &self.volumes[&vol.name];
+            vol.snapshot = Some(snapshots.taken[*index].1.join(relative));
+        }
+        Ok(snapshots)
+    }
+}
+
+impl Snapshots {
+    /// Unmounts and deletes the snapshots, logging the ones that couldn't be removed.
+    pub fn release(self) {
+        for (index, (filesystem, path)) in self.taken.into_iter().enumerate() {
+            let name = format!("{}-{}", self.name, index);
+            if let Err(e) = drop_snapshot(&filesystem, &name, &path) {
+                log::error!("❌ Failed to remove snapshot {:?}: {e}", path);
+            }
+        }
+    }
+}
+
+/// The snapshottable filesystem holding `path`.
+fn filesystem(path: &Path) -> Result<Filesystem> {
+    let output = Command::new("findmnt")
+        .args(["-n", "-r", "-o", "FSTYPE,SOURCE,TARGET", "-T"])
+        .arg(path)
+        .output()
+        .context("Failed to run findmnt")?;
+    if !output.status.success() {
+        anyhow::bail!("findmnt failed for {:?}", path);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = stdout.split_whitespace().collect();
+    let [fstype, source, target] = fields[..] else {
+        anyhow::bail!("Unexpected output from findmnt: {}", stdout.trim());
+    };
+    match fstype {
+        "btrfs" => {
+            // Snapshots don't descend into nested subvolumes, use the innermost one
+            let root = path
+                .ancestors()
+                .find(|dir| fs::metadata(dir).is_ok_and(|meta| meta.ino() == BTRFS_SUBVOLUME_INODE))
+                .context("No btrfs subvolume found")?;
+            Ok(Filesystem::Btrfs(root.to_path_buf()))
+        }
+        "zfs" => Ok(Filesystem::Zfs(source.to_string(), PathBuf::from(target))),
+        _ => {
+            let output = Command::new("lvs")
+                .args(["--noheadings", "-o", "vg_name,lv_name", source])
+                .stderr(Stdio::null())
+                .output();
+            let lvs = match output {
+                Ok(output) if output.status.success() => {
+                    String::from_utf8_lossy(&output.stdout).to_string()
+                }
+                _ => anyhow::bail!("{} on {} is neither btrfs, ZFS nor LVM", fstype, source),
+            };
+            let mut names = lvs.split_whitespace();
+            let (Some(vg), Some(lv)) = (names.next(), names.next()) else {
+                anyhow::bail!("Unexpected output from lvs: {}", lvs.trim());
+            };
+            Ok(Filesystem::Lvm(
+                vg.to_string(),
+                lv.to_string(),
+                fstype.to_string(),
+                PathBuf::from(target),
+            ))
+        }
+    }
+}
+
+/// Takes the snapshot `name` of `filesystem` and returns the directory it is readable at.
+fn snapshot(filesystem: &Filesystem, name: &str) -> Result<PathBuf> {
+    match filesystem {
+        Filesystem::Btrfs(root) => {
+            let path = root.join(format!("{}{}", SNAPSHOT_DIR_PREFIX, name));
+            run(Command::new("btrfs")
+                .args(["subvolume", "snapshot", "-r"])
+                .arg(root)
+                .arg(&path))?;
+            Ok(path)
+        }
+        Filesystem::Zfs(dataset, mountpoint) => {
+            run(Command::new("zfs")
+                .arg("snapshot")
+                .arg(format!("{}@{}", dataset, name)))?;
+            Ok(mountpoint.join(".zfs/snapshot").join(name))
+        }
+        Filesystem::Lvm(vg, lv, fstype, _) => {
+            run(Command::new("lvcreate").args([
+                "--snapshot",
+                "--extents",
+                "10%ORIGIN",
+                "--name",
+                name,
+                &format!("{}/{}", vg, lv),
+            ]))?;
+            let path = std::env::temp_dir().join(name);
+            // XFS refuses to mount a second filesystem with the same UUID
+            let options = if fstype == "xfs" { "ro,nouuid" } else { "ro" };
+            let mounted = fs::create_dir_all(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|()| {
+                    run(Command::new("mount")
+                        .args(["-o", options])
+                        .arg(format!("/dev/{}/{}", vg, name))
+                        .arg(&path))
+                });
+            if let Err(e) = mounted {
+                fs::remove_dir(&path).ok();
+                run(Command::new("lvremove").args(["-f", &format!("{}/{}", vg, name)])).ok();
+                return Err(e);
+            }
+            Ok(path)
+        }
+    }
+}
+
+fn drop_snapshot(filesystem: &Filesystem, name: &str, path: &Path) -> Result<()> {
+    match filesystem {
+        Filesystem::Btrfs(_) => run(Command::new("btrfs")
+            .args(["subvolume", "delete"])
+            .arg(path)),
+        Filesystem::Zfs(dataset, _) => run(Command::new("zfs")
+            .arg("destroy")
+            .arg(format!("{}@{}", dataset, name))),
+        Filesystem::Lvm(vg, ..) => {
+            run(Command::new("umount").arg(path))?;
+            fs::remove_dir(path).ok();
+            run(Command::new("lvremove").args(["-f", &format!("{}/{}", vg, name)]))
+        }
+    }
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
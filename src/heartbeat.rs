@@ -0,0 +1,95 @@
+//! Heartbeat log lines during long archive and upload steps (`heartbeat_secs`).
+//!
+//! While a step runs, a line like `⏳ Still uploading nextcloud_data: 12.3 GB / 48.0 GB
+//! (14 min)` is logged every `heartbeat_secs` seconds, so a slow step can be told apart
+//! from a hung one. Under systemd the same line becomes the unit's status text
+//! (`systemctl status dockup-backup`), and with `--progress json` a `heartbeat` event is
+//! emitted.
+
+use std::os::unix::net::UnixDatagram;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::{progress, utils::format_bytes};
+
+/// A step the heartbeat reports on.
+pub struct Step<'a> {
+    /// Verb for the log line, e.g. `archiving`.
+    pub action: &'a str,
+    pub project: &'a str,
+    pub item: &'a str,
+    /// Total bytes of the step, if known up front.
+    pub total: Option<u64>,
+}
+
+/// Runs `work` and reports on it every `every` seconds until it returns. `done` is asked
+/// for the bytes processed so far on every beat. With `every = 0` `work` just runs.
+pub fn run<T>(
+    every: u64,
+    step: &Step,
+    done: impl Fn() -> Option<u64> + Sync,
+    work: impl FnOnce() -> T,
+) -> T {
+    if every == 0 {
+        return work();
+    }
+    let started = Instant::now();
+    let (stop, stopped) = mpsc::channel::<()>();
+    let done = &done;
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            // Returns as soon as `stop` is dropped
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                stopped.recv_timeout(Duration::from_secs(every))
+            {
+                beat(step, done(), started.elapsed());
+            }
+        });
+        let result = work();
+        drop(stop);
+        result
+    })
+}
+
+fn beat(step: &Step, done: Option<u64>, elapsed: Duration) {
+    let progress = match (done, step.total) {
+        (Some(done), Some(total)) => {
+            format!(": {} / {}", format_bytes(done), format_bytes(total))
+        }
+        (Some(done), None) => format!(": {}", format_bytes(done)),
+        (None, Some(total)) => format!(" ({})", format_bytes(total)),
+        (None, None) => String::new(),
+    };
+    let line = format!(
+        "Still {} {}{} ({} min)",
+        step.action,
+        step.item,
+        progress,
+        elapsed.as_secs() / 60
+    );
+    log::info!("⏳ {} [{}]", line, step.project);
+    progress::emit("heartbeat", Some(step.project), Some(step.item), done);
+    notify_systemd(&format!("{}: {}", step.project, line));
+}
+
+/// Sets the status text of the systemd unit dockup runs in, if any.
+fn notify_systemd(status: &str) {
+    let Ok(path) = std::env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+    let message = format!("STATUS={}", status);
+    let sent = match path.strip_prefix('@') {
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name)
+                .and_then(|addr| socket.send_to_addr(message.as_bytes(), &addr))
+        }
+        None => socket.send_to(message.as_bytes(), &path),
+    };
+    if let Err(e) = sent {
+        log::debug!("Failed to notify systemd: {e}");
+    }
+}
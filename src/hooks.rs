@@ -0,0 +1,102 @@
+//! Hook commands run around a project's backup or restore (`hooks` and `project_hooks`).
+//!
+//! Each hook runs through `sh -c` in the project directory, the global command first and
+//! then the project's own. They get the project and the snapshot through environment
+//! variables, so a script can e.g. stop an app or dump a database before archiving.
+
+use anyhow::{Context, Result};
+use std::process::Command;
+
+use crate::config::{Config, HooksConfig};
+
+#[derive(Clone, Copy)]
+pub enum Hook {
+    PreBackup,
+    PostBackup,
+    OnFailure,
+    PostRestore,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::PreBackup => "pre_backup",
+            Hook::PostBackup => "post_backup",
+            Hook::OnFailure => "on_failure",
+            Hook::PostRestore => "post_restore",
+        }
+    }
+
+    fn command(self, hooks: &HooksConfig) -> Option<&str> {
+        match self {
+            Hook::PreBackup => hooks.pre_backup.as_deref(),
+            Hook::PostBackup => hooks.post_backup.as_deref(),
+            Hook::OnFailure => hooks.on_failure.as_deref(),
+            Hook::PostRestore => hooks.post_restore.as_deref(),
+        }
+    }
+}
+
+/// What a hook is told about the backup.
+pub struct HookEnv<'a> {
+    pub project: &'a str,
+    pub project_path: &'a str,
+    /// Snapshot folder on the backup target (backed up to or restored from).
+    pub backup_path: &'a str,
+    /// `pending` before the backup, `success` or `failure` afterwards. Always `success` for
+    /// `post_restore`.
+    pub status: &'a str,
+}
+
+/// Runs the global and the project's command for `hook`. Stops at the first failing one.
+pub fn run(config: &Config, hook: Hook, env: &HookEnv) -> Result<()> {
+    run_with_output(config, hook, env, |line| log::debug!("   {}", line))
+}
+
+/// Like [`run`], passing each line the commands print (stdout, then stderr) to `output`.
+pub fn run_with_output(
+    config: &Config,
+    hook: Hook,
+    env: &HookEnv,
+    mut output: impl FnMut(&str),
+) -> Result<()> {
+    let commands = [
+        hook.command(&config.hooks),
+        config
+            .project_hooks
+            .get(env.project)
+            .and_then(|hooks| hook.command(hooks)),
+    ];
+    for command in commands.into_iter().flatten() {
+        log::info!(
+            "🪝 Running {} hook for {}: {}",
+            hook.name(),
+            env.project,
+            command
+        );
+        let result = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(env.project_path)
+            .env("DOCKUP_HOOK", hook.name())
+            .env("DOCKUP_PROJECT", env.project)
+            .env("DOCKUP_PROJECT_PATH", env.project_path)
+            .env("DOCKUP_BACKUP_PATH", env.backup_path)
+            .env("DOCKUP_STATUS", env.status)
+            .output()
+            .with_context(|| format!("Failed to run {} hook", hook.name()))?;
+        let stdout = String::from_utf8_lossy(&result.stdout);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        stdout.lines().chain(stderr.lines()).for_each(&mut output);
+        if !result.status.success() {
+            anyhow::bail!(
+                "{} hook `{}` failed ({}): {}",
+                hook.name(),
+                command,
+                result.status,
+                stderr.trim()
+            );
+        }
+    }
+    Ok(())
+}
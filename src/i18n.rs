@@ -0,0 +1,235 @@
+//! Message catalogs for report emails and user-facing CLI strings.
+//!
+//! Messages are looked up by key in the language selected via the `language` config key
+//! (`en` or `de`) and fall back to English. `{}` placeholders are filled in order by [`tf`].
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+const EN: u8 = 0;
+const DE: u8 = 1;
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(EN);
+
+pub const SUPPORTED_LANGUAGES: [&str; 2] = ["en", "de"];
+
+pub fn set_language(code: &str) {
+    let lang = match code {
+        "de" => DE,
+        _ => EN,
+    };
+    LANGUAGE.store(lang, Ordering::Relaxed);
+}
+
+/// Looks up a message in the current language.
+pub fn t(key: &'static str) -> &'static str {
+    if LANGUAGE.load(Ordering::Relaxed) == DE {
+        if let Some(msg) = german(key) {
+            return msg;
+        }
+    }
+    english(key).unwrap_or(key)
+}
+
+/// Looks up a message and fills its `{}` placeholders in order.
+pub fn tf(key: &'static str, args: &[&dyn Display]) -> String {
+    let mut parts = t(key).split("{}");
+    let mut out = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+fn english(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "report.subject" => "Dockup Backup Report",
+        "verify.subject" => "Dockup Verification Failed",
+        "verify.error" => "Scheduled verification of the backups failed:\n{}",
+        "report.verification" => "🔍 Last verification {}: {} archives verified, {} failed",
+        "report.error" => "Backup encountered an error:\n{}",
+        "report.totals" => "Total Backups: {} - Total Duration: {} seconds - Total Size: {} bytes",
+        "report.project" => "Duration: {} seconds, Size: {} bytes",
+        "report.downtime" => "⏸ Containers were down for {} seconds",
+        "report.skipped" => "⏸ Skipped: {}",
+        "report.aborted" => "⏹ The run was aborted by a signal, projects not finished have no snapshot.",
+        "report.collapsed" => "✅ {} projects backed up successfully: {}",
+        "report.attached" => "The full report is attached.",
+        "report.col.name" => "Name",
+        "report.col.status" => "Status",
+        "report.col.type" => "Type",
+        "report.col.size" => "Size",
+        "report.col.duration" => "Duration",
+        "report.col.ratio" => "Ratio",
+        "report.col.throughput" => "Throughput",
+        "report.warn_size" => "{} is {} GB, above the {} GB limit",
+        "report.warn_inconsistent" => "Potentially inconsistent backup: database {} was archived while running. Configure db_dumps, a dockup.dump.cmd label or quiesce",
+        "watch.subject" => "Dockup: new project detected",
+        "watch.body" => "New project {} at {} was detected. It is included in the next backup run.",
+        "confirm.subject" => "Dockup: confirmation requested",
+        "limit.subject" => "Dockup: safety limit exceeded",
+        "limit.body" => "{} on {} exceeds its safety limit: {}.<br>{}",
+        "limit.forced" => "It runs anyway, --force was given.",
+        "limit.stopped" => "It was stopped unless confirmed on the host.",
+        "confirm.body" => {
+            "Someone wants to run: {} on {}.<br>If that is expected, pass on this token: <b>{}</b><br>It is valid for 10 minutes."
+        }
+        "report.warn_growth" => "{} grew by {}% within {} days",
+        "report.anomaly" => {
+            "{} is only {}, {}% smaller than its average of {} over the last {} snapshots. Check that its volume or bind mount is mounted and not empty"
+        }
+        "report.subject_anomaly" => "Dockup Backup Report: suspicious backup size",
+        "report.unprotected" => "⚠️ Running but not backed up",
+        "report.unprotected_hint" => {
+            "Move these projects into docker_parent or register them with `dockup adopt`."
+        }
+        "test_email.subject" => "Dockup Test Email",
+        "test_email.body" => "If you are reading this, the email configuration is working.",
+        "dry_run.config" => "🚧 Dry run: dockup config",
+        "dry_run.save_config" => "   Would save dockup config to {}",
+        "dry_run.project" => "🚧 Dry run: {}",
+        "dry_run.create_folder" => "   Would create remote folder: {}",
+        "dry_run.archive" => "   Would archive: {}",
+        "dry_run.archive_volume" => "   Would archive volume: {}",
+        "dry_run.skip" => "⏸  Would skip: {} ({})",
+        "prompt.test_config" => "Do you want to test the new configuration? (y/n):",
+        "prompt.add_completion" => {
+            "Do you want to automatically add the setup to your shell config? (y/n):"
+        }
+        "tui.tooltip" => " ({}) quit | ({}) help | ({}) select | ({}) | ({}) | ({}) | ({}) ",
+        "tui.help.switch" => "{} {}: switch column",
+        "tui.help.navigate" => "{} {}: navigate",
+        "tui.help.select" => "{}: select volume",
+        "tui.help.restore" => "{}: restore",
+        "tui.help.select_all" => "{}: select all    {}: deselect all",
+        "tui.help.compare" => "{}: mark date    {}: compare with marked date",
+        "tui.help.recreate" => "{}: stop and restart the project around the restore",
+        "tui.help.refresh" => "{}: refresh the snapshot list from the target",
+        "tui.help.quit" => "{}: quit",
+        "tui.help.toggle" => "{}: toggle help",
+        "tui.confirm" => "Press ENTER to confirm restore",
+        "tui.too_small" => "Terminal too small ({}x{}), please enlarge it. {} quits.",
+        "tui.no_backups" => "No backups found on the backup target. {} quits.",
+        _ => return None,
+    })
+}
+
+fn german(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "report.subject" => "Dockup Backup-Bericht",
+        "verify.subject" => "Dockup Prüfung fehlgeschlagen",
+        "verify.error" => "Die geplante Prüfung der Backups ist fehlgeschlagen:\n{}",
+        "report.verification" => "🔍 Letzte Prüfung {}: {} Archive geprüft, {} fehlerhaft",
+        "report.error" => "Beim Backup ist ein Fehler aufgetreten:\n{}",
+        "report.totals" => {
+            "Backups gesamt: {} - Gesamtdauer: {} Sekunden - Gesamtgröße: {} Bytes"
+        }
+        "report.project" => "Dauer: {} Sekunden, Größe: {} Bytes",
+        "report.downtime" => "⏸ Container waren {} Sekunden angehalten",
+        "report.skipped" => "⏸ Übersprungen: {}",
+        "report.aborted" => "⏹ Der Lauf wurde durch ein Signal abgebrochen, nicht fertige Projekte haben keinen Snapshot.",
+        "report.collapsed" => "✅ {} Projekte erfolgreich gesichert: {}",
+        "report.attached" => "Der vollständige Bericht ist angehängt.",
+        "report.col.name" => "Name",
+        "report.col.status" => "Status",
+        "report.col.type" => "Typ",
+        "report.col.size" => "Größe",
+        "report.col.duration" => "Dauer",
+        "report.col.ratio" => "Verhältnis",
+        "report.col.throughput" => "Durchsatz",
+        "report.warn_size" => "{} ist {} GB groß, über dem Limit von {} GB",
+        "report.warn_inconsistent" => "Möglicherweise inkonsistentes Backup: Datenbank {} wurde im laufenden Betrieb gesichert. Konfigurieren Sie db_dumps, ein dockup.dump.cmd-Label oder quiesce",
+        "watch.subject" => "Dockup: neues Projekt erkannt",
+        "watch.body" => "Neues Projekt {} in {} erkannt. Es wird beim nächsten Backup-Lauf gesichert.",
+        "confirm.subject" => "Dockup: Bestätigung angefordert",
+        "limit.subject" => "Dockup: Sicherheitsgrenze überschritten",
+        "limit.body" => "{} auf {} überschreitet seine Sicherheitsgrenze: {}.<br>{}",
+        "limit.forced" => "Es läuft trotzdem, --force wurde angegeben.",
+        "limit.stopped" => "Es wurde angehalten, sofern es nicht auf dem Host bestätigt wird.",
+        "confirm.body" => {
+            "Jemand möchte ausführen: {} auf {}.<br>Falls das erwartet ist, geben Sie diesen Token weiter: <b>{}</b><br>Er ist 10 Minuten gültig."
+        }
+        "report.warn_growth" => "{} ist um {}% gewachsen (innerhalb von {} Tagen)",
+        "report.anomaly" => {
+            "{} ist nur {} groß, {}% kleiner als der Durchschnitt von {} der letzten {} Snapshots. Prüfen Sie, ob das Volume oder der Bind-Mount eingehängt und nicht leer ist"
+        }
+        "report.subject_anomaly" => "Dockup Backup-Bericht: verdächtige Backup-Größe",
+        "report.unprotected" => "⚠️ Läuft, wird aber nicht gesichert",
+        "report.unprotected_hint" => {
+            "Verschieben Sie diese Projekte nach docker_parent oder registrieren Sie sie mit `dockup adopt`."
+        }
+        "test_email.subject" => "Dockup Test-E-Mail",
+        "test_email.body" => "Wenn Sie diese Nachricht lesen, funktioniert die E-Mail-Konfiguration.",
+        "dry_run.config" => "🚧 Probelauf: dockup Konfiguration",
+        "dry_run.save_config" => "   Würde die dockup Konfiguration nach {} sichern",
+        "dry_run.project" => "🚧 Probelauf: {}",
+        "dry_run.create_folder" => "   Würde Zielordner anlegen: {}",
+        "dry_run.archive" => "   Würde archivieren: {}",
+        "dry_run.archive_volume" => "   Würde Volume archivieren: {}",
+        "dry_run.skip" => "⏸  Würde überspringen: {} ({})",
+        "prompt.test_config" => "Möchten Sie die neue Konfiguration testen? (y/n):",
+        "prompt.add_completion" => {
+            "Soll die Einrichtung automatisch in Ihre Shell-Konfiguration eingetragen werden? (y/n):"
+        }
+        "tui.tooltip" => " ({}) Beenden | ({}) Hilfe | ({}) Auswählen | ({}) | ({}) | ({}) | ({}) ",
+        "tui.help.switch" => "{} {}: Spalte wechseln",
+        "tui.help.navigate" => "{} {}: navigieren",
+        "tui.help.select" => "{}: Volume auswählen",
+        "tui.help.restore" => "{}: wiederherstellen",
+        "tui.help.select_all" => "{}: alle auswählen    {}: keine auswählen",
+        "tui.help.compare" => "{}: Datum markieren    {}: mit markiertem Datum vergleichen",
+        "tui.help.recreate" => "{}: Projekt für die Wiederherstellung stoppen und neu starten",
+        "tui.help.refresh" => "{}: Snapshot-Liste vom Ziel aktualisieren",
+        "tui.help.quit" => "{}: beenden",
+        "tui.help.toggle" => "{}: Hilfe ein/aus",
+        "tui.confirm" => "ENTER drücken, um die Wiederherstellung zu bestätigen",
+        "tui.too_small" => "Terminal zu klein ({}x{}), bitte vergrößern. {} beendet.",
+        "tui.no_backups" => "Keine Backups auf dem Backup-Ziel gefunden. {} beendet.",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_placeholders_in_order() {
+        assert_eq!(
+            tf("report.totals", &[&3, &"12.5", &1024]),
+            "Total Backups: 3 - Total Duration: 12.5 seconds - Total Size: 1024 bytes"
+        );
+    }
+
+    #[test]
+    fn leaves_missing_arguments_empty_and_ignores_extra_ones() {
+        assert_eq!(tf("report.skipped", &[]), "⏸ Skipped: ");
+        assert_eq!(tf("report.skipped", &[&"app", &"other"]), "⏸ Skipped: app");
+    }
+
+    #[test]
+    fn unknown_keys_fall_back_to_the_key() {
+        assert_eq!(t("no.such.key"), "no.such.key");
+        assert_eq!(tf("no.such.key", &[&1]), "no.such.key");
+    }
+
+    #[test]
+    fn german_messages_have_the_same_placeholders() {
+        for key in [
+            "verify.error",
+            "report.verification",
+            "report.totals",
+            "report.project",
+            "limit.body",
+            "confirm.body",
+            "tui.too_small",
+        ] {
+            let english = english(key).unwrap().matches("{}").count();
+            let german = german(key).unwrap().matches("{}").count();
+            assert_eq!(english, german, "{}", key);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Docker images of the compose services (`images`).
+//!
+//! With `images = "digests"` every snapshot records the image of each service with its
+//! local ID and registry digest in `meta.json`, so the exact versions can be pulled again
+//! as `image@sha256:...` instead of whatever the tag points to by then. `images = "save"`
+//! additionally stores every image with `docker save` in `IMAGES/<service>.tar<ext>`, for
+//! images a fresh host can't pull: locally built ones or those whose registry is gone.
+//! `dockup restore --images` loads them back with `docker load`, `--pin-images` writes the
+//! compose override [`PIN_FILE`] running every service on its recorded digest.
+//!
+//! The services and their images come from `docker compose config`, so overrides and
+//! interpolation apply. Services with only a `build:` section use the image name compose
+//! gives them, `<project>-<service>`.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{
+    backup::BackupThingSummary,
+    compression,
+    config::Config,
+    dbdump, progress,
+    scanner::{ArchiveStats, BackupApplication, ImageRecord},
+    transport::StorageBackend,
+};
+
+/// Restore item loading the saved images of a snapshot.
+pub const RESTORE_ITEM: &str = "IMAGES";
+
+/// Override file written into the project by `restore --pin-images`.
+pub const PIN_FILE: &str = "dockup.pinned.yaml";
+
+/// File name of a saved image in `IMAGES/` (before encryption).
+fn archive_name(service: &str, algorithm: &str) -> String {
+    format!("{}{}", service, compression::extension(algorithm))
+}
+
+/// The image of every service of `app`, with ID and digest if it exists locally.
+pub fn resolve(app: &BackupApplication) -> Result<Vec<ImageRecord>> {
+    let output = Command::new("docker")
+        .args(["compose", "config", "--format", "json"])
+        .current_dir(&app.application_path)
+        .stderr(Stdio::null())
+        .output()
+        .context("Failed to run docker compose config")?;
+    if !output.status.success() {
+        anyhow::bail!("docker compose config failed in {:?}", app.application_path);
+    }
+    let config: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .context("Unexpected output from docker compose config")?;
+    let project = config["name"].as_str().unwrap_or(&app.name);
+    let Some(services) = config["services"].as_object() else {
+        return Ok(Vec::new());
+    };
+
+    let mut records = Vec::new();
+    for (service, definition) in services {
+        let image = match definition["image"].as_str() {
+            Some(image) => image.to_string(),
+            None if !definition["build"].is_null() => format!("{}-{}", project, service),
+            None => continue,
+        };
+        let (id, digest) = inspect(&image);
+        if id.is_none() {
+            log::warn!(
+                "⚠️  Image {} of {}/{} is not present on this host",
+                image,
+                app.name,
+                service
+            );
+        }
+        records.push(ImageRecord {
+            service: service.clone(),
+            image,
+            id,
+            digest,
+            archive: None,
+        });
+    }
+    records.sort_by(|a, b| a.service.cmp(&b.service));
+    Ok(records)
+}
+
+/// Local ID and first registry digest of `image`, both `None` if it isn't present.
+fn inspect(image: &str) -> (Option<String>, Option<String>) {
+    let output = Command::new("docker")
+        .args([
+            "image",
+            "inspect",
+            "--format",
+            "{{.Id}} {{join .RepoDigests \" \"}}",
+            image,
+        ])
+        .stderr(Stdio::null())
+        .output();
+    let Ok(output) = output else {
+        return (None, None);
+    };
+    if !output.status.success() {
+        return (None, None);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut fields = stdout.split_whitespace().map(str::to_string);
+    (fields.next(), fields.next())
+}
+
+/// Records the images of `app` in `app.images` and, with `images = "save"`, uploads each
+/// distinct image once to `<remote_base>/IMAGES`.
+pub fn backup_images(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    app: &mut BackupApplication,
+    remote_base: &str,
+) -> Vec<BackupThingSummary> {
+    let mut records = match resolve(app) {
+        Ok(records) => records,
+        Err(e) => {
+            log::warn!("⚠️  Could not look up the images of {}: {e}", app.name);
+            return Vec::new();
+        }
+    };
+    let mut summaries = Vec::new();
+    if config.images == "save" {
+        // Image ID -> archive it was saved to
+        let mut saved: HashMap<String, Option<String>> = HashMap::new();
+        for record in records.iter_mut() {
+            let Some(id) = record.id.clone() else {
+                continue;
+            };
+            if let Some(archive) = saved.get(&id) {
+                record.archive = archive.clone();
+                continue;
+            }
+            let started = Local::now();
+            progress::emit("archive", Some(&app.name), Some(&record.image), None);
+            let name = archive_name(&record.service, &config.compression);
+            let result = save(config, transport, app, record, &name, remote_base);
+            let archive = result.is_ok().then_some(name);
+            record.archive = archive.clone();
+            saved.insert(id, archive);
+            summaries.push(summary(&app.name, &record.image, started, result));
+        }
+    }
+    app.images = records;
+    summaries
+}
+
+fn save(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    app: &BackupApplication,
+    record: &ImageRecord,
+    name: &str,
+    remote_base: &str,
+) -> Result<(String, ArchiveStats)> {
+    let mut producer = Command::new("docker");
+    producer.args(["save", &record.image]);
+    let local = dbdump::staging(app, name)?;
+    let result = compression::write_archive(
+        producer,
+        &config.compression,
+        config.compression_level,
+        &local,
+    )
+    .and_then(|original| {
+        dbdump::seal_and_upload(
+            config,
+            transport,
+            &local,
+            &format!("{}/IMAGES", remote_base),
+            &record.image,
+            original,
+        )
+    });
+    fs::remove_file(&local).ok();
+    result
+}
+
+/// Logs the outcome of saving an image and turns it into a report row.
+fn summary(
+    app: &str,
+    image: &str,
+    started: chrono::DateTime<Local>,
+    result: Result<(String, ArchiveStats)>,
+) -> BackupThingSummary {
+    let seconds = (Local::now().timestamp_millis() - started.timestamp_millis()) as f64 / 1000.0;
+    let (status, size, stats) = match result {
+        Ok((size, stats)) => {
+            log::info!("✅ Saved image `{}`", image);
+            progress::emit("uploaded", Some(app), Some(image), None);
+            (
+                "✅".to_string(),
+                size,
+                Some(ArchiveStats { seconds, ..stats }),
+            )
+        }
+        Err(e) => {
+            log::error!("❌ Failed to save image `{}`: {:#}", image, e);
+            progress::emit("failed", Some(app), Some(image), None);
+            ("❌ Save failed".to_string(), "-".to_string(), None)
+        }
+    };
+    BackupThingSummary {
+        name: image.to_string(),
+        status,
+        size,
+        duration: format!("{:.2} seconds", seconds),
+        volume_type: "Image".to_string(),
+        stats,
+        downtime: None,
+    }
+}
+
+/// Writes [`PIN_FILE`] into the project directory of `backup`, setting every service's
+/// image to its recorded registry digest. Returns the file and the services without a
+/// digest (locally built or missing images), which keep their image.
+pub fn pin(backup: &BackupApplication) -> Result<(PathBuf, Vec<String>)> {
+    if backup.images.is_empty() {
+        anyhow::bail!(
+            "Backup {} of {} has no image digests, enable `images` to record them",
+            backup.snapshot_folder(),
+            backup.name
+        );
+    }
+    let mut content = format!(
+        "# Written by dockup: images of backup {}\nservices:\n",
+        backup.snapshot_folder()
+    );
+    let mut unpinned = Vec::new();
+    for record in &backup.images {
+        let Some(digest) = &record.digest else {
+            unpinned.push(record.service.clone());
+            continue;
+        };
+        // JSON strings are valid YAML scalars
+        content.push_str(&format!(
+            "  {}:\n    image: {}\n",
+            serde_json::to_string(&record.service)?,
+            serde_json::to_string(digest)?
+        ));
+    }
+    if unpinned.len() == backup.images.len() {
+        anyhow::bail!(
+            "None of the images of backup {} has a registry digest",
+            backup.snapshot_folder()
+        );
+    }
+    let path = backup.application_path.join(PIN_FILE);
+    fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok((path, unpinned))
+}
+
+/// Loads the downloaded image archive `file` into the local Docker daemon.
+pub fn load(file: &Path) -> Result<()> {
+    let mut decompress = dbdump::decompress(file)?;
+    let stdout = decompress.stdout.take().expect("stdout is piped");
+    let loaded = Command::new("docker")
+        .arg("load")
+        .stdin(stdout)
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to run docker load")?;
+    let decompressed = decompress.wait()?;
+    if !decompressed.success() || !loaded.success() {
+        anyhow::bail!("Failed to load {:?}", file);
+    }
+    Ok(())
+}
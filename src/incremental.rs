@@ -0,0 +1,203 @@
+//! Incremental backups with `rsync --link-dest`.
+//!
+//! Instead of tarballs, an incremental snapshot mirrors the project directory into `REPO/`
+//! and every volume into `VOLUMES/<name>/`. Files unchanged since the previous incremental
+//! snapshot of the project are hardlinked to it on the target, so every snapshot is
+//! complete on its own (restore and prune treat it like any other) while only changed
+//! files cost space and bandwidth. `meta.json` records the snapshot it was linked against
+//! in `previous`, forming the snapshot chain.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{
+    backup::{self, get_file_size, BackupThingSummary},
+    config::Config,
+    progress,
+    scanner::{BackupApplication, VolumeType},
+    transport::StorageBackend,
+};
+
+/// Mirrors the project and its volumes into the snapshot at `remote_base`.
+pub fn backup(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    app: &mut BackupApplication,
+    remote_base: &str,
+) -> Vec<BackupThingSummary> {
+    app.incremental = true;
+    app.compression = None;
+    app.previous = previous_snapshot(config, transport, app);
+    match &app.previous {
+        Some(previous) => log::info!("🔗 Linking unchanged files to snapshot {}", previous),
+        None => log::info!("🆕 No earlier incremental snapshot, transferring everything"),
+    }
+    let app = &*app;
+    let link_base = app
+        .previous
+        .as_ref()
+        .map(|previous| format!("{}/{}/{}", config.remote_backup_path, app.name, previous));
+
+    let mut summaries = vec![sync(
+        transport,
+        app,
+        "REPO",
+        "Repo",
+        &app.application_path,
+        &format!("{}/REPO", remote_base),
+        link_base.as_ref().map(|base| format!("{}/REPO", base)),
+    )];
+    for vol in &app.volumes {
+        let dir = app.volume_dir(vol);
+        // Volumes of other drivers may not be mounted on this host, copy them out instead
+        let staged = vol.driver.is_some();
+        let (source, volume_type) = match vol.volume_type {
+            VolumeType::Bind => (Ok(vol.snapshot.clone().unwrap_or(vol.path.clone())), "Bind"),
+            VolumeType::Mount if vol.snapshot.is_some() => {
+                (Ok(vol.snapshot.clone().unwrap()), "Docker")
+            }
+            VolumeType::Mount if staged => (
+                stage(&vol.docker_volume(&app.name), &vol.excludes),
+                "Docker",
+            ),
+            VolumeType::Mount => (mountpoint(&vol.docker_volume(&app.name)), "Docker"),
+        };
+        let source = match source {
+            Ok(source) => source,
+            Err(e) => {
+                log::error!("❌ Failed to locate Docker volume `{}`: {}", vol.name, e);
+                progress::emit("failed", Some(&app.name), Some(&vol.name), None);
+                summaries.push(BackupThingSummary {
+                    name: vol.name.clone(),
+                    status: "❌ Volume not found".into(),
+                    size: "-".into(),
+                    duration: "-".into(),
+                    volume_type: volume_type.to_string(),
+                    stats: None,
+                    downtime: None,
+                });
+                continue;
+            }
+        };
+        summaries.push(sync(
+            transport,
+            app,
+            &vol.name,
+            volume_type,
+            &source,
+            &format!("{}/VOLUMES/{}", remote_base, dir),
+            link_base
+                .as_ref()
+                .map(|base| format!("{}/VOLUMES/{}", base, dir)),
+        ));
+        if staged {
+            fs::remove_dir_all(&source).ok();
+        }
+    }
+    summaries
+}
+
+/// Copies the Docker volume `volume` into a local staging directory through the helper
+/// container the archives use, so it can be mirrored like a directory.
+fn stage(volume: &str, excludes: &[String]) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("dockup-stage-{}", volume));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir)?;
+    let mut producer = backup::volume_tar_command(volume, excludes, None)
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run the helper container")?;
+    let stdout = producer.stdout.take().expect("stdout is piped");
+    let extracted = Command::new("tar")
+        .args(["-xf", "-", "-C"])
+        .arg(&dir)
+        .stdin(stdout)
+        .status()
+        .context("Failed to run tar")?;
+    let produced = producer.wait()?;
+    if !produced.success() || !extracted.success() {
+        fs::remove_dir_all(&dir).ok();
+        anyhow::bail!("Failed to copy volume {} out of its container", volume);
+    }
+    Ok(dir)
+}
+
+/// Newest earlier incremental snapshot of the project on the target.
+fn previous_snapshot(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    app: &BackupApplication,
+) -> Option<String> {
+    let project = format!("{}/{}", config.remote_backup_path, app.name);
+    let current = app.snapshot_folder();
+    let mut folders = transport.list(&project).ok()?;
+    folders.sort();
+    folders
+        .into_iter()
+        .rev()
+        .filter(|folder| *folder < current)
+        .find(|folder| {
+            transport
+                .read(&format!("{}/{}/meta.json", project, folder))
+                .ok()
+                .and_then(|json| serde_json::from_str::<BackupApplication>(&json).ok())
+                .is_some_and(|meta| meta.incremental)
+        })
+}
+
+fn sync(
+    transport: &dyn StorageBackend,
+    app: &BackupApplication,
+    name: &str,
+    volume_type: &str,
+    source: &Path,
+    remote: &str,
+    link_dest: Option<String>,
+) -> BackupThingSummary {
+    let started = Local::now();
+    progress::emit("upload", Some(&app.name), Some(name), None);
+    let result = transport.sync_dir(source, remote, link_dest.as_deref());
+    let duration = format!(
+        "{:.2} seconds",
+        (Local::now().timestamp_millis() - started.timestamp_millis()) as f64 / 1000.0
+    );
+    let (status, size) = match result {
+        Ok(()) => {
+            log::info!("✅ `{}` synced", name);
+            progress::emit("uploaded", Some(&app.name), Some(name), None);
+            let size = get_file_size(&source.to_path_buf()).unwrap_or_else(|_| "-".into());
+            ("✅".to_string(), size)
+        }
+        Err(e) => {
+            log::error!("❌ Sync failed for `{}`: {}", name, e);
+            progress::emit("failed", Some(&app.name), Some(name), None);
+            ("❌ Sync failed".to_string(), "-".to_string())
+        }
+    };
+    BackupThingSummary {
+        name: name.to_string(),
+        status,
+        size,
+        duration,
+        volume_type: volume_type.to_string(),
+        stats: None,
+        downtime: None,
+    }
+}
+
+/// Host directory of a Docker volume.
+pub fn mountpoint(volume: &str) -> Result<PathBuf> {
+    let output = Command::new("docker")
+        .args(["volume", "inspect", "--format", "{{ .Mountpoint }}", volume])
+        .output()
+        .context("Failed to run docker volume inspect")?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
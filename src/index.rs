@@ -0,0 +1,113 @@
+//! `index.json` at the root of the backup target: the metadata of every snapshot in one
+//! file.
+//!
+//! Scanning the target reads one `meta.json` per snapshot, which takes minutes over SSH
+//! with hundreds of snapshots. The restore TUI, CLI restores, `verify` and `prune`
+//! read the index instead. Every backup adds its snapshots once their `meta.json` is
+//! uploaded and `prune` rewrites it without the pruned ones. A missing or unreadable index
+//! is rebuilt by scanning the target, `dockup catalog rebuild` rebuilds it as well.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+
+use crate::{config::Config, restore, scanner::BackupApplication, transport, verify};
+
+const INDEX_FILE: &str = "index.json";
+
+/// Version of the index format, an index with another version is rebuilt.
+const INDEX_VERSION: u32 = 1;
+
+/// Concurrent projects of a run add their snapshots one at a time.
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize)]
+struct Index {
+    version: u32,
+    updated: DateTime<Local>,
+    snapshots: Vec<BackupApplication>,
+}
+
+/// Snapshots listed in the index, `None` if there is no usable index.
+pub fn read(config: &Config) -> Option<Vec<BackupApplication>> {
+    let json = transport::open(config).read(&path(config)).ok()?;
+    match serde_json::from_str::<Index>(&json) {
+        Ok(index) if index.version == INDEX_VERSION => Some(index.snapshots),
+        Ok(index) => {
+            log::info!("Index version {} is outdated, rescanning", index.version);
+            None
+        }
+        Err(e) => {
+            log::warn!("⚠️  Invalid {}, rescanning the target: {e}", INDEX_FILE);
+            None
+        }
+    }
+}
+
+/// Replaces the index with `snapshots`.
+pub fn write(config: &Config, snapshots: &[BackupApplication]) -> Result<()> {
+    let mut snapshots = snapshots.to_vec();
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name).then(a.timestamp.cmp(&b.timestamp)));
+    let index = Index {
+        version: INDEX_VERSION,
+        updated: Local::now(),
+        snapshots,
+    };
+    let local = std::env::temp_dir().join(format!("dockup_index_{}.json", std::process::id()));
+    fs::write(&local, serde_json::to_string(&index)?)?;
+    // Uploaded under a temporary name and renamed, readers never see half an index
+    let result = verify::put_verified(&*transport::open(config), &local, &path(config));
+    fs::remove_file(&local).ok();
+    result
+        .map(drop)
+        .with_context(|| format!("Failed to upload {}", INDEX_FILE))
+}
+
+/// Adds `snapshot` to the index, replacing an entry for the same folder. Without an index
+/// the target is scanned, which finds `snapshot` as well. If that fails the index is
+/// deleted, so the next scan rebuilds it instead of missing `snapshot`.
+pub fn add(config: &Config, snapshot: &BackupApplication) -> Result<()> {
+    if config.canary {
+        return Ok(());
+    }
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let result = read(config)
+        .map(Ok)
+        .unwrap_or_else(|| restore::scan_snapshot_folders(config))
+        .and_then(|mut snapshots| {
+            snapshots.retain(|other| {
+                other.name != snapshot.name || other.snapshot_folder() != snapshot.snapshot_folder()
+            });
+            snapshots.push(snapshot.clone());
+            write(config, &snapshots)
+        });
+    if result.is_err() {
+        transport::open(config).delete(&path(config)).ok();
+    }
+    result
+}
+
+/// Removes the pruned `snapshots` from the index. Without an index there is nothing to do,
+/// the next scan doesn't find them anymore.
+pub fn remove(config: &Config, snapshots: &[&BackupApplication]) -> Result<()> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(mut indexed) = read(config) else {
+        return Ok(());
+    };
+    indexed.retain(|indexed| {
+        !snapshots.iter().any(|snapshot| {
+            snapshot.name == indexed.name && snapshot.snapshot_folder() == indexed.snapshot_folder()
+        })
+    });
+    let result = write(config, &indexed);
+    if result.is_err() {
+        transport::open(config).delete(&path(config)).ok();
+    }
+    result
+}
+
+fn path(config: &Config) -> String {
+    format!("{}/{}", config.remote_backup_path, INDEX_FILE)
+}
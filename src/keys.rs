@@ -0,0 +1,196 @@
+//! Key bindings of the restore TUI (`tui.keys`).
+//!
+//! Every action has default keys. `tui.keys.<action>` replaces them with a comma separated
+//! list of keys: a single character or one of `up`, `down`, `left`, `right`, `enter`,
+//! `space`, `tab`, `esc` and `backspace`. A configured key is taken away from the action it
+//! is bound to by default, so e.g. `left = "h"` needs `help` to move elsewhere.
+
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Restore,
+    SelectAll,
+    DeselectAll,
+    Mark,
+    Compare,
+    Recreate,
+    Refresh,
+    Help,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Select,
+        Action::Restore,
+        Action::SelectAll,
+        Action::DeselectAll,
+        Action::Mark,
+        Action::Compare,
+        Action::Recreate,
+        Action::Refresh,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    /// Name in `tui.keys.<action>`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Select => "select",
+            Action::Restore => "restore",
+            Action::SelectAll => "select_all",
+            Action::DeselectAll => "deselect_all",
+            Action::Mark => "mark",
+            Action::Compare => "compare",
+            Action::Recreate => "recreate",
+            Action::Refresh => "refresh",
+            Action::Help => "help",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn default_keys(self) -> &'static [KeyCode] {
+        match self {
+            Action::Up => &[KeyCode::Up],
+            Action::Down => &[KeyCode::Down],
+            Action::Left => &[KeyCode::Left],
+            Action::Right => &[KeyCode::Right],
+            Action::Select => &[KeyCode::Char(' ')],
+            Action::Restore => &[KeyCode::Enter],
+            Action::SelectAll => &[KeyCode::Char('a')],
+            Action::DeselectAll => &[KeyCode::Char('d')],
+            Action::Mark => &[KeyCode::Char('m')],
+            Action::Compare => &[KeyCode::Char('c')],
+            Action::Recreate => &[KeyCode::Char('r')],
+            Action::Refresh => &[KeyCode::Char('u')],
+            Action::Help => &[KeyCode::Char('h')],
+            Action::Quit => &[KeyCode::Char('q'), KeyCode::Esc],
+        }
+    }
+}
+
+/// Parses a comma separated list of keys as used in `tui.keys.<action>`.
+pub fn parse_keys(value: &str) -> Result<Vec<KeyCode>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| {
+            parse_key(key).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown key {:?}: expected a single character or one of up, down, left, right, enter, space, tab, esc, backspace",
+                    key
+                )
+            })
+        })
+        .collect()
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    Some(match name.to_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" => KeyCode::Enter,
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    })
+}
+
+fn label(key: KeyCode) -> String {
+    match key {
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::Enter => "ENTER".to_string(),
+        KeyCode::Char(' ') => "SPACE".to_string(),
+        KeyCode::Tab => "TAB".to_string(),
+        KeyCode::Esc => "ESC".to_string(),
+        KeyCode::Backspace => "BACKSPACE".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Which action a key triggers, from the defaults and `tui.keys`.
+pub struct KeyBindings {
+    actions: HashMap<KeyCode, Action>,
+}
+
+impl KeyBindings {
+    pub fn new(config: &HashMap<String, String>) -> Self {
+        let mut actions = HashMap::new();
+        for action in Action::ALL {
+            if !config.contains_key(action.name()) {
+                for key in action.default_keys() {
+                    actions.insert(*key, action);
+                }
+            }
+        }
+        // Configured keys win over the defaults of other actions
+        for action in Action::ALL {
+            let Some(value) = config.get(action.name()) else {
+                continue;
+            };
+            match parse_keys(value) {
+                Ok(keys) => {
+                    for key in keys {
+                        actions.insert(key, action);
+                    }
+                }
+                Err(e) => log::warn!("⚠️  Ignoring tui.keys.{}: {e}", action.name()),
+            }
+        }
+        Self { actions }
+    }
+
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.actions.get(&key).copied()
+    }
+
+    /// The keys bound to `action` for help texts, e.g. `↑/k`.
+    pub fn describe(&self, action: Action) -> String {
+        let mut keys: Vec<String> = self
+            .actions
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(key, _)| label(*key))
+            .collect();
+        if keys.is_empty() {
+            return "-".to_string();
+        }
+        keys.sort();
+        keys.join("/")
+    }
+}
@@ -0,0 +1,225 @@
+//! Backup target on a locally mounted directory (NAS mount, USB drive), no ssh involved.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::process::Command;
+
+use crate::{
+    config::Config,
+    transport::{run_rsync, StorageBackend, Throttle},
+    verify,
+};
+
+pub struct LocalBackend<'a> {
+    cfg: &'a Config,
+}
+
+impl<'a> LocalBackend<'a> {
+    pub fn new(cfg: &'a Config) -> Self {
+        Self { cfg }
+    }
+}
+
+impl LocalBackend<'_> {
+    /// Directories are only created below an existing backup root. A drive that isn't
+    /// mounted leaves an empty mountpoint, recreating the root there would silently fill
+    /// the root filesystem.
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.check()?;
+        fs::create_dir_all(path).with_context(|| format!("Failed to create {}", path.display()))
+    }
+}
+
+impl StorageBackend for LocalBackend<'_> {
+    fn mkdir(&self, path: &str) -> Result<()> {
+        self.create_dir(Path::new(path))
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(path).with_context(|| format!("Failed to list {}", path))? {
+            names.push(entry?.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn read(&self, path: &str) -> Result<String> {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        if path.trim_end_matches('/') == self.cfg.remote_backup_path.trim_end_matches('/') {
+            anyhow::bail!("Refusing to delete the backup root {}", path);
+        }
+        let target = Path::new(path);
+        if target.is_dir() {
+            fs::remove_dir_all(target)
+        } else {
+            fs::remove_file(target)
+        }
+        .with_context(|| format!("Failed to delete {}", path))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        fs::rename(from, to).with_context(|| format!("Failed to rename {} to {}", from, to))
+    }
+
+    fn put(&self, local: &Path, remote: &str) -> Result<()> {
+        if let Some(parent) = Path::new(remote).parent() {
+            self.create_dir(parent)?;
+        }
+        fs::copy(local, remote)
+            .with_context(|| format!("Failed to copy {:?} to {}", local, remote))?;
+        Ok(())
+    }
+
+    fn get(&self, remote: &str, local: &Path) -> Result<()> {
+        fs::copy(remote, local)
+            .with_context(|| format!("Failed to copy {} to {:?}", remote, local))?;
+        Ok(())
+    }
+
+    fn size(&self, path: &str) -> Result<u64> {
+        Ok(fs::metadata(path)
+            .with_context(|| format!("Failed to stat {}", path))?
+            .len())
+    }
+
+    fn read_range(
+        &self,
+        path: &str,
+        index: u64,
+        chunk_size: u64,
+        throttle: &mut Throttle,
+    ) -> Result<Vec<u8>> {
+        let mut file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
+        file.seek(SeekFrom::Start(index * chunk_size))?;
+        Ok(throttle.read_to_end(&mut file.take(chunk_size))?)
+    }
+
+    fn check(&self) -> Result<()> {
+        let root = Path::new(&self.cfg.remote_backup_path);
+        if !root.is_dir() {
+            anyhow::bail!(
+                "{} is not a directory, is the drive mounted?",
+                self.cfg.remote_backup_path
+            );
+        }
+        Ok(())
+    }
+
+    fn sha256(&self, path: &str) -> Result<Option<String>> {
+        verify::sha256_file(Path::new(path)).map(Some)
+    }
+
+    fn sync_dir(&self, local: &Path, remote: &str, link_dest: Option<&str>) -> Result<()> {
+        self.check()?;
+        let mut cmd = Command::new("rsync");
+        cmd.args(["-a", "--delete"]);
+        if let Some(link_dest) = link_dest {
+            cmd.arg(format!("--link-dest={}", link_dest));
+        }
+        let mut source = local.as_os_str().to_owned();
+        source.push("/");
+        run_rsync(cmd.arg(source).arg(format!("{}/", remote)))
+    }
+
+    fn fetch_dir(&self, remote: &str, local: &Path, keep_newer: bool) -> Result<()> {
+        run_rsync(
+            Command::new("rsync")
+                .arg("-a")
+                .arg(if keep_newer { "--update" } else { "--delete" })
+                .arg(format!("{}/", remote))
+                .arg(local),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::RawConfig, testing::TempDir, transport::Throttle};
+
+    fn config(root: &Path) -> Config {
+        let raw: RawConfig = serde_json::from_value(serde_json::json!({
+            "docker_parent": [],
+            "remote_backup_path": root,
+            "ssh_user": "",
+            "ssh_host": "",
+            "ssh_key": "",
+            "ssh_port": 22,
+            "email_host": "",
+            "email_port": 465,
+            "email_user": "",
+            "email_password": "",
+            "receiver_mail": "",
+            "interval": { "hour": 0, "day": 1, "week": 0, "month": 0, "year": 0 },
+            "storage_backend": "local",
+        }))
+        .unwrap();
+        raw.finalize().unwrap()
+    }
+
+    #[test]
+    fn never_creates_a_missing_backup_root() {
+        let mountpoint = TempDir::new("unmounted");
+        let root = mountpoint.join("dockup");
+        let config = config(&root);
+        let backend = LocalBackend::new(&config);
+        let local = mountpoint.join("archive.tar.gz");
+        fs::write(&local, "data").unwrap();
+
+        assert!(backend
+            .mkdir(&format!("{}/app/snap", root.display()))
+            .is_err());
+        assert!(backend
+            .put(
+                &local,
+                &format!("{}/app/snap/archive.tar.gz", root.display())
+            )
+            .is_err());
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn writes_below_an_existing_root() {
+        let mountpoint = TempDir::new("mounted");
+        let root = mountpoint.join("dockup");
+        fs::create_dir(&root).unwrap();
+        let config = config(&root);
+        let backend = LocalBackend::new(&config);
+        let local = mountpoint.join("archive.tar.gz");
+        fs::write(&local, "0123456789").unwrap();
+
+        let remote = format!("{}/app/snap/VOLUMES/archive.tar.gz", root.display());
+        backend.put(&local, &remote).unwrap();
+        assert_eq!(backend.size(&remote).unwrap(), 10);
+        assert_eq!(
+            backend
+                .read_range(&remote, 1, 4, &mut Throttle::new(None))
+                .unwrap(),
+            b"4567"
+        );
+        backend.mkdir(&format!("{}/other", root.display())).unwrap();
+        assert_eq!(
+            backend.list(&root.to_string_lossy()).unwrap(),
+            ["app", "other"]
+        );
+    }
+
+    #[test]
+    fn refuses_to_delete_the_root() {
+        let mountpoint = TempDir::new("delete");
+        let root = mountpoint.join("dockup");
+        fs::create_dir_all(root.join("app")).unwrap();
+        let config = config(&root);
+        let backend = LocalBackend::new(&config);
+
+        assert!(backend.delete(&format!("{}/", root.display())).is_err());
+        backend.delete(&format!("{}/app", root.display())).unwrap();
+        assert!(root.is_dir() && !root.join("app").exists());
+    }
+}
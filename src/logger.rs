@@ -4,8 +4,27 @@ use log::LevelFilter;
 use std::fs;
 use std::path::PathBuf;
 
+use std::borrow::Cow;
 use std::sync::atomic::{AtomicBool, Ordering};
 static STDOUT_ENABLED: AtomicBool = AtomicBool::new(true);
+static NO_EMOJI: AtomicBool = AtomicBool::new(false);
+static ASCII_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// `println!` that honours `--no-emoji` / `--ascii`.
+#[macro_export]
+macro_rules! plain_println {
+    ($($arg:tt)*) => {
+        println!("{}", $crate::logger::plain(&format!($($arg)*)))
+    };
+}
+
+/// `eprintln!` that honours `--no-emoji` / `--ascii`.
+#[macro_export]
+macro_rules! plain_eprintln {
+    ($($arg:tt)*) => {
+        eprintln!("{}", $crate::logger::plain(&format!($($arg)*)))
+    };
+}
 
 pub fn init() {
     let path: PathBuf = dirs::home_dir().unwrap().join(".dockup").join("logs");
@@ -20,7 +39,7 @@ pub fn init() {
                 "{} [{}] {}",
                 Local::now().format("%Y-%m-%d %H:%M:%S"),
                 record.level(),
-                message
+                plain(&message.to_string())
             ))
         })
         .chain(fern::log_file(log_file_path).unwrap());
@@ -29,7 +48,11 @@ pub fn init() {
     let stdout_config = Dispatch::new()
         .format(|out, message, record| {
             if STDOUT_ENABLED.load(Ordering::Relaxed) {
-                out.finish(format_args!("[{}] {}", record.level(), message))
+                out.finish(format_args!(
+                    "[{}] {}",
+                    record.level(),
+                    plain(&message.to_string())
+                ))
             } else {
                 out.finish(format_args!("")) // or drop silently
             }
@@ -51,3 +74,88 @@ pub fn disable_stdout_logging() {
 pub fn enable_stdout_logging() {
     STDOUT_ENABLED.store(true, Ordering::Relaxed);
 }
+
+/// `--no-emoji` replaces status emoji with text and drops decorative ones, `--ascii`
+/// additionally replaces box-drawing and every other non-ASCII character.
+pub fn set_output_style(no_emoji: bool, ascii: bool) {
+    NO_EMOJI.store(no_emoji || ascii, Ordering::Relaxed);
+    ASCII_ONLY.store(ascii, Ordering::Relaxed);
+}
+
+pub fn ascii_only() -> bool {
+    ASCII_ONLY.load(Ordering::Relaxed)
+}
+
+/// Applies the configured output style to user-facing text (logs, TUI, reports).
+pub fn plain(text: &str) -> Cow<'_, str> {
+    let no_emoji = NO_EMOJI.load(Ordering::Relaxed);
+    let ascii = ASCII_ONLY.load(Ordering::Relaxed);
+    if !no_emoji || text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\u{FE0F}' || c == '\u{200D}' {
+            continue;
+        }
+        if is_emoji(c) {
+            match emoji_label(c) {
+                Some(label) => out.push_str(label),
+                // Drop decorative emoji together with the spacing after them
+                None => {
+                    while chars.peek().is_some_and(|next| *next == ' ') {
+                        chars.next();
+                    }
+                }
+            }
+        } else if ascii && !c.is_ascii() {
+            out.push_str(ascii_replacement(c));
+        } else {
+            out.push(c);
+        }
+    }
+    Cow::Owned(out)
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF // pictographs, emoticons, transport, ...
+        | 0x2300..=0x23FF // misc technical (⏸ ⏬ ...)
+        | 0x2600..=0x27BF // misc symbols and dingbats (✅ ❌ ⚠ ⚙ ...)
+        | 0x2B00..=0x2BFF
+    )
+}
+
+fn emoji_label(c: char) -> Option<&'static str> {
+    match c {
+        '✅' => Some("[OK]"),
+        '❌' => Some("[ERROR]"),
+        '⚠' => Some("[WARN]"),
+        '⏸' => Some("[SKIPPED]"),
+        _ => None,
+    }
+}
+
+fn ascii_replacement(c: char) -> &'static str {
+    match c {
+        '─' | '━' | '═' => "-",
+        '│' | '┃' | '║' => "|",
+        '├' | '┤' | '┬' | '┴' | '┼' | '┌' | '┐' | '└' | '┘' | '╭' | '╮' | '╰' | '╯' => {
+            "+"
+        }
+        '←' => "<-",
+        '→' => "->",
+        '↑' => "^",
+        '↓' => "v",
+        'ä' => "ae",
+        'ö' => "oe",
+        'ü' => "ue",
+        'Ä' => "Ae",
+        'Ö' => "Oe",
+        'Ü' => "Ue",
+        'ß' => "ss",
+        _ => "?",
+    }
+}
@@ -1,16 +1,56 @@
 mod backup;
+mod canary;
+mod catalog;
+mod compression;
 mod config;
+mod confirm;
+mod coverage;
+mod crontab;
+mod crypto;
+mod dbdump;
+mod dedup;
 mod email;
+mod fssnap;
+mod heartbeat;
+mod hooks;
+mod i18n;
+mod images;
+mod incremental;
+mod index;
+mod keys;
+mod local;
 mod logger;
+mod progress;
+mod prune;
+mod quiesce;
+mod report;
 mod restore;
+mod rollback;
+mod s3;
 mod scanner;
+mod schedule;
+mod serve;
+mod shutdown;
+mod state;
+mod stats;
+mod systemd;
+mod telemetry;
+#[cfg(test)]
+mod testing;
+mod theme;
+mod thresholds;
+mod transport;
+mod usage;
 mod utils;
+mod verify;
+mod watch;
 
 use clap::CommandFactory;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use clap_complete::{generate, Shell};
 use std::fs;
 use std::io::Write;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(
@@ -22,6 +62,35 @@ use std::io::Write;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Replace emoji with plain text in logs, TUI and reports"
+    )]
+    no_emoji: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Only print ASCII, no emoji or box-drawing characters (implies --no-emoji)"
+    )]
+    ascii: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "human",
+        help = "Progress output: human logs only, or additional JSON events on stderr"
+    )]
+    progress: Progress,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Progress {
+    Human,
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -32,6 +101,29 @@ enum Commands {
     )]
     Scan,
 
+    #[command(
+        about = "Write the config without the interactive wizard",
+        long_about = "Writes ~/.dockup/config.json from an answers file and/or settings given as flags, without asking anything, e.g. to provision hosts with Ansible.\n\nThe answers file is JSON in the layout of config.json and may contain any subset of it. Every --set key=value (the keys of `dockup config set`) is applied on top. Settings the wizard would ask for get defaults (the default interval, SSH port 22, email port 465), except docker_parent, remote_backup_path and the SSH login, which must be given. An existing config is only replaced with --force."
+    )]
+    Init {
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "JSON answers file in the layout of config.json"
+        )]
+        from: Option<PathBuf>,
+
+        #[arg(
+            long = "set",
+            value_name = "KEY=VALUE",
+            help = "Setting as for `dockup config set` (repeatable)"
+        )]
+        settings: Vec<String>,
+
+        #[arg(long, help = "Replace an existing config")]
+        force: bool,
+    },
+
     #[command(
         about = "Backup all projects",
         long_about = "Performs a backup of all projects.\nUploads the backup to the remote server.\n\nThis command will create a tarball of each project and upload it to the specified remote location."
@@ -39,6 +131,26 @@ enum Commands {
     Backup {
         #[arg(short, help = "Mark as scheduled backup")]
         s: bool,
+
+        #[arg(
+            long = "tag",
+            help = "Tag this backup (e.g. pre-upgrade), see retention_tags for tag-based retention"
+        )]
+        tags: Vec<String>,
+
+        #[arg(
+            long = "project",
+            help = "Only back up this project (repeatable, default: all projects)"
+        )]
+        projects: Vec<String>,
+
+        #[arg(
+            long,
+            value_name = "PROJECT",
+            conflicts_with_all = ["s", "tags", "projects"],
+            help = "Trial run: back up only this project into .canary on the target, outside the history, e.g. after an upgrade or config change"
+        )]
+        canary: Option<String>,
     },
 
     #[command(
@@ -51,21 +163,142 @@ enum Commands {
         about = "Restore a specific project",
         long_about = "Restores a specific project from backup.\n\nChoose a project to restore from the backup.\nYou can select between different backup versions and what parts of the project to restore."
     )]
-    Restore {
-        #[arg(long, help = "The name of the project to restore")]
+    Restore(Box<RestoreArgs>),
+
+    #[command(
+        about = "Watch for new projects",
+        long_about = "Watches docker_parent for newly created compose projects and sends a notification for each, so new deployments don't go unprotected.\n\nRequires inotifywait (inotify-tools). Runs until interrupted."
+    )]
+    Watch {
+        #[arg(long, help = "Start a backup run as soon as a new project appears")]
+        backup: bool,
+    },
+
+    #[command(
+        about = "Serve metrics over HTTP",
+        long_about = "Starts an HTTP server exposing the catalog on /metrics in the Prometheus text format: last successful snapshot, size of the newest snapshot and failed archives per project, plus the last backup run.\n\nRuns until interrupted."
+    )]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:9797", help = "Address to listen on")]
+        listen: String,
+    },
+
+    #[command(
+        about = "Show which projects are backed up",
+        long_about = "Lists the projects dockup backs up with their last successful backup, the skipped ones and every running compose project or container that is not covered by any backup.\n\nExits with a non-zero code if a project has no successful backup within the backup interval. Use --unprotected to only list what is running but not backed up."
+    )]
+    Status {
+        #[arg(
+            long,
+            help = "Only list running projects and containers that are not backed up"
+        )]
+        unprotected: bool,
+    },
+
+    #[command(
+        about = "Install a systemd service and timer for scheduled backups",
+        long_about = "Writes dockup-backup.service and dockup-backup.timer, scheduled from the configured interval (the same schedule `dockup interval view` suggests for crontab).\n\nUse --enable to reload systemd and enable the timer right away."
+    )]
+    SetupSystemd {
+        #[arg(
+            long,
+            default_value = systemd::DEFAULT_DIR,
+            help = "Directory to write the unit files to"
+        )]
+        dir: PathBuf,
+
+        #[arg(long, help = "Run systemctl daemon-reload and enable --now the timer")]
+        enable: bool,
+    },
+
+    #[command(
+        about = "Adopt a project outside the docker parent directory",
+        long_about = "Registers a directory outside docker_parent as a managed project.\n\nThe directory must contain a compose file (compose.yaml, compose.yml, docker-compose.yaml or docker-compose.yml). Adopted projects are stored in the config and backed up like every other project."
+    )]
+    Adopt {
+        #[arg(help = "Path to the project directory")]
+        path: PathBuf,
+
+        #[arg(long, help = "Stop managing a previously adopted project")]
+        remove: bool,
+    },
+
+    #[command(
+        about = "Remove backups outside the retention policy",
+        long_about = "Applies the retention policy (interval values and retention_tags) to the backups on the target and deletes every backup it does not keep.\n\nUse --dry-run to preview which backup folders would be removed. A prune deleting more than prune_max_snapshots snapshots or prune_max_percent of the stored data (default 50%) sends an alert and asks for confirmation; use --force to prune anyway."
+    )]
+    Prune {
+        #[arg(long, help = "Only show which backups would be removed")]
+        dry_run: bool,
+
+        #[arg(long, help = "Only prune backups of this project")]
         project: Option<String>,
 
         #[arg(
             long,
-            help = "The version of the backup to restore (if omitted, latest version will be used)"
+            help = "Prune even if more than prune_max_snapshots or prune_max_percent would be deleted"
         )]
-        version: Option<String>,
+        force: bool,
+    },
+
+    #[command(
+        about = "Verify the checksums of the archives on the target",
+        long_about = "Recomputes the SHA-256 checksum of every archive of the newest snapshot of each project and compares it with the checksum recorded at upload, to detect bit rot or truncated uploads.\n\nSSH and local targets compute the checksums themselves, S3 archives are downloaded. Use --all to verify every snapshot and --download to hash downloaded copies instead of trusting the target's sha256sum. Exits with a non-zero code if an archive is missing or doesn't match.\n\nEvery run is recorded in the catalog. Set verify_schedule to a cron expression to run it on its own schedule via `dockup interval install` or `dockup setup-systemd`; scheduled runs (-s) email the failures."
+    )]
+    Verify {
+        #[arg(short, help = "Mark as scheduled verification, email failures")]
+        s: bool,
+
+        #[arg(long, help = "Only verify snapshots of this project")]
+        project: Option<String>,
 
-        #[arg(long, help = "Restore the repository")]
-        repo: bool,
+        #[arg(long, help = "Verify all snapshots instead of the newest per project")]
+        all: bool,
 
-        #[arg(long, help = "The volumes to restore")]
-        volumes: Vec<String>,
+        #[arg(long, help = "Download the archives and hash them locally")]
+        download: bool,
+    },
+
+    #[command(
+        about = "Show storage and transfer usage",
+        long_about = "Shows the number and uploaded size of the snapshots per project, as recorded in the catalog.\n\nUse --transfer to show the bytes uploaded to and downloaded from each backup target per month instead, e.g. to keep an eye on metered cloud egress."
+    )]
+    Usage {
+        #[arg(long, help = "Show transferred bytes per target and month")]
+        transfer: bool,
+    },
+
+    #[command(
+        about = "Show backup trends per project",
+        long_about = "Shows per project how many snapshots of the last days succeeded, the size of the newest snapshot, its growth per month and the average archive time, as recorded in the catalog.\n\nWith --project every snapshot of that project is listed as well. Use --format csv or --format json to export the history, e.g. for a spreadsheet."
+    )]
+    Stats {
+        #[arg(short, long, help = "Only show this project")]
+        project: Option<String>,
+        #[arg(
+            short,
+            long,
+            default_value_t = 90,
+            help = "Number of days to look back"
+        )]
+        days: u32,
+        #[arg(
+            short,
+            long,
+            value_enum,
+            default_value = "table",
+            help = "Output format"
+        )]
+        format: stats::Format,
+    },
+
+    #[command(
+        about = "Manage the local backup catalog",
+        long_about = "Manage the local backup catalog (~/.dockup/catalog.db).\n\nThe catalog records every snapshot and backup run. Export it as JSON to move it to another machine and import it there."
+    )]
+    Catalog {
+        #[command(subcommand)]
+        action: CatalogAction,
     },
 
     #[command(
@@ -96,6 +329,155 @@ enum Commands {
     },
 }
 
+/// Flags of `dockup restore`, boxed in [`Commands`] to keep the other variants small.
+#[derive(Args)]
+struct RestoreArgs {
+    #[arg(long, help = "The name of the project to restore")]
+    project: Option<String>,
+
+    #[arg(
+        long,
+        help = "The version of the backup to restore, e.g. 2025_01_31_030000 or a prefix like 2025_01_31 (if omitted, latest version will be used)"
+    )]
+    version: Option<String>,
+
+    #[arg(long, help = "Restore the repository")]
+    repo: bool,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "The volumes to restore (comma separated)"
+    )]
+    volumes: Vec<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "The database dumps to load into the running services (comma separated service names)"
+    )]
+    databases: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Load the Docker images saved with the backup (images = \"save\")"
+    )]
+    images: bool,
+
+    #[arg(
+        long,
+        help = "Write dockup.pinned.yaml, a compose override pinning every service to the image digest recorded in the backup"
+    )]
+    pin_images: bool,
+
+    #[arg(
+        long,
+        help = "Run `docker compose down` before restoring and `up -d` afterwards, waiting for the healthchecks"
+    )]
+    recreate: bool,
+
+    #[arg(
+        long,
+        help = "Only extract paths matching this glob, relative to the volume or repo root (repeatable)"
+    )]
+    include: Vec<String>,
+
+    #[arg(long, help = "Leave out paths matching this glob (repeatable)")]
+    exclude: Vec<String>,
+
+    #[arg(
+        long = "file",
+        help = "Only restore this file, relative to the --repo/--volumes item or, without one, to the project directory (repeatable)"
+    )]
+    files: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Limit the download rate in KiB/s (overrides restore_rate_limit)"
+    )]
+    limit_rate: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Wait until this local time (HH:MM) before starting the restore"
+    )]
+    start_at: Option<String>,
+
+    #[arg(
+        long,
+        help = "Revert the latest restore (of --project, if given) from its safety snapshot"
+    )]
+    undo: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "ask",
+        help = "What to do when live files are newer than the snapshot"
+    )]
+    on_conflict: restore::OnConflict,
+
+    #[arg(
+        long,
+        help = "Extract each volume next to it first and only swap it in if this shell command succeeds (sees DOCKUP_RESTORE_PATH, DOCKUP_TARGET_PATH and DOCKUP_VOLUME)"
+    )]
+    validate: Option<String>,
+
+    #[arg(
+        long,
+        help = "Restore into this directory instead of the project's own, e.g. to inspect a backup next to the live project"
+    )]
+    target_dir: Option<PathBuf>,
+
+    #[arg(
+        long = "as",
+        value_name = "NAME",
+        help = "Restore as a project of this name: next to the original directory unless --target-dir is given, Docker volumes as <NAME>_<volume>"
+    )]
+    as_name: Option<String>,
+
+    #[arg(
+        long,
+        help = "The backup target to restore from (`primary` or a name from `targets`). Without it the TUI shows the snapshots of all targets"
+    )]
+    target: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum CatalogAction {
+    #[command(
+        about = "Export the catalog as JSON",
+        long_about = "Writes all snapshots and runs of the local catalog as JSON, to stdout or the given file."
+    )]
+    Export {
+        #[arg(long, help = "Write to this file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        about = "Import a catalog JSON export",
+        long_about = "Merges a JSON export into the local catalog. Existing entries for the same snapshot or run are overwritten."
+    )]
+    Import {
+        #[arg(help = "Path to the JSON export")]
+        file: PathBuf,
+    },
+
+    #[command(
+        about = "Rebuild the catalog from the backup target",
+        long_about = "Reads the metadata of every snapshot on the backup target and reconstructs the local catalog from it.\n\nUse this when the catalog was lost but the backups still exist. Run history recorded locally is kept. The index.json on the target is rewritten from the scan as well."
+    )]
+    Rebuild,
+
+    #[command(
+        about = "Update the cached snapshot list from the backup target",
+        long_about = "Reads the index.json of the backup target and brings the snapshots in the catalog in line with it: snapshots deleted on the target are removed, new ones are added from their metadata.
+
+The restore TUI lists snapshots from the catalog, so it opens instantly and works offline. Run this (or press the refresh key in the TUI) to see snapshots from other hosts."
+    )]
+    Refresh,
+}
+
 #[derive(Subcommand)]
 enum ConfigAction {
     #[command(
@@ -104,6 +486,25 @@ enum ConfigAction {
     )]
     View,
 
+    #[command(
+        about = "Export the configuration as JSON",
+        long_about = "Writes the configuration as JSON, to stdout or the given file.\n\nUse --redact-secrets to mask passwords, access keys, key and identity paths and webhook URLs, e.g. for a bug report or an infra repository. --export-template leaves them out along with host specific settings (adopted projects) and produces an answers file for `dockup init --from`; the secrets it left out are listed so they can be passed with --set."
+    )]
+    Export {
+        #[arg(long, help = "Write to this file instead of stdout")]
+        output: Option<PathBuf>,
+
+        #[arg(long, help = "Mask passwords, keys and key paths")]
+        redact_secrets: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "redact_secrets",
+            help = "Leave out secrets and host specific settings, for `dockup init --from`"
+        )]
+        export_template: bool,
+    },
+
     #[command(
         about = "Set one key-value pair in the configuration",
         long_about = "Change one setting in the configuration.\n\nThis command allows you to set a specific key-value pair in the configuration settings."
@@ -117,9 +518,21 @@ enum ConfigAction {
 
     #[command(
         about = "Test the current configuration",
-        long_about = "Test the current configuration settings.\n\nThis command will test the SSH and email configuration settings to ensure they are valid.\n\nIf you don't receive an email, maybe look into your spam."
+        long_about = "Test the current configuration settings.\n\nThis command will test the backup target (SSH or S3) and email configuration settings to ensure they are valid.\n\nIf you don't receive an email, maybe look into your spam."
     )]
-    Test,
+    Test {
+        #[arg(
+            long,
+            help = "Send a test message through every notification channel (primary and fallback SMTP, webhook) and report each result"
+        )]
+        notify: bool,
+    },
+
+    #[command(
+        about = "Recover config, schedule and state from the backup target",
+        long_about = "Disaster recovery for a rebuilt host: downloads config.json and schedule.json from the backup target of the current configuration, replaces the local config and reinstalls the crontab entry or systemd timer the old host had. The catalog is merged into the local one, and hook scripts and image pins are put back where their directory exists.\n\nOn a fresh host the config prompt asks for the target first, the current config is kept as config.json.before-recover."
+    )]
+    Recover,
 }
 
 #[derive(Subcommand)]
@@ -146,95 +559,288 @@ enum IntervalAction {
         long_about = "Reset the backup interval settings to default values.\n\nThis command will reset the backup interval settings to their default values."
     )]
     Reset,
+
+    #[command(
+        about = "Install the suggested schedule into the crontab",
+        long_about = "Adds the suggested cron schedule (see `dockup interval view`) to the current user's crontab, or updates the existing dockup entry.\n\nThe entry is tagged with a marker comment, other crontab entries are left untouched. Rerun after changing the interval."
+    )]
+    Install,
+
+    #[command(
+        about = "Remove the dockup entry from the crontab",
+        long_about = "Removes the entry added by `dockup interval install` from the current user's crontab."
+    )]
+    Uninstall,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    // ssh re-invokes dockup as its askpass helper when password auth is configured
+    if let Some(password) = utils::askpass_password() {
+        println!("{password}");
+        return Ok(());
+    }
+
     let cli = Cli::parse();
+    logger::set_output_style(cli.no_emoji, cli.ascii);
+    progress::set_json(cli.progress == Progress::Json);
+    // Runs before the config is loaded, which would start the wizard
+    if let Commands::Init {
+        from,
+        settings,
+        force,
+    } = &cli.command
+    {
+        logger::init();
+        config::Config::init(from.as_deref(), settings, *force)?;
+        log::info!(
+            "✅ Config written to {}",
+            config::Config::config_path().display()
+        );
+        return Ok(());
+    }
     let mut cfg = config::Config::load_or_create().await?;
     logger::init();
+    i18n::set_language(&cfg.language);
 
     match cli.command {
         Commands::Scan => {
             scanner::scan_projects(&cfg)?;
         }
-        Commands::Backup { s } => {
-            let result = backup::run_backup(&cfg, s);
+        Commands::Init { .. } => unreachable!("handled before the config is loaded"),
+        Commands::Backup {
+            canary: Some(project),
+            ..
+        } => {
+            shutdown::install();
+            canary::run(&cfg, &project).await?
+        }
+        Commands::Backup {
+            s,
+            tags,
+            projects,
+            canary: None,
+        } => {
+            shutdown::install();
+            let started = chrono::Local::now();
+            let result = backup::run_backup(&cfg, s, &tags, &projects).await;
+            let backup_type = if s {
+                scanner::BackupType::Scheduled
+            } else {
+                scanner::BackupType::Manual
+            };
+            backup::save_last_run(&cfg, backup_type, started, &result);
+            let mut anomalies = Vec::new();
             match &result {
                 Ok(summaries) => {
-                    let mut total_backups = 0;
-                    let mut total_duration = 0.0;
-                    let mut total_size = 0.0;
-                    let mut summary_messages = String::new();
-                    for summary in summaries {
-                        let mut app_duration = 0.0;
-                        let mut app_size = 0.0;
-                        for vol in &summary.volume_statuses {
-                            total_backups += 1;
-                            if let Some(dur_str) = vol.duration.strip_suffix(" seconds") {
-                                if let Ok(dur) = dur_str.parse::<f64>() {
-                                    total_duration += dur;
-                                    app_duration += dur;
-                                }
-                            }
-                            let raw_size = vol.size.trim();
-                            let (value_part, _unit) = raw_size
-                                .chars()
-                                .partition::<String, _>(|c| c.is_ascii_digit() || *c == '.');
-
-                            if let Ok(raw) = value_part.parse::<f64>() {
-                                let multiplier = if raw_size.contains("KB") {
-                                    1_000.0
-                                } else if raw_size.contains("MB") {
-                                    1_000_000.0
-                                } else if raw_size.contains("GB") {
-                                    1_000_000_000.0
-                                } else if raw_size.contains("B") {
-                                    1.0
-                                } else {
-                                    1.0
-                                };
-
-                                let actual_size = raw * multiplier;
-                                total_size += actual_size;
-                                app_size += actual_size;
-                            }
-                        }
-                        summary_messages.push_str(&format!(
-                            "<h2>{}</h2> <p>Duration: {:.2} seconds, Size: {:.2} bytes</p>",
-                            summary.name, app_duration, app_size
-                        ));
-                        summary_messages.push_str("<table border=\"1\" cellpadding=\"8\" cellspacing=\"0\" style=\"border-collapse: collapse; font-family: sans-serif; font-size: 14px;\"><tr style=\"background-color: #f2f2f2;\"><th>Name</th><th>Status</th><th>Type</th><th>Size</th><th>Duration</th></tr>");
-                        for vol in &summary.volume_statuses {
-                            summary_messages.push_str(&format!(
-                                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
-                                vol.name, vol.status, vol.volume_type, vol.size, vol.duration
-                            ));
+                    anomalies = summaries
+                        .iter()
+                        .filter(|summary| !summary.anomalies.is_empty())
+                        .map(|summary| summary.name.clone())
+                        .collect();
+                    let report = report::build_report(&cfg, summaries);
+                    email::send_report_email(
+                        &cfg,
+                        i18n::t(if anomalies.is_empty() {
+                            "report.subject"
+                        } else {
+                            "report.subject_anomaly"
+                        }),
+                        &report.html,
+                        report.attachment.as_deref(),
+                    )
+                    .await?;
+                    match usage::budget_warning(&cfg) {
+                        Ok(Some(warning)) => log::warn!("⚠️  {}", warning),
+                        Ok(None) => {}
+                        Err(e) => log::warn!("⚠️  Could not estimate the backup cost: {e}"),
+                    }
+                    if cfg.auto_prune && cfg.read_only {
+                        log::warn!("⚠️  auto_prune is ignored on a read_only host");
+                    } else if cfg.auto_prune && !anomalies.is_empty() {
+                        // Pruning could drop the last good snapshots in favor of broken ones
+                        log::warn!("⚠️  auto_prune is skipped because of suspicious backup sizes");
+                    } else if cfg.auto_prune {
+                        if let Err(e) = auto_prune(&cfg).await {
+                            log::error!("❌ Pruning failed: {e}");
                         }
-                        summary_messages.push_str("</table>");
                     }
-                    let summary_line = format!(
-                        "<p>Total Backups: {} - Total Duration: {:.2} seconds - Total Size: {:.2} bytes</p>",
-                        total_backups, total_duration, total_size
-                    );
-                    let final_message = format!("{}{}", summary_line, summary_messages);
-                    email::send_summary_email(&cfg, "Dockup Backup Report", &final_message).await?;
                 }
                 Err(e) => {
-                    let msg = format!("Backup encountered an error:\n{e}");
-                    email::send_summary_email(&cfg, "Dockup Backup Report", &msg).await?;
+                    let msg = i18n::tf("report.error", &[&e]);
+                    email::send_summary_email(&cfg, i18n::t("report.subject"), &msg).await?;
                 }
             }
             result?;
+            if !anomalies.is_empty() {
+                anyhow::bail!(
+                    "Suspicious backup sizes in {}, see the report",
+                    anomalies.join(", ")
+                );
+            }
         }
         Commands::DryRun => backup::dry_run(&cfg)?,
-        Commands::Restore {
+        Commands::Catalog { action } => match action {
+            CatalogAction::Export { output } => {
+                let export = catalog::Catalog::open()?.export()?;
+                let json = serde_json::to_string_pretty(&export)?;
+                match output {
+                    Some(path) => {
+                        fs::write(&path, json)?;
+                        log::info!(
+                            "✅ Exported {} snapshots and {} runs to {}",
+                            export.snapshots.len(),
+                            export.runs.len(),
+                            path.display()
+                        );
+                    }
+                    None => println!("{}", json),
+                }
+            }
+            CatalogAction::Import { file } => {
+                let content = fs::read_to_string(&file)?;
+                let export: catalog::CatalogExport = serde_json::from_str(&content)?;
+                catalog::Catalog::open()?.import(&export)?;
+                log::info!(
+                    "✅ Imported {} snapshots and {} runs from {}",
+                    export.snapshots.len(),
+                    export.runs.len(),
+                    file.display()
+                );
+            }
+            CatalogAction::Rebuild => {
+                let count = catalog::Catalog::open()?.rebuild(&cfg)?;
+                log::info!("✅ Rebuilt catalog with {} snapshots", count);
+            }
+            CatalogAction::Refresh => {
+                let (added, removed) = catalog::Catalog::open()?.refresh(&cfg)?;
+                log::info!(
+                    "✅ Catalog refreshed, {} snapshots added, {} removed",
+                    added,
+                    removed
+                );
+            }
+        },
+        Commands::Adopt { path, remove } => {
+            if remove {
+                scanner::release_project(&mut cfg, &path)?;
+            } else {
+                scanner::adopt_project(&mut cfg, &path)?;
+            }
+        }
+        Commands::Watch { backup } => watch::run(&cfg, backup).await?,
+        Commands::Serve { listen } => serve::run(&listen).await?,
+        Commands::Status { unprotected } => coverage::run_status(&cfg, unprotected)?,
+        Commands::Verify {
+            s,
             project,
-            version,
-            repo,
-            volumes,
+            all,
+            download,
         } => {
-            restore::handle_restore_command(&cfg, project, version, repo, volumes);
+            let result = verify::run(&cfg, project.as_deref(), all, download, s);
+            if let (true, Err(e)) = (s, &result) {
+                let msg = i18n::tf("verify.error", &[e]);
+                email::send_summary_email(&cfg, i18n::t("verify.subject"), &msg).await?;
+            }
+            result?;
+        }
+        Commands::Usage { transfer } => usage::run_usage(&cfg, transfer)?,
+        Commands::Stats {
+            project,
+            days,
+            format,
+        } => stats::run_stats(project.as_deref(), days, format)?,
+        Commands::SetupSystemd { dir, enable } => systemd::setup(&cfg, &dir, enable)?,
+        Commands::Prune {
+            dry_run,
+            project,
+            force,
+        } => {
+            let action = match &project {
+                Some(project) => format!("dockup prune --project {}", project),
+                None => "dockup prune".to_string(),
+            };
+            if !dry_run {
+                confirm::forbid_read_only(&cfg, &action)?;
+            }
+            let decisions = prune::plan_target(&cfg, project.as_deref())?;
+            if !dry_run && decisions.iter().any(|decision| !decision.keep) {
+                confirm::require(&cfg, &action).await?;
+            }
+            if let Some(excess) = prune::over_limit(&cfg, &decisions).filter(|_| !dry_run) {
+                confirm::require_over_limit(&cfg, &action, &excess, force, true).await?;
+            }
+            prune::run_prune(&cfg, &decisions, dry_run)?;
+        }
+        Commands::Restore(args) => {
+            let RestoreArgs {
+                project,
+                version,
+                repo,
+                volumes,
+                databases,
+                images,
+                pin_images,
+                recreate,
+                include,
+                exclude,
+                files,
+                limit_rate,
+                start_at,
+                undo,
+                on_conflict,
+                validate,
+                target_dir,
+                as_name,
+                target,
+            } = *args;
+            let action = match &project {
+                Some(project) => format!("dockup restore --project {}", project),
+                None => "dockup restore".to_string(),
+            };
+            if undo {
+                confirm::forbid_read_only(&cfg, &action)?;
+                confirm::require(&cfg, &format!("{} --undo", action)).await?;
+                rollback::undo(project.as_deref())?;
+            } else {
+                if limit_rate.is_some() {
+                    cfg.restore_rate_limit = limit_rate;
+                }
+                if let Some(target) = target {
+                    cfg = cfg.target(&target)?;
+                }
+                // Restores from the TUI are checked there, `--target-dir` and `--as` only
+                // write next to the live project
+                if project.is_some() && target_dir.is_none() && as_name.is_none() {
+                    confirm::forbid_read_only(&cfg, &action)?;
+                    confirm::require(&cfg, &action).await?;
+                }
+                if let Some(start_at) = start_at {
+                    restore::wait_until(&start_at)?;
+                }
+                shutdown::install();
+                restore::handle_restore_command(
+                    &cfg,
+                    project,
+                    restore::CliRestoreOptions {
+                        version,
+                        repo,
+                        volumes,
+                        databases,
+                        images,
+                        options: restore::RestoreOptions {
+                            on_conflict,
+                            filter: restore::ExtractFilter { include, exclude },
+                            validate,
+                            pin_images,
+                            recreate,
+                            target_dir,
+                            as_name,
+                            files,
+                        },
+                    },
+                )?;
+            }
         }
         Commands::SetupCompletion { shell } => {
             let _path = match shell {
@@ -244,12 +850,10 @@ async fn main() -> anyhow::Result<()> {
                     let mut file = fs::File::create(&path)?;
                     generate(shell, &mut Cli::command(), "dockup", &mut file);
                     log::info!("Completion script installed to: {}", path.display());
-                    println!(
+                    plain_println!(
                         "👉 Add this to your ~/.zshrc if not already there:\n\n  fpath+=~/.zfunc\n  autoload -Uz compinit && compinit\n"
                     );
-                    println!(
-                        "Do you want to automatically add the setup to your shell config? (y/n):"
-                    );
+                    println!("{}", i18n::t("prompt.add_completion"));
                     let mut answer = String::new();
                     std::io::stdin().read_line(&mut answer)?;
                     if answer.trim() == "y" {
@@ -273,13 +877,11 @@ async fn main() -> anyhow::Result<()> {
                     let mut file = fs::File::create(&path)?;
                     generate(shell, &mut Cli::command(), "dockup", &mut file);
                     log::info!("✅ Bash completion written to: {}", path.display());
-                    println!(
+                    plain_println!(
                         "👉 Add this to your ~/.bashrc:\n\n  source {}\n",
                         path.display()
                     );
-                    println!(
-                        "Do you want to automatically add the setup to your shell config? (y/n):"
-                    );
+                    println!("{}", i18n::t("prompt.add_completion"));
                     let mut answer = String::new();
                     std::io::stdin().read_line(&mut answer)?;
                     if answer.trim() == "y" {
@@ -303,7 +905,7 @@ async fn main() -> anyhow::Result<()> {
         Commands::Interval { action } => match action {
             IntervalAction::View => {
                 let interval = cfg.cron_human_summary();
-                println!("{}", interval);
+                plain_println!("{}", interval);
             }
             IntervalAction::Set { key, value } => {
                 let mut cfg = cfg;
@@ -314,28 +916,71 @@ async fn main() -> anyhow::Result<()> {
             IntervalAction::Reset => {
                 cfg.reset_interval_to_default()?;
             }
+            IntervalAction::Install => crontab::install(&cfg)?,
+            IntervalAction::Uninstall => crontab::uninstall()?,
         },
         Commands::Config { action } => match action {
             ConfigAction::View => println!("{:#?}", cfg),
+            ConfigAction::Export {
+                output,
+                redact_secrets,
+                export_template,
+            } => {
+                let (json, secrets) = cfg.export(redact_secrets, export_template)?;
+                match output {
+                    Some(path) => {
+                        fs::write(&path, json)?;
+                        log::info!("✅ Exported the config to {}", path.display());
+                    }
+                    None => println!("{}", json),
+                }
+                // stderr, stdout may be the export itself
+                if export_template && !secrets.is_empty() {
+                    plain_eprintln!(
+                        "Secrets left out, pass them to `dockup init` with --set: {}",
+                        secrets.join(", ")
+                    );
+                } else if !redact_secrets && !export_template && !secrets.is_empty() {
+                    plain_eprintln!(
+                        "⚠️  The export contains secrets ({}), use --redact-secrets to share it",
+                        secrets.join(", ")
+                    );
+                }
+            }
             ConfigAction::Set { key, value } => {
                 let mut cfg = cfg;
                 cfg.set_key_value(&key, &value)?;
                 cfg.save()?;
                 log::info!("Updated config key `{key}` to `{value}`");
-                println!("Do you want to test the new configuration? (y/n):");
+                println!("{}", i18n::t("prompt.test_config"));
                 let mut input = String::new();
                 std::io::stdin().read_line(&mut input)?;
                 if input.trim() == "y" {
-                    cfg.test_ssh().await?;
+                    cfg.test_target().await?;
                     cfg.test_email().await?;
                 }
             }
-            ConfigAction::Test => {
-                cfg.test_ssh().await?;
-                cfg.test_email().await?;
+            ConfigAction::Test { notify } => {
+                cfg.test_target().await?;
+                if notify {
+                    email::test_notification_channels(&cfg).await?;
+                } else {
+                    cfg.test_email().await?;
+                }
             }
+            ConfigAction::Recover => schedule::recover(&cfg)?,
         },
     }
 
     Ok(())
 }
+
+/// `auto_prune` after a backup run. Nobody is asked when the safety limit is exceeded, the
+/// prune is skipped and the alert sent instead.
+async fn auto_prune(cfg: &config::Config) -> anyhow::Result<()> {
+    let decisions = prune::plan_target(cfg, None)?;
+    if let Some(excess) = prune::over_limit(cfg, &decisions) {
+        return confirm::require_over_limit(cfg, "auto_prune", &excess, false, false).await;
+    }
+    prune::run_prune(cfg, &decisions, false)
+}
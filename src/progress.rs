@@ -0,0 +1,56 @@
+//! Machine-readable progress events for wrapper scripts (`--progress json`).
+//!
+//! Every event is written as one JSON object per line to stderr, so stdout and the human
+//! log stay untouched:
+//!
+//! ```text
+//! {"phase":"upload","project":"nextcloud","volume":"db","bytes":1048576,"time":"..."}
+//! ```
+//!
+//! Phases of a backup run: `scan`, `project_start`, `archive`, `upload`, `heartbeat`,
+//! `uploaded`, `failed`, `project_done`, `skipped` and `done`.
+
+use chrono::Local;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::telemetry;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+#[derive(Serialize)]
+struct Event<'a> {
+    phase: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volume: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<u64>,
+    time: String,
+}
+
+pub fn set_json(enabled: bool) {
+    JSON.store(enabled, Ordering::Relaxed);
+}
+
+/// Emits a progress event if `--progress json` is active. Events also drive the spans of
+/// [`crate::telemetry`].
+pub fn emit(phase: &str, project: Option<&str>, volume: Option<&str>, bytes: Option<u64>) {
+    telemetry::record(phase, project, volume, bytes);
+    if !JSON.load(Ordering::Relaxed) {
+        return;
+    }
+    let event = Event {
+        phase,
+        project,
+        volume,
+        bytes,
+        time: Local::now().to_rfc3339(),
+    };
+    if let Ok(line) = serde_json::to_string(&event) {
+        let mut stderr = std::io::stderr().lock();
+        let _ = writeln!(stderr, "{}", line);
+    }
+}
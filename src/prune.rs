@@ -0,0 +1,511 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    catalog::Catalog,
+    config::{Config, IntervalConfig},
+    dedup, index, plain_println,
+    restore::{scan_backup_target, scan_snapshot_folders_complete},
+    scanner::BackupApplication,
+    transport,
+    utils::format_bytes,
+};
+
+/// Outcome of the retention policy for a single snapshot.
+#[derive(Debug)]
+pub struct Decision {
+    pub backup: BackupApplication,
+    pub keep: bool,
+    pub reasons: Vec<String>,
+}
+
+/// Evaluates retention for all snapshots, grouped by project.
+///
+/// Snapshots carrying a tag listed in `retention_tags` are kept for that many days (the
+/// longest matching rule wins). Once that has expired, and for everything else, the
+/// `interval` policy decides, with the
+/// project's `retention` overrides applied: the newest snapshot of each of the last N
+/// hours/days/weeks/months/years is kept.
+pub fn plan(config: &Config, backups: &[BackupApplication], now: DateTime<Local>) -> Vec<Decision> {
+    let mut by_project: BTreeMap<&str, Vec<&BackupApplication>> = BTreeMap::new();
+    for backup in backups {
+        by_project
+            .entry(backup.name.as_str())
+            .or_default()
+            .push(backup);
+    }
+
+    let mut decisions = Vec::new();
+    for (project, mut snapshots) in by_project {
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.timestamp));
+
+        let mut policy_pool = Vec::new();
+        for snapshot in snapshots {
+            let age = (now - snapshot.timestamp).num_days();
+            match tag_rule(config, snapshot) {
+                Some((tag, days)) if age <= days as i64 => {
+                    decisions.push(Decision {
+                        backup: snapshot.clone(),
+                        keep: true,
+                        reasons: vec![format!("tag `{tag}` (kept {days} days, age {age} days)")],
+                    });
+                }
+                _ => policy_pool.push(snapshot),
+            }
+        }
+        decisions.extend(apply_interval_policy(
+            &config.retention_for(project),
+            &policy_pool,
+        ));
+    }
+    decisions
+}
+
+/// Retention decisions for the snapshots on the target, optionally limited to one project.
+pub fn plan_target(config: &Config, project: Option<&str>) -> Result<Vec<Decision>> {
+    let mut backups = scan_backup_target(config)?;
+    if let Some(project) = project {
+        backups.retain(|backup| backup.name == project);
+        if backups.is_empty() {
+            anyhow::bail!("No backups found for project {}", project);
+        }
+    }
+    Ok(plan(config, &backups, Local::now()))
+}
+
+/// Describes how `decisions` exceed `prune_max_snapshots` or `prune_max_percent` of the
+/// stored data (the archive sizes recorded in `meta.json`), if they do. A mis-edited
+/// retention policy shouldn't silently delete the history.
+pub fn over_limit(config: &Config, decisions: &[Decision]) -> Option<String> {
+    let size = |decision: &&Decision| -> u64 {
+        decision
+            .backup
+            .stats
+            .iter()
+            .map(|stats| stats.compressed_bytes)
+            .sum()
+    };
+    let deleted: Vec<&Decision> = decisions.iter().filter(|d| !d.keep).collect();
+    let deleted_bytes: u64 = deleted.iter().map(size).sum();
+    let total_bytes: u64 = decisions.iter().map(|d| size(&d)).sum();
+    let mut excess = Vec::new();
+    if config.prune_max_snapshots > 0 && deleted.len() > config.prune_max_snapshots {
+        excess.push(format!(
+            "{} of {} snapshots would be deleted (limit {})",
+            deleted.len(),
+            decisions.len(),
+            config.prune_max_snapshots
+        ));
+    }
+    if config.prune_max_percent > 0 && total_bytes > 0 {
+        let percent = deleted_bytes as f64 * 100.0 / total_bytes as f64;
+        if percent > config.prune_max_percent as f64 {
+            excess.push(format!(
+                "{} of {} stored ({:.0}%) would be deleted (limit {}%)",
+                format_bytes(deleted_bytes),
+                format_bytes(total_bytes),
+                percent,
+                config.prune_max_percent
+            ));
+        }
+    }
+    (!excess.is_empty()).then(|| excess.join(", "))
+}
+
+/// Deletes every snapshot in `decisions` the retention policy does not keep. With
+/// `dry_run` the decisions are only printed.
+pub fn run_prune(config: &Config, decisions: &[Decision], dry_run: bool) -> Result<()> {
+    let transport = transport::open(config);
+
+    let mut pruned = Vec::new();
+    for decision in decisions {
+        let folder = format!(
+            "{}/{}/{}",
+            config.remote_backup_path,
+            decision.backup.name,
+            decision.backup.snapshot_folder()
+        );
+        if dry_run {
+            if decision.keep {
+                plain_println!("✅ Keep   {} ({})", folder, decision.reasons.join(", "));
+            } else {
+                plain_println!("🗑  Delete {}", folder);
+            }
+            continue;
+        }
+        if decision.keep {
+            continue;
+        }
+        match transport.delete(&folder) {
+            Ok(()) => {
+                log::info!("🗑  Pruned {}", folder);
+                pruned.push(&decision.backup);
+            }
+            Err(e) => log::error!("❌ Failed to prune {}: {e}", folder),
+        }
+    }
+    if !pruned.is_empty() {
+        if let Err(e) = index::remove(config, &pruned) {
+            log::error!("❌ Failed to update the index: {e:#}");
+        }
+        if let Err(e) = Catalog::open().and_then(|mut catalog| catalog.remove_snapshots(&pruned)) {
+            log::warn!("⚠️  Failed to remove the pruned snapshots from the catalog: {e}");
+        }
+    }
+    if !dry_run && decisions.iter().any(|d| !d.keep && d.backup.dedup) {
+        // Chunks may be shared with other projects, check against every remaining snapshot.
+        // The index may lag behind and a snapshot missing from the list would lose its chunks,
+        // so every folder is read and any unreadable one skips the cleanup.
+        let snapshots = match scan_snapshot_folders_complete(config) {
+            Ok(snapshots) => snapshots,
+            Err(e) => {
+                log::error!("❌ Skipped cleaning up the chunk store: {e:#}");
+                return Ok(());
+            }
+        };
+        let manifests: Vec<String> = snapshots
+            .iter()
+            .filter(|backup| backup.dedup)
+            .flat_map(|backup| {
+                backup.volumes.iter().map(move |vol| {
+                    format!(
+                        "{}/{}/{}/VOLUMES/{}{}",
+                        config.remote_backup_path,
+                        backup.name,
+                        backup.snapshot_folder(),
+                        backup.volume_dir(vol),
+                        dedup::MANIFEST_SUFFIX
+                    )
+                })
+            })
+            .collect();
+        match dedup::collect_garbage(config, &*transport, &manifests) {
+            Ok(removed) => log::info!("🧹 Removed {} unreferenced chunks", removed),
+            Err(e) => log::error!("❌ Failed to clean up the chunk store: {e}"),
+        }
+    }
+    if dry_run {
+        let removed = decisions.iter().filter(|d| !d.keep).count();
+        println!(
+            "\n{} of {} backups would be removed (dry run, nothing deleted)",
+            removed,
+            decisions.len()
+        );
+        if let Some(excess) = over_limit(config, decisions) {
+            println!(
+                "⚠️  Above the safety limit, a real run needs --force: {}",
+                excess
+            );
+        }
+    }
+    Ok(())
+}
+
+fn tag_rule(config: &Config, backup: &BackupApplication) -> Option<(String, u32)> {
+    backup
+        .tags
+        .iter()
+        .filter_map(|tag| {
+            config
+                .retention_tags
+                .get(tag)
+                .map(|days| (tag.clone(), *days))
+        })
+        .max_by_key(|(_, days)| *days)
+}
+
+fn apply_interval_policy(
+    interval: &IntervalConfig,
+    snapshots: &[&BackupApplication],
+) -> Vec<Decision> {
+    let tiers: [(&str, u32, &str); 5] = [
+        ("hourly", interval.hour, "%Y-%m-%d %H"),
+        ("daily", interval.day, "%Y-%m-%d"),
+        ("weekly", interval.week, "%G-W%V"),
+        ("monthly", interval.month, "%Y-%m"),
+        ("yearly", interval.year, "%Y"),
+    ];
+    let policy_configured = tiers.iter().any(|(_, count, _)| *count > 0);
+
+    let mut reasons: Vec<Vec<String>> = vec![Vec::new(); snapshots.len()];
+    if let Some(first) = reasons.first_mut() {
+        first.push("latest".to_string());
+    }
+    for (label, count, period_format) in tiers {
+        let mut periods = HashSet::new();
+        for (i, snapshot) in snapshots.iter().enumerate() {
+            if periods.len() >= count as usize {
+                break;
+            }
+            // Snapshots are sorted newest first, so the first one per period is its newest
+            if periods.insert(snapshot.timestamp.format(period_format).to_string()) {
+                reasons[i].push(label.to_string());
+            }
+        }
+    }
+
+    snapshots
+        .iter()
+        .zip(reasons)
+        .map(|(snapshot, reasons)| {
+            if !policy_configured {
+                return Decision {
+                    backup: (*snapshot).clone(),
+                    keep: true,
+                    reasons: vec!["no retention policy configured".to_string()],
+                };
+            }
+            Decision {
+                backup: (*snapshot).clone(),
+                keep: !reasons.is_empty(),
+                reasons,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RawConfig;
+    use chrono::TimeZone;
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap()
+    }
+
+    fn snapshot(name: &str, timestamp: DateTime<Local>, tags: &[&str]) -> BackupApplication {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "timestamp": timestamp,
+            "tags": tags,
+            "application_path": format!("/srv/{}", name),
+            "volumes": [],
+        }))
+        .unwrap()
+    }
+
+    fn config(interval: [u32; 5]) -> Config {
+        let [hour, day, week, month, year] = interval;
+        let raw: RawConfig = serde_json::from_value(serde_json::json!({
+            "docker_parent": [],
+            "remote_backup_path": "/backups",
+            "ssh_user": "",
+            "ssh_host": "",
+            "ssh_key": "",
+            "ssh_port": 22,
+            "email_host": "",
+            "email_port": 465,
+            "email_user": "",
+            "email_password": "",
+            "receiver_mail": "",
+            "interval": { "hour": hour, "day": day, "week": week, "month": month, "year": year },
+        }))
+        .unwrap();
+        raw.finalize().unwrap()
+    }
+
+    /// (timestamp, keep) per snapshot, newest first.
+    fn kept(decisions: &[Decision]) -> Vec<(DateTime<Local>, bool)> {
+        let mut kept: Vec<_> = decisions
+            .iter()
+            .map(|d| (d.backup.timestamp, d.keep))
+            .collect();
+        kept.sort_by_key(|&(timestamp, _)| std::cmp::Reverse(timestamp));
+        kept
+    }
+
+    #[test]
+    fn keeps_the_newest_snapshot_per_day() {
+        let config = config([0, 2, 0, 0, 0]);
+        let backups = [
+            snapshot("app", at(2024, 3, 3, 18), &[]),
+            snapshot("app", at(2024, 3, 3, 6), &[]),
+            snapshot("app", at(2024, 3, 2, 18), &[]),
+            snapshot("app", at(2024, 3, 2, 6), &[]),
+            snapshot("app", at(2024, 3, 1, 18), &[]),
+        ];
+        let decisions = plan(&config, &backups, at(2024, 3, 3, 20));
+        assert_eq!(
+            kept(&decisions),
+            vec![
+                (at(2024, 3, 3, 18), true),
+                (at(2024, 3, 3, 6), false),
+                (at(2024, 3, 2, 18), true),
+                (at(2024, 3, 2, 6), false),
+                (at(2024, 3, 1, 18), false),
+            ]
+        );
+        assert_eq!(decisions[0].reasons, vec!["latest", "daily"]);
+    }
+
+    #[test]
+    fn tiers_add_up() {
+        // 2024-03-04 is a Monday, so the 3rd is in the previous ISO week
+        let config = config([0, 1, 2, 3, 0]);
+        let backups = [
+            snapshot("app", at(2024, 3, 5, 12), &[]),
+            snapshot("app", at(2024, 3, 4, 12), &[]),
+            snapshot("app", at(2024, 3, 3, 12), &[]),
+            snapshot("app", at(2024, 3, 1, 12), &[]),
+            snapshot("app", at(2024, 2, 20, 12), &[]),
+            snapshot("app", at(2024, 1, 20, 12), &[]),
+            snapshot("app", at(2023, 12, 20, 12), &[]),
+        ];
+        let decisions = plan(&config, &backups, at(2024, 3, 5, 13));
+        assert_eq!(
+            kept(&decisions),
+            vec![
+                (at(2024, 3, 5, 12), true),    // latest, daily, weekly, monthly
+                (at(2024, 3, 4, 12), false),   // same week and month
+                (at(2024, 3, 3, 12), true),    // weekly
+                (at(2024, 3, 1, 12), false),   // same week as the 3rd
+                (at(2024, 2, 20, 12), true),   // monthly
+                (at(2024, 1, 20, 12), true),   // monthly
+                (at(2023, 12, 20, 12), false), // beyond three months
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_everything_without_a_policy() {
+        let config = config([0, 0, 0, 0, 0]);
+        let backups = [
+            snapshot("app", at(2024, 3, 3, 12), &[]),
+            snapshot("app", at(2020, 1, 1, 12), &[]),
+        ];
+        let decisions = plan(&config, &backups, at(2024, 3, 3, 13));
+        assert!(decisions.iter().all(|d| d.keep));
+    }
+
+    #[test]
+    fn tagged_snapshots_expire_after_their_days() {
+        let mut config = config([0, 1, 0, 0, 0]);
+        config.retention_tags.insert("release".to_string(), 30);
+        config.retention_tags.insert("nightly".to_string(), 5);
+        let now = at(2024, 3, 31, 12);
+        let backups = [
+            snapshot("app", at(2024, 3, 31, 6), &[]),
+            snapshot("app", at(2024, 3, 21, 12), &["release"]),
+            snapshot("app", at(2024, 2, 1, 12), &["release"]),
+            snapshot("app", at(2024, 3, 21, 6), &["nightly"]),
+            // The longest matching rule wins
+            snapshot("app", at(2024, 3, 20, 12), &["nightly", "release"]),
+        ];
+        let decisions = plan(&config, &backups, now);
+        assert_eq!(
+            kept(&decisions),
+            vec![
+                (at(2024, 3, 31, 6), true),
+                (at(2024, 3, 21, 12), true),
+                (at(2024, 3, 21, 6), false),
+                (at(2024, 3, 20, 12), true),
+                (at(2024, 2, 1, 12), false),
+            ]
+        );
+        let kept_by_tag = decisions
+            .iter()
+            .find(|d| d.backup.timestamp == at(2024, 3, 21, 12))
+            .unwrap();
+        assert_eq!(
+            kept_by_tag.reasons,
+            vec!["tag `release` (kept 30 days, age 10 days)"]
+        );
+        // Expired ones are left to the policy, which has a newer snapshot for its only day
+        let expired = decisions
+            .iter()
+            .find(|d| d.backup.timestamp == at(2024, 2, 1, 12))
+            .unwrap();
+        assert!(expired.reasons.is_empty());
+    }
+
+    #[test]
+    fn expired_tags_fall_back_to_the_policy() {
+        let mut config = config([0, 1, 0, 3, 0]);
+        config.retention_tags.insert("release".to_string(), 30);
+        let backups = [
+            snapshot("app", at(2024, 3, 31, 6), &[]),
+            snapshot("app", at(2024, 2, 1, 12), &["release"]),
+            snapshot("app", at(2024, 1, 20, 12), &["release"]),
+            snapshot("app", at(2024, 1, 10, 12), &[]),
+        ];
+        let decisions = plan(&config, &backups, at(2024, 3, 31, 12));
+        assert_eq!(
+            kept(&decisions),
+            vec![
+                (at(2024, 3, 31, 6), true),
+                (at(2024, 2, 1, 12), true), // monthly, although the tag expired
+                (at(2024, 1, 20, 12), true), // monthly
+                (at(2024, 1, 10, 12), false), // same month as the 20th
+            ]
+        );
+        let expired = decisions
+            .iter()
+            .find(|d| d.backup.timestamp == at(2024, 2, 1, 12))
+            .unwrap();
+        assert_eq!(expired.reasons, vec!["monthly"]);
+    }
+
+    #[test]
+    fn tagged_snapshots_dont_count_for_the_policy() {
+        let mut config = config([0, 1, 0, 0, 0]);
+        config.retention_tags.insert("release".to_string(), 30);
+        let backups = [
+            snapshot("app", at(2024, 3, 3, 18), &["release"]),
+            snapshot("app", at(2024, 3, 3, 6), &[]),
+        ];
+        let decisions = plan(&config, &backups, at(2024, 3, 3, 20));
+        // The untagged one is the newest of the policy's snapshots
+        assert!(decisions.iter().all(|d| d.keep));
+    }
+
+    #[test]
+    fn project_retention_overrides_the_interval() {
+        let mut config = config([0, 1, 0, 0, 0]);
+        config
+            .retention
+            .insert("db".to_string(), [("day".to_string(), 3)].into());
+        let backups: Vec<_> = ["app", "db"]
+            .iter()
+            .flat_map(|name| (1..=3).map(move |day| snapshot(name, at(2024, 3, day, 12), &[])))
+            .collect();
+        let decisions = plan(&config, &backups, at(2024, 3, 3, 13));
+        let kept = |name: &str| {
+            decisions
+                .iter()
+                .filter(|d| d.backup.name == name && d.keep)
+                .count()
+        };
+        assert_eq!(kept("app"), 1);
+        assert_eq!(kept("db"), 3);
+    }
+
+    #[test]
+    fn project_retention_keeps_the_other_tiers() {
+        // `website` turns daily snapshots off and keeps two weeks, monthly comes from the interval
+        let mut config = config([0, 7, 0, 2, 0]);
+        config.retention.insert(
+            "website".to_string(),
+            [("day".to_string(), 0), ("week".to_string(), 2)].into(),
+        );
+        let backups = [
+            snapshot("website", at(2024, 3, 13, 12), &[]),
+            snapshot("website", at(2024, 3, 12, 12), &[]),
+            snapshot("website", at(2024, 3, 6, 12), &[]),
+            snapshot("website", at(2024, 2, 28, 12), &[]),
+            snapshot("website", at(2024, 1, 31, 12), &[]),
+        ];
+        let decisions = plan(&config, &backups, at(2024, 3, 13, 13));
+        assert_eq!(
+            kept(&decisions),
+            vec![
+                (at(2024, 3, 13, 12), true),  // latest, weekly, monthly
+                (at(2024, 3, 12, 12), false), // same week
+                (at(2024, 3, 6, 12), true),   // weekly
+                (at(2024, 2, 28, 12), true),  // monthly
+                (at(2024, 1, 31, 12), false), // beyond two months
+            ]
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Stops or pauses a project's containers while it is archived (`quiesce.<project>`).
+//!
+//! Archiving volumes of running databases can capture half-written files. `stop` runs
+//! `docker compose stop` and `start` afterwards, `pause` freezes the processes with
+//! `docker compose pause`/`unpause`, which is quicker but leaves unflushed data in memory.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::Config;
+
+#[derive(Clone, Copy)]
+pub enum Quiesce {
+    Stop,
+    Pause,
+}
+
+impl Quiesce {
+    /// The configured mode for `project`, if any.
+    pub fn for_project(config: &Config, project: &str) -> Option<Self> {
+        match config.quiesce.get(project).map(String::as_str) {
+            Some("stop") => Some(Quiesce::Stop),
+            Some("pause") => Some(Quiesce::Pause),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Quiesce::Stop => "stop",
+            Quiesce::Pause => "pause",
+        }
+    }
+
+    /// Stops or pauses the containers of the compose project in `dir`.
+    pub fn halt(self, dir: &Path) -> Result<()> {
+        compose(dir, self.name())
+    }
+
+    /// Starts or unpauses them again.
+    pub fn resume(self, dir: &Path) -> Result<()> {
+        match self {
+            Quiesce::Stop => compose(dir, "start"),
+            Quiesce::Pause => compose(dir, "unpause"),
+        }
+    }
+}
+
+fn compose(dir: &Path, action: &str) -> Result<()> {
+    let output = Command::new("docker")
+        .args(["compose", action])
+        .current_dir(dir)
+        .output()
+        .with_context(|| format!("Failed to run docker compose {}", action))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "docker compose {} failed: {}",
+            action,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
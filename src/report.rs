@@ -0,0 +1,237 @@
+use crate::{
+    backup::AppSummary,
+    catalog::{Catalog, VerificationRecord},
+    compression,
+    config::Config,
+    coverage::{self, Unprotected},
+    i18n::{t, tf},
+    logger::plain,
+    scanner::ArchiveStats,
+    shutdown,
+};
+
+/// Projects above which `report_mode = "auto"` collapses the report.
+const AUTO_SUMMARY_PROJECTS: usize = 10;
+
+pub struct Report {
+    pub html: String,
+    /// Full report attached as file when the email body is summarized.
+    pub attachment: Option<String>,
+}
+
+/// Builds the HTML backup report. Depending on `report_mode` successful projects are
+/// collapsed into a single line and the full report is attached instead.
+pub fn build_report(cfg: &Config, summaries: &[AppSummary]) -> Report {
+    let unprotected = coverage::for_report(cfg);
+    let dashboard = cfg.dashboard_url.as_deref();
+    let verification = Catalog::open()
+        .and_then(|catalog| catalog.last_verification())
+        .ok()
+        .flatten();
+    let verification = verification.as_ref();
+    let full = render(summaries, &unprotected, verification, dashboard, false);
+    let summarize = match cfg.report_mode.as_str() {
+        "summary" => true,
+        "auto" => summaries.len() > AUTO_SUMMARY_PROJECTS,
+        _ => false,
+    };
+    if summarize {
+        Report {
+            html: render(summaries, &unprotected, verification, dashboard, true),
+            attachment: Some(full),
+        }
+    } else {
+        Report {
+            html: full,
+            attachment: None,
+        }
+    }
+}
+
+fn render(
+    summaries: &[AppSummary],
+    unprotected: &[Unprotected],
+    verification: Option<&VerificationRecord>,
+    dashboard: Option<&str>,
+    collapse_successful: bool,
+) -> String {
+    // Project headings link to the project's page of `dockup serve`
+    let heading = |name: &str| match dashboard {
+        Some(url) => format!(
+            "<h2><a href=\"{}/projects/{}\">{}</a></h2>",
+            url, name, name
+        ),
+        None => format!("<h2>{}</h2>", name),
+    };
+    let mut total_backups = 0;
+    let mut total_duration = 0.0;
+    let mut total_size = 0.0;
+    let mut summary_messages = String::new();
+    let mut collapsed = Vec::new();
+    for summary in summaries {
+        if let Some(reason) = &summary.skipped {
+            summary_messages.push_str(&format!(
+                "{} <p>{}</p>",
+                heading(&summary.name),
+                tf("report.skipped", &[reason])
+            ));
+            continue;
+        }
+        let mut app_duration = 0.0;
+        let mut app_size = 0.0;
+        for vol in &summary.volume_statuses {
+            total_backups += 1;
+            if let Some(dur_str) = vol.duration.strip_suffix(" seconds") {
+                if let Ok(dur) = dur_str.parse::<f64>() {
+                    total_duration += dur;
+                    app_duration += dur;
+                }
+            }
+            if let Some(size) = parse_size(&vol.size) {
+                total_size += size;
+                app_size += size;
+            }
+        }
+        if collapse_successful
+            && summary.failed_count() == 0
+            && summary.warnings.is_empty()
+            && summary.anomalies.is_empty()
+        {
+            collapsed.push(summary.name.as_str());
+            continue;
+        }
+        summary_messages.push_str(&format!(
+            "{} <p>{}</p>",
+            heading(&summary.name),
+            tf(
+                "report.project",
+                &[&format!("{:.2}", app_duration), &format!("{:.2}", app_size)]
+            )
+        ));
+        let downtime = summary
+            .volume_statuses
+            .iter()
+            .filter_map(|vol| vol.downtime)
+            .reduce(f64::max);
+        if let Some(downtime) = downtime {
+            summary_messages.push_str(&format!(
+                "<p>{}</p>",
+                tf("report.downtime", &[&format!("{:.1}", downtime)])
+            ));
+        }
+        for anomaly in &summary.anomalies {
+            summary_messages.push_str(&format!(
+                "<p style=\"color: #c00000;\"><b>🚨 {}</b></p>",
+                anomaly
+            ));
+        }
+        for warning in &summary.warnings {
+            summary_messages.push_str(&format!("<p>⚠️ {}</p>", warning));
+        }
+        summary_messages.push_str(&format!("<table border=\"1\" cellpadding=\"8\" cellspacing=\"0\" style=\"border-collapse: collapse; font-family: sans-serif; font-size: 14px;\"><tr style=\"background-color: #f2f2f2;\"><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th></tr>",
+            t("report.col.name"),
+            t("report.col.status"),
+            t("report.col.type"),
+            t("report.col.size"),
+            t("report.col.duration"),
+            t("report.col.ratio"),
+            t("report.col.throughput")
+        ));
+        for vol in &summary.volume_statuses {
+            let (ratio, throughput) = match &vol.stats {
+                Some(stats) => (format_ratio(stats), format_throughput(stats)),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            summary_messages.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                vol.name, vol.status, vol.volume_type, vol.size, vol.duration, ratio, throughput
+            ));
+        }
+        summary_messages.push_str("</table>");
+    }
+    if !collapsed.is_empty() {
+        summary_messages.push_str(&format!(
+            "<p>{}</p><p>{}</p>",
+            tf(
+                "report.collapsed",
+                &[&collapsed.len(), &collapsed.join(", ")]
+            ),
+            t("report.attached")
+        ));
+    }
+    if !unprotected.is_empty() {
+        summary_messages.push_str(&format!("<h2>{}</h2><ul>", t("report.unprotected")));
+        for item in unprotected {
+            summary_messages.push_str(&format!("<li>{}</li>", coverage::describe(item)));
+        }
+        summary_messages.push_str(&format!("</ul><p>{}</p>", t("report.unprotected_hint")));
+    }
+    if let Some(verification) = verification {
+        summary_messages.push_str(&format!(
+            "<p>{}</p>",
+            tf(
+                "report.verification",
+                &[
+                    &verification.finished.format("%Y-%m-%d %H:%M"),
+                    &verification.verified,
+                    &verification.failed
+                ]
+            )
+        ));
+        if let Some(error) = &verification.error {
+            summary_messages.push_str(&format!("<pre>{}</pre>", error));
+        }
+    }
+    let summary_line = format!(
+        "<p>{}</p>",
+        tf(
+            "report.totals",
+            &[
+                &total_backups,
+                &format!("{:.2}", total_duration),
+                &format!("{:.2}", total_size)
+            ]
+        )
+    );
+    let aborted = if shutdown::requested() {
+        format!("<p>{}</p>", t("report.aborted"))
+    } else {
+        String::new()
+    };
+    plain(&format!("{}{}{}", aborted, summary_line, summary_messages)).into_owned()
+}
+
+/// Compressed size in percent of the original. Archives that barely shrink are flagged,
+/// compressing them (e.g. media or already compressed dumps) only costs CPU time.
+fn format_ratio(stats: &ArchiveStats) -> String {
+    match stats.ratio() {
+        Some(ratio) if ratio >= compression::INCOMPRESSIBLE_RATIO => {
+            format!("⚠️ {:.0}%", ratio * 100.0)
+        }
+        Some(ratio) => format!("{:.0}%", ratio * 100.0),
+        None => "-".to_string(),
+    }
+}
+
+fn format_throughput(stats: &ArchiveStats) -> String {
+    match stats.throughput() {
+        Some(bytes) => format!("{:.1} MB/s", bytes / 1_000_000.0),
+        None => "-".to_string(),
+    }
+}
+
+/// Parses sizes as printed by `du -sh` (`512`, `4.0K`, `12M`, `1.5G`, also `12MB`) to bytes.
+pub fn parse_size(raw: &str) -> Option<f64> {
+    let raw = raw.trim();
+    let (value_part, unit): (String, String) =
+        raw.chars().partition(|c| c.is_ascii_digit() || *c == '.');
+    let value = value_part.parse::<f64>().ok()?;
+    let multiplier = match unit.trim().trim_end_matches('B') {
+        "K" => 1_000.0,
+        "M" => 1_000_000.0,
+        "G" => 1_000_000_000.0,
+        "T" => 1_000_000_000_000.0,
+        _ => 1.0,
+    };
+    Some(value * multiplier)
+}
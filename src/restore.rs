@@ -1,63 +1,196 @@
-use std::collections::HashSet;
-use std::io;
+use anyhow::Context;
+use chrono::{Local, NaiveTime};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::fs::DirBuilderExt;
 use std::process::Stdio;
+use std::time::SystemTime;
 
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::event::{self, Event, KeyEvent, KeyEventKind};
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Style, Stylize},
+    style::{Style, Styled, Stylize},
     symbols::border,
     text::{Line, Text},
     widgets::{Block, Paragraph, Widget},
     DefaultTerminal, Frame,
 };
 
+use crate::i18n::{t, tf};
+use crate::keys::{Action, KeyBindings};
 use crate::logger::disable_stdout_logging;
 use crate::logger::enable_stdout_logging;
-use crate::{config::Config, scanner::BackupApplication, utils::run_remote_cmd_with_output};
+use crate::logger::{ascii_only, plain};
+use crate::theme::Theme;
+use crate::{
+    catalog::Catalog,
+    compression,
+    config::{Config, PRIMARY_TARGET},
+    confirm, crypto, dbdump, dedup,
+    hooks::{self, Hook, HookEnv},
+    images, index, plain_eprintln, plain_println,
+    rollback::SafetySnapshot,
+    scanner::{self, BackupApplication, VolumeDriver, VolumeType},
+    shutdown,
+    transport::{self, StorageBackend},
+    utils::format_bytes,
+};
+
+/// How a restore treats the live data it restores over.
+#[derive(Default, Clone)]
+pub struct RestoreOptions {
+    pub on_conflict: OnConflict,
+    pub filter: ExtractFilter,
+    /// Shell command that has to succeed on a restored volume before it replaces the live
+    /// one (`--validate`).
+    pub validate: Option<String>,
+    /// Write an override file pinning the recorded image digests (`--pin-images`).
+    pub pin_images: bool,
+    /// Take the project down before restoring and start it again afterwards, waiting for
+    /// its healthchecks (`--recreate`).
+    pub recreate: bool,
+    /// Restore into this directory instead of the project's (`--target-dir`).
+    pub target_dir: Option<PathBuf>,
+    /// Restore as a project of this name (`--as`).
+    pub as_name: Option<String>,
+    /// Single files to restore (`--file`), relative to the selected item or, without one,
+    /// to the project directory.
+    pub files: Vec<String>,
+}
+
+/// What `dockup restore --project` restores, from its command line flags.
+pub struct CliRestoreOptions {
+    /// Snapshot folder name or a prefix of it, latest if omitted.
+    pub version: Option<String>,
+    pub repo: bool,
+    pub volumes: Vec<String>,
+    pub databases: Vec<String>,
+    pub images: bool,
+    pub options: RestoreOptions,
+}
+
+/// What to do with an item whose live data changed after the snapshot being restored.
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnConflict {
+    /// Ask per item (overwrite when not running in a terminal)
+    Ask,
+    /// Replace the live data with the snapshot
+    #[default]
+    Overwrite,
+    /// Leave the item alone
+    Skip,
+    /// Restore the snapshot but keep files that are newer than their copy in it
+    MergeNewer,
+}
+
+/// Limits a restore to parts of the archives (`--include`/`--exclude`). Filtered items
+/// are extracted over the live data instead of replacing it.
+#[derive(Default, Clone)]
+pub struct ExtractFilter {
+    /// Globs relative to the archive root, e.g. `config/**`. Everything if empty.
+    pub include: Vec<String>,
+    /// Globs of paths to leave out, matched anywhere in the archive.
+    pub exclude: Vec<String>,
+}
+
+impl ExtractFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Adds the filter to a `tar -x` command, after all other arguments.
+    fn apply(&self, tar: &mut Command) {
+        for pattern in &self.exclude {
+            tar.arg(format!("--exclude={}", pattern));
+        }
+        if !self.include.is_empty() {
+            // Archives are created from `.`, so members are named `./<path>`
+            tar.arg("--wildcards").args(
+                self.include
+                    .iter()
+                    .map(|pattern| format!("./{}", pattern.trim_start_matches("./"))),
+            );
+        }
+    }
+}
 
 pub fn handle_restore_command(
     config: &Config,
     project: Option<String>,
-    version: Option<String>,
-    repo: bool,
-    volumes: Vec<String>,
-) {
-    let no_args_provided = project.is_none();
-
-    if no_args_provided {
-        if let Err(e) = enter_interactive_shell(config) {
-            eprintln!("❌ Error in interactive shell: {e}");
+    cli: CliRestoreOptions,
+) -> anyhow::Result<()> {
+    match project {
+        None => {
+            if let Err(e) = enter_interactive_shell(config) {
+                plain_eprintln!("❌ Error in interactive shell: {e}");
+            }
+            Ok(())
         }
+        Some(project) => restore_from_cli(config, &project, &cli),
+    }
+}
+
+/// Blocks until the next occurrence of the local time `HH:MM`.
+pub fn wait_until(start_at: &str) -> anyhow::Result<()> {
+    let time = NaiveTime::parse_from_str(start_at, "%H:%M")
+        .with_context(|| format!("Invalid start time {}, expected HH:MM", start_at))?;
+    let now = Local::now().naive_local();
+    let mut start = now.date().and_time(time);
+    if start <= now {
+        start += chrono::Duration::days(1);
+    }
+    let wait = (start - now).to_std()?;
+    log::info!(
+        "⏳ Waiting until {} to start the restore",
+        start.format("%Y-%m-%d %H:%M")
+    );
+    std::thread::sleep(wait);
+    Ok(())
+}
+
+const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// Bordered panel in the theme's colors, drawn with plain ASCII in `--ascii` mode.
+fn panel<'a>(title: &'a str, theme: &Theme) -> Block<'a> {
+    let block = Block::default()
+        .title(title)
+        .borders(ratatui::widgets::Borders::ALL)
+        .border_style(theme.border())
+        .style(theme.text());
+    if ascii_only() {
+        block.border_set(ASCII_BORDER)
     } else {
-        todo!(
-            "Implement restore logic here, from direct CLI call {},{},{},{:?}",
-            project.unwrap(),
-            version.unwrap_or_default(),
-            repo,
-            volumes
-        );
+        block
     }
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
-    let popup_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage((100 - percent_y) / 2),
-            Constraint::Percentage(percent_y),
-            Constraint::Percentage((100 - percent_y) / 2),
-        ])
-        .split(area);
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_x) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
-        ])
-        .split(popup_layout[1])[1]
+/// Line with the `--no-emoji` / `--ascii` output style applied.
+fn plain_line(text: &str) -> Line<'static> {
+    Line::from(plain(text).into_owned())
+}
+
+/// `percent_x` by `percent_y` of `area` in its center, but at least `min` (width, height)
+/// as far as `area` allows, so popups stay usable on small terminals.
+fn centered_rect(percent_x: u16, percent_y: u16, min: (u16, u16), area: Rect) -> Rect {
+    let scale = |length: u16, percent: u16| (u32::from(length) * u32::from(percent) / 100) as u16;
+    let width = scale(area.width, percent_x).max(min.0).min(area.width);
+    let height = scale(area.height, percent_y).max(min.1).min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
 }
 
 fn enter_interactive_shell(config: &Config) -> io::Result<()> {
@@ -91,11 +224,31 @@ pub struct RestoreApp<'a> {
     selected_column: Column,
     selected_volumes: HashSet<String>,
     toggled_repo: bool,
+    /// Restore with `RestoreOptions::recreate`.
+    recreate: bool,
     show_help: bool,
     restore_message: Vec<Line<'a>>,
     show_restore_popup: bool,
+    /// Date of the selected project marked to compare the selected one with.
+    marked_date_index: Option<usize>,
+    show_compare: bool,
+    keys: KeyBindings,
+    theme: Theme,
+    /// Only filled when several targets are browsed at once.
+    locations: SnapshotLocations,
+    /// Outcome of the last refresh, shown instead of the tooltip until the next key.
+    notice: Option<String>,
+    /// The terminal was used outside of the TUI, e.g. for a confirmation token.
+    redraw: bool,
 }
 
+/// Below this size only a hint to enlarge the terminal is shown.
+const MIN_WIDTH: u16 = 30;
+const MIN_HEIGHT: u16 = 10;
+/// Below this width the columns are stacked, below this height the summary is hidden.
+const STACKED_WIDTH: u16 = 80;
+const COMPACT_HEIGHT: u16 = 24;
+
 #[derive(PartialEq)]
 enum Column {
     Projects,
@@ -105,17 +258,19 @@ enum Column {
 
 impl<'a> RestoreApp<'a> {
     pub async fn new(config: &Config) -> Self {
-        let backups = scan_backup_target(config).await.unwrap_or_else(|e| {
-            eprintln!("❌ Error scanning backup target: {e}");
-            Vec::new()
-        });
-
-        let mut seen = HashSet::new();
-        let projects: Vec<String> = backups
-            .iter()
-            .map(|b| b.name.clone())
-            .filter(|name| seen.insert(name.clone()))
-            .collect();
+        let (backups, locations) = if !config.targets.is_empty() {
+            scan_all_targets(config)
+        } else if config.target_name == PRIMARY_TARGET {
+            (cached_backups(config), HashMap::new())
+        } else {
+            // The catalog only knows the primary target
+            let backups = scan_backup_target(config).unwrap_or_else(|e| {
+                plain_eprintln!("❌ Error scanning backup target: {e}");
+                Vec::new()
+            });
+            (backups, HashMap::new())
+        };
+        let projects = project_names(&backups);
 
         Self {
             config: config.clone(),
@@ -128,17 +283,98 @@ impl<'a> RestoreApp<'a> {
             selected_column: Column::Projects,
             selected_volumes: HashSet::new(),
             toggled_repo: false,
+            recreate: false,
             show_help: false,
             restore_message: Vec::new(),
             show_restore_popup: false,
+            marked_date_index: None,
+            show_compare: false,
+            keys: KeyBindings::new(&config.tui.keys),
+            theme: Theme::from_config(&config.tui),
+            locations,
+            notice: None,
+            redraw: false,
         }
     }
+
+    /// Reloads the snapshots from the target, refreshing the catalog with only the primary
+    /// target.
+    fn refresh(&mut self) {
+        let refreshed = if !self.config.targets.is_empty() {
+            let (backups, locations) = scan_all_targets(&self.config);
+            self.backups = backups;
+            self.locations = locations;
+            Ok("Refreshed".to_string())
+        } else if self.config.target_name == PRIMARY_TARGET {
+            Catalog::open()
+                .and_then(|mut catalog| {
+                    let (added, removed) = catalog.refresh(&self.config)?;
+                    Ok((catalog.snapshots()?, added, removed))
+                })
+                .map(|(backups, added, removed)| {
+                    self.backups = backups;
+                    format!("Refreshed, {} snapshots added, {} removed", added, removed)
+                })
+        } else {
+            scan_backup_target(&self.config).map(|backups| {
+                self.backups = backups;
+                "Refreshed".to_string()
+            })
+        };
+        self.notice = Some(refreshed.unwrap_or_else(|e| format!("Refresh failed: {e}")));
+        self.projects = project_names(&self.backups);
+        self.selected_project_index = 0;
+        self.selected_date_index = 0;
+        self.selected_volume_index = 0;
+        self.selected_volumes.clear();
+        self.marked_date_index = None;
+    }
+}
+
+/// Snapshots of the single target from the catalog, which is refreshed from the target
+/// first if it has none yet (e.g. on a new machine).
+fn cached_backups(config: &Config) -> Vec<BackupApplication> {
+    let mut catalog = match Catalog::open() {
+        Ok(catalog) => catalog,
+        Err(e) => {
+            log::warn!("⚠️  Catalog unavailable, scanning the target: {e}");
+            return scan_backup_target(config).unwrap_or_else(|e| {
+                plain_eprintln!("❌ Error scanning backup target: {e}");
+                Vec::new()
+            });
+        }
+    };
+    match catalog.snapshots() {
+        Ok(snapshots) if !snapshots.is_empty() => return snapshots,
+        Ok(_) => {}
+        Err(e) => log::warn!("⚠️  {e}"),
+    }
+    catalog
+        .refresh(config)
+        .and_then(|_| catalog.snapshots())
+        .unwrap_or_else(|e| {
+            plain_eprintln!("❌ Error scanning backup target: {e}");
+            Vec::new()
+        })
+}
+
+/// Project names in the order of their first snapshot.
+fn project_names(backups: &[BackupApplication]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    backups
+        .iter()
+        .map(|b| b.name.clone())
+        .filter(|name| seen.insert(name.clone()))
+        .collect()
 }
 
 impl<'a> RestoreApp<'a> {
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
         log::debug!("{:?}", self.backups);
         while !self.exit {
+            if std::mem::take(&mut self.redraw) {
+                terminal.clear()?;
+            }
             terminal.draw(|frame| self.draw(frame))?;
             self.handle_events()?;
         }
@@ -146,18 +382,41 @@ impl<'a> RestoreApp<'a> {
     }
 
     fn draw(&self, frame: &mut Frame) {
+        let area = frame.area();
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT || self.projects.is_empty() {
+            let quit = self.keys.describe(Action::Quit);
+            let message = if self.projects.is_empty() {
+                tf("tui.no_backups", &[&quit])
+            } else {
+                tf("tui.too_small", &[&area.width, &area.height, &quit])
+            };
+            Paragraph::new(plain_line(&message))
+                .wrap(ratatui::widgets::Wrap { trim: true })
+                .render(area, frame.buffer_mut());
+            return;
+        }
+
+        // Small terminals drop the summary first, then the margin and the tooltip
+        let compact = area.height < COMPACT_HEIGHT;
+        let mut rows = vec![Constraint::Min(5)];
+        if !compact {
+            rows.push(Constraint::Length(5));
+        }
+        rows.push(Constraint::Length(1));
         let layout = Layout::default()
             .direction(Direction::Vertical)
-            .margin(1)
-            .constraints(vec![
-                Constraint::Min(5),
-                Constraint::Length(5),
-                Constraint::Length(1),
-            ])
-            .split(frame.area());
+            .margin(if compact { 0 } else { 1 })
+            .constraints(rows)
+            .split(area);
 
+        // Narrow terminals stack the columns
+        let direction = if area.width < STACKED_WIDTH {
+            Direction::Vertical
+        } else {
+            Direction::Horizontal
+        };
         let chunk = Layout::default()
-            .direction(Direction::Horizontal)
+            .direction(direction)
             .constraints(vec![
                 Constraint::Ratio(1, 3),
                 Constraint::Ratio(1, 3),
@@ -168,24 +427,31 @@ impl<'a> RestoreApp<'a> {
         self.draw_projects(chunk[0], frame.buffer_mut());
         self.draw_dates(chunk[1], frame.buffer_mut());
         self.draw_volumes(chunk[2], frame.buffer_mut());
-        self.draw_summary(layout[1], frame.buffer_mut());
-        self.draw_tooltip(layout[2], frame.buffer_mut());
-        if self.show_help {
-            let area = centered_rect(60, 20, frame.area());
+        if !compact {
+            self.draw_summary(layout[1], frame.buffer_mut());
+        }
+        self.draw_tooltip(layout[layout.len() - 1], frame.buffer_mut());
+        // The help needs its full height to be of any use, the tooltip has to do otherwise
+        let help = centered_rect(60, 25, (44, 12), area);
+        if self.show_help && help.height >= 12 && help.width >= 44 {
             use ratatui::widgets::Clear;
-            Clear.render(area, frame.buffer_mut());
-            self.draw_floating_help(area, frame.buffer_mut());
+            Clear.render(help, frame.buffer_mut());
+            self.draw_floating_help(help, frame.buffer_mut());
+        }
+        if self.show_compare {
+            self.draw_compare(centered_rect(80, 60, (60, 16), area), frame.buffer_mut());
         }
         if self.show_restore_popup {
-            let popup = centered_rect(60, 30, frame.area());
+            let popup = centered_rect(60, 30, (50, 12), area);
             // clear any background behind it
             ratatui::widgets::Clear.render(popup, frame.buffer_mut());
-            Paragraph::new(Text::from(self.restore_message.clone()))
-                .block(
-                    Block::default()
-                        .title("Restore")
-                        .borders(ratatui::widgets::Borders::ALL),
-                )
+            let lines = self
+                .restore_message
+                .iter()
+                .map(|line| plain_line(&line.to_string()))
+                .collect::<Vec<_>>();
+            Paragraph::new(Text::from(lines))
+                .block(panel("Restore", &self.theme))
                 .render(popup, frame.buffer_mut());
         }
     }
@@ -194,22 +460,41 @@ impl<'a> RestoreApp<'a> {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
                 self.handle_key_event(key_event)
             }
+            // The next loop iteration redraws for the new size
+            Event::Resize(_, _) => {}
             _ => {}
         };
         Ok(())
     }
 
     fn handle_key_event(&mut self, key_event: KeyEvent) {
-        if key_event.code == KeyCode::Esc || key_event.code == KeyCode::Char('q') {
+        // Any key closes the comparison
+        if self.show_compare {
+            self.show_compare = false;
+            return;
+        }
+        self.notice = None;
+        let Some(action) = self.keys.action(key_event.code) else {
+            return;
+        };
+        if action == Action::Quit {
             self.exit();
             return;
         }
-        if key_event.code == KeyCode::Char('h') {
+        if action == Action::Refresh && !self.show_restore_popup {
+            self.refresh();
+            return;
+        }
+        // Without backups there is nothing to select
+        if self.projects.is_empty() {
+            return;
+        }
+        if action == Action::Help {
             self.show_help = !self.show_help;
             return;
         }
-        match key_event.code {
-            KeyCode::Char('a') => {
+        match action {
+            Action::SelectAll => {
                 self.selected_volumes = HashSet::new();
                 let volumes = get_volumes(
                     get_backups(&self.backups, &self.projects[self.selected_project_index])
@@ -220,10 +505,13 @@ impl<'a> RestoreApp<'a> {
                     self.selected_volumes.insert(volume);
                 }
             }
-            KeyCode::Char('d') => {
+            Action::DeselectAll => {
                 self.selected_volumes = HashSet::new();
             }
-            KeyCode::Enter => {
+            Action::Recreate => {
+                self.recreate = !self.recreate;
+            }
+            Action::Restore => {
                 if self.show_restore_popup {
                     self.start_restore_process();
                 } else {
@@ -233,32 +521,34 @@ impl<'a> RestoreApp<'a> {
             _ => {}
         }
         match self.selected_column {
-            Column::Projects => match key_event.code {
-                KeyCode::Up => {
+            Column::Projects => match action {
+                Action::Up => {
                     if self.selected_project_index > 0 {
                         self.selected_project_index -= 1;
                     }
                     self.selected_date_index = 0;
+                    self.marked_date_index = None;
                 }
-                KeyCode::Down => {
+                Action::Down => {
                     if self.selected_project_index < self.projects.len() - 1 {
                         self.selected_project_index += 1;
                     }
                     self.selected_date_index = 0;
+                    self.marked_date_index = None;
                 }
-                KeyCode::Right => {
+                Action::Right => {
                     self.selected_column = Column::Dates;
                 }
                 _ => {}
             },
-            Column::Dates => match key_event.code {
-                KeyCode::Up => {
+            Column::Dates => match action {
+                Action::Up => {
                     if self.selected_date_index > 0 {
                         self.selected_date_index -= 1;
                     }
                     self.selected_volume_index = 0;
                 }
-                KeyCode::Down => {
+                Action::Down => {
                     let available_dates =
                         get_backups(&self.backups, &self.projects[self.selected_project_index])
                             .len();
@@ -267,21 +557,32 @@ impl<'a> RestoreApp<'a> {
                     }
                     self.selected_volume_index = 0;
                 }
-                KeyCode::Left => {
+                Action::Left => {
                     self.selected_column = Column::Projects;
                 }
-                KeyCode::Right => {
+                Action::Right => {
                     self.selected_column = Column::Volumes;
                 }
+                Action::Mark => {
+                    self.marked_date_index = match self.marked_date_index {
+                        Some(marked) if marked == self.selected_date_index => None,
+                        _ => Some(self.selected_date_index),
+                    };
+                }
+                Action::Compare => {
+                    self.show_compare = self
+                        .marked_date_index
+                        .is_some_and(|marked| marked != self.selected_date_index);
+                }
                 _ => {}
             },
-            Column::Volumes => match key_event.code {
-                KeyCode::Up => {
+            Column::Volumes => match action {
+                Action::Up => {
                     if self.selected_volume_index > 0 {
                         self.selected_volume_index -= 1;
                     }
                 }
-                KeyCode::Down => {
+                Action::Down => {
                     let available_volumes = get_volumes(
                         get_backups(&self.backups, &self.projects[self.selected_project_index])
                             [self.selected_date_index]
@@ -292,15 +593,15 @@ impl<'a> RestoreApp<'a> {
                         self.selected_volume_index += 1;
                     }
                 }
-                KeyCode::Left => {
+                Action::Left => {
                     self.selected_column = Column::Dates;
                     self.toggled_repo = false;
                     self.selected_volumes = HashSet::new();
                 }
-                KeyCode::Right => {
+                Action::Right => {
                     self.selected_column = Column::Projects;
                 }
-                KeyCode::Char(' ') => {
+                Action::Select => {
                     let selected_volume = get_volumes(
                         get_backups(&self.backups, &self.projects[self.selected_project_index])
                             [self.selected_date_index]
@@ -335,11 +636,7 @@ impl<'a> RestoreApp<'a> {
         );
 
         Paragraph::new(Text::from(project_names))
-            .block(
-                Block::default()
-                    .title("Projects")
-                    .borders(ratatui::widgets::Borders::ALL),
-            )
+            .block(panel("Projects", &self.theme))
             .render(area, buf);
     }
 
@@ -347,7 +644,27 @@ impl<'a> RestoreApp<'a> {
         let dates = get_backups(&self.backups, &self.projects[self.selected_project_index]);
         let binding = dates
             .iter()
-            .map(|app| app.timestamp.format("%d. %B %Y %H:%M:%S").to_string())
+            .enumerate()
+            .map(|(i, app)| {
+                let mark = if self.marked_date_index == Some(i) {
+                    "* "
+                } else {
+                    ""
+                };
+                let targets = match self
+                    .locations
+                    .get(&(app.name.clone(), app.snapshot_folder()))
+                {
+                    Some(targets) => format!(" [{}]", targets.join(", ")),
+                    None => String::new(),
+                };
+                format!(
+                    "{}{}{}",
+                    mark,
+                    app.timestamp.format("%d. %B %Y %H:%M:%S"),
+                    targets
+                )
+            })
             .collect::<Vec<String>>();
         let dates = style_selected(
             &binding,
@@ -356,11 +673,7 @@ impl<'a> RestoreApp<'a> {
         );
 
         Paragraph::new(Text::from(dates))
-            .block(
-                Block::default()
-                    .title("Dates")
-                    .borders(ratatui::widgets::Borders::ALL),
-            )
+            .block(panel("Dates", &self.theme))
             .render(area, buf);
     }
 
@@ -377,17 +690,38 @@ impl<'a> RestoreApp<'a> {
             self.selected_column == Column::Volumes,
         );
         Paragraph::new(Text::from(volume_names))
-            .block(
-                Block::default()
-                    .title("Volumes")
-                    .borders(ratatui::widgets::Borders::ALL),
-            )
+            .block(panel("Volumes", &self.theme))
+            .render(area, buf);
+    }
+
+    fn draw_compare(&self, area: Rect, buf: &mut Buffer) {
+        let backups = get_backups(&self.backups, &self.projects[self.selected_project_index]);
+        let (Some(marked), Some(selected)) = (
+            self.marked_date_index.and_then(|i| backups.get(i)),
+            backups.get(self.selected_date_index),
+        ) else {
+            return;
+        };
+        // Dates are sorted newest first
+        let (older, newer) = if self.marked_date_index > Some(self.selected_date_index) {
+            (marked, selected)
+        } else {
+            (selected, marked)
+        };
+        let lines = compare_snapshots(older, newer)
+            .iter()
+            .map(|line| plain_line(line))
+            .collect::<Vec<_>>();
+        ratatui::widgets::Clear.render(area, buf);
+        Paragraph::new(Text::from(lines))
+            .block(panel("Compare", &self.theme))
+            .wrap(ratatui::widgets::Wrap { trim: false })
             .render(area, buf);
     }
 
     fn draw_summary(&self, area: Rect, buf: &mut Buffer) {
         let summary_text = format!(
-            "Selected Project:   {}\nSelected Backup:    {}\nSelected Volume(s): {}",
+            "Selected Project:   {}\nSelected Backup:    {}\nSelected Volume(s): {}\nRecreate:           {}",
             self.projects[self.selected_project_index],
             get_backups(&self.backups, &self.projects[self.selected_project_index])
                 [self.selected_date_index]
@@ -397,42 +731,68 @@ impl<'a> RestoreApp<'a> {
                 .iter()
                 .cloned()
                 .collect::<Vec<String>>()
-                .join(", ")
+                .join(", "),
+            if self.recreate { "yes" } else { "no" }
         );
 
         Paragraph::new(Text::from(summary_text))
-            .block(
-                Block::default()
-                    .title("Summary")
-                    .borders(ratatui::widgets::Borders::ALL),
-            )
+            .block(panel("Summary", &self.theme))
             .render(area, buf);
     }
 
     fn draw_tooltip(&self, layout: Rect, buf: &mut Buffer) {
-        let tooltip_text = " (q)uit | (h)elp | (space) select | (up) | (down) | (left) | (right) ";
-        let paragraph =
-            Paragraph::new(tooltip_text.blue().bold()).wrap(ratatui::widgets::Wrap { trim: false });
+        if let Some(notice) = &self.notice {
+            Paragraph::new(plain(notice).into_owned().set_style(self.theme.accent()))
+                .render(layout, buf);
+            return;
+        }
+        let key = |action| self.keys.describe(action);
+        let tooltip_text = plain(&tf(
+            "tui.tooltip",
+            &[
+                &key(Action::Quit),
+                &key(Action::Help),
+                &key(Action::Select),
+                &key(Action::Up),
+                &key(Action::Down),
+                &key(Action::Left),
+                &key(Action::Right),
+            ],
+        ))
+        .into_owned();
+        let paragraph = Paragraph::new(tooltip_text.set_style(self.theme.accent()).bold())
+            .wrap(ratatui::widgets::Wrap { trim: false });
         paragraph.render(layout, buf);
     }
 
     fn draw_floating_help(&self, area: Rect, buf: &mut Buffer) {
+        let key = |action| self.keys.describe(action);
         let text = Text::from(vec![
-            Line::from("← →: switch column"),
-            Line::from("↑ ↓: navigate"),
-            Line::from("SPACE: select volume"),
-            Line::from("ENTER: restore"),
-            Line::from("a: select all    d: deselect all"),
-            Line::from("q: quit"),
-            Line::from("h: toggle help"),
+            plain_line(&tf(
+                "tui.help.switch",
+                &[&key(Action::Left), &key(Action::Right)],
+            )),
+            plain_line(&tf(
+                "tui.help.navigate",
+                &[&key(Action::Up), &key(Action::Down)],
+            )),
+            plain_line(&tf("tui.help.select", &[&key(Action::Select)])),
+            plain_line(&tf("tui.help.restore", &[&key(Action::Restore)])),
+            plain_line(&tf(
+                "tui.help.select_all",
+                &[&key(Action::SelectAll), &key(Action::DeselectAll)],
+            )),
+            plain_line(&tf(
+                "tui.help.compare",
+                &[&key(Action::Mark), &key(Action::Compare)],
+            )),
+            plain_line(&tf("tui.help.recreate", &[&key(Action::Recreate)])),
+            plain_line(&tf("tui.help.refresh", &[&key(Action::Refresh)])),
+            plain_line(&tf("tui.help.quit", &[&key(Action::Quit)])),
+            plain_line(&tf("tui.help.toggle", &[&key(Action::Help)])),
         ]);
         Paragraph::new(text)
-            .block(
-                Block::default()
-                    .title("Help")
-                    .borders(ratatui::widgets::Borders::ALL)
-                    .style(Style::default().bg(ratatui::style::Color::White)),
-            )
+            .block(panel("Help", &self.theme).style(self.theme.popup()))
             .render(area, buf);
     }
 
@@ -455,8 +815,11 @@ impl<'a> RestoreApp<'a> {
             "📁 Repo: {}",
             if repo { "yes" } else { "no" }
         )));
+        if self.recreate {
+            lines.push(Line::from("🔄 The project is taken down and started again"));
+        }
         lines.push(Line::from(""));
-        lines.push(Line::from("Press ENTER to confirm restore"));
+        lines.push(Line::from(t("tui.confirm")));
 
         self.restore_message = lines;
         self.show_restore_popup = true;
@@ -468,14 +831,18 @@ impl<'a> Widget for &'a RestoreApp<'a> {
         let title = Line::from(" Dockup Restore ".bold());
         let instructions = Line::from(vec![
             " Select ".into(),
-            "<Up> <Down>".blue().bold(),
+            "<Up> <Down>".set_style(self.theme.accent()).bold(),
             " Quit ".into(),
-            "<Q> ".blue().bold(),
+            "<Q> ".set_style(self.theme.accent()).bold(),
         ]);
         let block = Block::bordered()
             .title(title.centered())
             .title_bottom(instructions.centered())
-            .border_set(border::THICK);
+            .border_set(if ascii_only() {
+                ASCII_BORDER
+            } else {
+                border::THICK
+            });
 
         let counter_text = Text::from(vec![Line::from(vec!["Value: ".into()])]);
 
@@ -486,38 +853,87 @@ impl<'a> Widget for &'a RestoreApp<'a> {
     }
 }
 
-async fn scan_backup_target(config: &Config) -> anyhow::Result<Vec<BackupApplication>> {
+/// (project, snapshot folder) -> targets holding that snapshot.
+type SnapshotLocations = HashMap<(String, String), Vec<String>>;
+
+/// Snapshots of every configured target, each listed once, and the targets holding them.
+/// A target that can't be read is reported and left out.
+fn scan_all_targets(config: &Config) -> (Vec<BackupApplication>, SnapshotLocations) {
+    let mut backups = Vec::new();
+    let mut locations = SnapshotLocations::new();
+    for name in config.target_names() {
+        match config
+            .target(&name)
+            .and_then(|target| scan_backup_target(&target))
+        {
+            Ok(scanned) => {
+                for backup in scanned {
+                    let targets = locations
+                        .entry((backup.name.clone(), backup.snapshot_folder()))
+                        .or_default();
+                    if targets.is_empty() {
+                        backups.push(backup);
+                    }
+                    targets.push(name.clone());
+                }
+            }
+            Err(e) => plain_eprintln!("❌ Error scanning backup target {name}: {e}"),
+        }
+    }
+    (backups, locations)
+}
+
+/// Reads the metadata of every snapshot on the backup target, from its `index.json` if
+/// there is one. Otherwise the target is scanned and the index written.
+pub fn scan_backup_target(config: &Config) -> anyhow::Result<Vec<BackupApplication>> {
+    if let Some(snapshots) = index::read(config) {
+        return Ok(snapshots);
+    }
+    let snapshots = scan_snapshot_folders(config)?;
+    if !config.read_only {
+        if let Err(e) = index::write(config, &snapshots) {
+            log::warn!("⚠️  {e:#}");
+        }
+    }
+    Ok(snapshots)
+}
+
+/// Reads the `meta.json` of every snapshot folder on the backup target, one read per
+/// snapshot. Folders whose `meta.json` can't be read are skipped.
+pub fn scan_snapshot_folders(config: &Config) -> anyhow::Result<Vec<BackupApplication>> {
+    scan_folders(config, false)
+}
+
+/// Like [`scan_snapshot_folders`], but fails if any `meta.json` can't be read, for callers
+/// that must not act on a partial list. A snapshot still being written has none yet.
+pub fn scan_snapshot_folders_complete(config: &Config) -> anyhow::Result<Vec<BackupApplication>> {
+    scan_folders(config, true)
+}
+
+fn scan_folders(config: &Config, complete: bool) -> anyhow::Result<Vec<BackupApplication>> {
     log::debug!("Scanning backup target: {}", config.remote_backup_path);
     let mut backups = Vec::new();
-    let listing =
-        run_remote_cmd_with_output(config, &format!("ls -1 {}", config.remote_backup_path))
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let transport = transport::open(config);
+    let listing = transport.list(&config.remote_backup_path)?;
 
     let application_folders = listing
-        .lines()
+        .iter()
         .filter(|line| !line.contains("."))
         .collect::<Vec<_>>();
 
     for app in application_folders {
         log::debug!("Found backup application: {}", app);
-        let listing = run_remote_cmd_with_output(
-            config,
-            &format!("ls -1 {}/{}", config.remote_backup_path, app),
-        )
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let listing = transport.list(&format!("{}/{}", config.remote_backup_path, app))?;
         let backup_folders = listing
-            .lines()
+            .iter()
             .filter(|line| !line.contains("."))
             .collect::<Vec<_>>();
         log::debug!("Found backup folders: {:?}", backup_folders);
         for backup_folder in backup_folders {
-            let meta = run_remote_cmd_with_output(
-                config,
-                &format!(
-                    "cat {}/{}/{}/meta.json",
-                    config.remote_backup_path, app, backup_folder
-                ),
-            );
+            let meta = transport.read(&format!(
+                "{}/{}/{}/meta.json",
+                config.remote_backup_path, app, backup_folder
+            ));
 
             let meta = match meta {
                 Ok(meta) => {
@@ -527,6 +943,12 @@ async fn scan_backup_target(config: &Config) -> anyhow::Result<Vec<BackupApplica
                     log::debug!("Parsed meta.json: {:?}", meta);
                     meta
                 }
+                Err(e) if complete => {
+                    return Err(e.context(format!(
+                        "Failed to read meta.json of {}/{}",
+                        app, backup_folder
+                    )));
+                }
                 Err(e) => {
                     log::error!("Failed to read meta.json: {}", e);
                     continue;
@@ -569,6 +991,123 @@ fn get_volumes(backup: BackupApplication) -> Vec<String> {
     volumes.push("REPO".to_string());
     volumes
 }
+/// What changed from `older` to `newer` of the same project: the archived volumes and their
+/// sizes, the dumps and the snapshot settings.
+fn compare_snapshots(older: &BackupApplication, newer: &BackupApplication) -> Vec<String> {
+    let date = |backup: &BackupApplication| backup.timestamp.format("%d. %B %Y %H:%M").to_string();
+    let mut lines = vec![format!("{} -> {}", date(older), date(newer)), String::new()];
+
+    let older_volumes = get_volumes(older.clone());
+    let newer_volumes = get_volumes(newer.clone());
+    let mut names: Vec<&String> = older_volumes.iter().collect();
+    names.extend(
+        newer_volumes
+            .iter()
+            .filter(|name| !older_volumes.contains(*name)),
+    );
+    let size = |backup: &BackupApplication, name: &str| {
+        backup
+            .stats
+            .iter()
+            .find(|stats| stats.name == name)
+            .map(|stats| stats.original_bytes)
+    };
+    let width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or_default();
+    for name in names {
+        let change = match (older_volumes.contains(name), newer_volumes.contains(name)) {
+            (true, false) => "only in older".to_string(),
+            (false, true) => "only in newer".to_string(),
+            _ => match (size(older, name), size(newer, name)) {
+                (Some(before), Some(after)) => {
+                    let sign = if after >= before { "+" } else { "-" };
+                    format!(
+                        "{} -> {} ({}{})",
+                        format_bytes(before),
+                        format_bytes(after),
+                        sign,
+                        format_bytes(after.abs_diff(before))
+                    )
+                }
+                _ => "size unknown".to_string(),
+            },
+        };
+        lines.push(format!("{:width$}  {}", name, change));
+    }
+
+    for volume in &newer.volumes {
+        if let Some(old) = older.volumes.iter().find(|old| old.name == volume.name) {
+            if old.path != volume.path {
+                lines.push(format!(
+                    "{}: path {:?} -> {:?}",
+                    volume.name, old.path, volume.path
+                ));
+            }
+            if old.excludes != volume.excludes {
+                lines.push(format!(
+                    "{}: excludes [{}] -> [{}]",
+                    volume.name,
+                    old.excludes.join(", "),
+                    volume.excludes.join(", ")
+                ));
+            }
+        }
+    }
+
+    let added: Vec<&str> = newer
+        .dumps
+        .iter()
+        .filter(|dump| !older.dumps.contains(dump))
+        .map(String::as_str)
+        .collect();
+    let removed: Vec<&str> = older
+        .dumps
+        .iter()
+        .filter(|dump| !newer.dumps.contains(dump))
+        .map(String::as_str)
+        .collect();
+    if !added.is_empty() {
+        lines.push(format!("Dumps only in newer: {}", added.join(", ")));
+    }
+    if !removed.is_empty() {
+        lines.push(format!("Dumps only in older: {}", removed.join(", ")));
+    }
+
+    let yes_no = |flag: bool| String::from(if flag { "yes" } else { "no" });
+    let settings = [
+        (
+            "compression",
+            older.compression.clone().unwrap_or_else(|| "gzip".into()),
+            newer.compression.clone().unwrap_or_else(|| "gzip".into()),
+        ),
+        (
+            "encrypted",
+            yes_no(older.encrypted),
+            yes_no(newer.encrypted),
+        ),
+        (
+            "incremental",
+            yes_no(older.incremental),
+            yes_no(newer.incremental),
+        ),
+        ("dedup", yes_no(older.dedup), yes_no(newer.dedup)),
+        ("tags", older.tags.join(", "), newer.tags.join(", ")),
+    ];
+    let changed: Vec<String> = settings
+        .iter()
+        .filter(|(_, before, after)| before != after)
+        .map(|(name, before, after)| format!("{}: {} -> {}", name, before, after))
+        .collect();
+    if !changed.is_empty() {
+        lines.push(String::new());
+        lines.extend(changed);
+    }
+    lines
+}
+
 fn style_selected(list: &Vec<String>, selected_index: usize, home_column: bool) -> Vec<Line> {
     list.iter()
         .enumerate()
@@ -608,7 +1147,11 @@ fn style_checkboxes<'a>(
         .collect()
 }
 
-use std::{fs, process::Command};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 impl<'a> RestoreApp<'a> {
     /// Kick off the actual scp/tar restore now that user has confirmed.
@@ -617,129 +1160,1434 @@ impl<'a> RestoreApp<'a> {
         let backups = get_backups(&self.backups, project);
         let backup = &backups[self.selected_date_index];
 
-        // Folder name matches folder on the server
-        let folder = backup.timestamp.format("%Y_%m_%d_%H%M%S").to_string();
-        let remote_base = format!(
-            "{}/{}/{}",
-            self.config.remote_backup_path, backup.name, folder
-        );
-
         // Build list: volumes + "REPO" if toggled
         let mut items: Vec<String> = self.selected_volumes.iter().cloned().collect();
         if self.toggled_repo && !items.contains(&"REPO".into()) {
             items.push("REPO".into());
         }
 
-        for name in items {
-            self.restore_message
-                .push(Line::from(format!("🚧 Restoring Repo")));
-            if name == "REPO" {
-                let remote = format!("{}/REPO/repo.tar.gz", remote_base);
-                let tmp = std::env::temp_dir().join("repo.tar.gz");
-
-                // Download
-                self.restore_message
-                    .push(Line::from(format!("⏬ Downloading repo")));
-                let output = Command::new("scp")
-                    .args(&[
-                        "-i",
-                        &self.config.ssh_key,
-                        "-P",
-                        &self.config.ssh_port.to_string(),
-                        &format!(
-                            "{}@{}:{}",
-                            self.config.ssh_user, self.config.ssh_host, remote
-                        ),
-                        tmp.to_str().unwrap(),
-                    ])
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::piped())
-                    .output()?;
-                if !output.status.success() {
-                    let err = String::from_utf8_lossy(&output.stderr);
-                    self.restore_message
-                        .push(Line::from(format!("⚠️ failed scp repo: {}", err)));
-                    continue;
+        // In the merged view the snapshot is pulled from the first target holding it
+        let config = match self
+            .locations
+            .get(&(backup.name.clone(), backup.snapshot_folder()))
+        {
+            Some(targets) => {
+                self.restore_message.push(Line::from(format!(
+                    "📦 Restoring from target {}",
+                    targets[0]
+                )));
+                self.config.target(&targets[0]).map_err(io::Error::other)?
+            }
+            None => self.config.clone(),
+        };
+
+        let action = format!(
+            "dockup restore --project {} --version {}",
+            backup.name,
+            backup.snapshot_folder()
+        );
+        if let Err(e) = confirm::forbid_read_only(&config, &action) {
+            self.restore_message.push(Line::from(format!("❌ {e}")));
+            return Ok(());
+        }
+        if config.confirm_destructive {
+            if let Err(e) = confirm_outside_tui(&config, &action) {
+                self.restore_message.push(Line::from(format!("❌ {e}")));
+                self.redraw = true;
+                return Ok(());
+            }
+            self.redraw = true;
+        }
+
+        // The TUI owns the terminal, conflicts are only reported
+        let messages = &mut self.restore_message;
+        let options = RestoreOptions {
+            recreate: self.recreate,
+            ..RestoreOptions::default()
+        };
+        restore_snapshot(&config, backup, &items, &options, |msg| {
+            messages.push(Line::from(msg.to_string()))
+        })?;
+
+        // keep popup visible so user sees the messages
+        Ok(())
+    }
+}
+
+/// Asks for the confirmation token of `action` on the plain terminal, the TUI is suspended
+/// meanwhile and has to be redrawn afterwards.
+fn confirm_outside_tui(config: &Config, action: &str) -> anyhow::Result<()> {
+    ratatui::restore();
+    enable_stdout_logging();
+    let confirmed = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(confirm::require(config, action))
+    });
+    disable_stdout_logging();
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(io::stdout(), crossterm::terminal::EnterAlternateScreen)?;
+    confirmed
+}
+
+/// Non-interactive restore: picks the snapshot by `version` (snapshot folder name or a
+/// prefix of it, latest if omitted) and restores the repo and/or the given volumes.
+fn restore_from_cli(config: &Config, project: &str, cli: &CliRestoreOptions) -> anyhow::Result<()> {
+    let CliRestoreOptions {
+        version,
+        repo,
+        volumes,
+        databases,
+        images,
+        options,
+    } = cli;
+    let backups = get_backups(&scan_backup_target(config)?, project);
+    if backups.is_empty() {
+        anyhow::bail!("No backups found for project {}", project);
+    }
+    let backup = match version.as_deref() {
+        None => &backups[0],
+        Some(version) => backups
+            .iter()
+            .find(|b| b.snapshot_folder().starts_with(version))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No backup of {} matches version {} (available: {})",
+                    project,
+                    version,
+                    backups
+                        .iter()
+                        .map(|b| b.snapshot_folder())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?,
+    };
+
+    let mut items = Vec::new();
+    for volume in volumes {
+        if !backup.volumes.iter().any(|v| &v.name == volume) {
+            anyhow::bail!(
+                "Backup {} of {} has no volume {}",
+                backup.snapshot_folder(),
+                project,
+                volume
+            );
+        }
+        items.push(volume.clone());
+    }
+    if *repo {
+        items.push("REPO".to_string());
+    }
+    for database in databases {
+        if !backup.dumps.contains(database) {
+            anyhow::bail!(
+                "Backup {} of {} has no dump of {}",
+                backup.snapshot_folder(),
+                project,
+                database
+            );
+        }
+        items.push(format!("{}{}", dbdump::RESTORE_PREFIX, database));
+    }
+    if *images {
+        if !backup.images.iter().any(|image| image.archive.is_some()) {
+            anyhow::bail!(
+                "Backup {} of {} has no saved images",
+                backup.snapshot_folder(),
+                project
+            );
+        }
+        items.push(images::RESTORE_ITEM.to_string());
+    }
+    let with_files;
+    let options = if options.files.is_empty() {
+        options
+    } else {
+        let mut include = options.filter.include.clone();
+        include.extend(resolve_files(backup, &mut items, &options.files)?);
+        with_files = RestoreOptions {
+            filter: ExtractFilter {
+                include,
+                exclude: options.filter.exclude.clone(),
+            },
+            ..options.clone()
+        };
+        &with_files
+    };
+    if items.is_empty() && !options.pin_images {
+        anyhow::bail!("Nothing to restore, pass --repo, --volumes, --databases and/or --images");
+    }
+
+    let relocated;
+    let backup = if options.target_dir.is_some() || options.as_name.is_some() {
+        if !databases.is_empty() || options.recreate {
+            anyhow::bail!(
+                "--databases and --recreate act on the running project and can't be combined with --target-dir/--as"
+            );
+        }
+        relocated = relocate(
+            backup,
+            options.target_dir.as_deref(),
+            options.as_name.as_deref(),
+        )?;
+        log::info!(
+            "Restoring into {} instead of {}",
+            relocated.application_path.display(),
+            backup.application_path.display()
+        );
+        &relocated
+    } else {
+        backup
+    };
+
+    log::info!(
+        "Restoring {} from backup {}",
+        project,
+        backup.snapshot_folder()
+    );
+    if backup.incremental && (!options.filter.is_empty() || options.validate.is_some()) {
+        anyhow::bail!(
+            "--include/--exclude/--validate are not supported for incremental backups like {}",
+            backup.snapshot_folder()
+        );
+    }
+
+    let failed = restore_snapshot(config, backup, &items, options, |msg| {
+        plain_println!("{}", msg)
+    })?;
+    if failed > 0 {
+        anyhow::bail!("{} of {} items failed to restore", failed, items.len());
+    }
+    Ok(())
+}
+
+/// Turns `--file` paths into `--include` patterns. With a single repo or volume in `items`
+/// they are relative to it. Without one the item is picked from the paths, relative to the
+/// project directory or absolute: the bind mount holding them, else the repo. All files
+/// have to be in the same item.
+fn resolve_files(
+    backup: &BackupApplication,
+    items: &mut Vec<String>,
+    files: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let selected: Vec<&String> = items
+        .iter()
+        .filter(|item| !item.starts_with(dbdump::RESTORE_PREFIX) && *item != images::RESTORE_ITEM)
+        .collect();
+    match selected.len() {
+        0 => {}
+        1 => {
+            return Ok(files
+                .iter()
+                .map(|file| file.trim_start_matches('/').to_string())
+                .collect())
+        }
+        _ => anyhow::bail!("--file needs a single --repo or --volumes item, or none"),
+    }
+
+    let mut item = None;
+    let mut include = Vec::new();
+    for file in files {
+        let path = backup.application_path.join(file);
+        // The innermost bind mount wins, the repo holds everything else of the project
+        let (name, relative) = backup
+            .volumes
+            .iter()
+            .filter(|v| matches!(v.volume_type, VolumeType::Bind))
+            .filter_map(|v| {
+                let root = backup.application_path.join(&v.path);
+                Some((v.name.as_str(), path.strip_prefix(root).ok()?))
+            })
+            .min_by_key(|(_, relative)| relative.components().count())
+            .or_else(|| Some(("REPO", path.strip_prefix(&backup.application_path).ok()?)))
+            .with_context(|| {
+                format!(
+                    "{} is neither in the project nor in one of its bind mounts",
+                    file
+                )
+            })?;
+        match item {
+            Some(item) if item != name => anyhow::bail!(
+                "--file {} is in {}, the others in {}: restore them one at a time",
+                file,
+                name,
+                item
+            ),
+            _ => item = Some(name),
+        }
+        include.push(relative.to_string_lossy().to_string());
+    }
+    if let Some(item) = item {
+        items.push(item.to_string());
+    }
+    Ok(include)
+}
+
+/// A copy of `backup` restoring into `dir` (default: next to the project, named `name`)
+/// instead of the live project. Bind mounts inside the project move along, those outside
+/// go to `<dir>/.volumes/<volume>` (their `destination`, `path` still names the archive). Docker volumes are restored as `<project>_<volume>` of
+/// the new compose project name, `name` or the directory's. The snapshot keeps its name,
+/// it is still looked up under the original project on the target.
+fn relocate(
+    backup: &BackupApplication,
+    dir: Option<&Path>,
+    name: Option<&str>,
+) -> anyhow::Result<BackupApplication> {
+    let dir = match (dir, name) {
+        (Some(dir), _) => std::env::current_dir()?.join(dir),
+        (None, Some(name)) => backup
+            .application_path
+            .parent()
+            .context("Project directory has no parent")?
+            .join(name),
+        (None, None) => backup.application_path.clone(),
+    };
+    if dir == backup.application_path {
+        anyhow::bail!("{} is the live project directory", dir.display());
+    }
+    let fallback = dir
+        .file_name()
+        .context("Target directory has no name")?
+        .to_string_lossy()
+        .to_string();
+    let project = scanner::normalize_project_name(match name {
+        Some(name) => name,
+        None => &fallback,
+    });
+    let mut relocated = backup.clone();
+    for v in relocated.volumes.iter_mut() {
+        match v.volume_type {
+            VolumeType::Bind => {
+                let relative = match v.path.strip_prefix(&backup.application_path) {
+                    Ok(relative) => Some(relative),
+                    Err(_) => v.path.is_relative().then_some(v.path.as_path()),
+                };
+                v.destination = Some(match relative {
+                    Some(relative) => dir.join(relative),
+                    None => dir.join(".volumes").join(&v.name),
+                });
+            }
+            VolumeType::Mount => {
+                let live = v.docker_volume(&backup.name);
+                let volume = format!("{}_{}", project, v.name);
+                if volume == live {
+                    anyhow::bail!(
+                        "Docker volume {} is the live one, pass --as with another name",
+                        live
+                    );
                 }
+                v.docker_name = Some(volume);
+            }
+        }
+    }
+    relocated.application_path = dir;
+    Ok(relocated)
+}
 
-                self.restore_message
-                    .push(Line::from(format!("📂 Extracting repo")));
-
-                // Extract
-                let dest = &backup.application_path;
-                fs::remove_dir_all(dest).ok();
-                fs::create_dir_all(dest)?;
-                let status = Command::new("tar")
-                    .args(&["-xzf", tmp.to_str().unwrap(), "-C", dest.to_str().unwrap()])
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::piped())
-                    .status()?;
-                if status.success() {
-                    self.restore_message.push(Line::from("✅ repo restored"));
-                } else {
-                    self.restore_message
-                        .push(Line::from("⚠️ repo extract failed"));
+/// Downloads and extracts `items` (volume names, `REPO`, `DB:<service>` for a database
+/// dump or `IMAGES`) of a snapshot, reporting each step through `report`. Returns the number of items
+/// that failed. Items follow the project's `restore_order`, dumps are loaded last, into the
+/// running database. With `recreate` the project is taken down first and started again
+/// before the dumps are loaded. The `post_restore` hook runs once everything is restored.
+fn restore_snapshot(
+    config: &Config,
+    backup: &BackupApplication,
+    items: &[String],
+    options: &RestoreOptions,
+    mut report: impl FnMut(&str),
+) -> io::Result<usize> {
+    // Folder name matches folder on the server
+    let remote_base = format!(
+        "{}/{}/{}",
+        config.remote_backup_path,
+        backup.name,
+        backup.snapshot_folder()
+    );
+    let (dumps, items): (Vec<String>, Vec<String>) = restore_order(config, &backup.name, items)
+        .into_iter()
+        .partition(|item| item.starts_with(dbdump::RESTORE_PREFIX));
+    if options.recreate {
+        report("⏹️ Taking the project down");
+        if let Err(err) = compose_down(&backup.application_path) {
+            report(&format!("⚠️ {:#}", err));
+        }
+    }
+    let mut failed = restore_items(config, backup, &remote_base, &items, options, &mut report)?;
+    if failed == 0 && options.pin_images {
+        match images::pin(backup) {
+            Ok((path, unpinned)) => {
+                report(&format!("📌 Image digests pinned in {}", path.display()));
+                for service in unpinned {
+                    report(&format!(
+                        "⚠️ {}: no registry digest recorded, image left as is",
+                        service
+                    ));
                 }
+            }
+            Err(err) => {
+                report(&format!("⚠️ {:#}", err));
+                failed += 1;
+            }
+        }
+    }
+    // Started even after a failure, the items that failed still hold the previous data
+    if options.recreate {
+        failed += compose_up(&backup.application_path, &mut report);
+    }
+    failed += restore_dumps(
+        config,
+        &*transport::open(config),
+        backup,
+        &remote_base,
+        &dumps,
+        &mut report,
+    );
+    if failed > 0 {
+        report("⏭️ post_restore hook skipped, not every item was restored");
+        return Ok(failed);
+    }
+    let env = HookEnv {
+        project: &backup.name,
+        project_path: &backup.application_path.to_string_lossy(),
+        backup_path: &remote_base,
+        status: "success",
+    };
+    report("🪝 Running post_restore hook");
+    let hooked = hooks::run_with_output(config, Hook::PostRestore, &env, |line| {
+        report(&format!("   {}", line))
+    });
+    if let Err(err) = hooked {
+        report(&format!("⚠️ {:#}", err));
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+/// `items` sorted by the project's `restore_order`: listed items first, in that order, then
+/// the others as given.
+fn restore_order(config: &Config, project: &str, items: &[String]) -> Vec<String> {
+    let mut items = items.to_vec();
+    if let Some(order) = config.restore_order.get(project) {
+        items.sort_by_key(|item| {
+            order
+                .iter()
+                .position(|listed| listed == item)
+                .unwrap_or(order.len())
+        });
+    }
+    items
+}
+
+fn restore_items(
+    config: &Config,
+    backup: &BackupApplication,
+    remote_base: &str,
+    items: &[String],
+    options: &RestoreOptions,
+    mut report: impl FnMut(&str),
+) -> io::Result<usize> {
+    let transport = transport::open(config);
+    let mut items = items.to_vec();
+    let mut failed = 0;
+    // Images first, so a `post_restore` hook can start the project right away
+    if items.iter().any(|item| item == images::RESTORE_ITEM) {
+        items.retain(|item| item != images::RESTORE_ITEM);
+        failed += restore_images(config, &*transport, backup, remote_base, &mut report);
+    }
+    let mut safety = config
+        .restore_safety_snapshot
+        .then(|| SafetySnapshot::new(&backup.name, &backup.snapshot_folder()));
+
+    if backup.incremental {
+        let failed = restore_incremental(
+            &*transport,
+            backup,
+            remote_base,
+            &items,
+            options.on_conflict,
+            safety.as_mut(),
+            &mut report,
+        );
+        // Directories already moved aside have to be in the journal, even after an error
+        finish_safety(safety, &mut report);
+        return failed;
+    }
+
+    // Archives are decrypted in a directory only the restoring user can read, and the
+    // plaintext is deleted as soon as it is extracted
+    let work_dir = std::env::temp_dir().join(format!(
+        "dockup-restore-{}-{}",
+        backup.name,
+        std::process::id()
+    ));
+    fs::remove_dir_all(&work_dir).ok();
+    fs::DirBuilder::new().mode(0o700).create(&work_dir)?;
+
+    for (index, name) in items.iter().enumerate() {
+        if shutdown::requested() {
+            report(&format!(
+                "⏹️ Restore stopped, skipped {}",
+                items[index..].join(", ")
+            ));
+            failed += items.len() - index;
+            break;
+        }
+        if name == "REPO" {
+            report("🚧 Restoring Repo");
+            let merge = match resolve_conflict(
+                options.on_conflict,
+                backup,
+                name,
+                &backup.application_path,
+                &mut report,
+            ) {
+                OnConflict::Skip => continue,
+                resolution => resolution == OnConflict::MergeNewer,
+            };
+            let tarname = backup.repo_archive();
+            let remote = format!("{}/REPO/{}", remote_base, tarname);
+            let tmp = work_dir.join(&tarname);
+
+            // Download
+            report("⏬ Downloading repo");
+            if let Err(err) = fetch(config, &*transport, backup, &remote, &tmp) {
+                report(&format!("⚠️ failed scp repo: {}", err));
+                failed += 1;
+                continue;
+            }
+
+            let restored = if merge || !options.filter.is_empty() {
+                if !save_current(safety.as_mut(), &backup.application_path, true, &mut report) {
+                    fs::remove_file(&tmp).ok();
+                    failed += 1;
+                    continue;
+                }
+                report("📂 Extracting repo");
+                extract(&tmp, &backup.application_path, merge, &options.filter)
             } else {
-                self.restore_message
-                    .push(Line::from(format!("🚧 Restoring volume: {}", name)));
-                // Find Volume entry
-                if let Some(v) = backup.volumes.iter().find(|v| &v.name == &name) {
-                    // remote tarball path uses underscores for slashes
-                    let tarname = format!("{}.tar.gz", v.path.to_string_lossy().replace('/', "_"));
-                    let remote = format!("{}/VOLUMES/{}", remote_base, tarname);
-                    let tmp = std::env::temp_dir().join(&tarname);
-
-                    self.restore_message
-                        .push(Line::from(format!("⏬ Downloading {}", name)));
-
-                    let output = Command::new("scp")
-                        .args(&[
-                            "-i",
-                            &self.config.ssh_key,
-                            "-P",
-                            &self.config.ssh_port.to_string(),
-                            &format!(
-                                "{}@{}:{}",
-                                self.config.ssh_user, self.config.ssh_host, remote
-                            ),
-                            tmp.to_str().unwrap(),
-                        ])
-                        .stdout(Stdio::null())
-                        .stderr(Stdio::piped())
-                        .output()?;
-                    if !output.status.success() {
-                        let err = String::from_utf8_lossy(&output.stderr);
-                        self.restore_message
-                            .push(Line::from(format!("⚠️ failed scp {}: {}", name, err)));
-                        continue;
-                    }
+                extract_staged(
+                    &tmp,
+                    &backup.application_path,
+                    "repo",
+                    false,
+                    &RestoreOptions::default(),
+                    safety.as_mut(),
+                    &mut report,
+                )
+            };
+            fs::remove_file(&tmp).ok();
+            match restored {
+                Ok(true) => report("✅ repo restored"),
+                Ok(false) => {
+                    report("⚠️ repo extract failed");
+                    failed += 1;
+                }
+                Err(err) => {
+                    report(&format!("⚠️ repo extract failed: {}", err));
+                    failed += 1;
+                }
+            }
+        } else {
+            report(&format!("🚧 Restoring volume: {}", name));
+            // Find Volume entry
+            let Some(v) = backup.volumes.iter().find(|v| &v.name == name) else {
+                report(&format!("⚠️ unknown volume {}", name));
+                failed += 1;
+                continue;
+            };
+            let merge = match resolve_conflict(
+                options.on_conflict,
+                backup,
+                name,
+                v.restore_path(),
+                &mut report,
+            ) {
+                OnConflict::Skip => continue,
+                resolution => resolution == OnConflict::MergeNewer,
+            };
+            let docker_volume = matches!(v.volume_type, VolumeType::Mount);
+            if docker_volume && (merge || !options.filter.is_empty()) {
+                report(&format!(
+                    "⚠️ {}: merging and --include/--exclude are not supported for Docker volumes",
+                    name
+                ));
+                failed += 1;
+                continue;
+            }
+            let (tmp, fetched) = if backup.dedup {
+                let manifest = format!(
+                    "{}/VOLUMES/{}{}",
+                    remote_base,
+                    backup.volume_dir(v),
+                    dedup::MANIFEST_SUFFIX
+                );
+                let tmp = work_dir.join(format!("{}.tar", backup.volume_dir(v)));
+                report(&format!("⏬ Downloading chunks of {}", name));
+                let fetched = dedup::restore(config, &*transport, &manifest, &tmp);
+                (tmp, fetched)
+            } else {
+                let tarname = backup.volume_archive(v);
+                let remote = format!("{}/VOLUMES/{}", remote_base, tarname);
+                let tmp = work_dir.join(&tarname);
+                report(&format!("⏬ Downloading {}", name));
+                let fetched = fetch(config, &*transport, backup, &remote, &tmp);
+                (tmp, fetched)
+            };
+            if let Err(err) = fetched {
+                report(&format!("⚠️ failed scp {}: {}", name, err));
+                failed += 1;
+                continue;
+            }
 
-                    self.restore_message
-                        .push(Line::from(format!("📂 Extracting {}", name)));
-
-                    // destroy and recreate target
-                    let dest = &v.path;
-                    fs::remove_dir_all(dest).ok();
-                    fs::create_dir_all(dest)?;
-                    // extract
-                    let status = Command::new("tar")
-                        .args(&["-xzf", tmp.to_str().unwrap(), "-C", dest.to_str().unwrap()])
-                        .stdout(Stdio::null())
-                        .stderr(Stdio::piped())
-                        .status()?;
-                    if status.success() {
-                        self.restore_message
-                            .push(Line::from(format!("✅ {}", name)));
-                    } else {
-                        self.restore_message
-                            .push(Line::from(format!("⚠️ extract {}", name)));
+            if docker_volume {
+                let volume = v.docker_volume(&backup.name);
+                report(&format!(
+                    "📂 Extracting {} into Docker volume {}",
+                    name, volume
+                ));
+                let extracted = extract_docker_volume(
+                    &tmp,
+                    &volume,
+                    v.driver.as_ref(),
+                    options.validate.as_deref(),
+                    &mut report,
+                );
+                fs::remove_file(&tmp).ok();
+                match extracted {
+                    Ok(()) => report(&format!("✅ {}", name)),
+                    Err(err) => {
+                        report(&format!("⚠️ extract {}: {:#}", name, err));
+                        failed += 1;
                     }
                 }
+                continue;
+            }
+
+            let restored = if options.validate.is_some() || (!merge && options.filter.is_empty()) {
+                extract_staged(
+                    &tmp,
+                    v.restore_path(),
+                    name,
+                    merge,
+                    options,
+                    safety.as_mut(),
+                    &mut report,
+                )
+            } else {
+                if !save_current(safety.as_mut(), v.restore_path(), true, &mut report) {
+                    fs::remove_file(&tmp).ok();
+                    failed += 1;
+                    continue;
+                }
+                report(&format!("📂 Extracting {}", name));
+                let extracted = extract(&tmp, v.restore_path(), merge, &options.filter);
+                if let Ok(false) = extracted {
+                    report(&format!("⚠️ extract {}", name));
+                }
+                extracted
+            };
+            fs::remove_file(&tmp).ok();
+            match restored {
+                Ok(true) => report(&format!("✅ {}", name)),
+                Ok(false) => failed += 1,
+                Err(err) => {
+                    report(&format!("⚠️ extract {}: {}", name, err));
+                    failed += 1;
+                }
             }
         }
+    }
+    fs::remove_dir_all(&work_dir).ok();
+    finish_safety(safety, &mut report);
+    Ok(failed)
+}
 
-        // keep popup visible so user sees the messages
-        Ok(())
+/// Seconds `restore --recreate` waits for the services to become healthy.
+const RECREATE_WAIT_SECS: u32 = 300;
+
+/// `docker compose down` for the project in `dir`, if it has a compose file yet.
+fn compose_down(dir: &Path) -> anyhow::Result<()> {
+    if scanner::compose_file(dir).is_none() {
+        return Ok(());
+    }
+    let output = Command::new("docker")
+        .args(["compose", "down"])
+        .current_dir(dir)
+        .output()
+        .context("Failed to run docker compose down")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "docker compose down failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Starts the project in `dir` with `docker compose up -d --wait` and reports the state
+/// of every service. Uses [`images::PIN_FILE`] if the project has one. Returns 1 if the
+/// project didn't come up healthy.
+fn compose_up(dir: &Path, report: &mut impl FnMut(&str)) -> usize {
+    let mut up = Command::new("docker");
+    up.arg("compose");
+    if dir.join(images::PIN_FILE).exists() {
+        for file in scanner::compose_files(dir) {
+            up.arg("-f").arg(file);
+        }
+        up.args(["-f", images::PIN_FILE]);
+    }
+    report("▶️ Starting the project, waiting for healthchecks");
+    let started = up
+        .args(["up", "-d", "--wait", "--wait-timeout"])
+        .arg(RECREATE_WAIT_SECS.to_string())
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output();
+    let healthy = match started {
+        Ok(output) if output.status.success() => true,
+        Ok(output) => {
+            report(&format!(
+                "⚠️ docker compose up failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+            false
+        }
+        Err(err) => {
+            report(&format!("⚠️ failed to run docker compose up: {}", err));
+            false
+        }
+    };
+    for (service, state, health) in service_states(dir) {
+        let ready = state == "running" && matches!(health.as_str(), "" | "healthy");
+        let health = if health.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", health)
+        };
+        let icon = if ready { "✅" } else { "⚠️" };
+        report(&format!("{} {}: {}{}", icon, service, state, health));
+    }
+    if healthy {
+        report("✅ Project running");
+        0
+    } else {
+        1
+    }
+}
+
+/// Service, state and health of every container of the project in `dir`.
+fn service_states(dir: &Path) -> Vec<(String, String, String)> {
+    let Ok(output) = Command::new("docker")
+        .args(["compose", "ps", "--all", "--format", "json"])
+        .current_dir(dir)
+        .stderr(Stdio::null())
+        .output()
+    else {
+        return Vec::new();
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Older compose versions print an array, newer ones a line per container
+    let containers: Vec<serde_json::Value> = serde_json::from_str(&stdout).unwrap_or_else(|_| {
+        stdout
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    });
+    let field = |container: &serde_json::Value, key: &str| {
+        container[key].as_str().unwrap_or_default().to_string()
+    };
+    let mut states: Vec<_> = containers
+        .iter()
+        .map(|c| (field(c, "Service"), field(c, "State"), field(c, "Health")))
+        .collect();
+    states.sort();
+    states
+}
+
+/// Downloads the saved images of the snapshot and loads them with `docker load`. Returns
+/// the number of images that failed.
+fn restore_images(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    backup: &BackupApplication,
+    remote_base: &str,
+    mut report: impl FnMut(&str),
+) -> usize {
+    let mut archives: Vec<&String> = backup
+        .images
+        .iter()
+        .filter_map(|image| image.archive.as_ref())
+        .collect();
+    archives.sort();
+    archives.dedup();
+    let mut failed = 0;
+    for archive in archives {
+        report(&format!("⏬ Downloading image {}", archive));
+        let remote = format!("{}/IMAGES/{}", remote_base, archive);
+        let tmp = std::env::temp_dir().join(format!("dockup-{}-{}", backup.name, archive));
+        let loaded = fetch(config, transport, backup, &remote, &tmp).and_then(|()| {
+            report(&format!("📥 Loading image {}", archive));
+            images::load(&tmp)
+        });
+        fs::remove_file(&tmp).ok();
+        match loaded {
+            Ok(()) => report(&format!("✅ {}", archive)),
+            Err(err) => {
+                report(&format!("⚠️ image {}: {:#}", archive, err));
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+/// Downloads the dumps of `items` (`DB:<service>`) and loads them into their services.
+/// Returns the number of dumps that failed.
+fn restore_dumps(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    backup: &BackupApplication,
+    remote_base: &str,
+    items: &[String],
+    mut report: impl FnMut(&str),
+) -> usize {
+    let mut failed = 0;
+    for item in items {
+        let service = item.trim_start_matches(dbdump::RESTORE_PREFIX);
+        report(&format!("🚧 Restoring database: {}", service));
+        let dump = dbdump::dump_name(service, backup.compression.as_deref().unwrap_or("gzip"));
+        let remote = format!("{}/DUMPS/{}", remote_base, dump);
+        let tmp = std::env::temp_dir().join(format!("dockup-{}-{}", backup.name, dump));
+        report(&format!("⏬ Downloading dump of {}", service));
+        let loaded = fetch(config, transport, backup, &remote, &tmp).and_then(|()| {
+            report(&format!("📥 Loading dump into {}", service));
+            dbdump::load(backup, service, &tmp)
+        });
+        fs::remove_file(&tmp).ok();
+        match loaded {
+            Ok(()) => report(&format!("✅ {}", service)),
+            Err(err) => {
+                report(&format!("⚠️ database {}: {:#}", service, err));
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+/// Warns if files in `dest` were modified after `backup` was taken and decides how to
+/// restore `name` over them. Without such files the item is simply overwritten.
+fn resolve_conflict(
+    on_conflict: OnConflict,
+    backup: &BackupApplication,
+    name: &str,
+    dest: &Path,
+    report: &mut impl FnMut(&str),
+) -> OnConflict {
+    let (newer, total) = count_newer(dest, backup.timestamp.into());
+    if newer == 0 {
+        return OnConflict::Overwrite;
+    }
+    report(&format!(
+        "⚠️ {} of {} files in {} are newer than the snapshot",
+        newer,
+        total,
+        dest.display()
+    ));
+    let resolution = match on_conflict {
+        OnConflict::Ask if io::stdin().is_terminal() => ask_conflict(name),
+        OnConflict::Ask => OnConflict::Overwrite,
+        resolution => resolution,
+    };
+    match resolution {
+        OnConflict::Skip => report(&format!("⏭️ Skipping {}", name)),
+        OnConflict::MergeNewer => report(&format!("🔀 Keeping newer files of {}", name)),
+        _ => {}
+    }
+    resolution
+}
+
+/// Counts the files below `dir` modified after `since`, and all files.
+fn count_newer(dir: &Path, since: SystemTime) -> (usize, usize) {
+    let mut newer = 0;
+    let mut total = 0;
+    for entry in walkdir::WalkDir::new(dir).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        total += 1;
+        if entry
+            .metadata()
+            .ok()
+            .and_then(|meta| meta.modified().ok())
+            .is_some_and(|modified| modified > since)
+        {
+            newer += 1;
+        }
+    }
+    (newer, total)
+}
+
+fn ask_conflict(name: &str) -> OnConflict {
+    loop {
+        print!("{}: [o]verwrite, [s]kip or [m]erge newer files? ", name);
+        io::stdout().flush().ok();
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            return OnConflict::Skip;
+        }
+        match input.trim().to_lowercase().as_str() {
+            "o" | "overwrite" => return OnConflict::Overwrite,
+            "s" | "skip" => return OnConflict::Skip,
+            "m" | "merge" => return OnConflict::MergeNewer,
+            _ => {}
+        }
+    }
+}
+
+/// Moves the live data at `dest` aside before it is replaced (copies it for a `merge`).
+/// Returns false if that failed, the item is then not restored.
+fn save_current(
+    safety: Option<&mut SafetySnapshot>,
+    dest: &Path,
+    merge: bool,
+    report: &mut impl FnMut(&str),
+) -> bool {
+    let Some(safety) = safety else {
+        return true;
+    };
+    match safety.take(dest, merge) {
+        Ok(()) => true,
+        Err(err) => {
+            report(&format!("⚠️ {:#}", err));
+            false
+        }
+    }
+}
+
+fn finish_safety(safety: Option<SafetySnapshot>, mut report: impl FnMut(&str)) {
+    if let Some(safety) = safety {
+        match safety.commit() {
+            Ok(()) => report("🛟 Previous state kept, revert with `dockup restore --undo`"),
+            Err(err) => report(&format!("⚠️ failed to record safety snapshot: {}", err)),
+        }
+    }
+}
+
+/// Restores items of an incremental snapshot by mirroring their directories back with rsync.
+fn restore_incremental(
+    transport: &dyn StorageBackend,
+    backup: &BackupApplication,
+    remote_base: &str,
+    items: &[String],
+    on_conflict: OnConflict,
+    mut safety: Option<&mut SafetySnapshot>,
+    report: &mut impl FnMut(&str),
+) -> io::Result<usize> {
+    let mut failed = 0;
+    for name in items {
+        let (remote, dest) = if name == "REPO" {
+            (
+                format!("{}/REPO", remote_base),
+                backup.application_path.clone(),
+            )
+        } else {
+            let Some(v) = backup.volumes.iter().find(|v| &v.name == name) else {
+                report(&format!("⚠️ unknown volume {}", name));
+                failed += 1;
+                continue;
+            };
+            (
+                format!("{}/VOLUMES/{}", remote_base, backup.volume_dir(v)),
+                v.restore_path().clone(),
+            )
+        };
+        let merge = match resolve_conflict(on_conflict, backup, name, &dest, report) {
+            OnConflict::Skip => continue,
+            resolution => resolution == OnConflict::MergeNewer,
+        };
+        if !save_current(safety.as_deref_mut(), &dest, merge, report) {
+            failed += 1;
+            continue;
+        }
+        report(&format!("⏬ Syncing {}", name));
+        fs::create_dir_all(&dest)?;
+        match transport.fetch_dir(&remote, &dest, merge) {
+            Ok(()) => report(&format!("✅ {}", name)),
+            Err(err) => {
+                report(&format!("⚠️ sync {}: {}", name, err));
+                failed += 1;
+            }
+        }
+    }
+    Ok(failed)
+}
+
+/// Downloads an archive to `local`, decrypting it first if the snapshot is encrypted.
+fn fetch(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    backup: &BackupApplication,
+    remote: &str,
+    local: &Path,
+) -> anyhow::Result<()> {
+    let limit = config.restore_rate_limit;
+    if !backup.encrypted {
+        return transport.download(remote, local, limit);
+    }
+    let method = backup.encryption_method.as_deref().unwrap_or("age");
+    let suffix = crypto::suffix(method);
+    let mut encrypted = local.as_os_str().to_owned();
+    encrypted.push(suffix);
+    let encrypted = PathBuf::from(encrypted);
+    transport.download(&format!("{}{}", remote, suffix), &encrypted, limit)?;
+    crypto::open(config, method, &encrypted, local)?;
+    fs::remove_file(&encrypted).ok();
+    Ok(())
+}
+
+/// Extracts the tarball of `name` to a staging directory next to `dest`, runs the
+/// `validate` command on it if one is given and only swaps it into place if everything
+/// succeeded. The live data is not touched until then and is kept as `<dest>.bak`, unless
+/// the safety snapshot took it. Returns whether the item was restored.
+fn extract_staged(
+    tarball: &Path,
+    dest: &Path,
+    name: &str,
+    merge: bool,
+    options: &RestoreOptions,
+    safety: Option<&mut SafetySnapshot>,
+    report: &mut impl FnMut(&str),
+) -> io::Result<bool> {
+    let mut staging = dest.as_os_str().to_owned();
+    staging.push(".restore-staging");
+    let staging = PathBuf::from(staging);
+    fs::remove_dir_all(&staging).ok();
+
+    // A partial restore keeps the rest of the live data, so it has to be in the staging copy
+    if (merge || !options.filter.is_empty()) && dest.exists() {
+        let copied = Command::new("cp")
+            .arg("-a")
+            .arg(dest)
+            .arg(&staging)
+            .status()?;
+        if !copied.success() {
+            report(&format!("⚠️ failed to copy {} for validation", name));
+            return Ok(false);
+        }
+    }
+    report(&format!("📂 Extracting {} to {}", name, staging.display()));
+    if !extract(tarball, &staging, merge, &options.filter)? {
+        report(&format!("⚠️ extract {}", name));
+        fs::remove_dir_all(&staging).ok();
+        return Ok(false);
+    }
+
+    if let Some(validate) = &options.validate {
+        report(&format!("🔍 Validating {}", name));
+        let valid = Command::new("sh")
+            .args(["-c", validate])
+            .env("DOCKUP_RESTORE_PATH", &staging)
+            .env("DOCKUP_TARGET_PATH", dest)
+            .env("DOCKUP_VOLUME", name)
+            .stdout(Stdio::null())
+            .status()?;
+        if !valid.success() {
+            report(&format!(
+                "⚠️ validation of {} failed, live data left untouched, restored copy kept at {}",
+                name,
+                staging.display()
+            ));
+            return Ok(false);
+        }
+    }
+
+    if !save_current(safety, dest, false, report) {
+        fs::remove_dir_all(&staging).ok();
+        return Ok(false);
+    }
+    // Only still there without a safety snapshot
+    if dest.exists() {
+        let mut previous = dest.as_os_str().to_owned();
+        previous.push(".bak");
+        let previous = PathBuf::from(previous);
+        fs::remove_dir_all(&previous).ok();
+        fs::rename(dest, &previous)?;
+        report(&format!(
+            "🗄  Previous {} kept at {}",
+            name,
+            previous.display()
+        ));
+    }
+    fs::rename(&staging, dest)?;
+    Ok(true)
+}
+
+/// Extracts `tarball` into the Docker volume `volume` through a helper container, the way
+/// the backup reads it. The archive goes to the staging volume `<volume>-restore-staging`
+/// first and the `validate` command runs against it, so a broken archive or a failed
+/// validation leaves the live volume untouched. Only then is the live volume (created with
+/// its recorded `driver` if missing) emptied and the staging copy copied into it. There is
+/// no safety snapshot, `restore --undo` can't revert it.
+fn extract_docker_volume(
+    tarball: &Path,
+    volume: &str,
+    driver: Option<&VolumeDriver>,
+    validate: Option<&str>,
+    report: &mut impl FnMut(&str),
+) -> anyhow::Result<()> {
+    let staging = format!("{}-restore-staging", volume);
+    remove_docker_volume(&staging);
+    create_docker_volume(&staging, None)?;
+
+    // Decompress on the host, the helper image's tar may not know the algorithm
+    let mut decompress = dbdump::decompress(tarball)?;
+    let stdout = decompress.stdout.take().expect("stdout is piped");
+    let extracted = helper_container(&[(&staging, "/data")], "tar -xf - -C /data")
+        .stdin(stdout)
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to run the helper container")?;
+    let decompressed = decompress.wait()?;
+    if !decompressed.success() || !extracted.success() {
+        remove_docker_volume(&staging);
+        anyhow::bail!("Failed to extract, Docker volume {} left untouched", volume);
+    }
+
+    if let Some(validate) = validate {
+        report(&format!("🔍 Validating {}", volume));
+        // Volume names work wherever the command mounts the path, e.g. `docker run -v`
+        let valid = Command::new("sh")
+            .args(["-c", validate])
+            .env("DOCKUP_RESTORE_PATH", &staging)
+            .env("DOCKUP_TARGET_PATH", volume)
+            .env("DOCKUP_VOLUME", volume)
+            .stdout(Stdio::null())
+            .status()?;
+        if !valid.success() {
+            anyhow::bail!(
+                "Validation failed, Docker volume {} left untouched, restored copy kept in {}",
+                volume,
+                staging
+            );
+        }
+    }
+
+    let exists = Command::new("docker")
+        .args(["volume", "inspect", volume])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !exists {
+        create_docker_volume(volume, driver)?;
+    }
+    let copied = helper_container(
+        &[(&staging, "/staging:ro"), (volume, "/data")],
+        "find /data -mindepth 1 -delete && cp -a /staging/. /data/",
+    )
+    .stdout(Stdio::null())
+    .status()
+    .context("Failed to run the helper container")?;
+    if !copied.success() {
+        anyhow::bail!(
+            "Failed to copy the restored data into Docker volume {}, it is kept in {}",
+            volume,
+            staging
+        );
+    }
+    remove_docker_volume(&staging);
+    Ok(())
+}
+
+/// `docker volume create` with the recorded `driver` and its options, if any.
+fn create_docker_volume(volume: &str, driver: Option<&VolumeDriver>) -> anyhow::Result<()> {
+    let mut create = Command::new("docker");
+    create.args(["volume", "create"]);
+    if let Some(driver) = driver {
+        create.args(["--driver", &driver.name]);
+        for (key, value) in &driver.options {
+            create.args(["--opt", &format!("{}={}", key, value)]);
+        }
+    }
+    let created = create
+        .arg(volume)
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to run docker volume create")?;
+    if !created.success() {
+        anyhow::bail!("Failed to create Docker volume {}", volume);
+    }
+    Ok(())
+}
+
+fn remove_docker_volume(volume: &str) {
+    Command::new("docker")
+        .args(["volume", "rm", "-f", volume])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok();
+}
+
+/// `alpine` helper container with the Docker volumes `mounts` (volume, mount point) running
+/// `script`, labeled so a shutdown can stop it.
+fn helper_container(mounts: &[(&str, &str)], script: &str) -> Command {
+    let mut run = Command::new("docker");
+    run.args(["run", "--rm", "-i", "--label", shutdown::HELPER_LABEL]);
+    for (volume, target) in mounts {
+        run.arg("-v").arg(format!("{}:{}", volume, target));
+    }
+    run.args(["alpine", "sh", "-c", script]);
+    run
+}
+
+/// Extracts the tarball into `dest`. With `merge` files that are newer than their archived
+/// copy are kept. A `filter` only extracts the matching paths. Replacing the live data is
+/// left to [`extract_staged`], this never deletes anything.
+fn extract(tarball: &Path, dest: &Path, merge: bool, filter: &ExtractFilter) -> io::Result<bool> {
+    fs::create_dir_all(dest)?;
+    let mut tar = Command::new("tar");
+    tar.arg("-x");
+    if merge {
+        tar.arg("--keep-newer-files");
+    }
+    if let Some(flag) = compression::extract_flag(tarball) {
+        tar.arg(flag);
+    }
+    tar.args([
+        "-f",
+        tarball.to_str().unwrap(),
+        "-C",
+        dest.to_str().unwrap(),
+    ]);
+    filter.apply(&mut tar);
+    let status = tar.stdout(Stdio::null()).stderr(Stdio::piped()).status()?;
+    Ok(status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::RawConfig,
+        rollback::UndoJournal,
+        testing::{with_home, TempDir},
+    };
+
+    fn config(root: &Path) -> Config {
+        let raw: RawConfig = serde_json::from_value(serde_json::json!({
+            "docker_parent": [],
+            "remote_backup_path": root,
+            "ssh_user": "",
+            "ssh_host": "",
+            "ssh_key": "",
+            "ssh_port": 22,
+            "email_host": "",
+            "email_port": 465,
+            "email_user": "",
+            "email_password": "",
+            "receiver_mail": "",
+            "interval": { "hour": 0, "day": 1, "week": 0, "month": 0, "year": 0 },
+            "storage_backend": "local",
+        }))
+        .unwrap();
+        raw.finalize().unwrap()
+    }
+
+    /// Temp dir with a tarball of `files`, archived from `.` like the backup does.
+    fn archive(name: &str, files: &[(&str, &str)]) -> (TempDir, PathBuf) {
+        let root = TempDir::new(name);
+        let src = root.join("src");
+        for (path, content) in files {
+            let path = src.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let tarball = root.join("volume.tar");
+        let status = Command::new("tar")
+            .arg("-cf")
+            .arg(&tarball)
+            .arg("-C")
+            .arg(&src)
+            .arg(".")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        (root, tarball)
+    }
+
+    fn extract_into(tarball: &Path, dest: &Path, options: &RestoreOptions) -> bool {
+        extract_staged(tarball, dest, "data", false, options, None, &mut |_| {}).unwrap()
+    }
+
+    #[test]
+    fn extracts_only_included_paths() {
+        let (root, tarball) = archive("include", &[("config/app.yml", "new"), ("data/db", "new")]);
+        let dest = root.join("dest");
+        let filter = ExtractFilter {
+            include: vec!["config/*".to_string()],
+            exclude: Vec::new(),
+        };
+        assert!(extract(&tarball, &dest, false, &filter).unwrap());
+        assert!(dest.join("config/app.yml").exists());
+        assert!(!dest.join("data").exists());
+    }
+
+    #[test]
+    fn leaves_out_excluded_paths() {
+        let (root, tarball) = archive(
+            "exclude",
+            &[("app.log", "new"), ("logs/old.log", "new"), ("db", "new")],
+        );
+        let dest = root.join("dest");
+        let filter = ExtractFilter {
+            include: Vec::new(),
+            exclude: vec!["*.log".to_string()],
+        };
+        assert!(extract(&tarball, &dest, false, &filter).unwrap());
+        assert!(dest.join("db").exists());
+        assert!(!dest.join("app.log").exists());
+        assert!(!dest.join("logs/old.log").exists());
+    }
+
+    #[test]
+    fn swaps_in_the_restored_copy_and_keeps_the_old_one() {
+        let (root, tarball) = archive("staged", &[("db", "new")]);
+        let dest = root.join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("db"), "old").unwrap();
+        fs::write(dest.join("stray"), "old").unwrap();
+
+        assert!(extract_into(&tarball, &dest, &RestoreOptions::default()));
+        assert_eq!(fs::read_to_string(dest.join("db")).unwrap(), "new");
+        assert!(!dest.join("stray").exists());
+        let previous = root.join("dest.bak");
+        assert_eq!(fs::read_to_string(previous.join("db")).unwrap(), "old");
+        assert!(previous.join("stray").exists());
+        assert!(!root.join("dest.restore-staging").exists());
+    }
+
+    #[test]
+    fn partial_restores_keep_the_rest_of_the_live_data() {
+        let (root, tarball) = archive("partial", &[("config/app.yml", "new"), ("db", "new")]);
+        let dest = root.join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("db"), "old").unwrap();
+        let options = RestoreOptions {
+            filter: ExtractFilter {
+                include: vec!["config/*".to_string()],
+                exclude: Vec::new(),
+            },
+            ..Default::default()
+        };
+
+        assert!(extract_into(&tarball, &dest, &options));
+        assert_eq!(
+            fs::read_to_string(dest.join("config/app.yml")).unwrap(),
+            "new"
+        );
+        assert_eq!(fs::read_to_string(dest.join("db")).unwrap(), "old");
+    }
+
+    #[test]
+    fn failed_validation_leaves_the_live_data_alone() {
+        let (root, tarball) = archive("invalid", &[("db", "new")]);
+        let dest = root.join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("db"), "old").unwrap();
+        let options = RestoreOptions {
+            validate: Some("test -f \"$DOCKUP_RESTORE_PATH/missing\"".to_string()),
+            ..Default::default()
+        };
+
+        assert!(!extract_into(&tarball, &dest, &options));
+        assert_eq!(fs::read_to_string(dest.join("db")).unwrap(), "old");
+        assert!(!root.join("dest.bak").exists());
+        let staging = root.join("dest.restore-staging");
+        assert_eq!(fs::read_to_string(staging.join("db")).unwrap(), "new");
+    }
+
+    /// Snapshot `name` on a local backup target in `root` holding `tarball` as the archive of
+    /// the volume `<root>/data`, whose live copy has an old `db`. Returns the remote base.
+    fn stored_volume(
+        root: &Path,
+        name: &str,
+        tarball: &Path,
+    ) -> (Config, BackupApplication, String) {
+        let dest = root.join("data");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("db"), "old").unwrap();
+        let backups = root.join("backups");
+        let backup: BackupApplication = serde_json::from_value(serde_json::json!({
+            "name": name,
+            "timestamp": Local::now(),
+            "application_path": root.join(name),
+            "volumes": [{ "name": "data", "path": dest, "volume_type": "Bind", "compression": "none" }],
+        }))
+        .unwrap();
+        let remote_base = backups.join(name).join("snap");
+        fs::create_dir_all(remote_base.join("VOLUMES")).unwrap();
+        let archive_name = backup.volume_archive(&backup.volumes[0]);
+        fs::copy(tarball, remote_base.join("VOLUMES").join(archive_name)).unwrap();
+        (
+            config(&backups),
+            backup,
+            remote_base.to_string_lossy().to_string(),
+        )
+    }
+
+    #[test]
+    fn a_failed_swap_still_records_the_safety_snapshot() {
+        let (root, tarball) = archive("undo", &[("db", "new")]);
+        let (config, backup, remote_base) = stored_volume(&root, "undo", &tarball);
+        // Succeeds but takes the staging copy away, so the final rename fails after the
+        // live data was moved aside
+        let options = RestoreOptions {
+            validate: Some("rm -rf \"$DOCKUP_RESTORE_PATH\"".to_string()),
+            ..Default::default()
+        };
+
+        let (failed, journal) = with_home(&root, || {
+            let failed = restore_items(
+                &config,
+                &backup,
+                &remote_base,
+                &["data".to_string()],
+                &options,
+                |_| {},
+            );
+            (failed, UndoJournal::load())
+        });
+        assert_eq!(failed.unwrap(), 1);
+        let run = journal.runs.last().unwrap();
+        assert_eq!(run.entries.len(), 1);
+        assert_eq!(run.entries[0].path, root.join("data"));
+        assert_eq!(
+            fs::read_to_string(run.entries[0].saved.join("db")).unwrap(),
+            "old"
+        );
+    }
+
+    #[test]
+    fn restores_leave_no_archives_behind() {
+        let (root, tarball) = archive("cleanup", &[("db", "new")]);
+        let (config, backup, remote_base) = stored_volume(&root, "cleanup", &tarball);
+        let work_dir =
+            std::env::temp_dir().join(format!("dockup-restore-cleanup-{}", std::process::id()));
+
+        let failed = with_home(&root, || {
+            restore_items(
+                &config,
+                &backup,
+                &remote_base,
+                &["data".to_string()],
+                &RestoreOptions::default(),
+                |_| {},
+            )
+        });
+        assert_eq!(failed.unwrap(), 0);
+        assert_eq!(fs::read_to_string(root.join("data/db")).unwrap(), "new");
+        assert!(!work_dir.exists());
     }
 }
@@ -0,0 +1,184 @@
+//! Safety snapshots taken before a restore overwrites live data.
+//!
+//! Each restored directory is renamed to `<dir>.pre-restore-<timestamp>` next to it (instant,
+//! no copy) before the backup is extracted, or copied there when the restore merges. The
+//! snapshots of a restore are recorded in `~/.dockup/restore_undo.json`, `dockup restore
+//! --undo` swaps them back. Only the newest safety snapshot of a directory is kept, older
+//! ones are deleted when it is restored again.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Serialize, Deserialize)]
+pub struct UndoEntry {
+    /// Directory that was restored.
+    pub path: PathBuf,
+    /// Its previous contents.
+    pub saved: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UndoRun {
+    pub project: String,
+    pub snapshot: String,
+    pub restored: DateTime<Local>,
+    pub entries: Vec<UndoEntry>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct UndoJournal {
+    pub runs: Vec<UndoRun>,
+}
+
+impl UndoJournal {
+    fn path() -> Option<PathBuf> {
+        Some(dirs::home_dir()?.join(".dockup").join("restore_undo.json"))
+    }
+
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().context("No home directory")?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Collects the safety snapshots of one restore.
+pub struct SafetySnapshot {
+    run: UndoRun,
+}
+
+impl SafetySnapshot {
+    pub fn new(project: &str, snapshot: &str) -> Self {
+        Self {
+            run: UndoRun {
+                project: project.to_string(),
+                snapshot: snapshot.to_string(),
+                restored: Local::now(),
+                entries: Vec::new(),
+            },
+        }
+    }
+
+    /// Moves `dest` aside so it can be restored into, or copies it with `copy` when the
+    /// restore merges into the live data. Nothing to do if it doesn't exist.
+    pub fn take(&mut self, dest: &Path, copy: bool) -> Result<()> {
+        if !dest.exists() {
+            return Ok(());
+        }
+        let mut saved = dest.as_os_str().to_owned();
+        saved.push(format!(
+            ".pre-restore-{}",
+            self.run.restored.format("%Y%m%d%H%M%S")
+        ));
+        let saved = PathBuf::from(saved);
+        if copy {
+            let status = Command::new("cp")
+                .arg("-a")
+                .arg(dest)
+                .arg(&saved)
+                .status()
+                .context("Failed to run cp")?;
+            if !status.success() {
+                anyhow::bail!("Failed to copy {:?} before restoring", dest);
+            }
+        } else {
+            fs::rename(dest, &saved)
+                .with_context(|| format!("Failed to move {:?} aside before restoring", dest))?;
+        }
+        log::info!("🛟 Previous state saved to {}", saved.display());
+        self.run.entries.push(UndoEntry {
+            path: dest.to_path_buf(),
+            saved,
+        });
+        Ok(())
+    }
+
+    /// Records the snapshots in the undo journal, dropping older ones of the same paths.
+    pub fn commit(self) -> Result<()> {
+        if self.run.entries.is_empty() {
+            return Ok(());
+        }
+        let mut journal = UndoJournal::load();
+        for run in &mut journal.runs {
+            run.entries.retain(|entry| {
+                let replaced = self.run.entries.iter().any(|new| new.path == entry.path);
+                if replaced {
+                    fs::remove_dir_all(&entry.saved).ok();
+                }
+                !replaced
+            });
+        }
+        journal.runs.retain(|run| !run.entries.is_empty());
+        journal.runs.push(self.run);
+        journal.save()
+    }
+}
+
+/// Reverts the latest restore (of `project`, if given) by swapping the safety snapshots back.
+pub fn undo(project: Option<&str>) -> Result<()> {
+    let mut journal = UndoJournal::load();
+    let index = journal
+        .runs
+        .iter()
+        .rposition(|run| project.is_none_or(|project| run.project == project))
+        .context("No restore to undo")?;
+    let run = journal.runs.remove(index);
+    log::info!(
+        "↩️  Undoing restore of {} from {} ({})",
+        run.project,
+        run.snapshot,
+        run.restored.format("%Y-%m-%d %H:%M")
+    );
+    let mut failed = Vec::new();
+    for entry in run.entries {
+        if !entry.saved.exists() {
+            log::error!("❌ Safety snapshot {} is gone", entry.saved.display());
+            continue;
+        }
+        let result = fs::remove_dir_all(&entry.path)
+            .or_else(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            })
+            .and_then(|_| fs::rename(&entry.saved, &entry.path));
+        match result {
+            Ok(()) => log::info!("✅ Reverted {}", entry.path.display()),
+            Err(e) => {
+                log::error!("❌ Failed to revert {}: {e}", entry.path.display());
+                failed.push(entry);
+            }
+        }
+    }
+    let count = failed.len();
+    if !failed.is_empty() {
+        // Keep what could not be reverted so the undo can be retried
+        journal.runs.insert(
+            index,
+            UndoRun {
+                project: run.project,
+                snapshot: run.snapshot,
+                restored: run.restored,
+                entries: failed,
+            },
+        );
+    }
+    journal.save()?;
+    if count > 0 {
+        anyhow::bail!("{} directories could not be reverted", count);
+    }
+    Ok(())
+}
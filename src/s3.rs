@@ -0,0 +1,248 @@
+//! S3-compatible object storage (AWS S3, MinIO, Wasabi, ...) through the `aws` CLI.
+//!
+//! Remote paths map to object keys inside `s3.bucket`, so `remote_backup_path` acts as
+//! key prefix. Folders don't exist in S3, `mkdir` is a no-op.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+use crate::{
+    config::Config,
+    transport::{StorageBackend, Throttle},
+};
+
+pub struct S3Backend<'a> {
+    cfg: &'a Config,
+}
+
+impl<'a> S3Backend<'a> {
+    pub fn new(cfg: &'a Config) -> Self {
+        Self { cfg }
+    }
+
+    fn uri(&self, path: &str) -> String {
+        format!("s3://{}/{}", self.cfg.s3.bucket, key(path))
+    }
+
+    fn aws(&self) -> Command {
+        let s3 = &self.cfg.s3;
+        let mut cmd = Command::new("aws");
+        if let Some(endpoint) = &s3.endpoint {
+            cmd.arg("--endpoint-url").arg(endpoint);
+        }
+        if let Some(region) = &s3.region {
+            cmd.env("AWS_DEFAULT_REGION", region);
+        }
+        if let Some(access_key) = &s3.access_key {
+            cmd.env("AWS_ACCESS_KEY_ID", access_key);
+        }
+        if let Some(secret_key) = &s3.secret_key {
+            cmd.env("AWS_SECRET_ACCESS_KEY", secret_key);
+        }
+        cmd
+    }
+
+    fn run(&self, args: &[&str]) -> Result<Output> {
+        self.run_aws("s3", args)
+    }
+
+    fn run_api(&self, args: &[&str]) -> Result<Output> {
+        self.run_aws("s3api", args)
+    }
+
+    fn run_aws(&self, service: &str, args: &[&str]) -> Result<Output> {
+        let output = self
+            .aws()
+            .arg(service)
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .context("Failed to run the aws CLI, is it installed?")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "aws {} {} failed\nstderr: {}",
+                service,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(output)
+    }
+}
+
+impl StorageBackend for S3Backend<'_> {
+    fn mkdir(&self, _path: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<String>> {
+        let prefix = format!("{}/", self.uri(path).trim_end_matches('/'));
+        let output = self.run(&["ls", &prefix])?;
+        // `PRE name/` for common prefixes, `date time size name` for objects
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                match line.strip_prefix("PRE ") {
+                    Some(dir) => Some(dir.trim_end_matches('/').to_string()),
+                    None => {
+                        let mut name = line;
+                        for _ in 0..3 {
+                            name = name.trim_start().split_once(char::is_whitespace)?.1;
+                        }
+                        Some(name.trim().to_string())
+                    }
+                }
+            })
+            .filter(|name| !name.is_empty())
+            .collect())
+    }
+
+    fn read(&self, path: &str) -> Result<String> {
+        let output = self.run(&["cp", &self.uri(path), "-"])?;
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        if path.trim_end_matches('/') == self.cfg.remote_backup_path.trim_end_matches('/') {
+            anyhow::bail!("Refusing to delete the backup root {}", path);
+        }
+        // Remove the "folder" (every key below it) and an object with that exact key
+        let folder = format!("{}/", self.uri(path).trim_end_matches('/'));
+        self.run(&["rm", "--recursive", "--only-show-errors", &folder])?;
+        self.run(&["rm", "--only-show-errors", &self.uri(path)])?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        // Server-side copy and delete, the object is never downloaded
+        self.run(&["mv", "--only-show-errors", &self.uri(from), &self.uri(to)])
+            .map(|_| ())
+    }
+
+    fn put(&self, local: &Path, remote: &str) -> Result<()> {
+        let local = local.to_string_lossy();
+        self.run(&["cp", "--only-show-errors", &local, &self.uri(remote)])
+            .map(|_| ())
+    }
+
+    fn get(&self, remote: &str, local: &Path) -> Result<()> {
+        let local = local.to_string_lossy();
+        self.run(&["cp", "--only-show-errors", &self.uri(remote), &local])
+            .map(|_| ())
+    }
+
+    fn size(&self, path: &str) -> Result<u64> {
+        let output = self.run_api(&[
+            "head-object",
+            "--bucket",
+            &self.cfg.s3.bucket,
+            "--key",
+            key(path),
+            "--query",
+            "ContentLength",
+            "--output",
+            "text",
+        ])?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout
+            .trim()
+            .parse()
+            .with_context(|| format!("Unexpected size for {}: {}", path, stdout))
+    }
+
+    fn read_range(
+        &self,
+        path: &str,
+        index: u64,
+        chunk_size: u64,
+        throttle: &mut Throttle,
+    ) -> Result<Vec<u8>> {
+        let start = index * chunk_size;
+        let range = format!("bytes={}-{}", start, start + chunk_size - 1);
+        // The object body goes to stdout, so the throttle holds the download back. The
+        // response follows it, reduced to the body's length.
+        let args = [
+            "get-object",
+            "--bucket",
+            &self.cfg.s3.bucket,
+            "--key",
+            key(path),
+            "--range",
+            &range,
+            "--query",
+            "ContentLength",
+            "--output",
+            "text",
+            "/dev/stdout",
+        ];
+        let output = throttle
+            .output(self.aws().arg("s3api").args(args))
+            .context("Failed to run the aws CLI, is it installed?")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "aws s3api {} failed\nstderr: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        split_body(output.stdout)
+            .with_context(|| format!("Unexpected get-object output for {}", path))
+    }
+
+    fn check(&self) -> Result<()> {
+        self.run(&["ls", &format!("s3://{}/", self.cfg.s3.bucket)])
+            .map(|_| ())
+    }
+}
+
+fn key(path: &str) -> &str {
+    path.trim_start_matches('/')
+}
+
+/// Splits the output of `get-object ... --query ContentLength --output text /dev/stdout`
+/// into the body, checking it against the length printed after it.
+fn split_body(mut stdout: Vec<u8>) -> Option<Vec<u8>> {
+    if stdout.last() == Some(&b'\n') {
+        stdout.pop();
+    }
+    let digits = stdout
+        .iter()
+        .rev()
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+    // The body may end in digits itself, the length printed is the one that matches
+    for len in 1..=digits {
+        let body_len = stdout.len() - len;
+        let printed = std::str::from_utf8(&stdout[body_len..]).ok()?;
+        if printed.parse::<usize>().ok() == Some(body_len) {
+            stdout.truncate(body_len);
+            return Some(stdout);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_body_from_its_length() {
+        assert_eq!(split_body(b"hello5\n".to_vec()), Some(b"hello".to_vec()));
+        assert_eq!(split_body(b"0\n".to_vec()), Some(Vec::new()));
+        // A body ending in digits
+        assert_eq!(split_body(b"abc1236\n".to_vec()), Some(b"abc123".to_vec()));
+        assert_eq!(
+            split_body(b"123456789110\n".to_vec()),
+            Some(b"1234567891".to_vec())
+        );
+    }
+
+    #[test]
+    fn rejects_a_length_mismatch() {
+        assert_eq!(split_body(b"hello9\n".to_vec()), None);
+        assert_eq!(split_body(b"hello\n".to_vec()), None);
+    }
+}
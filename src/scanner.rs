@@ -1,15 +1,65 @@
-use crate::config::Config;
+use crate::{compression, config::Config, dbdump};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
-use yaml_rust::YamlLoader;
+use std::process::Command;
+use yaml_rust::{Yaml, YamlLoader};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Volume {
     pub name: String,
     pub path: PathBuf,
     pub volume_type: VolumeType,
+    /// Set when adaptive compression stored this volume with a different algorithm than
+    /// the snapshot's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
+    /// Globs left out of the archive, from the `dockup.exclude` label of the service and
+    /// `excludes` in the config.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excludes: Vec<String>,
+    /// Docker volume name of a named volume, resolved from the top-level `volumes:` section
+    /// (`external`, `name:`) and the compose project name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docker_name: Option<String>,
+    /// Driver of a named volume that doesn't live on this host's disk, recorded at backup
+    /// time so the volume can be recreated the same way.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub driver: Option<VolumeDriver>,
+    /// Read-only snapshot of the volume to archive instead of the live data, while
+    /// `fs_snapshots` holds one.
+    #[serde(skip)]
+    pub snapshot: Option<PathBuf>,
+    /// Where a restore puts the volume instead of `path` (`restore --target-dir`).
+    #[serde(skip)]
+    pub destination: Option<PathBuf>,
+}
+
+/// A volume driver other than plain `local`: a plugin (e.g. NFS/CIFS drivers) or `local`
+/// with mount options (`type: nfs`, `device: ...`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VolumeDriver {
+    pub name: String,
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+}
+
+impl Volume {
+    /// The Docker volume backing a named volume. Snapshots from before `docker_name` was
+    /// recorded fall back to compose's default `<project>_<volume>`.
+    pub fn docker_volume(&self, project: &str) -> String {
+        self.docker_name
+            .clone()
+            .unwrap_or_else(|| format!("{}_{}", project, self.name))
+    }
+
+    /// Directory a restore extracts a bind mount to.
+    pub fn restore_path(&self) -> &PathBuf {
+        self.destination.as_ref().unwrap_or(&self.path)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -38,14 +88,320 @@ pub struct BackupApplication {
     pub name: String,
     pub timestamp: chrono::DateTime<chrono::Local>,
     pub backup_type: Option<BackupType>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Archives were encrypted and carry a `.age` / `.gpg` suffix.
+    #[serde(default)]
+    pub encrypted: bool,
+    /// `age` or `gpg`, missing in snapshots from before gpg support (which are age).
+    #[serde(default)]
+    pub encryption_method: Option<String>,
+    /// Compression algorithm of the archives, gzip when missing.
+    #[serde(default)]
+    pub compression: Option<String>,
+    /// Size and throughput per uploaded archive.
+    #[serde(default)]
+    pub stats: Vec<ArchiveStats>,
+    /// Stored as plain directories by rsync instead of archives, see `incremental`.
+    #[serde(default)]
+    pub incremental: bool,
+    /// Volumes are stored as manifests of the chunk store, see `dedup`.
+    #[serde(default)]
+    pub dedup: bool,
+    /// Snapshot folder this incremental snapshot hardlinks unchanged files to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+    /// Database services dumped to `DUMPS/`, see `db_dumps`.
+    #[serde(default)]
+    pub dumps: Vec<String>,
+    /// Dump commands from `dockup.dump.*` labels of the compose services.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dump_commands: Vec<DumpCommand>,
+    /// Database services archived while running, without a dump or `quiesce`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inconsistent_databases: Vec<String>,
+    /// Images of the compose services, see `images`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageRecord>,
     pub application_path: PathBuf,
     pub volumes: Vec<Volume>,
 }
 
+/// A service labelled with `dockup.dump.cmd` and `dockup.dump.file`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DumpCommand {
+    pub service: String,
+    /// Run in the service's container with `sh -c`.
+    pub cmd: String,
+    /// Path in the container the command writes its dump to.
+    pub file: String,
+}
+
+/// The image a compose service used when the snapshot was taken.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImageRecord {
+    pub service: String,
+    pub image: String,
+    /// Local image ID, missing if the image wasn't present on the host.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Registry digest (`repo@sha256:...`), missing for locally built images.
+    #[serde(default)]
+    pub digest: Option<String>,
+    /// File in `IMAGES/` the image was saved to, shared by services using the same image.
+    #[serde(default)]
+    pub archive: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchiveStats {
+    pub name: String,
+    /// Size of the uncompressed tar stream.
+    pub original_bytes: u64,
+    /// Size of the uploaded archive (compressed, and encrypted if enabled).
+    pub compressed_bytes: u64,
+    /// Time to archive and upload.
+    pub seconds: f64,
+    /// Missing in snapshots from before checksums, see `verify`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<Checksum>,
+}
+
+/// SHA-256 of an uploaded archive.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checksum {
+    /// Path relative to the snapshot folder, e.g. `VOLUMES/data.tar.gz`.
+    pub file: String,
+    pub sha256: String,
+}
+
+impl ArchiveStats {
+    /// Compressed size relative to the original, 1.0 means no gain at all.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.original_bytes > 0).then(|| self.compressed_bytes as f64 / self.original_bytes as f64)
+    }
+
+    /// Original bytes processed per second.
+    pub fn throughput(&self) -> Option<f64> {
+        (self.seconds > 0.0).then(|| self.original_bytes as f64 / self.seconds)
+    }
+}
+
+impl BackupApplication {
+    /// A freshly scanned project, before it is backed up.
+    fn new(
+        name: String,
+        application_path: PathBuf,
+        volumes: Vec<Volume>,
+        dump_commands: Vec<DumpCommand>,
+    ) -> Self {
+        BackupApplication {
+            name,
+            timestamp: chrono::Local::now(),
+            backup_type: None,
+            tags: Vec::new(),
+            encrypted: false,
+            encryption_method: None,
+            compression: None,
+            stats: Vec::new(),
+            incremental: false,
+            dedup: false,
+            previous: None,
+            dumps: Vec::new(),
+            dump_commands,
+            inconsistent_databases: Vec::new(),
+            images: Vec::new(),
+            application_path,
+            volumes,
+        }
+    }
+
+    /// The project's compose file, see [`compose_file`].
+    pub fn compose_file(&self) -> PathBuf {
+        compose_file(&self.application_path)
+            .unwrap_or_else(|| self.application_path.join(COMPOSE_FILES[3]))
+    }
+
+    /// Name of the snapshot folder on the backup target.
+    pub fn snapshot_folder(&self) -> String {
+        self.timestamp.format("%Y_%m_%d_%H%M%S").to_string()
+    }
+
+    fn archive_extension(&self) -> &'static str {
+        compression::extension(self.compression.as_deref().unwrap_or("gzip"))
+    }
+
+    /// File name of the project archive in `REPO/` (before encryption).
+    pub fn repo_archive(&self) -> String {
+        format!("repo{}", self.archive_extension())
+    }
+
+    /// File name of a volume's archive in `VOLUMES/` (before encryption).
+    pub fn volume_archive(&self, volume: &Volume) -> String {
+        let sanitized = self.volume_dir(volume);
+        let extension = match &volume.compression {
+            Some(algorithm) => compression::extension(algorithm),
+            None => self.archive_extension(),
+        };
+        format!("{}{}", sanitized, extension)
+    }
+
+    /// Directory name of a volume in `VOLUMES/` of an incremental snapshot.
+    pub fn volume_dir(&self, volume: &Volume) -> String {
+        volume
+            .path
+            .to_string_lossy()
+            .trim_start_matches("./")
+            .replace('/', "_")
+    }
+}
+
+/// Project excluded from scans/backups by a `.dockup-skip` marker file.
+#[derive(Debug, Clone)]
+pub struct SkippedProject {
+    pub name: String,
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug)]
+pub struct ScanResult {
+    pub apps: Vec<BackupApplication>,
+    pub skipped: Vec<SkippedProject>,
+}
+
+/// Marker file that temporarily excludes a project; its content is the reason.
+const SKIP_MARKER: &str = ".dockup-skip";
+
+/// Parsed volumes per compose file, reused while the file's hash is unchanged so hosts
+/// with hundreds of projects don't reparse every YAML on each scan.
+#[derive(Serialize, Deserialize, Default)]
+struct ScanCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+/// Part of every cache hash, bumped when parsing changes so all projects are parsed again.
+const CACHE_FORMAT: &str = "2";
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    hash: String,
+    volumes: Vec<Volume>,
+}
+
+impl ScanCache {
+    fn path() -> Option<PathBuf> {
+        Some(dirs::home_dir()?.join(".dockup").join("scan_cache.json"))
+    }
+
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the cache back if anything changed; a failure only costs the next scan time.
+    fn save(&self) {
+        if !self.changed {
+            return;
+        }
+        let Some(path) = Self::path() else {
+            return;
+        };
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, serde_json::to_string(self).unwrap_or_default()));
+        if let Err(e) = written {
+            log::debug!("Failed to write scan cache {:?}: {e}", path);
+        }
+    }
+
+    /// Volumes of the project with the compose file `compose`, parsed only if one of the
+    /// merged `files` changed since the last scan.
+    fn volumes(
+        &mut self,
+        compose: &Path,
+        files: &[PathBuf],
+        app_root: &Path,
+    ) -> Result<Vec<Volume>> {
+        let mut hasher = Sha256::new();
+        hasher.update(CACHE_FORMAT);
+        for file in files {
+            let content = fs::read(file).with_context(|| format!("Failed to read {:?}", file))?;
+            hasher.update(file.to_string_lossy().as_bytes());
+            hasher.update(&content);
+        }
+        // Volume paths may use variables from it
+        if let Ok(env) = fs::read(app_root.join(".env")) {
+            hasher.update(&env);
+        }
+        let hash = format!("{:x}", hasher.finalize());
+        if let Some(entry) = self.entries.get(compose).filter(|entry| entry.hash == hash) {
+            log::debug!("Scan cache hit for {:?}", compose);
+            return Ok(entry.volumes.clone());
+        }
+        let volumes = parse_volumes(files, app_root)?;
+        self.entries.insert(
+            compose.to_path_buf(),
+            CacheEntry {
+                hash,
+                volumes: volumes.clone(),
+            },
+        );
+        self.changed = true;
+        Ok(volumes)
+    }
+
+    /// Drops entries of compose files not seen in this scan.
+    fn retain_seen(&mut self, seen: &[PathBuf]) {
+        let before = self.entries.len();
+        self.entries.retain(|path, _| seen.contains(path));
+        self.changed |= self.entries.len() != before;
+    }
+}
+
 /// Entry point for scan
-pub fn scan_projects(config: &Config) -> Result<Vec<BackupApplication>> {
-    let apps = discover_projects(&config.docker_parent)?;
-    for app in &apps {
+pub fn scan_projects(config: &Config) -> Result<ScanResult> {
+    let mut cache = ScanCache::load();
+    let mut result = if config.discovery == "docker" {
+        ScanResult {
+            apps: Vec::new(),
+            skipped: Vec::new(),
+        }
+    } else {
+        discover_projects(
+            &config.docker_parent,
+            config.scan_depth,
+            &config.adopted_projects,
+            &mut cache,
+        )?
+    };
+    match config.discovery.as_str() {
+        "docker" => discover_running_projects(&mut result, &mut cache)?,
+        "both" => {
+            if let Err(e) = discover_running_projects(&mut result, &mut cache) {
+                log::warn!("⚠️  Could not discover running compose projects: {e:#}");
+            }
+        }
+        _ => {}
+    }
+    let seen: Vec<PathBuf> = result
+        .apps
+        .iter()
+        .map(BackupApplication::compose_file)
+        .collect();
+    cache.retain_seen(&seen);
+    cache.save();
+    apply_project_filters(config, &mut result);
+    for app in result.apps.iter_mut() {
+        apply_only_volumes(config, app);
+        flag_inconsistent_databases(config, app);
+    }
+    for app in &result.apps {
         log::info!("📦 Project: {}", app.name);
         log::info!("   Path: {:?}", app.application_path);
         log::info!("   Volumes:");
@@ -53,82 +409,704 @@ pub fn scan_projects(config: &Config) -> Result<Vec<BackupApplication>> {
             log::info!("      - Name: {}, Path: {:?}", volume.name, volume.path);
         });
     }
-    Ok(apps)
+    for skipped in &result.skipped {
+        log::warn!("⏸  Skipping {}: {}", skipped.name, skipped.reason);
+    }
+    Ok(result)
+}
+
+/// Image names (without registry and tag) that look like a database keeping its data in
+/// files, which are rarely consistent when copied while the database runs.
+const DATABASE_IMAGES: [&str; 18] = [
+    "postgres",
+    "postgis",
+    "timescale",
+    "mysql",
+    "mariadb",
+    "percona",
+    "mongo",
+    "redis",
+    "valkey",
+    "keydb",
+    "elasticsearch",
+    "opensearch",
+    "influxdb",
+    "cassandra",
+    "couchdb",
+    "clickhouse",
+    "neo4j",
+    "cockroach",
+];
+
+/// Records the database services of `app` with volumes that are backed up while running:
+/// no `quiesce` for the project, no `dockup.dump.cmd` label and no `db_dumps` support.
+fn flag_inconsistent_databases(config: &Config, app: &mut BackupApplication) {
+    if config.quiesce.contains_key(&app.name) {
+        return;
+    }
+    let Ok(services) = database_services(&app.compose_file()) else {
+        return;
+    };
+    for (service, image) in services {
+        let dumped = app.dump_commands.iter().any(|cmd| cmd.service == service)
+            || (config.db_dumps != "off" && dbdump::Engine::from_image(&image).is_some());
+        if !dumped {
+            log::warn!(
+                "⚠️  {}: database service `{}` is backed up while running, configure db_dumps, a dockup.dump.cmd label or quiesce",
+                app.name,
+                service
+            );
+            app.inconsistent_databases.push(service);
+        }
+    }
+}
+
+/// Services of a compose file with a database image and at least one volume, as
+/// `(service, image)`.
+pub fn database_services(compose_file: &Path) -> Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(compose_file)
+        .with_context(|| format!("Failed to read {:?}", compose_file))?;
+    let yamls = YamlLoader::load_from_str(&content)?;
+    let Some(services) = yamls.first().and_then(|root| root["services"].as_hash()) else {
+        return Ok(Vec::new());
+    };
+    Ok(services
+        .iter()
+        .filter_map(|(name, service)| {
+            let name = name.as_str()?;
+            let image = service["image"].as_str()?;
+            let base = image.rsplit('/').next()?.split([':', '@']).next()?;
+            let has_volumes = service["volumes"].as_vec().is_some_and(|v| !v.is_empty());
+            (has_volumes && DATABASE_IMAGES.iter().any(|db| base.contains(db)))
+                .then_some((name.to_string(), image.to_string()))
+        })
+        .collect())
+}
+
+/// Moves projects ruled out by `include_projects`/`exclude_projects` to the skipped ones.
+fn apply_project_filters(config: &Config, result: &mut ScanResult) {
+    let matches =
+        |patterns: &[String], name: &str| patterns.iter().any(|pattern| glob_match(pattern, name));
+    let (apps, filtered): (Vec<_>, Vec<_>) = std::mem::take(&mut result.apps)
+        .into_iter()
+        .partition(|app| {
+            (config.include_projects.is_empty() || matches(&config.include_projects, &app.name))
+                && !matches(&config.exclude_projects, &app.name)
+        });
+    result.apps = apps;
+    result
+        .skipped
+        .extend(filtered.into_iter().map(|app| SkippedProject {
+            name: app.name,
+            path: app.application_path,
+            reason: "excluded by include_projects/exclude_projects".to_string(),
+        }));
+}
+
+/// Drops the volumes of `app` not in its `only_volumes` allow-list, if it has one.
+fn apply_only_volumes(config: &Config, app: &mut BackupApplication) {
+    let Some(allowed) = config.only_volumes.get(&app.name) else {
+        return;
+    };
+    for pattern in allowed {
+        if !app.volumes.iter().any(|vol| glob_match(pattern, &vol.name)) {
+            log::warn!(
+                "⚠️  only_volumes.{} names `{}`, which matches no volume of the project",
+                app.name,
+                pattern
+            );
+        }
+    }
+    app.volumes.retain(|vol| {
+        let keep = allowed.iter().any(|pattern| glob_match(pattern, &vol.name));
+        if !keep {
+            log::info!(
+                "⏭️  Volume `{}` of {} not in only_volumes",
+                vol.name,
+                app.name
+            );
+        }
+        keep
+    });
+}
+
+/// Matches `name` against `pattern`, where `*` stands for any number of characters.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|&i| name.is_char_boundary(i))
+                .any(|i| glob_match(rest, &name[i..]))
+        }
+    }
 }
 
 /// Discover valid backup projects
-fn discover_projects(base: &str) -> Result<Vec<BackupApplication>> {
-    let mut projects = Vec::new();
-
-    for entry in fs::read_dir(base)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_dir() {
-            let compose = path.join("docker-compose.yml");
-            if compose.exists() {
-                let volumes = parse_volumes(&compose, &path)?;
-                let name = path.file_name().unwrap().to_string_lossy().to_string();
-                projects.push(BackupApplication {
-                    name,
-                    timestamp: chrono::Local::now(),
-                    backup_type: None,
-                    application_path: path.clone(),
-                    volumes: volumes,
-                });
-            }
+fn discover_projects(
+    bases: &[String],
+    depth: usize,
+    adopted: &[String],
+    cache: &mut ScanCache,
+) -> Result<ScanResult> {
+    let mut result = ScanResult {
+        apps: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    for (path, name) in parent_project_dirs(bases, depth)? {
+        let name = resolve_project_name(&path, name);
+        let clash = result
+            .apps
+            .iter()
+            .map(|app| (&app.name, &app.application_path))
+            .chain(
+                result
+                    .skipped
+                    .iter()
+                    .map(|skipped| (&skipped.name, &skipped.path)),
+            )
+            .find(|(known, _)| **known == name);
+        if let Some((_, other)) = clash {
+            log::warn!(
+                "⚠️  Project {:?} clashes with {:?} of the same name, ignoring it",
+                path,
+                other
+            );
+            continue;
+        }
+        inspect_project(&path, name, &mut result, cache)?;
+    }
+
+    // Projects registered via `dockup adopt` that live outside docker_parent
+    for path in adopted {
+        let path = PathBuf::from(path);
+        if compose_file(&path).is_none() {
+            log::warn!("⚠️  Adopted project {:?} has no compose file", path);
+            continue;
+        }
+        let name = resolve_project_name(&path, project_name(&path));
+        if result.apps.iter().any(|app| app.name == name) {
+            log::warn!(
+                "⚠️  Adopted project {:?} clashes with project `{}` in docker_parent, ignoring it",
+                path,
+                name
+            );
+            continue;
         }
+        inspect_project(&path, name, &mut result, cache)?;
     }
 
+    Ok(result)
+}
+
+/// A compose project running on the Docker daemon, from the `com.docker.compose.*` labels
+/// of its containers.
+struct RunningProject {
+    name: String,
+    working_dir: PathBuf,
+    containers: Vec<String>,
+}
+
+/// Adds the compose projects running on the Docker daemon that aren't in `result` yet.
+/// Projects whose working directory has a compose file are inspected like those in
+/// `docker_parent`, the others are backed up from their containers' mounts.
+fn discover_running_projects(result: &mut ScanResult, cache: &mut ScanCache) -> Result<()> {
+    for project in running_projects()? {
+        let known = |name: &str, path: &Path| {
+            name == project.name
+                || path == project.working_dir
+                || path
+                    .canonicalize()
+                    .is_ok_and(|path| Some(path) == project.working_dir.canonicalize().ok())
+        };
+        if result
+            .apps
+            .iter()
+            .any(|app| known(&app.name, &app.application_path))
+            || result
+                .skipped
+                .iter()
+                .any(|skipped| skipped.name == project.name)
+        {
+            continue;
+        }
+        if compose_file(&project.working_dir).is_some() {
+            let name =
+                resolve_project_name(&project.working_dir, project_name(&project.working_dir));
+            inspect_project(&project.working_dir, name, result, cache)?;
+            continue;
+        }
+        log::debug!(
+            "No compose file in {:?}, using the mounts of {}",
+            project.working_dir,
+            project.name
+        );
+        let volumes = container_mounts(&project.containers)?;
+        result.apps.push(BackupApplication::new(
+            project.name,
+            project.working_dir,
+            volumes,
+            Vec::new(),
+        ));
+    }
+    Ok(())
+}
+
+fn running_projects() -> Result<Vec<RunningProject>> {
+    let output = Command::new("docker")
+        .args([
+            "ps",
+            "--filter",
+            "label=com.docker.compose.project",
+            "--format",
+            "{{.Names}}\t{{.Label \"com.docker.compose.project\"}}\t{{.Label \"com.docker.compose.project.working_dir\"}}",
+        ])
+        .output()
+        .context("Failed to run docker ps")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "docker ps failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut projects: Vec<RunningProject> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut fields = line.split('\t');
+        let (Some(container), Some(name), Some(dir)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        match projects.iter_mut().find(|project| project.name == name) {
+            Some(project) => project.containers.push(container.to_string()),
+            None => projects.push(RunningProject {
+                name: name.to_string(),
+                working_dir: PathBuf::from(dir),
+                containers: vec![container.to_string()],
+            }),
+        }
+    }
     Ok(projects)
 }
 
-/// Parse volume mounts from a docker-compose.yml file
+/// Driver of the Docker volume `volume` if it isn't a plain `local` one, from
+/// `docker volume inspect`. `None` as well if the volume doesn't exist.
+pub fn volume_driver(volume: &str) -> Option<VolumeDriver> {
+    let output = Command::new("docker")
+        .args([
+            "volume",
+            "inspect",
+            "--format",
+            "{{.Driver}}\t{{json .Options}}",
+            volume,
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (name, options) = stdout.trim().split_once('\t')?;
+    let options: BTreeMap<String, String> = serde_json::from_str(options).unwrap_or_default();
+    (name != "local" || !options.is_empty()).then(|| VolumeDriver {
+        name: name.to_string(),
+        options,
+    })
+}
+
+/// Bind mounts and named volumes of `containers`, from `docker inspect`.
+fn container_mounts(containers: &[String]) -> Result<Vec<Volume>> {
+    let output = Command::new("docker")
+        .args([
+            "inspect",
+            "--format",
+            "{{range .Mounts}}{{.Type}}\t{{.Name}}\t{{.Source}}\n{{end}}",
+        ])
+        .args(containers)
+        .output()
+        .context("Failed to run docker inspect")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "docker inspect failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut volumes: Vec<Volume> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut fields = line.split('\t');
+        let volume = match (fields.next(), fields.next(), fields.next()) {
+            (Some("bind"), _, Some(source)) => Volume {
+                name: source.to_string(),
+                path: PathBuf::from(source),
+                volume_type: VolumeType::Bind,
+                compression: None,
+                excludes: Vec::new(),
+                docker_name: None,
+                driver: None,
+                snapshot: None,
+                destination: None,
+            },
+            (Some("volume"), Some(name), _) if !name.is_empty() => Volume {
+                name: name.to_string(),
+                path: PathBuf::from(format!("/var/lib/docker/volumes/{}", name)),
+                volume_type: VolumeType::Mount,
+                compression: None,
+                excludes: Vec::new(),
+                docker_name: Some(name.to_string()),
+                driver: None,
+                snapshot: None,
+                destination: None,
+            },
+            _ => continue,
+        };
+        if !volumes.iter().any(|known| known.name == volume.name) {
+            volumes.push(volume);
+        }
+    }
+    Ok(volumes)
+}
+
+/// Compose file names in the order `docker compose` prefers them.
+pub const COMPOSE_FILES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+/// The compose file of the project in `dir`, if it has one.
+pub fn compose_file(dir: &Path) -> Option<PathBuf> {
+    COMPOSE_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+}
+
+/// The files `docker compose` merges for the project in `dir`: those in `COMPOSE_FILE` of
+/// the project's `.env`, else its compose file and an override file next to it.
+pub fn compose_files(dir: &Path) -> Vec<PathBuf> {
+    if let Some(files) = env_compose_files(dir) {
+        return files;
+    }
+    let Some(compose) = compose_file(dir) else {
+        return Vec::new();
+    };
+    let overrides = [
+        "compose.override.yaml",
+        "compose.override.yml",
+        "docker-compose.override.yaml",
+        "docker-compose.override.yml",
+    ];
+    let override_file = overrides
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists());
+    std::iter::once(compose).chain(override_file).collect()
+}
+
+/// `COMPOSE_FILE` from the project's `.env`, split like `docker compose` does.
+fn env_compose_files(dir: &Path) -> Option<Vec<PathBuf>> {
+    let env = dotenv(dir);
+    let separator = env
+        .get("COMPOSE_PATH_SEPARATOR")
+        .map_or(":", String::as_str);
+    let files: Vec<PathBuf> = env
+        .get("COMPOSE_FILE")?
+        .split(separator)
+        .filter(|file| !file.is_empty())
+        .map(|file| dir.join(file))
+        .collect();
+    (!files.is_empty()).then_some(files)
+}
+
+/// Variables from the project's `.env` file.
+fn dotenv(dir: &Path) -> HashMap<String, String> {
+    let Ok(content) = fs::read_to_string(dir.join(".env")) else {
+        return HashMap::new();
+    };
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, value) = line.trim_start_matches("export ").split_once('=')?;
+            let value = value.trim().trim_matches(['"', '\'']);
+            Some((name.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Replaces `${VAR}`, `${VAR:-default}`, `${VAR-default}` and `$VAR` like `docker compose`
+/// does, taking variables from the environment first and then from `env`. `$$` is a
+/// literal `$`, unset variables without a default become empty.
+fn interpolate(value: &str, env: &HashMap<String, String>) -> String {
+    let lookup = |name: &str| std::env::var(name).ok().or_else(|| env.get(name).cloned());
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+        } else if let Some((expr, after)) = rest
+            .strip_prefix('{')
+            .and_then(|braced| braced.split_once('}'))
+        {
+            rest = after;
+            let split = expr.find(|c: char| !is_name(c)).unwrap_or(expr.len());
+            let (name, operator) = expr.split_at(split);
+            let value = lookup(name);
+            let resolved = if let Some(default) = operator.strip_prefix(":-") {
+                value
+                    .filter(|value| !value.is_empty())
+                    .unwrap_or_else(|| default.to_string())
+            } else if let Some(default) = operator.strip_prefix('-') {
+                value.unwrap_or_else(|| default.to_string())
+            } else {
+                value.unwrap_or_else(|| {
+                    log::warn!("⚠️  Variable {} is not set, using an empty string", name);
+                    String::new()
+                })
+            };
+            out.push_str(&resolved);
+        } else {
+            let len = rest.find(|c: char| !is_name(c)).unwrap_or(rest.len());
+            if len == 0 {
+                out.push('$');
+                continue;
+            }
+            let (name, after) = rest.split_at(len);
+            out.push_str(&lookup(name).unwrap_or_else(|| {
+                log::warn!("⚠️  Variable {} is not set, using an empty string", name);
+                String::new()
+            }));
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn project_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().to_string()
+}
+
+/// Project directories below all `docker_parent` directories, in the order the parents are
+/// listed. A directory reached twice, because a parent is listed twice, through a symlink or
+/// nested in another parent, is only returned the first time.
+fn parent_project_dirs(bases: &[String], depth: usize) -> Result<Vec<(PathBuf, String)>> {
+    let mut dirs = Vec::new();
+    let mut seen = Vec::new();
+    for base in bases {
+        let base = Path::new(base);
+        fs::read_dir(base).with_context(|| format!("Failed to read docker_parent {:?}", base))?;
+        let mut found = Vec::new();
+        find_project_dirs(base, base, depth, &mut found);
+        for (path, name) in found {
+            let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+            if seen.contains(&canonical) {
+                continue;
+            }
+            seen.push(canonical);
+            dirs.push((path, name));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Directories with a compose file below `dir`, up to `depth` levels deep, with their
+/// project names: the path below `base` joined by `_`, so `client-a/app` is `client-a_app`.
+/// Projects are not searched for nested ones, hidden directories are left out.
+fn find_project_dirs(base: &Path, dir: &Path, depth: usize, found: &mut Vec<(PathBuf, String)>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("⚠️  Failed to read {:?}: {e}", dir);
+            return;
+        }
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    for path in dirs {
+        if compose_file(&path).is_some() {
+            let name = path
+                .strip_prefix(base)
+                .unwrap_or(&path)
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("_");
+            found.push((path, name));
+        } else if depth > 1 && !project_name(&path).starts_with('.') {
+            find_project_dirs(base, &path, depth - 1, found);
+        }
+    }
+}
+
+fn inspect_project(
+    path: &Path,
+    name: String,
+    result: &mut ScanResult,
+    cache: &mut ScanCache,
+) -> Result<()> {
+    let Some(compose) = compose_file(path) else {
+        return Ok(());
+    };
+    let marker = path.join(SKIP_MARKER);
+    if marker.exists() {
+        let reason = fs::read_to_string(&marker).unwrap_or_default();
+        let reason = match reason.trim() {
+            "" => "no reason given".to_string(),
+            reason => reason.to_string(),
+        };
+        result.skipped.push(SkippedProject {
+            name,
+            path: path.to_path_buf(),
+            reason,
+        });
+        return Ok(());
+    }
+    let volumes = cache.volumes(&compose, &compose_files(path), path)?;
+    let dump_commands = parse_dump_labels(&compose)?;
+    result.apps.push(BackupApplication::new(
+        name,
+        path.to_path_buf(),
+        volumes,
+        dump_commands,
+    ));
+    Ok(())
+}
+
+/// Registers a directory outside `docker_parent` as managed project.
+pub fn adopt_project(config: &mut Config, path: &Path) -> Result<()> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {:?}", path))?;
+    if compose_file(&path).is_none() {
+        anyhow::bail!("{:?} does not contain a compose file", path);
+    }
+    let path_str = path.to_string_lossy().to_string();
+    if config.adopted_projects.contains(&path_str) {
+        log::info!("Project {:?} is already adopted", path);
+        return Ok(());
+    }
+    config.adopted_projects.push(path_str);
+    config.save()?;
+    log::info!(
+        "✅ Adopted project `{}` at {:?}",
+        resolve_project_name(&path, project_name(&path)),
+        path
+    );
+    Ok(())
+}
+
+pub fn release_project(config: &mut Config, path: &Path) -> Result<()> {
+    let path_str = path
+        .canonicalize()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string_lossy().to_string());
+    let before = config.adopted_projects.len();
+    config.adopted_projects.retain(|p| p != &path_str);
+    if config.adopted_projects.len() == before {
+        anyhow::bail!("{:?} is not an adopted project", path);
+    }
+    config.save()?;
+    log::info!("✅ Removed adopted project {:?}", path);
+    Ok(())
+}
+
+/// Parse volume mounts from a compose file
 use std::collections::HashSet;
 
-/// Parses a Docker Compose file and extracts unique volume host paths,
-/// resolving them relative to the given `app_root`.
-pub fn parse_volumes(compose_file: &Path, app_root: &Path) -> Result<Vec<Volume>> {
-    let content = fs::read_to_string(compose_file)
-        .with_context(|| format!("Failed to read {:?}", compose_file))?;
-    let yamls = YamlLoader::load_from_str(&content)?;
-    let root = &yamls[0];
+/// Parses Docker Compose files and extracts unique volume host paths,
+/// resolving them relative to the given `app_root` after interpolating variables from
+/// its `.env` file. The files are merged like
+/// `docker compose` does: volumes of all files are combined, a service's
+/// `dockup.exclude` label from a later file wins.
+pub fn parse_volumes(compose_files: &[PathBuf], app_root: &Path) -> Result<Vec<Volume>> {
+    let roots = load_compose(compose_files)?;
+
+    let mut excludes: HashMap<&Yaml, Vec<String>> = HashMap::new();
+    for services in roots.iter().filter_map(|root| root["services"].as_hash()) {
+        for (name, service) in services {
+            if let Some(globs) = compose_value(&service["labels"], "dockup.exclude") {
+                let globs = globs
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|glob| !glob.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                excludes.insert(name, globs);
+            }
+        }
+    }
 
+    let env = dotenv(app_root);
+    let docker_names = docker_volume_names(&roots, app_root, &env);
     let mut volumes = Vec::new();
     let mut seen = HashSet::new();
 
-    if let Some(services) = root["services"].as_hash() {
-        for (_, service) in services {
+    for services in roots.iter().filter_map(|root| root["services"].as_hash()) {
+        for (name, service) in services {
+            let excludes = excludes.get(name).cloned().unwrap_or_default();
             if let Some(service_volumes) = service["volumes"].as_vec() {
                 for vol in service_volumes {
-                    if let Some(vol_str) = vol.as_str() {
-                        if let Some((host_path, _)) = vol_str.split_once(':') {
-                            if seen.insert(host_path) {
-                                let is_bind = host_path.starts_with('/')
-                                    || host_path.starts_with("./")
-                                    || host_path.starts_with("../");
-
-                                let resolved_path = if is_bind {
-                                    if host_path.starts_with('/') {
-                                        PathBuf::from(host_path)
-                                    } else {
-                                        app_root.join(host_path)
-                                    }
-                                } else {
-                                    // If it's not a bind mount, use dummy path for completeness
-                                    PathBuf::from(format!("/var/lib/docker/volumes/{}", host_path))
-                                };
-
-                                volumes.push(Volume {
-                                    name: host_path.to_string(),
-                                    path: resolved_path,
-                                    volume_type: if is_bind {
-                                        VolumeType::Bind
-                                    } else {
-                                        VolumeType::Mount
-                                    },
-                                });
+                    let Some((host_path, is_bind)) = volume_source(vol, &env) else {
+                        continue;
+                    };
+                    if host_path.is_empty() {
+                        log::warn!(
+                            "⚠️  A volume of service {:?} in {:?} has an empty host path, skipping it",
+                            name.as_str().unwrap_or_default(),
+                            app_root
+                        );
+                        continue;
+                    }
+                    if seen.insert(host_path.clone()) {
+                        let resolved_path = if is_bind {
+                            if host_path.starts_with('/') {
+                                PathBuf::from(&host_path)
+                            } else {
+                                app_root.join(&host_path)
                             }
-                        }
+                        } else {
+                            // If it's not a bind mount, use dummy path for completeness
+                            PathBuf::from(format!("/var/lib/docker/volumes/{}", host_path))
+                        };
+
+                        let docker_name = (!is_bind).then(|| {
+                            docker_names.get(&host_path).cloned().unwrap_or_else(|| {
+                                format!(
+                                    "{}_{}",
+                                    compose_project_name(&roots, app_root, &env),
+                                    host_path
+                                )
+                            })
+                        });
+                        volumes.push(Volume {
+                            docker_name,
+                            name: host_path,
+                            path: resolved_path,
+                            volume_type: if is_bind {
+                                VolumeType::Bind
+                            } else {
+                                VolumeType::Mount
+                            },
+                            compression: None,
+                            excludes: excludes.clone(),
+                            driver: None,
+                            snapshot: None,
+                            destination: None,
+                        });
                     }
                 }
             }
@@ -137,3 +1115,348 @@ pub fn parse_volumes(compose_file: &Path, app_root: &Path) -> Result<Vec<Volume>
 
     Ok(volumes)
 }
+
+/// The first document of every compose file, in merge order.
+fn load_compose(compose_files: &[PathBuf]) -> Result<Vec<Yaml>> {
+    let mut roots = Vec::new();
+    for compose_file in compose_files {
+        let content = fs::read_to_string(compose_file)
+            .with_context(|| format!("Failed to read {:?}", compose_file))?;
+        let mut yamls = YamlLoader::load_from_str(&content)
+            .with_context(|| format!("Failed to parse {:?}", compose_file))?;
+        if !yamls.is_empty() {
+            roots.push(yamls.swap_remove(0));
+        }
+    }
+    Ok(roots)
+}
+
+/// The compose project name: the top-level `name:`, `COMPOSE_PROJECT_NAME` from `.env` or
+/// the directory name, normalized like `docker compose` does.
+fn compose_project_name(roots: &[Yaml], app_root: &Path, env: &HashMap<String, String>) -> String {
+    declared_name(roots, env).unwrap_or_else(|| normalize_project_name(&project_name(app_root)))
+}
+
+/// The project name set by the top-level `name:` or `COMPOSE_PROJECT_NAME`, if any.
+fn declared_name(roots: &[Yaml], env: &HashMap<String, String>) -> Option<String> {
+    roots
+        .iter()
+        .rev()
+        .find_map(|root| root["name"].as_str())
+        .map(|name| interpolate(name, env))
+        .or_else(|| env.get("COMPOSE_PROJECT_NAME").cloned())
+        .map(|name| normalize_project_name(&name))
+        .filter(|name| !name.is_empty())
+}
+
+pub fn normalize_project_name(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .collect()
+}
+
+/// Name of the project in `dir`: the compose project name if its compose files or `.env`
+/// set one, `fallback` (derived from the directory) otherwise.
+fn resolve_project_name(dir: &Path, fallback: String) -> String {
+    load_compose(&compose_files(dir))
+        .ok()
+        .and_then(|roots| declared_name(&roots, &dotenv(dir)))
+        .unwrap_or(fallback)
+}
+
+/// Docker volume names of the top-level `volumes:` entries, by the name services mount them
+/// with. External volumes and those with a `name:` are used as named, the others get the
+/// compose project name as prefix. Later files override earlier ones.
+fn docker_volume_names(
+    roots: &[Yaml],
+    app_root: &Path,
+    env: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let project = compose_project_name(roots, app_root, env);
+    let mut names = HashMap::new();
+    for declared in roots.iter().filter_map(|root| root["volumes"].as_hash()) {
+        for (key, volume) in declared {
+            let Some(key) = key.as_str() else {
+                continue;
+            };
+            // `external: {name: ...}` is the legacy form of `external: true` plus `name:`
+            let name = volume["name"]
+                .as_str()
+                .or_else(|| volume["external"]["name"].as_str())
+                .map(|name| interpolate(name, env));
+            let external = volume["external"].as_bool().unwrap_or(false)
+                || volume["external"].as_hash().is_some();
+            let docker_name = match name {
+                Some(name) => name,
+                None if external => key.to_string(),
+                None => format!("{}_{}", project, key),
+            };
+            names.insert(key.to_string(), docker_name);
+        }
+    }
+    names
+}
+
+/// Host side of a service volume, from the short `src:dst` form or the long form (`type`,
+/// `source`, `target`), and whether it is a bind mount. `None` for anonymous volumes, tmpfs
+/// and other types without data on the host.
+fn volume_source(volume: &Yaml, env: &HashMap<String, String>) -> Option<(String, bool)> {
+    if let Some(short) = volume.as_str() {
+        let short = interpolate(short, env);
+        let (host_path, _) = short.split_once(':')?;
+        let is_bind = host_path.starts_with('/')
+            || host_path.starts_with("./")
+            || host_path.starts_with("../");
+        return Some((host_path.to_string(), is_bind));
+    }
+    let source = interpolate(volume["source"].as_str()?, env);
+    match volume["type"].as_str()? {
+        // Relative bind sources may omit `./`, name them like the short form does
+        "bind" if source.starts_with('/') || source.starts_with('.') => Some((source, true)),
+        "bind" => Some((format!("./{}", source), true)),
+        "volume" => Some((source, false)),
+        _ => None,
+    }
+}
+
+/// Reads the `dockup.dump.cmd`/`dockup.dump.file` labels of the services in a compose file.
+pub fn parse_dump_labels(compose_file: &Path) -> Result<Vec<DumpCommand>> {
+    let content = fs::read_to_string(compose_file)
+        .with_context(|| format!("Failed to read {:?}", compose_file))?;
+    let yamls = YamlLoader::load_from_str(&content)?;
+    let Some(services) = yamls.first().and_then(|root| root["services"].as_hash()) else {
+        return Ok(Vec::new());
+    };
+
+    let mut commands = Vec::new();
+    for (name, service) in services {
+        let Some(name) = name.as_str() else {
+            continue;
+        };
+        let labels = &service["labels"];
+        match (
+            compose_value(labels, "dockup.dump.cmd"),
+            compose_value(labels, "dockup.dump.file"),
+        ) {
+            (Some(cmd), Some(file)) => commands.push(DumpCommand {
+                service: name.to_string(),
+                cmd,
+                file,
+            }),
+            (Some(_), None) => log::warn!(
+                "⚠️  Service {} in {:?} has dockup.dump.cmd but no dockup.dump.file, ignoring it",
+                name,
+                compose_file
+            ),
+            _ => {}
+        }
+    }
+    Ok(commands)
+}
+
+/// Reads `key` from a compose mapping like `environment` or `labels`, given as map or as
+/// `KEY=value` list.
+pub fn compose_value(mapping: &Yaml, key: &str) -> Option<String> {
+    if let Some(hash) = mapping.as_hash() {
+        return match hash.get(&Yaml::String(key.to_string()))? {
+            Yaml::String(value) => Some(value.clone()),
+            Yaml::Integer(value) => Some(value.to_string()),
+            _ => None,
+        };
+    }
+    mapping.as_vec()?.iter().find_map(|item| {
+        let (name, value) = item.as_str()?.split_once('=')?;
+        (name == key).then(|| value.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn interpolates_braced_and_plain_variables() {
+        let env = env(&[("DOCKUP_TEST_DIR", "/srv/data")]);
+        assert_eq!(interpolate("${DOCKUP_TEST_DIR}/db", &env), "/srv/data/db");
+        assert_eq!(interpolate("$DOCKUP_TEST_DIR/db", &env), "/srv/data/db");
+        assert_eq!(interpolate("./data", &env), "./data");
+    }
+
+    #[test]
+    fn applies_defaults() {
+        let env = env(&[("DOCKUP_TEST_EMPTY", ""), ("DOCKUP_TEST_SET", "set")]);
+        assert_eq!(interpolate("${DOCKUP_TEST_UNSET:-./data}", &env), "./data");
+        assert_eq!(interpolate("${DOCKUP_TEST_UNSET-./data}", &env), "./data");
+        // `:-` also replaces empty values, `-` only unset ones
+        assert_eq!(interpolate("${DOCKUP_TEST_EMPTY:-./data}", &env), "./data");
+        assert_eq!(interpolate("${DOCKUP_TEST_EMPTY-./data}", &env), "");
+        assert_eq!(interpolate("${DOCKUP_TEST_SET:-./data}", &env), "set");
+    }
+
+    #[test]
+    fn unset_variables_become_empty() {
+        let env = env(&[]);
+        assert_eq!(interpolate("${DOCKUP_TEST_UNSET}/db", &env), "/db");
+        assert_eq!(interpolate("$DOCKUP_TEST_UNSET/db", &env), "/db");
+    }
+
+    #[test]
+    fn escapes_dollar_signs() {
+        let env = env(&[("DOCKUP_TEST_DIR", "/srv")]);
+        assert_eq!(interpolate("$$DOCKUP_TEST_DIR", &env), "$DOCKUP_TEST_DIR");
+        assert_eq!(interpolate("price$$", &env), "price$");
+        // A `$` not followed by a name stays as it is
+        assert_eq!(interpolate("a $ b", &env), "a $ b");
+        assert_eq!(interpolate("${DOCKUP_TEST_DIR}$", &env), "/srv$");
+    }
+
+    /// Fresh directory tree under the system temp dir with a compose project per entry.
+    fn parents(name: &str, projects: &[&str]) -> TempDir {
+        let root = TempDir::new(name);
+        for project in projects {
+            let dir = root.join(project);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("compose.yaml"), "services: {}\n").unwrap();
+        }
+        root
+    }
+
+    fn names(dirs: &[(PathBuf, String)]) -> Vec<&str> {
+        dirs.iter().map(|(_, name)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn scans_every_parent_in_order() {
+        let root = parents("order", &["stacks/web", "apps/db", "apps/cache"]);
+        let bases = [
+            root.join("stacks").to_string_lossy().to_string(),
+            root.join("apps").to_string_lossy().to_string(),
+        ];
+        let dirs = parent_project_dirs(&bases, 1).unwrap();
+        assert_eq!(names(&dirs), ["web", "cache", "db"]);
+    }
+
+    #[test]
+    fn scans_overlapping_parents_once() {
+        let root = parents("overlap", &["stacks/web", "stacks/clients/shop"]);
+        let stacks = root.join("stacks").to_string_lossy().to_string();
+        let clients = root.join("stacks/clients").to_string_lossy().to_string();
+        let listed_twice = [stacks.clone(), format!("{stacks}/")];
+        let dirs = parent_project_dirs(&listed_twice, 1).unwrap();
+        assert_eq!(names(&dirs), ["web"]);
+        // With depth 2 the nested parent's project is already found through `stacks`
+        let nested = [stacks, clients];
+        let dirs = parent_project_dirs(&nested, 2).unwrap();
+        assert_eq!(names(&dirs), ["clients_shop", "web"]);
+    }
+
+    #[test]
+    fn fails_on_a_missing_parent() {
+        let root = parents("missing", &["stacks/web"]);
+        let bases = [root.join("nope").to_string_lossy().to_string()];
+        assert!(parent_project_dirs(&bases, 1).is_err());
+    }
+
+    fn app(name: &str) -> BackupApplication {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "timestamp": chrono::Local::now(),
+            "application_path": format!("/srv/{}", name),
+            "volumes": [],
+        }))
+        .unwrap()
+    }
+
+    fn filtered(include: &[&str], exclude: &[&str], projects: &[&str]) -> (Vec<String>, usize) {
+        let raw: crate::config::RawConfig = serde_json::from_value(serde_json::json!({
+            "docker_parent": [],
+            "remote_backup_path": "/backups",
+            "ssh_user": "",
+            "ssh_host": "",
+            "ssh_key": "",
+            "ssh_port": 22,
+            "email_host": "",
+            "email_port": 465,
+            "email_user": "",
+            "email_password": "",
+            "receiver_mail": "",
+            "interval": { "hour": 0, "day": 2, "week": 7, "month": 4, "year": 12 },
+            "include_projects": include,
+            "exclude_projects": exclude,
+        }))
+        .unwrap();
+        let mut result = ScanResult {
+            apps: projects.iter().map(|name| app(name)).collect(),
+            skipped: Vec::new(),
+        };
+        apply_project_filters(&raw.finalize().unwrap(), &mut result);
+        let names = result.apps.into_iter().map(|app| app.name).collect();
+        (names, result.skipped.len())
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_match("web", "web"));
+        assert!(!glob_match("web", "webshop"));
+        assert!(glob_match("web*", "webshop"));
+        assert!(glob_match("*-prod", "shop-prod"));
+        assert!(glob_match("shop-*-db", "shop-eu-db"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*-prod", "shop-staging"));
+        assert!(glob_match("*ü*", "grün"));
+    }
+
+    #[test]
+    fn keeps_only_included_projects() {
+        let (apps, skipped) = filtered(&["shop-*"], &[], &["shop-eu", "blog", "shop-us"]);
+        assert_eq!(apps, ["shop-eu", "shop-us"]);
+        assert_eq!(skipped, 1);
+    }
+
+    #[test]
+    fn exclusion_wins_over_inclusion() {
+        let (apps, skipped) = filtered(&["shop-*"], &["*-us"], &["shop-eu", "blog", "shop-us"]);
+        assert_eq!(apps, ["shop-eu"]);
+        assert_eq!(skipped, 2);
+        let (apps, _) = filtered(&[], &["blog"], &["shop-eu", "blog"]);
+        assert_eq!(apps, ["shop-eu"]);
+    }
+
+    #[test]
+    fn reads_exclude_labels_and_lets_later_files_win() {
+        let root = TempDir::new("excludes");
+        let base = root.join("compose.yaml");
+        let override_file = root.join("compose.override.yaml");
+        fs::write(
+            &base,
+            "services:\n  web:\n    volumes:\n      - ./web:/data\n    labels:\n      dockup.exclude: \"cache/, *.log ,\"\n  db:\n    volumes:\n      - ./db:/var/lib/db\n    labels:\n      dockup.exclude: tmp\n",
+        )
+        .unwrap();
+        fs::write(
+            &override_file,
+            "services:\n  db:\n    labels:\n      - dockup.exclude=*.pid\n  cache:\n    volumes:\n      - ./cache:/data\n",
+        )
+        .unwrap();
+
+        let volumes = parse_volumes(&[base, override_file], &root).unwrap();
+        let excludes = |name: &str| {
+            volumes
+                .iter()
+                .find(|vol| vol.name == name)
+                .unwrap()
+                .excludes
+                .clone()
+        };
+        assert_eq!(excludes("./web"), ["cache/", "*.log"]);
+        assert_eq!(excludes("./db"), ["*.pid"]);
+        assert!(excludes("./cache").is_empty());
+    }
+}
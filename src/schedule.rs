@@ -0,0 +1,136 @@
+//! Backup of the installed schedule and disaster recovery (`dockup config recover`).
+//!
+//! Every backup run uploads `schedule.json` next to `config.json`. It holds the dockup
+//! crontab entries and/or the systemd units (backup and scheduled verify), so a rebuilt host gets its config and its
+//! schedule back with one command and resumes backing itself up.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{config::Config, crontab, crypto, state, systemd, transport};
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Schedule {
+    /// Installed crontab line, see `dockup interval install`.
+    pub crontab_entry: Option<String>,
+    /// Installed timer, see `dockup setup-systemd`.
+    pub systemd: Option<SystemdUnits>,
+    /// Installed `verify_schedule` crontab line.
+    #[serde(default)]
+    pub verify_crontab_entry: Option<String>,
+    /// Installed `verify_schedule` timer.
+    #[serde(default)]
+    pub verify_systemd: Option<SystemdUnits>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SystemdUnits {
+    pub dir: PathBuf,
+    pub service: String,
+    pub timer: String,
+    pub enabled: bool,
+}
+
+impl Schedule {
+    /// Reads the schedule installed on this host.
+    pub fn capture() -> Self {
+        let dir = Path::new(systemd::DEFAULT_DIR);
+        let units = |units: &systemd::Units| {
+            systemd::installed(dir, units).map(|(service, timer, enabled)| SystemdUnits {
+                dir: dir.to_path_buf(),
+                service,
+                timer,
+                enabled,
+            })
+        };
+        Schedule {
+            crontab_entry: crontab::entry().ok().flatten(),
+            systemd: units(&systemd::BACKUP),
+            verify_crontab_entry: crontab::verify_entry().ok().flatten(),
+            verify_systemd: units(&systemd::VERIFY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.crontab_entry.is_none() && self.systemd.is_none()
+    }
+
+    /// Installs the captured schedule on this host.
+    pub fn reinstate(&self) -> Result<()> {
+        for entry in [&self.crontab_entry, &self.verify_crontab_entry]
+            .into_iter()
+            .flatten()
+        {
+            crontab::install_entry(entry)?;
+        }
+        for (names, units) in [
+            (&systemd::BACKUP, &self.systemd),
+            (&systemd::VERIFY, &self.verify_systemd),
+        ] {
+            if let Some(units) = units {
+                systemd::install_units(
+                    &units.dir,
+                    names,
+                    &units.service,
+                    &units.timer,
+                    units.enabled,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Uploads `schedule.json` to the target root.
+pub fn backup_schedule(config: &Config) -> Result<()> {
+    let schedule = Schedule::capture();
+    let local = PathBuf::from("/tmp").join("dockup_schedule.json");
+    fs::write(&local, serde_json::to_string_pretty(&schedule)?)?;
+    let result = transport::open(config).put(
+        &local,
+        &format!("{}/schedule.json", config.remote_backup_path),
+    );
+    fs::remove_file(&local).ok();
+    result
+}
+
+/// Restores `config.json`, the schedule and the state from the target configured in
+/// `config`.
+pub fn recover(config: &Config) -> Result<()> {
+    let transport = transport::open(config);
+    let config_json = crypto::read_sealed(
+        config,
+        &*transport,
+        &format!("{}/config.json", config.remote_backup_path),
+    )
+    .context("No readable config.json found on the backup target")?;
+    let path = dirs::home_dir()
+        .context("Failed to get home directory")?
+        .join(".dockup")
+        .join("config.json");
+    if path.exists() {
+        let previous = path.with_extension("json.before-recover");
+        fs::copy(&path, &previous)?;
+        log::info!("Current config saved to {}", previous.display());
+    }
+    fs::write(&path, config_json)?;
+    log::info!("✅ Config restored to {}", path.display());
+
+    match transport.read(&format!("{}/schedule.json", config.remote_backup_path)) {
+        Ok(json) => {
+            let schedule: Schedule =
+                serde_json::from_str(&json).context("Invalid schedule.json")?;
+            if schedule.is_empty() {
+                log::info!("No schedule was installed on the old host");
+            } else {
+                schedule.reinstate()?;
+            }
+        }
+        Err(_) => log::warn!(
+            "⚠️  No schedule.json on the target, install a schedule with `dockup interval install` or `dockup setup-systemd`"
+        ),
+    }
+    state::recover_state(config)
+}
@@ -0,0 +1,271 @@
+//! `dockup serve`: a small HTTP server exposing the catalog to Prometheus on `/metrics`.
+//!
+//! Every scrape reads the local catalog, so the gauges are up to date as soon as a backup
+//! run has recorded its snapshots. `GET /` lists the projects and `GET /projects/<name>`
+//! shows the recent snapshots of one with the outcome of every archive, the pages the
+//! email report links to (`dashboard_url`). Anything else gets a 404.
+
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::catalog::Catalog;
+
+/// Snapshots listed on a project page.
+const PAGE_SNAPSHOTS: usize = 30;
+
+/// Serves `/metrics` and the dashboard pages on `listen` until interrupted.
+pub async fn run(listen: &str) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen on {}", listen))?;
+    log::info!("📈 Serving metrics on http://{}/metrics", listen);
+    log::info!("📋 Dashboard on http://{}/", listen);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream).await {
+                log::warn!("⚠️  Request from {} failed: {:#}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(&mut stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // Drain the headers, the request has no body we care about
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => match tokio::task::spawn_blocking(metrics).await? {
+            Ok(body) => response("200 OK", "text/plain; version=0.0.4", &body),
+            Err(e) => {
+                log::error!("❌ Failed to read the catalog: {:#}", e);
+                response(
+                    "500 Internal Server Error",
+                    "text/plain",
+                    "catalog unavailable\n",
+                )
+            }
+        },
+        (Some("GET"), Some("/")) => page(tokio::task::spawn_blocking(index).await?),
+        (Some("GET"), Some(path)) if path.starts_with("/projects/") => {
+            let project = path.trim_start_matches("/projects/").to_string();
+            page(tokio::task::spawn_blocking(move || project_page(&project)).await?)
+        }
+        _ => response("404 Not Found", "text/plain", "not found\n"),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Turns a rendered page into a response, `None` meaning an unknown project.
+fn page(rendered: Result<Option<String>>) -> String {
+    match rendered {
+        Ok(Some(body)) => response("200 OK", "text/html; charset=utf-8", &body),
+        Ok(None) => response("404 Not Found", "text/plain", "not found\n"),
+        Err(e) => {
+            log::error!("❌ Failed to read the catalog: {:#}", e);
+            response(
+                "500 Internal Server Error",
+                "text/plain",
+                "catalog unavailable\n",
+            )
+        }
+    }
+}
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title></head>\
+         <body style=\"font-family: sans-serif; font-size: 14px;\">{}</body></html>",
+        escape(title),
+        body
+    )
+}
+
+/// Every project in the catalog with its newest successful snapshot.
+fn index() -> Result<Option<String>> {
+    let catalog = Catalog::open()?;
+    let mut body = String::from("<h1>dockup</h1>");
+    if let Some(run) = catalog.last_run()? {
+        write!(
+            body,
+            "<p>Last run {} ({}, {} of {} archives failed)</p>",
+            run.finished.format("%Y-%m-%d %H:%M"),
+            escape(&run.status),
+            run.failed_archives,
+            run.total_archives
+        )?;
+    }
+    body.push_str("<table border=\"1\" cellpadding=\"6\" style=\"border-collapse: collapse;\"><tr><th>Project</th><th>Last success</th><th>Failed archives</th></tr>");
+    for project in catalog.project_metrics()? {
+        write!(
+            body,
+            "<tr><td><a href=\"/projects/{}\">{}</a></td><td>{}</td><td>{}</td></tr>",
+            escape(&project.project),
+            escape(&project.project),
+            project.last_success.map_or("-".to_string(), |time| time
+                .format("%Y-%m-%d %H:%M")
+                .to_string()),
+            project.failed_archives
+        )?;
+    }
+    body.push_str("</table>");
+    Ok(Some(html_page("dockup", &body)))
+}
+
+/// The recent snapshots of `project` and the status of each archive.
+fn project_page(project: &str) -> Result<Option<String>> {
+    let snapshots = Catalog::open()?.project_snapshots(project, PAGE_SNAPSHOTS)?;
+    if snapshots.is_empty() {
+        return Ok(None);
+    }
+    let mut body = format!(
+        "<p><a href=\"/\">All projects</a></p><h1>{}</h1>",
+        escape(project)
+    );
+    for snapshot in &snapshots {
+        let meta = &snapshot.meta;
+        write!(
+            body,
+            "<h2 id=\"{}\">{}</h2><p>{}</p>",
+            meta.snapshot_folder(),
+            meta.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            escape(
+                &[
+                    meta.backup_type.map(|t| t.to_string()),
+                    (!meta.tags.is_empty()).then(|| format!("tags: {}", meta.tags.join(", "))),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(", ")
+            )
+        )?;
+        body.push_str("<table border=\"1\" cellpadding=\"6\" style=\"border-collapse: collapse;\"><tr><th>Name</th><th>Status</th><th>Type</th><th>Size</th><th>Duration</th></tr>");
+        for archive in &snapshot.archives {
+            write!(
+                body,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&archive.name),
+                escape(&archive.status),
+                escape(&archive.volume_type),
+                escape(&archive.size),
+                escape(&archive.duration)
+            )?;
+        }
+        body.push_str("</table>");
+    }
+    Ok(Some(html_page(project, &body)))
+}
+
+/// Escapes text for HTML.
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders the catalog in the Prometheus text exposition format.
+fn metrics() -> Result<String> {
+    let catalog = Catalog::open()?;
+    let projects = catalog.project_metrics()?;
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "# HELP dockup_last_success_timestamp_seconds Newest snapshot without failed archives."
+    )?;
+    writeln!(out, "# TYPE dockup_last_success_timestamp_seconds gauge")?;
+    for project in &projects {
+        if let Some(success) = project.last_success {
+            writeln!(
+                out,
+                "dockup_last_success_timestamp_seconds{{project=\"{}\"}} {}",
+                label(&project.project),
+                success.timestamp()
+            )?;
+        }
+    }
+    writeln!(
+        out,
+        "# HELP dockup_last_snapshot_bytes Uploaded size of the newest snapshot."
+    )?;
+    writeln!(out, "# TYPE dockup_last_snapshot_bytes gauge")?;
+    for project in &projects {
+        writeln!(
+            out,
+            "dockup_last_snapshot_bytes{{project=\"{}\"}} {}",
+            label(&project.project),
+            project.last_bytes
+        )?;
+    }
+    writeln!(
+        out,
+        "# HELP dockup_failed_archives Failed archives over all recorded snapshots."
+    )?;
+    writeln!(out, "# TYPE dockup_failed_archives gauge")?;
+    for project in &projects {
+        writeln!(
+            out,
+            "dockup_failed_archives{{project=\"{}\"}} {}",
+            label(&project.project),
+            project.failed_archives
+        )?;
+    }
+
+    if let Some(run) = catalog.last_run()? {
+        writeln!(
+            out,
+            "# HELP dockup_last_run_timestamp_seconds Time the last backup run finished."
+        )?;
+        writeln!(out, "# TYPE dockup_last_run_timestamp_seconds gauge")?;
+        writeln!(
+            out,
+            "dockup_last_run_timestamp_seconds {}",
+            run.finished.timestamp()
+        )?;
+        writeln!(
+            out,
+            "# HELP dockup_last_run_failed_archives Failed archives of the last backup run."
+        )?;
+        writeln!(out, "# TYPE dockup_last_run_failed_archives gauge")?;
+        writeln!(
+            out,
+            "dockup_last_run_failed_archives {}",
+            run.failed_archives
+        )?;
+    }
+    Ok(out)
+}
+
+/// Escapes a label value.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
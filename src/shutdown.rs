@@ -0,0 +1,80 @@
+//! Graceful shutdown of backups and restores on SIGINT/SIGTERM.
+//!
+//! The first signal only sets a flag: the archive or restore item in progress is finished,
+//! nothing new is started. An interrupted backup deletes the incomplete snapshot folders on
+//! the target, so they are never mistaken for a complete snapshot, and the run is recorded
+//! as `aborted` in the catalog, `last_run.json` and the report. A second signal quits at
+//! once.
+
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Label of the helper containers streaming Docker volumes, to find leftovers.
+pub const HELPER_LABEL: &str = "dockup.helper";
+
+/// Status of archives and restore items not done because of a shutdown.
+pub const ABORTED: &str = "⏹ Aborted";
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Handles SIGINT and SIGTERM for the rest of the process. Must be called inside the
+/// runtime.
+pub fn install() {
+    tokio::spawn(async {
+        let (Ok(mut interrupt), Ok(mut terminate)) = (
+            signal(SignalKind::interrupt()),
+            signal(SignalKind::terminate()),
+        ) else {
+            log::warn!("⚠️  Failed to install the signal handlers");
+            return;
+        };
+        loop {
+            tokio::select! {
+                _ = interrupt.recv() => {}
+                _ = terminate.recv() => {}
+            }
+            if REQUESTED.swap(true, Ordering::SeqCst) {
+                log::error!("❌ Quitting immediately");
+                remove_helpers();
+                std::process::exit(130);
+            }
+            log::warn!(
+                "⏹  Stopping after the current archive, send the signal again to quit immediately"
+            );
+        }
+    });
+}
+
+/// Whether a shutdown was requested.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Removes helper containers left behind when their `docker run` was interrupted.
+pub fn remove_helpers() {
+    let output = Command::new("docker")
+        .args(["ps", "-aq", "--filter"])
+        .arg(format!("label={}", HELPER_LABEL))
+        .stderr(Stdio::null())
+        .output();
+    let Ok(output) = output else {
+        return;
+    };
+    let ids = String::from_utf8_lossy(&output.stdout);
+    let ids: Vec<&str> = ids.split_whitespace().collect();
+    if ids.is_empty() {
+        return;
+    }
+    let removed = Command::new("docker")
+        .args(["rm", "-f"])
+        .args(&ids)
+        .stdout(Stdio::null())
+        .status();
+    match removed {
+        Ok(status) if status.success() => {
+            log::info!("🧹 Removed {} helper containers", ids.len())
+        }
+        _ => log::warn!("⚠️  Failed to remove helper containers {}", ids.join(" ")),
+    }
+}
@@ -0,0 +1,180 @@
+//! Backup of dockup's own state for disaster recovery (`.state/` on the target).
+//!
+//! Next to `config.json` and `schedule.json` every backup run uploads what else a rebuilt
+//! host needs to pick up where the old one stopped: the catalog as an export, the hook
+//! scripts the hook commands call by absolute path and the image pin files of the projects.
+//! `state.json` lists them with their original paths. `dockup config recover` merges the
+//! catalog and puts the files back, keeping differing local ones as `.before-recover`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    catalog::{Catalog, CatalogExport},
+    config::{Config, HooksConfig},
+    crypto, images,
+    scanner::BackupApplication,
+    transport::{self, StorageBackend},
+};
+
+/// Folder below `remote_backup_path` holding the state. Remote scans skip folders with a
+/// dot, so it never shows up as a project.
+const STATE_DIR: &str = ".state";
+
+#[derive(Serialize, Deserialize, Default)]
+struct StateManifest {
+    /// Whether `catalog.json` was uploaded.
+    catalog: bool,
+    files: Vec<StateFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StateFile {
+    /// File name in `.state/`.
+    name: String,
+    /// Where it lived on the host.
+    path: PathBuf,
+}
+
+/// Uploads the catalog, hook scripts and pin files of `apps` to `.state/`.
+pub fn backup_state(config: &Config, apps: &[BackupApplication]) -> Result<()> {
+    let transport = transport::open(config);
+    let remote = format!("{}/{}", config.remote_backup_path, STATE_DIR);
+    transport.mkdir(&remote)?;
+    let mut manifest = StateManifest::default();
+
+    match Catalog::open().and_then(|catalog| catalog.export()) {
+        Ok(export) => {
+            upload(
+                config,
+                &*transport,
+                &serde_json::to_string(&export)?,
+                &format!("{}/catalog.json", remote),
+            )?;
+            manifest.catalog = true;
+        }
+        Err(e) => log::warn!("⚠️  Catalog not included in the state backup: {e}"),
+    }
+
+    let mut files = hook_scripts(config);
+    files.extend(
+        apps.iter()
+            .map(|app| app.application_path.join(images::PIN_FILE))
+            .filter(|path| path.is_file()),
+    );
+    files.sort();
+    files.dedup();
+    for path in files {
+        let name = path
+            .to_string_lossy()
+            .trim_start_matches('/')
+            .replace('/', "_");
+        match crypto::put_sealed(config, &*transport, &path, &format!("{}/{}", remote, name)) {
+            Ok(_) => manifest.files.push(StateFile { name, path }),
+            Err(e) => log::warn!("⚠️  Failed to upload {}: {e}", path.display()),
+        }
+    }
+
+    upload(
+        config,
+        &*transport,
+        &serde_json::to_string_pretty(&manifest)?,
+        &format!("{}/state.json", remote),
+    )?;
+    log::info!(
+        "✅ State uploaded ({} files{})",
+        manifest.files.len(),
+        if manifest.catalog { ", catalog" } else { "" }
+    );
+    Ok(())
+}
+
+/// Absolute paths of existing files named in the hook commands, e.g. `/opt/hooks/stop.sh`.
+/// Scripts relative to a project are in its repo archive already.
+fn hook_scripts(config: &Config) -> Vec<PathBuf> {
+    std::iter::once(&config.hooks)
+        .chain(config.project_hooks.values())
+        .flat_map(|hooks: &HooksConfig| {
+            [
+                &hooks.pre_backup,
+                &hooks.post_backup,
+                &hooks.on_failure,
+                &hooks.post_restore,
+            ]
+        })
+        .flatten()
+        .flat_map(|command| command.split_whitespace())
+        .map(|word| PathBuf::from(word.trim_matches(|c| c == '"' || c == '\'')))
+        .filter(|path| path.is_absolute() && path.is_file())
+        .collect()
+}
+
+fn upload(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    content: &str,
+    remote: &str,
+) -> Result<()> {
+    let local = std::env::temp_dir().join(format!("dockup_state_{}", std::process::id()));
+    fs::write(&local, content)?;
+    let result = crypto::put_sealed(config, transport, &local, remote);
+    fs::remove_file(&local).ok();
+    result.map(|_| ())
+}
+
+/// Merges the uploaded catalog into the local one and puts the state files back. A file
+/// whose directory doesn't exist (a project not restored yet) is skipped.
+pub fn recover_state(config: &Config) -> Result<()> {
+    let transport = transport::open(config);
+    let remote = format!("{}/{}", config.remote_backup_path, STATE_DIR);
+    let Ok(manifest) = crypto::read_sealed(config, &*transport, &format!("{}/state.json", remote))
+    else {
+        log::warn!(
+            "⚠️  No state on the target, the catalog can be rebuilt with `dockup catalog rebuild`"
+        );
+        return Ok(());
+    };
+    let manifest: StateManifest = serde_json::from_str(&manifest).context("Invalid state.json")?;
+
+    if manifest.catalog {
+        let export: CatalogExport = serde_json::from_str(&crypto::read_sealed(
+            config,
+            &*transport,
+            &format!("{}/catalog.json", remote),
+        )?)
+        .context("Invalid catalog.json")?;
+        Catalog::open()?.import(&export)?;
+        log::info!("✅ Catalog restored ({} snapshots)", export.snapshots.len());
+    }
+
+    for file in &manifest.files {
+        if !file.path.parent().is_some_and(Path::is_dir) {
+            log::info!(
+                "⏭️  {} skipped, its directory doesn't exist (restore the project first)",
+                file.path.display()
+            );
+            continue;
+        }
+        let downloaded = file.path.with_extension("recovering");
+        crypto::get_sealed(
+            config,
+            &*transport,
+            &format!("{}/{}", remote, file.name),
+            &downloaded,
+        )?;
+        if file.path.exists() {
+            if fs::read(&file.path)? == fs::read(&downloaded)? {
+                fs::remove_file(&downloaded)?;
+                continue;
+            }
+            let mut previous = file.path.clone().into_os_string();
+            previous.push(".before-recover");
+            fs::rename(&file.path, &previous)?;
+        }
+        fs::rename(&downloaded, &file.path)?;
+        log::info!("✅ Restored {}", file.path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,201 @@
+//! `dockup stats`: backup size, duration and success of each project over time, from the
+//! catalog.
+//!
+//! The table lists per project the number of snapshots in the period, how many of them
+//! succeeded completely, the size of the newest snapshot, its growth per month over the
+//! period and the average archive time. With `--project` every snapshot is listed as
+//! well. `--format csv` writes one line per snapshot for spreadsheets, `--format json`
+//! both the summaries and the snapshots.
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    catalog::{Catalog, SnapshotRecord},
+    plain_println,
+    utils::format_bytes,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+/// One snapshot in the history.
+#[derive(Serialize)]
+struct Point {
+    project: String,
+    timestamp: DateTime<Local>,
+    /// Uploaded bytes of all archives.
+    bytes: u64,
+    /// Time to archive and upload all archives, one after another.
+    seconds: f64,
+    archives: usize,
+    failed_archives: usize,
+}
+
+#[derive(Serialize)]
+struct ProjectStats {
+    project: String,
+    snapshots: usize,
+    successful: usize,
+    latest_bytes: u64,
+    /// Change of the snapshot size per 30 days over the period, in percent.
+    growth_per_month: Option<f64>,
+    average_seconds: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Export<'a> {
+    since: DateTime<Local>,
+    projects: &'a [ProjectStats],
+    snapshots: &'a [Point],
+}
+
+pub fn run_stats(project: Option<&str>, days: u32, format: Format) -> Result<()> {
+    let since = Local::now() - chrono::Duration::days(days as i64);
+    let points: Vec<Point> = Catalog::open()?
+        .history(since, project)?
+        .iter()
+        .map(point)
+        .collect();
+    if points.is_empty() && format == Format::Table {
+        plain_println!(
+            "No snapshots in the last {} days in the catalog, see `dockup catalog refresh`",
+            days
+        );
+        return Ok(());
+    }
+
+    let mut by_project: BTreeMap<&str, Vec<&Point>> = BTreeMap::new();
+    for point in &points {
+        by_project.entry(&point.project).or_default().push(point);
+    }
+    let projects: Vec<ProjectStats> = by_project
+        .into_iter()
+        .map(|(project, points)| summarize(project, &points))
+        .collect();
+
+    match format {
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&Export {
+                since,
+                projects: &projects,
+                snapshots: &points,
+            })?
+        ),
+        Format::Csv => {
+            println!("project,timestamp,bytes,seconds,archives,failed_archives");
+            for point in &points {
+                println!(
+                    "{},{},{},{:.2},{},{}",
+                    point.project,
+                    point.timestamp.to_rfc3339(),
+                    point.bytes,
+                    point.seconds,
+                    point.archives,
+                    point.failed_archives
+                );
+            }
+        }
+        Format::Table => {
+            print_table(&projects, days);
+            if project.is_some() {
+                println!();
+                plain_println!(
+                    "{:<17} {:>12} {:>10} {:>8}",
+                    "Snapshot",
+                    "Size",
+                    "Duration",
+                    "Status"
+                );
+                for point in points.iter().rev() {
+                    plain_println!(
+                        "{:<17} {:>12} {:>9.0}s {:>8}",
+                        point.timestamp.format("%Y-%m-%d %H:%M"),
+                        format_bytes(point.bytes),
+                        point.seconds,
+                        match point.failed_archives {
+                            0 => "✅".to_string(),
+                            failed => format!("❌ {}", failed),
+                        }
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn point(snapshot: &SnapshotRecord) -> Point {
+    let archives = &snapshot.archives;
+    Point {
+        project: snapshot.meta.name.clone(),
+        timestamp: snapshot.meta.timestamp,
+        bytes: archives.iter().filter_map(|a| a.compressed_bytes).sum(),
+        seconds: archives.iter().filter_map(|a| a.seconds).sum(),
+        archives: archives.len(),
+        failed_archives: archives.iter().filter(|a| a.status != "✅").count(),
+    }
+}
+
+fn summarize(project: &str, points: &[&Point]) -> ProjectStats {
+    let successful: Vec<&&Point> = points.iter().filter(|p| p.failed_archives == 0).collect();
+    // Growth between the first and the last complete snapshot, failed ones are smaller
+    let growth_per_month = match (successful.first(), successful.last()) {
+        (Some(first), Some(last)) if first.bytes > 0 && last.timestamp > first.timestamp => {
+            let days = (last.timestamp - first.timestamp).num_seconds() as f64 / 86_400.0;
+            let change = (last.bytes as f64 - first.bytes as f64) / first.bytes as f64;
+            Some(change * 100.0 * 30.0 / days.max(1.0))
+        }
+        _ => None,
+    };
+    let timed: Vec<f64> = points
+        .iter()
+        .map(|p| p.seconds)
+        .filter(|s| *s > 0.0)
+        .collect();
+    ProjectStats {
+        project: project.to_string(),
+        snapshots: points.len(),
+        successful: successful.len(),
+        latest_bytes: successful.last().map_or(0, |p| p.bytes),
+        growth_per_month,
+        average_seconds: (!timed.is_empty())
+            .then(|| timed.iter().sum::<f64>() / timed.len() as f64),
+    }
+}
+
+fn print_table(projects: &[ProjectStats], days: u32) {
+    plain_println!("Last {} days", days);
+    plain_println!(
+        "{:<24} {:>9} {:>8} {:>12} {:>12} {:>10}",
+        "Project",
+        "Snapshots",
+        "Success",
+        "Size",
+        "Growth/mo",
+        "Avg time"
+    );
+    for stats in projects {
+        plain_println!(
+            "{:<24} {:>9} {:>7.0}% {:>12} {:>12} {:>10}",
+            stats.project,
+            stats.snapshots,
+            stats.successful as f64 * 100.0 / stats.snapshots as f64,
+            format_bytes(stats.latest_bytes),
+            stats
+                .growth_per_month
+                .map_or("-".to_string(), |growth| format!("{:+.1}%", growth)),
+            stats
+                .average_seconds
+                .map_or("-".to_string(), |seconds| format!("{:.0}s", seconds))
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! `dockup setup-systemd`: service and timer units for scheduled backups.
+//!
+//! The timer schedule is derived from `suggested_cron()`, so it follows the configured
+//! interval the same way the crontab suggestion does. With `verify_schedule` set,
+//! `dockup-verify.service` and `.timer` run `dockup verify -s` on their own schedule.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::Config;
+
+/// Names of a service unit and the timer starting it.
+pub struct Units {
+    service: &'static str,
+    timer: &'static str,
+}
+
+pub const BACKUP: Units = Units {
+    service: "dockup-backup.service",
+    timer: "dockup-backup.timer",
+};
+
+pub const VERIFY: Units = Units {
+    service: "dockup-verify.service",
+    timer: "dockup-verify.timer",
+};
+
+pub const DEFAULT_DIR: &str = "/etc/systemd/system";
+
+/// Writes the service and timer units to `dir`, and the verify units if `verify_schedule`
+/// is set. With `enable` systemd is reloaded and the timers enabled and started.
+pub fn setup(config: &Config, dir: &Path, enable: bool) -> Result<()> {
+    let cron = config
+        .suggested_cron()
+        .context("No backup interval is configured, set one with `dockup interval set`")?;
+    write_timer(dir, &BACKUP, "backup", &cron, "backup -s", enable)?;
+    if let Some(schedule) = &config.verify_schedule {
+        write_timer(dir, &VERIFY, "verify", schedule, "verify -s", enable)?;
+    }
+    Ok(())
+}
+
+fn write_timer(
+    dir: &Path,
+    units: &Units,
+    what: &str,
+    cron: &str,
+    command: &str,
+    enable: bool,
+) -> Result<()> {
+    let calendar = on_calendar(cron)
+        .with_context(|| format!("Cannot convert cron schedule `{}` to systemd", cron))?;
+    let exe = std::env::current_exe().context("Failed to locate the dockup binary")?;
+    let user = std::env::var("USER").unwrap_or_else(|_| "root".into());
+
+    let service = format!(
+        "[Unit]\n\
+         Description=Dockup scheduled {}\n\
+         Wants=network-online.target\n\
+         After=network-online.target docker.service\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         NotifyAccess=main\n\
+         User={}\n\
+         ExecStart={} {}\n",
+        what,
+        user,
+        exe.display(),
+        command
+    );
+    let timer = format!(
+        "[Unit]\n\
+         Description=Run dockup {} ({})\n\
+         \n\
+         [Timer]\n\
+         OnCalendar={}\n\
+         Persistent=true\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        what, cron, calendar
+    );
+
+    log::info!("🕒 {} timer schedule: OnCalendar={}", what, calendar);
+    install_units(dir, units, &service, &timer, enable)
+}
+
+/// Unit files of an installed timer in `dir` (service, timer) and whether it is enabled.
+pub fn installed(dir: &Path, units: &Units) -> Option<(String, String, bool)> {
+    let service = fs::read_to_string(dir.join(units.service)).ok()?;
+    let timer = fs::read_to_string(dir.join(units.timer)).ok()?;
+    let enabled = Command::new("systemctl")
+        .args(["is-enabled", "--quiet", units.timer])
+        .status()
+        .is_ok_and(|status| status.success());
+    Some((service, timer, enabled))
+}
+
+/// Writes the given unit files to `dir`, optionally enabling the timer.
+pub fn install_units(
+    dir: &Path,
+    units: &Units,
+    service: &str,
+    timer: &str,
+    enable: bool,
+) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    for (name, content) in [(units.service, service), (units.timer, timer)] {
+        let path = dir.join(name);
+        fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))?;
+        log::info!("📝 Wrote {}", path.display());
+    }
+
+    if enable {
+        systemctl(&["daemon-reload"])?;
+        systemctl(&["enable", "--now", units.timer])?;
+        log::info!("✅ {} enabled", units.timer);
+    } else {
+        log::info!(
+            "Enable it with: systemctl daemon-reload && systemctl enable --now {}",
+            units.timer
+        );
+    }
+    Ok(())
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl")
+        .args(args)
+        .status()
+        .context("Failed to run systemctl")?;
+    if !status.success() {
+        anyhow::bail!("systemctl {} failed", args.join(" "));
+    }
+    Ok(())
+}
+
+/// Converts a five-field cron expression as produced by `suggested_cron()` into a systemd
+/// `OnCalendar=` expression.
+fn on_calendar(cron: &str) -> Option<String> {
+    let fields: Vec<&str> = cron.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields.as_slice() else {
+        return None;
+    };
+    let weekdays = match *weekday {
+        "*" => String::new(),
+        list => {
+            let names = list
+                .split(',')
+                .map(|d| {
+                    let d: usize = d.parse().ok()?;
+                    ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"]
+                        .get(d % 7)
+                        .copied()
+                })
+                .collect::<Option<Vec<_>>>()?;
+            format!("{} ", names.join(","))
+        }
+    };
+    Some(format!(
+        "{}*-{}-{} {}:{}:00",
+        weekdays,
+        field(month, 1)?,
+        field(day, 1)?,
+        field(hour, 0)?,
+        field(minute, 0)?
+    ))
+}
+
+/// Translates a single cron field; `first` is the lowest value of the field.
+fn field(value: &str, first: u32) -> Option<String> {
+    if value == "*" {
+        return Some("*".into());
+    }
+    if let Some((range, step)) = value.split_once('/') {
+        let step: u32 = step.parse().ok()?;
+        let start = match range {
+            "*" => first,
+            range => range.split('-').next()?.parse().ok()?,
+        };
+        return Some(format!("{:02}/{}", start, step));
+    }
+    let value: u32 = value.parse().ok()?;
+    Some(format!("{:02}", value))
+}
@@ -0,0 +1,256 @@
+//! OpenTelemetry traces of backup runs (`otlp_endpoint`).
+//!
+//! Spans are built from the progress events (see [`crate::progress`]): one for the run, one
+//! per project and, per archive, one for creating it and one for its transfer. When the run
+//! is done they are sent in one batch as OTLP/HTTP JSON with `curl`, so a tracing backend
+//! shows where a long backup spends its time.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+
+static TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+
+struct Span {
+    id: String,
+    parent: Option<String>,
+    name: String,
+    start: u128,
+    end: Option<u128>,
+    project: Option<String>,
+    volume: Option<String>,
+    bytes: Option<u64>,
+    failed: bool,
+}
+
+/// Spans of the current run. Open spans are found by kind, project and volume.
+struct Trace {
+    endpoint: String,
+    trace_id: String,
+    spans: Vec<Span>,
+    open: HashMap<(&'static str, String, String), usize>,
+}
+
+/// Starts collecting spans for a run if an OTLP endpoint is configured (`otlp_endpoint` or
+/// `OTEL_EXPORTER_OTLP_ENDPOINT`).
+pub fn init(config: &Config) {
+    let endpoint = config
+        .otlp_endpoint
+        .clone()
+        .or_else(|| std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok())
+        .filter(|endpoint| !endpoint.is_empty());
+    *lock() = endpoint.map(|endpoint| Trace {
+        endpoint,
+        trace_id: random_hex(16),
+        spans: Vec::new(),
+        open: HashMap::new(),
+    });
+}
+
+/// Updates the spans for a progress event, see [`crate::progress::emit`].
+pub fn record(phase: &str, project: Option<&str>, volume: Option<&str>, bytes: Option<u64>) {
+    let mut guard = lock();
+    let Some(trace) = guard.as_mut() else {
+        return;
+    };
+    let project = project.unwrap_or_default();
+    let volume = volume.unwrap_or_default();
+    match phase {
+        "scan" => trace.start("run", "", "", "backup run".to_string()),
+        "project_start" => trace.start("project", project, "", format!("project {}", project)),
+        "archive" => trace.start("archive", project, volume, format!("archive {}", volume)),
+        "upload" => {
+            trace.end("archive", project, volume, false, None);
+            trace.start("upload", project, volume, format!("upload {}", volume));
+        }
+        "uploaded" | "failed" => {
+            let failed = phase == "failed";
+            trace.end("archive", project, volume, failed, None);
+            trace.end("upload", project, volume, failed, bytes);
+        }
+        "project_done" => trace.end("project", project, "", false, None),
+        "done" => trace.end("run", "", "", false, None),
+        _ => {}
+    }
+}
+
+/// Sends the spans of the run to the OTLP endpoint. Failures are only logged, tracing
+/// must not fail a backup.
+pub fn export() {
+    let Some(mut trace) = lock().take() else {
+        return;
+    };
+    let now = now();
+    for span in &mut trace.spans {
+        span.end.get_or_insert(now);
+    }
+    let url = format!("{}/v1/traces", trace.endpoint.trim_end_matches('/'));
+    match post(&url, &trace.payload()) {
+        Ok(()) => log::info!("📡 Sent {} spans to {}", trace.spans.len(), url),
+        Err(e) => log::warn!("⚠️  Failed to export traces to {}: {e}", url),
+    }
+}
+
+impl Trace {
+    fn start(&mut self, kind: &'static str, project: &str, volume: &str, name: String) {
+        let parent = match kind {
+            "run" => None,
+            "project" => self.open_id("run", "", ""),
+            _ => self.open_id("project", project, ""),
+        };
+        self.spans.push(Span {
+            id: random_hex(8),
+            parent,
+            name,
+            start: now(),
+            end: None,
+            project: (!project.is_empty()).then(|| project.to_string()),
+            volume: (!volume.is_empty()).then(|| volume.to_string()),
+            bytes: None,
+            failed: false,
+        });
+        self.open.insert(
+            (kind, project.to_string(), volume.to_string()),
+            self.spans.len() - 1,
+        );
+    }
+
+    fn end(
+        &mut self,
+        kind: &'static str,
+        project: &str,
+        volume: &str,
+        failed: bool,
+        bytes: Option<u64>,
+    ) {
+        let key = (kind, project.to_string(), volume.to_string());
+        if let Some(index) = self.open.remove(&key) {
+            let span = &mut self.spans[index];
+            span.end = Some(now());
+            span.failed = failed;
+            span.bytes = bytes.or(span.bytes);
+        }
+    }
+
+    fn open_id(&self, kind: &'static str, project: &str, volume: &str) -> Option<String> {
+        let key = (kind, project.to_string(), volume.to_string());
+        self.open
+            .get(&key)
+            .map(|&index| self.spans[index].id.clone())
+    }
+
+    fn payload(&self) -> Value {
+        let spans: Vec<Value> = self
+            .spans
+            .iter()
+            .map(|span| {
+                let mut attributes = Vec::new();
+                if let Some(project) = &span.project {
+                    attributes.push(attribute(
+                        "dockup.project",
+                        json!({ "stringValue": project }),
+                    ));
+                }
+                if let Some(volume) = &span.volume {
+                    attributes.push(attribute("dockup.volume", json!({ "stringValue": volume })));
+                }
+                if let Some(bytes) = span.bytes {
+                    attributes.push(attribute(
+                        "dockup.bytes",
+                        json!({ "intValue": bytes.to_string() }),
+                    ));
+                }
+                json!({
+                    "traceId": self.trace_id,
+                    "spanId": span.id,
+                    "parentSpanId": span.parent.clone().unwrap_or_default(),
+                    "name": span.name,
+                    "kind": 1,
+                    "startTimeUnixNano": span.start.to_string(),
+                    "endTimeUnixNano": span.end.unwrap_or(span.start).to_string(),
+                    "attributes": attributes,
+                    "status": { "code": if span.failed { 2 } else { 1 } },
+                })
+            })
+            .collect();
+        json!({
+            "resourceSpans": [{
+                "resource": { "attributes": [
+                    attribute("service.name", json!({ "stringValue": "dockup" })),
+                    attribute("service.version", json!({ "stringValue": env!("CARGO_PKG_VERSION") })),
+                ]},
+                "scopeSpans": [{ "scope": { "name": "dockup" }, "spans": spans }],
+            }]
+        })
+    }
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn post(url: &str, payload: &Value) -> anyhow::Result<()> {
+    let mut child = Command::new("curl")
+        .args([
+            "-fsS",
+            "-X",
+            "POST",
+            "-H",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(payload.to_string().as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "curl exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Option<Trace>> {
+    // A panic while holding the lock leaves complete spans behind, keep using them
+    TRACE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos())
+        .unwrap_or_default()
+}
+
+/// `bytes` random bytes as hex, the format of OTLP trace and span ids.
+fn random_hex(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    if File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut buf))
+        .is_err()
+    {
+        // Ids only have to be unique within the trace
+        let fallback = now().to_be_bytes();
+        buf.copy_from_slice(&fallback[16 - bytes..]);
+    }
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,56 @@
+//! Helpers shared by the unit tests.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Fresh directory below the system temp dir, removed again when dropped, also when an
+/// assertion fails.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` has to be unique per test, tests run in parallel.
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("dockup-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs `f` with `HOME` set to `home`, where the catalog and the undo journal are kept. Tests
+/// doing so run one at a time, the variable is shared by the whole process.
+pub fn with_home<T>(home: &Path, f: impl FnOnce() -> T) -> T {
+    static HOME: Mutex<()> = Mutex::new(());
+    let _lock = HOME.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let previous = std::env::var_os("HOME");
+    std::env::set_var("HOME", home);
+    let result = f();
+    match previous {
+        Some(previous) => std::env::set_var("HOME", previous),
+        None => std::env::remove_var("HOME"),
+    }
+    result
+}
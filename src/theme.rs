@@ -0,0 +1,101 @@
+//! Color themes of the restore TUI (`tui.theme` and `tui.colors`).
+//!
+//! `dark` (default) and `light` set all colors, `tui.colors.<slot>` overrides single ones
+//! for a custom theme. Colors are names (`blue`, `lightyellow`, `reset` for the terminal's
+//! default), `#rrggbb` or ANSI indices.
+
+use anyhow::Result;
+use ratatui::style::{Color, Style};
+use std::collections::HashMap;
+
+use crate::config::TuiConfig;
+
+pub const THEMES: [&str; 2] = ["dark", "light"];
+
+/// Slots that can be set with `tui.colors.<slot>`.
+pub const SLOTS: [&str; 5] = ["text", "border", "accent", "popup_fg", "popup_bg"];
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    /// Lists and panel contents.
+    pub text: Color,
+    pub border: Color,
+    /// Tooltip and key hints.
+    pub accent: Color,
+    /// Help popup.
+    pub popup_fg: Color,
+    pub popup_bg: Color,
+}
+
+impl Theme {
+    pub fn from_config(config: &TuiConfig) -> Self {
+        let mut theme = match config.theme.as_deref() {
+            Some("light") => Theme::LIGHT,
+            _ => Theme::DARK,
+        };
+        theme.apply(&config.colors);
+        theme
+    }
+
+    const DARK: Theme = Theme {
+        text: Color::Reset,
+        border: Color::Reset,
+        accent: Color::LightBlue,
+        popup_fg: Color::White,
+        popup_bg: Color::DarkGray,
+    };
+
+    const LIGHT: Theme = Theme {
+        text: Color::Reset,
+        border: Color::Reset,
+        accent: Color::Blue,
+        popup_fg: Color::Black,
+        popup_bg: Color::Gray,
+    };
+
+    fn apply(&mut self, colors: &HashMap<String, String>) {
+        for (slot, value) in colors {
+            let Ok(color) = parse_color(value) else {
+                log::warn!(
+                    "⚠️  Ignoring tui.colors.{}: invalid color {:?}",
+                    slot,
+                    value
+                );
+                continue;
+            };
+            match slot.as_str() {
+                "text" => self.text = color,
+                "border" => self.border = color,
+                "accent" => self.accent = color,
+                "popup_fg" => self.popup_fg = color,
+                "popup_bg" => self.popup_bg = color,
+                _ => log::warn!("⚠️  Ignoring unknown color slot tui.colors.{}", slot),
+            }
+        }
+    }
+
+    pub fn text(&self) -> Style {
+        Style::default().fg(self.text)
+    }
+
+    pub fn border(&self) -> Style {
+        Style::default().fg(self.border)
+    }
+
+    pub fn accent(&self) -> Style {
+        Style::default().fg(self.accent)
+    }
+
+    pub fn popup(&self) -> Style {
+        Style::default().fg(self.popup_fg).bg(self.popup_bg)
+    }
+}
+
+pub fn parse_color(value: &str) -> Result<Color> {
+    value.parse().map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid color {:?}: expected a name, #rrggbb or 0-255",
+            value
+        )
+    })
+}
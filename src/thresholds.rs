@@ -0,0 +1,126 @@
+//! Size warnings for archives, configured under `size_warning`.
+//!
+//! Catches runaway data (log directories, caches) before it fills the backup disk: an
+//! archive above `max_archive_gb`, or one that grew by more than `max_growth_percent`
+//! compared to the newest snapshot from at least a week ago in the catalog.
+//!
+//! The opposite is worse: an archive far smaller than usual mostly means an empty bind
+//! mount or a volume that wasn't mounted, and the snapshot is useless for a restore.
+//! Archives that shrank by more than `max_shrink_percent` compared to their average are
+//! anomalies, which fail the run.
+
+use chrono::{Duration, Local};
+
+use crate::{
+    backup::BackupThingSummary, catalog::Catalog, config::Config, i18n::tf,
+    scanner::BackupApplication, utils::format_bytes,
+};
+
+/// Age of the snapshot growth is compared against.
+const GROWTH_WINDOW_DAYS: i64 = 7;
+
+/// Number of previous snapshots an archive's size is averaged over.
+const AVERAGE_SNAPSHOTS: usize = 10;
+
+/// An archive needs this many previous snapshots before it can be an anomaly.
+const MIN_SNAPSHOTS: usize = 3;
+
+/// Archives averaging less than this are too small for their size to tell anything.
+const MIN_AVERAGE_BYTES: f64 = 1_000_000.0;
+
+/// Warnings for the archives of a finished project backup, also logged.
+pub fn size_warnings(
+    config: &Config,
+    catalog: Option<&Catalog>,
+    app: &BackupApplication,
+    archives: &[BackupThingSummary],
+) -> Vec<String> {
+    let limits = &config.size_warning;
+    let mut warnings = Vec::new();
+    for archive in archives {
+        let Some(stats) = &archive.stats else {
+            continue;
+        };
+        let gb = stats.compressed_bytes as f64 / 1_000_000_000.0;
+        if let Some(max) = limits.max_archive_gb.filter(|max| gb > *max) {
+            warnings.push(tf(
+                "report.warn_size",
+                &[&archive.name, &format!("{:.1}", gb), &max],
+            ));
+        }
+
+        let Some(max) = limits.max_growth_percent else {
+            continue;
+        };
+        let before = Local::now() - Duration::days(GROWTH_WINDOW_DAYS);
+        let previous = catalog
+            .and_then(|c| {
+                c.archive_bytes_before(&app.name, &archive.name, before)
+                    .ok()
+            })
+            .flatten()
+            .filter(|bytes| *bytes > 0);
+        if let Some(previous) = previous {
+            let growth = (stats.compressed_bytes as f64 / previous as f64 - 1.0) * 100.0;
+            if growth > max {
+                warnings.push(tf(
+                    "report.warn_growth",
+                    &[
+                        &archive.name,
+                        &format!("{:.0}", growth),
+                        &GROWTH_WINDOW_DAYS,
+                    ],
+                ));
+            }
+        }
+    }
+    for warning in &warnings {
+        log::warn!("⚠️  {}: {}", app.name, warning);
+    }
+    warnings
+}
+
+/// Archives of a finished project backup that are far smaller than their average over the
+/// previous snapshots, also logged. Compares the uncompressed size, so compression,
+/// deduplication and encryption don't matter.
+pub fn size_anomalies(
+    config: &Config,
+    catalog: Option<&Catalog>,
+    app: &BackupApplication,
+    archives: &[BackupThingSummary],
+) -> Vec<String> {
+    let (Some(max), Some(catalog)) = (config.size_warning.max_shrink_percent, catalog) else {
+        return Vec::new();
+    };
+    let mut anomalies = Vec::new();
+    for archive in archives {
+        let Some(stats) = &archive.stats else {
+            continue;
+        };
+        let average = catalog
+            .archive_average(&app.name, &archive.name, AVERAGE_SNAPSHOTS)
+            .ok()
+            .flatten()
+            .filter(|(average, count)| *count >= MIN_SNAPSHOTS && *average >= MIN_AVERAGE_BYTES);
+        let Some((average, count)) = average else {
+            continue;
+        };
+        let shrink = (1.0 - stats.original_bytes as f64 / average) * 100.0;
+        if shrink > max {
+            anomalies.push(tf(
+                "report.anomaly",
+                &[
+                    &archive.name,
+                    &format_bytes(stats.original_bytes),
+                    &format!("{:.0}", shrink),
+                    &format_bytes(average as u64),
+                    &count,
+                ],
+            ));
+        }
+    }
+    for anomaly in &anomalies {
+        log::error!("🚨 {}: {}", app.name, anomaly);
+    }
+    anomalies
+}
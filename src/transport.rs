@@ -0,0 +1,866 @@
+//! Every remote operation dockup performs against the backup target goes through here.
+//!
+//! dockup only needs a small, fixed set of operations on the target, described by
+//! [`StorageBackend`]. The `ssh` backend maps them to:
+//!
+//! | Operation  | Remote command                                     |
+//! |------------|----------------------------------------------------|
+//! | mkdir      | `mkdir -p -- <path>`                               |
+//! | list       | `ls -1 -- <path>`                                  |
+//! | read       | `cat -- <path>`                                    |
+//! | delete     | `rm -rf -- <path>`                                 |
+//! | rename     | `mv -f -- <from> <to>`                             |
+//! | put        | `scp -t <path>` (legacy protocol)                  |
+//! | get        | `scp -f <path>` (legacy protocol)                  |
+//! | size       | `wc -c -- <path>`                                  |
+//! | read_range | `dd bs=<n> skip=<i> count=1 status=none if=<path>` |
+//! | sha256     | `sha256sum -- <path>`                              |
+//!
+//! With `remote_allowlist` enabled every path is additionally checked to live under
+//! `remote_backup_path`, so the backup account can be locked down with the forced command
+//! in `forced-command.sh`.
+//!
+//! Incremental backups additionally transfer directories with `rsync` (`sync_dir` /
+//! `fetch_dir`), which is not part of the hardened command set.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{
+    catalog::Catalog,
+    config::Config,
+    local::LocalBackend,
+    s3::S3Backend,
+    utils::{rsync_command, scp_command, ssh_command},
+};
+
+/// Storage operations dockup needs on a backup target. Paths are absolute and live under
+/// `remote_backup_path`.
+pub trait StorageBackend {
+    fn mkdir(&self, path: &str) -> Result<()>;
+    fn list(&self, path: &str) -> Result<Vec<String>>;
+    fn read(&self, path: &str) -> Result<String>;
+    fn delete(&self, path: &str) -> Result<()>;
+    /// Renames the file `from` to `to`, replacing `to` if it exists.
+    fn rename(&self, from: &str, to: &str) -> Result<()>;
+    fn put(&self, local: &Path, remote: &str) -> Result<()>;
+    fn get(&self, remote: &str, local: &Path) -> Result<()>;
+    fn size(&self, path: &str) -> Result<u64>;
+    /// Reads chunk `index` of `chunk_size` bytes (the last chunk may be shorter).
+    fn read_range(
+        &self,
+        path: &str,
+        index: u64,
+        chunk_size: u64,
+        throttle: &mut Throttle,
+    ) -> Result<Vec<u8>>;
+
+    /// Verifies the target is reachable using only allowed operations.
+    fn check(&self) -> Result<()>;
+
+    /// SHA-256 of `path` computed on the target, `None` if the backend can't compute it
+    /// without downloading the file.
+    fn sha256(&self, _path: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Mirrors the local directory `local` into `remote`. Files unchanged since the
+    /// directory `link_dest` on the target are hardlinked to it (`rsync --link-dest`).
+    fn sync_dir(&self, _local: &Path, _remote: &str, _link_dest: Option<&str>) -> Result<()> {
+        anyhow::bail!("Incremental backups are not supported by this storage backend")
+    }
+
+    /// Mirrors the remote directory `remote` into the local directory `local`. With
+    /// `keep_newer` local files newer than the remote copy are kept and nothing is deleted.
+    fn fetch_dir(&self, _remote: &str, _local: &Path, _keep_newer: bool) -> Result<()> {
+        anyhow::bail!("Incremental backups are not supported by this storage backend")
+    }
+
+    /// Downloads `remote` chunk by chunk into `<local>.part`. Every finished chunk is
+    /// flushed to disk and recorded in `<local>.part.json`, so an interrupted download
+    /// continues from the last complete chunk the next time the same file is requested.
+    /// With `rate_limit` (KiB/s) every chunk is read through a [`Throttle`], so the data
+    /// never arrives faster than the limit.
+    fn download(&self, remote: &str, local: &Path, rate_limit: Option<u64>) -> Result<()> {
+        let size = self.size(remote)?;
+        let part = with_suffix(local, ".part");
+        let state_path = with_suffix(local, ".part.json");
+
+        let mut state = DownloadState::load(&state_path)
+            .filter(|s| s.remote == remote && s.size == size && s.chunk_size == CHUNK_SIZE)
+            .unwrap_or(DownloadState {
+                remote: remote.to_string(),
+                size,
+                chunk_size: CHUNK_SIZE,
+                chunks: 0,
+            });
+        if state.chunks > 0 {
+            log::info!(
+                "⏯  Resuming download of {} at chunk {}/{}",
+                remote,
+                state.chunks,
+                size.div_ceil(CHUNK_SIZE)
+            );
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&part)
+            .with_context(|| format!("Failed to open {:?}", part))?;
+        // Drop anything written after the last recorded chunk
+        file.set_len(state.chunks * CHUNK_SIZE)?;
+        file.seek(SeekFrom::End(0))?;
+
+        let mut throttle = Throttle::new(rate_limit);
+        while state.chunks * CHUNK_SIZE < size {
+            let expected = CHUNK_SIZE.min(size - state.chunks * CHUNK_SIZE);
+            let chunk = self.read_range(remote, state.chunks, CHUNK_SIZE, &mut throttle)?;
+            if chunk.len() as u64 != expected {
+                anyhow::bail!(
+                    "Short read for chunk {} of {}: got {} of {} bytes",
+                    state.chunks,
+                    remote,
+                    chunk.len(),
+                    expected
+                );
+            }
+            file.write_all(&chunk)?;
+            file.sync_data()?;
+            state.chunks += 1;
+            state.save(&state_path)?;
+        }
+
+        fs::rename(&part, local).with_context(|| format!("Failed to move {:?}", part))?;
+        fs::remove_file(&state_path).ok();
+        Ok(())
+    }
+}
+
+/// Chunk size of resumable downloads.
+const CHUNK_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Amount read at once by a [`Throttle`], small enough that the limit holds within a chunk.
+const THROTTLE_STEP: usize = 64 * 1024;
+
+/// Keeps the rate data is read at below a limit in KiB/s by pausing after every small read.
+/// A remote sender writing into a pipe is held back by it, so the link isn't saturated in
+/// bursts. Without a limit data is read at full speed.
+pub struct Throttle {
+    limit: Option<u64>,
+    started: Instant,
+    transferred: u64,
+}
+
+impl Throttle {
+    pub fn new(limit: Option<u64>) -> Self {
+        Self {
+            limit: limit.filter(|limit| *limit > 0),
+            started: Instant::now(),
+            transferred: 0,
+        }
+    }
+
+    /// Reads `reader` to the end.
+    pub fn read_to_end(&mut self, reader: &mut dyn Read) -> std::io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut buf = vec![0; THROTTLE_STEP];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            data.extend_from_slice(&buf[..n]);
+            self.pace(n as u64);
+        }
+        Ok(data)
+    }
+
+    /// Runs `cmd` like [`Command::output`], reading its stdout through the throttle. Stderr
+    /// is drained on a separate thread, a command filling its pipe would block otherwise.
+    pub fn output(&mut self, cmd: &mut Command) -> std::io::Result<Output> {
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stderr = child.stderr.take().map(|mut err| {
+            std::thread::spawn(move || {
+                let mut data = Vec::new();
+                err.read_to_end(&mut data).map(|_| data)
+            })
+        });
+        let stdout = match child.stdout.take() {
+            Some(mut out) => self.read_to_end(&mut out),
+            None => Ok(Vec::new()),
+        };
+        let stdout = match stdout {
+            Ok(stdout) => stdout,
+            Err(e) => {
+                child.kill().ok();
+                child.wait().ok();
+                return Err(e);
+            }
+        };
+        let status = child.wait()?;
+        let stderr = match stderr {
+            Some(reader) => reader
+                .join()
+                .map_err(|_| std::io::Error::other("stderr reader panicked"))??,
+            None => Vec::new(),
+        };
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+
+    fn pace(&mut self, bytes: u64) {
+        self.transferred += bytes;
+        if let Some(limit) = self.limit {
+            let target = Duration::from_secs_f64(self.transferred as f64 / (limit * 1024) as f64);
+            if let Some(wait) = target.checked_sub(self.started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+    }
+}
+
+/// Progress of a resumable download, stored next to the partial file.
+#[derive(Serialize, Deserialize)]
+struct DownloadState {
+    remote: String,
+    size: u64,
+    chunk_size: u64,
+    chunks: u64,
+}
+
+impl DownloadState {
+    fn load(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(serde_json::to_string(self)?.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Opens the backend selected by `storage_backend`. The bytes it transfers are added to
+/// the target's monthly total in the catalog when it is dropped.
+pub fn open(cfg: &Config) -> Box<dyn StorageBackend + '_> {
+    let inner: Box<dyn StorageBackend + '_> = match cfg.storage_backend.as_str() {
+        "s3" => Box::new(S3Backend::new(cfg)),
+        "local" => Box::new(LocalBackend::new(cfg)),
+        _ => Box::new(SshBackend::new(cfg)),
+    };
+    Box::new(Metered {
+        inner,
+        target: &cfg.target_name,
+        uploaded: AtomicU64::new(0),
+        downloaded: AtomicU64::new(0),
+    })
+}
+
+/// Counts the file and metadata bytes moved through a backend. Directories mirrored with
+/// rsync (`sync_dir` / `fetch_dir`) are not counted.
+struct Metered<'a> {
+    inner: Box<dyn StorageBackend + 'a>,
+    target: &'a str,
+    uploaded: AtomicU64,
+    downloaded: AtomicU64,
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
+
+// `download` keeps the default implementation, so its chunks are counted in `read_range`
+impl StorageBackend for Metered<'_> {
+    fn mkdir(&self, path: &str) -> Result<()> {
+        self.inner.mkdir(path)
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<String>> {
+        self.inner.list(path)
+    }
+
+    fn read(&self, path: &str) -> Result<String> {
+        let content = self.inner.read(path)?;
+        self.downloaded
+            .fetch_add(content.len() as u64, Ordering::Relaxed);
+        Ok(content)
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        self.inner.delete(path)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        self.inner.rename(from, to)
+    }
+
+    fn put(&self, local: &Path, remote: &str) -> Result<()> {
+        self.inner.put(local, remote)?;
+        self.uploaded.fetch_add(file_size(local), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn get(&self, remote: &str, local: &Path) -> Result<()> {
+        self.inner.get(remote, local)?;
+        self.downloaded
+            .fetch_add(file_size(local), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn size(&self, path: &str) -> Result<u64> {
+        self.inner.size(path)
+    }
+
+    fn read_range(
+        &self,
+        path: &str,
+        index: u64,
+        chunk_size: u64,
+        throttle: &mut Throttle,
+    ) -> Result<Vec<u8>> {
+        let chunk = self.inner.read_range(path, index, chunk_size, throttle)?;
+        self.downloaded
+            .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        Ok(chunk)
+    }
+
+    fn check(&self) -> Result<()> {
+        self.inner.check()
+    }
+
+    fn sha256(&self, path: &str) -> Result<Option<String>> {
+        self.inner.sha256(path)
+    }
+
+    fn sync_dir(&self, local: &Path, remote: &str, link_dest: Option<&str>) -> Result<()> {
+        self.inner.sync_dir(local, remote, link_dest)
+    }
+
+    fn fetch_dir(&self, remote: &str, local: &Path, keep_newer: bool) -> Result<()> {
+        self.inner.fetch_dir(remote, local, keep_newer)
+    }
+}
+
+impl Drop for Metered<'_> {
+    fn drop(&mut self) {
+        let uploaded = *self.uploaded.get_mut();
+        let downloaded = *self.downloaded.get_mut();
+        if uploaded == 0 && downloaded == 0 {
+            return;
+        }
+        if let Err(e) = Catalog::open()
+            .and_then(|catalog| catalog.record_transfer(self.target, uploaded, downloaded))
+        {
+            log::warn!("⚠️  Failed to record transferred bytes in the catalog: {e}");
+        }
+    }
+}
+
+pub struct SshBackend<'a> {
+    cfg: &'a Config,
+}
+
+impl<'a> SshBackend<'a> {
+    pub fn new(cfg: &'a Config) -> Self {
+        Self { cfg }
+    }
+}
+
+impl StorageBackend for SshBackend<'_> {
+    fn mkdir(&self, path: &str) -> Result<()> {
+        self.run("mkdir -p --", path).map(|_| ())
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<String>> {
+        let output = self.run("ls -1 --", path)?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    fn read(&self, path: &str) -> Result<String> {
+        let output = self.run("cat --", path)?;
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        self.check_path(path)?;
+        if path.trim_end_matches('/') == self.cfg.remote_backup_path.trim_end_matches('/') {
+            anyhow::bail!("Refusing to delete the backup root {}", path);
+        }
+        self.run("rm -rf --", path).map(|_| ())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        self.check_path(from)?;
+        self.check_path(to)?;
+        self.exec(&format!(
+            "mv -f -- {} {}",
+            shell_quote(from),
+            shell_quote(to)
+        ))
+        .map(|_| ())
+    }
+
+    fn put(&self, local: &Path, remote: &str) -> Result<()> {
+        self.check_path(remote)?;
+        let output = self
+            .scp()
+            .arg(local)
+            .arg(self.remote_spec(remote))
+            .stdout(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "SCP upload failed: {:?}\nstderr: {}",
+                local,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    fn get(&self, remote: &str, local: &Path) -> Result<()> {
+        self.check_path(remote)?;
+        let output = self
+            .scp()
+            .arg(self.remote_spec(remote))
+            .arg(local)
+            .stdout(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "SCP download failed: {}\nstderr: {}",
+                remote,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    fn size(&self, path: &str) -> Result<u64> {
+        let output = self.run("wc -c --", path)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout
+            .split_whitespace()
+            .next()
+            .and_then(|size| size.parse().ok())
+            .with_context(|| format!("Unexpected wc output for {}: {}", path, stdout))
+    }
+
+    fn read_range(
+        &self,
+        path: &str,
+        index: u64,
+        chunk_size: u64,
+        throttle: &mut Throttle,
+    ) -> Result<Vec<u8>> {
+        self.check_path(path)?;
+        let cmd = format!(
+            "dd bs={} skip={} count=1 status=none if={}",
+            chunk_size,
+            index,
+            shell_quote(path)
+        );
+        let output = throttle
+            .output(ssh_command(self.cfg).arg(&cmd))
+            .with_context(|| format!("Failed to run over ssh: {}", cmd))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "SSH command failed: {}\nstderr: {}",
+                cmd,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(output.stdout)
+    }
+
+    fn sha256(&self, path: &str) -> Result<Option<String>> {
+        let output = self.run("sha256sum --", path)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let sum = stdout
+            .split_whitespace()
+            .next()
+            .with_context(|| format!("Unexpected sha256sum output for {}: {}", path, stdout))?;
+        Ok(Some(sum.to_string()))
+    }
+
+    fn check(&self) -> Result<()> {
+        self.list(&self.cfg.remote_backup_path).map(|_| ())
+    }
+
+    fn sync_dir(&self, local: &Path, remote: &str, link_dest: Option<&str>) -> Result<()> {
+        self.check_rsync()?;
+        let mut cmd = rsync_command(self.cfg);
+        cmd.arg("--delete");
+        if let Some(link_dest) = link_dest {
+            cmd.arg(format!("--link-dest={}", link_dest));
+        }
+        let mut source = local.as_os_str().to_owned();
+        source.push("/");
+        run_rsync(
+            cmd.arg(source)
+                .arg(format!("{}/", self.remote_spec(remote))),
+        )
+    }
+
+    fn fetch_dir(&self, remote: &str, local: &Path, keep_newer: bool) -> Result<()> {
+        self.check_rsync()?;
+        let mut cmd = rsync_command(self.cfg);
+        cmd.arg(if keep_newer { "--update" } else { "--delete" })
+            .arg(format!("{}/", self.remote_spec(remote)))
+            .arg(local);
+        run_rsync(&mut cmd)
+    }
+}
+
+impl SshBackend<'_> {
+    fn run(&self, op: &str, path: &str) -> Result<Output> {
+        self.check_path(path)?;
+        self.exec(&format!("{} {}", op, shell_quote(path)))
+    }
+
+    fn exec(&self, cmd: &str) -> Result<Output> {
+        let output = ssh_command(self.cfg)
+            .arg(cmd)
+            .output()
+            .with_context(|| format!("Failed to run over ssh: {}", cmd))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "SSH command failed: {}\nstderr: {}",
+                cmd,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(output)
+    }
+
+    fn scp(&self) -> std::process::Command {
+        let mut cmd = scp_command(self.cfg);
+        if self.cfg.remote_allowlist {
+            // SFTP mode would bypass the forced command, stick to `scp -t` / `scp -f`
+            cmd.arg("-O");
+        }
+        cmd
+    }
+
+    fn remote_spec(&self, path: &str) -> String {
+        format!("{}@{}:{}", self.cfg.ssh_user, self.cfg.ssh_host, path)
+    }
+
+    fn check_rsync(&self) -> Result<()> {
+        if self.cfg.remote_allowlist {
+            anyhow::bail!("Incremental backups use rsync, which remote_allowlist does not allow");
+        }
+        Ok(())
+    }
+
+    fn check_path(&self, path: &str) -> Result<()> {
+        if !self.cfg.remote_allowlist {
+            return Ok(());
+        }
+        let root = Path::new(&self.cfg.remote_backup_path);
+        let target = Path::new(path);
+        if target
+            .components()
+            .any(|c| matches!(c, Component::ParentDir | Component::CurDir))
+            || !target.starts_with(root)
+        {
+            anyhow::bail!(
+                "Remote path {} is outside the backup root {} (remote_allowlist is enabled)",
+                path,
+                self.cfg.remote_backup_path
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Runs a prepared `rsync` command, failing with its stderr.
+pub fn run_rsync(cmd: &mut Command) -> Result<()> {
+    let output = cmd
+        .stdout(Stdio::null())
+        .output()
+        .context("Failed to run rsync, is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!("rsync failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+/// Quotes a path for the remote shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::RawConfig, testing::TempDir};
+
+    fn config(root: &Path, remote_allowlist: bool) -> Config {
+        let raw: RawConfig = serde_json::from_value(serde_json::json!({
+            "docker_parent": [],
+            "remote_backup_path": root,
+            "ssh_user": "backup",
+            "ssh_host": "target",
+            "ssh_key": "",
+            "ssh_port": 22,
+            "email_host": "",
+            "email_port": 465,
+            "email_user": "",
+            "email_password": "",
+            "receiver_mail": "",
+            "interval": { "hour": 0, "day": 1, "week": 0, "month": 0, "year": 0 },
+            "remote_allowlist": remote_allowlist,
+        }))
+        .unwrap();
+        raw.finalize().unwrap()
+    }
+
+    /// Fresh backup root with a project folder holding `meta.json`, and a file next to it
+    /// that must stay out of reach.
+    fn backup_root(name: &str) -> TempDir {
+        let dir = TempDir::new(name);
+        fs::create_dir_all(dir.join("backups/app/snap")).unwrap();
+        fs::write(dir.join("backups/app/snap/meta.json"), "{}").unwrap();
+        fs::write(dir.join("secret"), "secret").unwrap();
+        dir
+    }
+
+    /// Runs `forced-command.sh` for the root `<dir>/backups` like sshd would, `{}` in
+    /// `command` standing for `<dir>`.
+    fn forced(dir: &Path, command: &str) -> Output {
+        Command::new("bash")
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("forced-command.sh"))
+            .arg(dir.join("backups"))
+            .env(
+                "SSH_ORIGINAL_COMMAND",
+                command.replace("{}", &dir.to_string_lossy()),
+            )
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn forced_command_allows_the_operations_inside_the_root() {
+        let dir = backup_root("forced-allow");
+        let mkdir = forced(&dir, "mkdir -p -- '{}/backups/app/next'");
+        assert!(mkdir.status.success());
+        assert!(dir.join("backups/app/next").is_dir());
+        let ls = forced(&dir, "ls -1 -- '{}/backups/app'");
+        assert_eq!(ls.stdout, b"next\nsnap\n");
+        let cat = forced(&dir, "cat -- '{}/backups/app/snap/meta.json'");
+        assert_eq!(cat.stdout, b"{}");
+        let wc = forced(&dir, "wc -c -- '{}/backups/app/snap/meta.json'");
+        assert!(String::from_utf8_lossy(&wc.stdout).starts_with("2 "));
+        assert!(forced(&dir, "rm -rf -- '{}/backups/app/next'")
+            .status
+            .success());
+        assert!(!dir.join("backups/app/next").exists());
+    }
+
+    #[test]
+    fn forced_command_denies_paths_outside_the_root() {
+        let dir = backup_root("forced-outside");
+        for command in [
+            "cat -- '{}/secret'",
+            "cat -- '{}/backups/../secret'",
+            "cat -- '{}/backups-other/x'",
+            "cat -- /etc/passwd",
+            "rm -rf -- '{}/backups'",
+            "rm -rf -- '{}/backups/'",
+        ] {
+            let output = forced(&dir, command);
+            assert!(!output.status.success(), "{command} was allowed");
+            assert!(output.stdout.is_empty());
+        }
+        assert!(dir.join("backups/app/snap/meta.json").is_file());
+    }
+
+    #[test]
+    fn forced_command_denies_other_commands() {
+        let dir = backup_root("forced-other");
+        for command in [
+            "",
+            "id",
+            "sh -c id",
+            "cat -n -- '{}/backups/app/snap/meta.json'",
+            "cat '{}/backups/app/snap/meta.json'",
+            "ls -1 -- '{}/backups'; id",
+            "chmod 777 -- '{}/backups/app'",
+        ] {
+            assert!(
+                !forced(&dir, command).status.success(),
+                "{command} was allowed"
+            );
+        }
+    }
+
+    #[test]
+    fn forced_command_allows_chunk_reads_inside_the_root() {
+        let dir = backup_root("forced-dd");
+        fs::write(dir.join("backups/app/snap/data"), "0123456789").unwrap();
+        let chunk = forced(
+            &dir,
+            "dd bs=4 skip=1 count=1 status=none if='{}/backups/app/snap/data'",
+        );
+        assert!(chunk.status.success());
+        assert_eq!(chunk.stdout, b"4567");
+        for command in [
+            "dd bs=4 skip=0 count=1 status=none if='{}/secret'",
+            "dd bs=4 skip=0 count=2 status=none if='{}/backups/app/snap/data'",
+            "dd bs=4 skip=0 count=1 status=none of='{}/backups/app/snap/data'",
+            "dd if='{}/backups/app/snap/data' of='{}/backups/copy'",
+            "dd bs=4 skip=x count=1 status=none if='{}/backups/app/snap/data'",
+        ] {
+            assert!(
+                !forced(&dir, command).status.success(),
+                "{command} was allowed"
+            );
+        }
+        assert!(!dir.join("backups/copy").exists());
+    }
+
+    #[test]
+    fn download_discards_a_stale_partial_file() {
+        let dir = backup_root("download");
+        let remote = dir.join("backups/app/snap/data");
+        fs::write(&remote, "0123456789").unwrap();
+        let local = dir.join("restored");
+        // Left over from an interrupted download of a file that has changed since
+        fs::write(with_suffix(&local, ".part"), "garbage").unwrap();
+        let stale = DownloadState {
+            remote: remote.to_string_lossy().to_string(),
+            size: 3,
+            chunk_size: CHUNK_SIZE,
+            chunks: 1,
+        };
+        stale.save(&with_suffix(&local, ".part.json")).unwrap();
+
+        let config = config(&dir.join("backups"), false);
+        crate::local::LocalBackend::new(&config)
+            .download(&remote.to_string_lossy(), &local, None)
+            .unwrap();
+        assert_eq!(fs::read(&local).unwrap(), b"0123456789");
+        assert!(!with_suffix(&local, ".part").exists());
+        assert!(!with_suffix(&local, ".part.json").exists());
+    }
+
+    #[test]
+    fn forced_command_renames_only_within_the_root() {
+        let dir = backup_root("forced-mv");
+        let partial = "{}/backups/app/snap/meta.json.partial";
+        fs::write(dir.join("backups/app/snap/meta.json.partial"), "new").unwrap();
+        let outside = forced(&dir, &format!("mv -f -- '{partial}' '{{}}/stolen'"));
+        assert!(!outside.status.success());
+        let from_outside = forced(&dir, "mv -f -- '{}/secret' '{}/backups/app/secret'");
+        assert!(!from_outside.status.success());
+        let rename = forced(
+            &dir,
+            &format!("mv -f -- '{partial}' '{{}}/backups/app/snap/meta.json'"),
+        );
+        assert!(rename.status.success());
+        assert_eq!(
+            fs::read(dir.join("backups/app/snap/meta.json")).unwrap(),
+            b"new"
+        );
+        assert!(dir.join("secret").is_file() && !dir.join("stolen").exists());
+    }
+
+    #[test]
+    fn forced_command_hashes_files_inside_the_root() {
+        let dir = backup_root("forced-sha256");
+        let sum = forced(&dir, "sha256sum -- '{}/backups/app/snap/meta.json'");
+        assert!(sum.status.success());
+        // SHA-256 of `{}`
+        assert!(String::from_utf8_lossy(&sum.stdout)
+            .starts_with("44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a "));
+        assert!(!forced(&dir, "sha256sum -- '{}/secret'").status.success());
+        assert!(!forced(&dir, "sha256sum -c -- '{}/backups/sums'")
+            .status
+            .success());
+    }
+
+    #[test]
+    fn allowlist_checks_paths_before_running_anything() {
+        let locked = config(Path::new("/srv/backups"), true);
+        let backend = SshBackend::new(&locked);
+        assert!(backend.check_path("/srv/backups/app/snap").is_ok());
+        assert!(backend.check_path("/srv/backups/app/../../etc").is_err());
+        assert!(backend.check_path("/srv/backups-other/app").is_err());
+        assert!(backend.check_path("/etc/passwd").is_err());
+        assert!(backend.check_rsync().is_err());
+        let open = config(Path::new("/srv/backups"), false);
+        assert!(SshBackend::new(&open).check_path("/etc/passwd").is_ok());
+        // The root itself is never deleted
+        assert!(SshBackend::new(&open).delete("/srv/backups/").is_err());
+    }
+
+    #[test]
+    fn throttle_reads_everything() {
+        let data: Vec<u8> = (0..200_000).map(|i| i as u8).collect();
+        for limit in [None, Some(0), Some(1_000_000)] {
+            let read = Throttle::new(limit).read_to_end(&mut &data[..]).unwrap();
+            assert_eq!(read, data);
+        }
+    }
+
+    #[test]
+    fn throttle_holds_the_rate() {
+        // 256 KiB at 1024 KiB/s take a quarter second, paced in 64 KiB steps
+        let data = vec![0u8; 256 * 1024];
+        let started = Instant::now();
+        Throttle::new(Some(1024))
+            .read_to_end(&mut &data[..])
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(240));
+    }
+
+    #[test]
+    fn throttle_captures_command_output() {
+        let output = Throttle::new(Some(1024))
+            .output(Command::new("sh").args(["-c", "printf data; echo oops >&2; exit 3"]))
+            .unwrap();
+        assert_eq!(output.stdout, b"data");
+        assert_eq!(output.stderr, b"oops\n");
+        assert_eq!(output.status.code(), Some(3));
+    }
+
+    #[test]
+    fn throttle_keeps_reading_with_a_full_stderr() {
+        // Far more than a pipe buffer on stderr before anything goes to stdout
+        let output = Throttle::new(None)
+            .output(Command::new("sh").args(["-c", "head -c 1048576 /dev/zero >&2; printf data"]))
+            .unwrap();
+        assert_eq!(output.stdout, b"data");
+        assert_eq!(output.stderr.len(), 1048576);
+        assert!(output.status.success());
+    }
+}
@@ -0,0 +1,170 @@
+//! `dockup usage`: what the backups take up on the target and, with `--transfer`, how many
+//! bytes were moved to and from each target per month.
+//!
+//! With `prices.<target>.*` configured it also estimates this month's cost per target:
+//! the stored snapshots at the storage price plus the bytes downloaded so far at the egress
+//! price, projected to the end of the month. Secondary targets are assumed to hold a copy
+//! of the primary target's snapshots, the catalog only knows those.
+
+use anyhow::Result;
+use chrono::{Datelike, Local, Months};
+
+use crate::{
+    catalog::{Catalog, StoredRecord},
+    config::Config,
+    plain_println,
+    utils::format_bytes,
+};
+
+/// Estimated cost of one target for the current month.
+struct Estimate {
+    target: String,
+    storage: f64,
+    egress: f64,
+    /// Storage plus the egress so far extrapolated to the whole month.
+    projected: f64,
+}
+
+pub fn run_usage(config: &Config, transfer: bool) -> Result<()> {
+    let catalog = Catalog::open()?;
+    if transfer {
+        let records = catalog.transfer()?;
+        if records.is_empty() {
+            plain_println!("No transfers recorded yet");
+            return Ok(());
+        }
+        plain_println!(
+            "{:<8} {:<16} {:>12} {:>12}",
+            "Month",
+            "Target",
+            "Uploaded",
+            "Downloaded"
+        );
+        for record in records {
+            plain_println!(
+                "{:<8} {:<16} {:>12} {:>12}",
+                record.month,
+                record.target,
+                format_bytes(record.uploaded),
+                format_bytes(record.downloaded)
+            );
+        }
+        return Ok(());
+    }
+
+    let records = catalog.stored()?;
+    if records.is_empty() {
+        plain_println!("No snapshots in the catalog, see `dockup catalog rebuild`");
+        return Ok(());
+    }
+    plain_println!("{:<24} {:>9} {:>12}", "Project", "Snapshots", "Stored");
+    let mut total = 0;
+    for record in &records {
+        total += record.bytes;
+        plain_println!(
+            "{:<24} {:>9} {:>12}",
+            record.project,
+            record.snapshots,
+            format_bytes(record.bytes)
+        );
+    }
+    plain_println!("{:<24} {:>9} {:>12}", "Total", "", format_bytes(total));
+
+    let estimates = estimate(config, &catalog, &records)?;
+    if estimates.is_empty() {
+        return Ok(());
+    }
+    println!();
+    plain_println!("Estimated cost this month");
+    plain_println!(
+        "{:<16} {:>10} {:>10} {:>10}",
+        "Target",
+        "Storage",
+        "Egress",
+        "Projected"
+    );
+    for estimate in &estimates {
+        plain_println!(
+            "{:<16} {:>10.2} {:>10.2} {:>10.2}",
+            estimate.target,
+            estimate.storage,
+            estimate.egress,
+            estimate.projected
+        );
+    }
+    let projected: f64 = estimates.iter().map(|estimate| estimate.projected).sum();
+    match config.cost_budget {
+        Some(budget) => plain_println!(
+            "{:<16} {:>10} {:>10} {:>10.2} (budget {:.2})",
+            "Total",
+            "",
+            "",
+            projected,
+            budget
+        ),
+        None => plain_println!("{:<16} {:>10} {:>10} {:>10.2}", "Total", "", "", projected),
+    }
+    if let Some(warning) = over_budget(config, projected) {
+        log::warn!("⚠️  {}", warning);
+    }
+    Ok(())
+}
+
+/// Warning if the projected cost of this month exceeds `cost_budget`.
+pub fn budget_warning(config: &Config) -> Result<Option<String>> {
+    if config.cost_budget.is_none() {
+        return Ok(None);
+    }
+    let catalog = Catalog::open()?;
+    let projected = estimate(config, &catalog, &catalog.stored()?)?
+        .iter()
+        .map(|estimate| estimate.projected)
+        .sum();
+    Ok(over_budget(config, projected))
+}
+
+fn over_budget(config: &Config, projected: f64) -> Option<String> {
+    let budget = config.cost_budget?;
+    (projected > budget).then(|| {
+        format!(
+            "Projected backup cost of {:.2} this month exceeds the budget of {:.2}",
+            projected, budget
+        )
+    })
+}
+
+/// Estimates for every target with prices, in `target_names` order.
+fn estimate(config: &Config, catalog: &Catalog, stored: &[StoredRecord]) -> Result<Vec<Estimate>> {
+    if config.prices.is_empty() {
+        return Ok(Vec::new());
+    }
+    let gb = |bytes: u64| bytes as f64 / 1e9;
+    let stored_gb = gb(stored.iter().map(|record| record.bytes).sum());
+    let now = Local::now();
+    let month = now.format("%Y-%m").to_string();
+    let first = now.date_naive().with_day(1).expect("day 1 exists");
+    let days = (first + Months::new(1) - first).num_days() as f64;
+    let elapsed = now.day() as f64;
+    let transfer = catalog.transfer()?;
+
+    let mut estimates = Vec::new();
+    for target in config.target_names() {
+        let Some(prices) = config.prices.get(&target) else {
+            continue;
+        };
+        let downloaded = transfer
+            .iter()
+            .filter(|record| record.target == target && record.month == month)
+            .map(|record| record.downloaded)
+            .sum();
+        let storage = stored_gb * prices.storage_per_gb;
+        let egress = gb(downloaded) * prices.egress_per_gb;
+        estimates.push(Estimate {
+            target,
+            storage,
+            egress,
+            projected: storage + egress / elapsed * days,
+        });
+    }
+    Ok(estimates)
+}
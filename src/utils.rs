@@ -1,27 +1,95 @@
-use anyhow::{Context, Result};
 use std::process::Command;
 
 use crate::config::Config;
 
-pub fn run_remote_cmd_with_output(cfg: &Config, cmd: &str) -> Result<String> {
-    let full_cmd = format!(
-        "ssh -i {} -p {} {}@{} '{}'",
-        cfg.ssh_key, cfg.ssh_port, cfg.ssh_user, cfg.ssh_host, cmd
-    );
-
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg(&full_cmd)
-        .output()
-        .with_context(|| format!("Failed to run: {}", full_cmd))?;
-
-    if !output.status.success() {
-        anyhow::bail!(
-            "SSH command failed: {}\nstderr: {}",
-            cmd,
-            String::from_utf8_lossy(&output.stderr)
-        );
+/// Set on the environment of ssh/scp so dockup knows it was invoked as askpass helper.
+const ASKPASS_MARKER: &str = "DOCKUP_ASKPASS";
+const ASKPASS_PASSWORD: &str = "DOCKUP_ASKPASS_PASSWORD";
+
+/// Returns the password to hand back to ssh when dockup runs as its askpass helper.
+pub fn askpass_password() -> Option<String> {
+    std::env::var_os(ASKPASS_MARKER)?;
+    std::env::var(ASKPASS_PASSWORD).ok()
+}
+
+/// Builds an `ssh` command for the configured target, ready for the remote command argument.
+pub fn ssh_command(cfg: &Config) -> Command {
+    let mut cmd = Command::new("ssh");
+    cmd.arg("-p").arg(cfg.ssh_port.to_string());
+    apply_ssh_auth(cfg, &mut cmd);
+    cmd.arg(format!("{}@{}", cfg.ssh_user, cfg.ssh_host));
+    cmd
+}
+
+/// Builds an `scp` command with port and authentication set, ready for source and target.
+pub fn scp_command(cfg: &Config) -> Command {
+    let mut cmd = Command::new("scp");
+    cmd.arg("-P").arg(cfg.ssh_port.to_string());
+    apply_ssh_auth(cfg, &mut cmd);
+    cmd
+}
+
+/// Builds an `rsync -a` command tunnelled through ssh with port and authentication set,
+/// ready for further options, source and target.
+pub fn rsync_command(cfg: &Config) -> Command {
+    let mut ssh = Command::new("ssh");
+    ssh.arg("-p").arg(cfg.ssh_port.to_string());
+    apply_ssh_auth(cfg, &mut ssh);
+
+    // rsync splits `-e` itself, honouring single and double quotes but no backslashes
+    let shell = std::iter::once("ssh".to_string())
+        .chain(ssh.get_args().map(|arg| {
+            let arg = arg.to_string_lossy();
+            match arg.contains('\'') {
+                true => format!("\"{}\"", arg),
+                false => format!("'{}'", arg),
+            }
+        }))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut cmd = Command::new("rsync");
+    cmd.arg("-a").arg("-e").arg(shell);
+    // askpass settings have to reach the ssh process rsync spawns
+    for (key, value) in ssh.get_envs() {
+        if let Some(value) = value {
+            cmd.env(key, value);
+        }
+    }
+    cmd
+}
+
+fn apply_ssh_auth(cfg: &Config, cmd: &mut Command) {
+    if cfg.uses_password_auth() {
+        cmd.args([
+            "-o",
+            "PubkeyAuthentication=no",
+            "-o",
+            "PreferredAuthentications=password,keyboard-interactive",
+        ]);
+        // Without a stored password ssh falls back to prompting on the terminal
+        if let Some(password) = &cfg.ssh_password {
+            match std::env::current_exe() {
+                Ok(exe) => {
+                    cmd.env("SSH_ASKPASS", exe)
+                        .env("SSH_ASKPASS_REQUIRE", "force")
+                        .env(ASKPASS_MARKER, "1")
+                        .env(ASKPASS_PASSWORD, password);
+                }
+                Err(e) => log::warn!("⚠️  Could not locate dockup binary for askpass: {e}"),
+            }
+        }
+    } else {
+        cmd.arg("-i").arg(&cfg.ssh_key);
     }
+}
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+/// `bytes` in decimal units, like the sizes in the report.
+pub fn format_bytes(bytes: u64) -> String {
+    let bytes = bytes as f64;
+    match bytes {
+        b if b >= 1e9 => format!("{:.1} GB", b / 1e9),
+        b if b >= 1e6 => format!("{:.1} MB", b / 1e6),
+        b if b >= 1e3 => format!("{:.1} KB", b / 1e3),
+        b => format!("{} B", b),
+    }
 }
@@ -0,0 +1,220 @@
+//! `dockup verify`: detecting bit rot and truncated uploads on the backup target.
+//!
+//! Every archive is hashed with SHA-256 when it is uploaded, checked against the copy on
+//! the target right after the upload and stored with its stats in `meta.json`. `verify` recomputes the checksums on the target, over SSH
+//! with `sha256sum` or directly for local targets, and otherwise (S3, or with
+//! `--download`) by downloading each archive. Incremental snapshots and volumes in the
+//! chunk store have no checksums, chunks are verified by their hash on restore.
+//!
+//! Every run is recorded in the catalog and shows up in `dockup status` and the backup
+//! report. `verify_schedule` runs it on its own schedule, apart from the backups.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use crate::{
+    catalog::{Catalog, VerificationRecord},
+    config::Config,
+    plain_println, restore,
+    scanner::{BackupApplication, Checksum},
+    transport::{self, StorageBackend},
+};
+
+/// SHA-256 of the file at `path`, hex encoded.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Suffix of archives while they are uploaded.
+pub const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Uploads `local` to `remote` and checks the copy on the target right away, instead of
+/// trusting the exit status of the upload: by its SHA-256 where the target can compute it
+/// (one `sha256sum` over SSH), otherwise by its size. The upload goes to
+/// `<remote>.partial` and is only renamed to `remote` once it checked out, so an
+/// interrupted upload never looks like a complete archive. Returns the SHA-256 of `local`.
+pub fn put_verified(transport: &dyn StorageBackend, local: &Path, remote: &str) -> Result<String> {
+    let partial = format!("{}{}", remote, PARTIAL_SUFFIX);
+    let sha256 = sha256_file(local)?;
+    let uploaded = transport
+        .put(local, &partial)
+        .and_then(|()| check_upload(transport, local, &partial, &sha256))
+        .and_then(|()| transport.rename(&partial, remote));
+    if let Err(e) = uploaded {
+        transport.delete(&partial).ok();
+        return Err(e);
+    }
+    Ok(sha256)
+}
+
+fn check_upload(
+    transport: &dyn StorageBackend,
+    local: &Path,
+    remote: &str,
+    sha256: &str,
+) -> Result<()> {
+    match transport.sha256(remote) {
+        Ok(Some(uploaded)) if uploaded != sha256 => {
+            anyhow::bail!(
+                "Upload of {:?} is corrupt, its checksum on the target differs",
+                local
+            )
+        }
+        Ok(Some(_)) => return Ok(()),
+        Ok(None) => {}
+        Err(e) => log::debug!("No checksum from the target, comparing sizes: {e:#}"),
+    }
+    let size = fs::metadata(local)?.len();
+    let uploaded = transport.size(remote)?;
+    if uploaded != size {
+        anyhow::bail!(
+            "Upload of {:?} is truncated, {} of {} bytes on the target",
+            local,
+            uploaded,
+            size
+        );
+    }
+    Ok(())
+}
+
+/// Verifies the newest snapshot of every project, or of `project` only, or all snapshots
+/// with `all`, and records the outcome in the catalog. Fails if an archive is missing or
+/// doesn't match its checksum.
+pub fn run(
+    config: &Config,
+    project: Option<&str>,
+    all: bool,
+    download: bool,
+    scheduled: bool,
+) -> Result<()> {
+    let started = chrono::Local::now();
+    let mut snapshots = restore::scan_backup_target(config)?;
+    if let Some(project) = project {
+        snapshots.retain(|snapshot| snapshot.name == project);
+        if snapshots.is_empty() {
+            anyhow::bail!("No snapshots of {} on the backup target", project);
+        }
+    }
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name).then(b.timestamp.cmp(&a.timestamp)));
+    if !all {
+        snapshots.dedup_by(|newer, older| newer.name == older.name);
+    }
+
+    let transport = transport::open(config);
+    let mut verified = 0;
+    let mut failures = Vec::new();
+    for snapshot in &snapshots {
+        let checksums: Vec<&Checksum> = snapshot
+            .stats
+            .iter()
+            .filter_map(|stats| stats.checksum.as_ref())
+            .collect();
+        plain_println!("🔍 {} {}", snapshot.name, snapshot.snapshot_folder());
+        if checksums.is_empty() {
+            plain_println!(
+                "   ⏭️  No checksums recorded (older, incremental or deduplicated snapshot)"
+            );
+            continue;
+        }
+        for checksum in checksums {
+            match verify_file(config, &*transport, snapshot, checksum, download) {
+                Ok(true) => {
+                    verified += 1;
+                    plain_println!("   ✅ {}", checksum.file);
+                }
+                Ok(false) => {
+                    plain_println!("   ❌ {}: checksum mismatch", checksum.file);
+                    failures.push((snapshot, checksum, "checksum mismatch".to_string()));
+                }
+                Err(e) => {
+                    plain_println!("   ❌ {}: {:#}", checksum.file, e);
+                    failures.push((snapshot, checksum, format!("{:#}", e)));
+                }
+            }
+        }
+    }
+
+    let record = VerificationRecord {
+        started,
+        finished: chrono::Local::now(),
+        scheduled,
+        verified,
+        failed: failures.len(),
+        error: (!failures.is_empty()).then(|| {
+            failures
+                .iter()
+                .map(|(snapshot, checksum, reason)| {
+                    format!(
+                        "{}/{}/{}: {}",
+                        snapshot.name,
+                        snapshot.snapshot_folder(),
+                        checksum.file,
+                        reason
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }),
+    };
+    if let Err(e) = Catalog::open().and_then(|catalog| catalog.record_verification(&record)) {
+        log::warn!("⚠️  Failed to record the verification in the catalog: {e}");
+    }
+    if let Some(error) = record.error {
+        anyhow::bail!(
+            "{} archives failed verification, {} verified:\n{}",
+            record.failed,
+            verified,
+            error
+        );
+    }
+    plain_println!("✅ {} archives verified", verified);
+    Ok(())
+}
+
+/// Whether the archive of `checksum` on the target still has that checksum.
+fn verify_file(
+    config: &Config,
+    transport: &dyn StorageBackend,
+    snapshot: &BackupApplication,
+    checksum: &Checksum,
+    download: bool,
+) -> Result<bool> {
+    let remote = format!(
+        "{}/{}/{}/{}",
+        config.remote_backup_path,
+        snapshot.name,
+        snapshot.snapshot_folder(),
+        checksum.file
+    );
+    let remote_sum = if download {
+        None
+    } else {
+        // Targets without sha256sum (or a forced command denying it) fall back to a download
+        transport.sha256(&remote).unwrap_or_else(|e| {
+            log::debug!("No checksum from the target for {}: {e}", remote);
+            None
+        })
+    };
+    let actual = match remote_sum {
+        Some(sum) => sum,
+        None => {
+            let local = std::env::temp_dir().join(format!(
+                "dockup-verify-{}-{}",
+                std::process::id(),
+                checksum.file.replace('/', "_")
+            ));
+            let fetched = transport
+                .get(&remote, &local)
+                .and_then(|()| sha256_file(&local));
+            std::fs::remove_file(&local).ok();
+            fetched?
+        }
+    };
+    Ok(actual == checksum.sha256)
+}
@@ -0,0 +1,140 @@
+//! `dockup watch`: notices new compose projects under `docker_parent` as they appear.
+//!
+//! Uses `inotifywait` (inotify-tools) on `docker_parent` and each project directory, so new
+//! deployments don't go unprotected until someone looks. The watcher is restarted whenever
+//! a directory is added, to pick up the new directory as well.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+use crate::{
+    backup,
+    config::Config,
+    email,
+    i18n::{t, tf},
+    report,
+    scanner::{compose_file, scan_projects, BackupType, COMPOSE_FILES},
+};
+
+/// Watches the `docker_parent` directories until interrupted. New projects are logged and reported by
+/// email; with `backup` a backup run is started right away.
+pub async fn run(config: &Config, backup: bool) -> Result<()> {
+    let parents: Vec<PathBuf> = config.docker_parent.iter().map(PathBuf::from).collect();
+    let scan = scan_projects(config)?;
+    let mut known: HashSet<PathBuf> = scan
+        .apps
+        .into_iter()
+        .map(|app| app.application_path)
+        .chain(scan.skipped.into_iter().map(|skipped| skipped.path))
+        .collect();
+    log::info!(
+        "👀 Watching {} ({} projects known)",
+        config.docker_parent.join(", "),
+        known.len()
+    );
+    let is_parent = |dir: Option<&Path>| dir.is_some_and(|dir| parents.iter().any(|p| p == dir));
+
+    loop {
+        let mut watcher = Command::new("inotifywait")
+            .args([
+                "-m",
+                "-q",
+                "-e",
+                "create",
+                "-e",
+                "moved_to",
+                "-e",
+                "close_write",
+            ])
+            .args(["--format", "%w%f"])
+            .args(&parents)
+            .args(project_dirs(&parents)?)
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to run inotifywait, is inotify-tools installed?")?;
+        let stdout = watcher.stdout.take().expect("stdout is piped");
+        let mut lines = BufReader::new(stdout).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            let path = PathBuf::from(line);
+            if is_parent(path.parent()) && path.is_dir() {
+                // New directory, restart to watch it too. It may already hold a compose file.
+                log::debug!("New directory {}", path.display());
+                if compose_file(&path).is_some() {
+                    detected(config, &mut known, &path, backup).await;
+                }
+                break;
+            }
+            if path
+                .file_name()
+                .is_some_and(|name| COMPOSE_FILES.iter().any(|file| name == *file))
+            {
+                if let Some(project) = path.parent().filter(|p| is_parent(p.parent())) {
+                    detected(config, &mut known, project, backup).await;
+                }
+            }
+        }
+        watcher.kill().await.ok();
+    }
+}
+
+fn project_dirs(parents: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for parent in parents {
+        for entry in fs::read_dir(parent)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+async fn detected(config: &Config, known: &mut HashSet<PathBuf>, project: &Path, backup: bool) {
+    if !known.insert(project.to_path_buf()) {
+        return;
+    }
+    let name = project
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    log::warn!("🆕 New project detected: {} ({})", name, project.display());
+
+    let message = tf("watch.body", &[&name, &project.display()]);
+    if let Err(e) = email::send_summary_email(config, t("watch.subject"), &message).await {
+        log::error!("❌ Failed to send new project notification: {e}");
+    }
+    if backup {
+        backup_now(config).await;
+    }
+}
+
+async fn backup_now(config: &Config) {
+    log::info!("🚀 Starting backup for the new project");
+    let started = chrono::Local::now();
+    let result = backup::run_backup(config, false, &[], &[]).await;
+    backup::save_last_run(config, BackupType::Manual, started, &result);
+    match result {
+        Ok(summaries) => {
+            let report = report::build_report(config, &summaries);
+            if let Err(e) = email::send_report_email(
+                config,
+                t("report.subject"),
+                &report.html,
+                report.attachment.as_deref(),
+            )
+            .await
+            {
+                log::error!("❌ Failed to send backup report: {e}");
+            }
+        }
+        Err(e) => log::error!("❌ Backup failed: {e}"),
+    }
+}